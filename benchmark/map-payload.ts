@@ -0,0 +1,83 @@
+// Throughput of a map server that echoes its datums, for payloads from 1 KiB to 4 MiB.
+//
+// Build the release binaries first with `pnpm build`, then run `pnpm bench`. The `map_bench` client
+// from `tests/src/` sends the requests and measures the time until every response arrived.
+import { spawn } from 'child_process'
+import { promisify } from 'util'
+
+import { map } from '../index.js'
+
+const sleep = promisify(setTimeout)
+
+const SOCKET_PATH = '/tmp/map-bench.sock'
+const SERVER_INFO_PATH = '/tmp/map-bench.info'
+
+// Payload size in bytes and number of requests, about 256 MiB in total for the larger payloads.
+const RUNS: [number, number][] = [
+    [1024, 20000],
+    [64 * 1024, 4000],
+    [1024 * 1024, 256],
+    [4 * 1024 * 1024, 64],
+]
+
+// Runs a command to completion, the server handles the requests on this event loop in the meantime.
+async function run(command: string, args: string[]): Promise<string> {
+    const child = spawn(command, args, { stdio: ['ignore', 'pipe', 'inherit'] })
+    let stdout = ''
+    child.stdout.on('data', (data) => {
+        stdout += data
+    })
+    const code = await new Promise<number | null>((resolve) => child.on('close', resolve))
+    if (code !== 0) {
+        throw new Error(`${command} ${args.join(' ')} exited with ${code}`)
+    }
+    return stdout
+}
+
+interface Result {
+    size: number
+    count: number
+    seconds: number
+    messagesPerSecond: number
+    mibPerSecond: number
+}
+
+async function main() {
+    // Reads the value more than once, like handlers that inspect it before forwarding it.
+    const server = new map.AsyncServer(async (datum) => {
+        const value = datum.value
+        if (value.length !== datum.value.length) {
+            throw new Error('The value changed between two reads')
+        }
+        return [new map.Message(value, { keys: datum.keys })]
+    })
+    const started = server.start({ socketPath: SOCKET_PATH, serverInfoPath: SERVER_INFO_PATH })
+    await sleep(500)
+
+    const results: Result[] = []
+    try {
+        await run('cargo', ['build', '--release', '-p', 'tests', '--bin', 'map_bench'])
+        for (const [size, count] of RUNS) {
+            const output = await run('target/release/map_bench', [SOCKET_PATH, String(size), String(count)])
+            results.push(JSON.parse(output) as Result)
+        }
+    } finally {
+        await server.stop()
+        await started
+    }
+
+    console.table(
+        results.map(({ size, count, seconds, messagesPerSecond, mibPerSecond }) => ({
+            'payload (KiB)': size / 1024,
+            requests: count,
+            seconds,
+            'messages/s': messagesPerSecond,
+            'MiB/s': mibPerSecond,
+        })),
+    )
+}
+
+main().catch((error) => {
+    console.error(error)
+    process.exit(1)
+})
@@ -1,10 +1,191 @@
 /* auto-generated by NAPI-RS */
 /* eslint-disable */
+/** Sets the level and format of the logs, options that are not set keep their current value. */
+export declare function configureLogging(options: LogOptions): void
+/** Outcome of draining a server on `stop()`. */
+export interface DrainSummary {
+    /** Requests that settled while the server was draining. */
+    drained: number
+    /** Requests that were still in flight when the server was closed. */
+    abandoned: number
+}
+/**
+ * Returns the configuration a server started with `options` runs with, e.g. to log it on start
+ * up.
+ */
+export declare function loadServerConfig(options?: ServerOptions | undefined | null): ServerConfig
+/**
+ * Level and format of the logs of the servers.
+ *
+ * The same options can be set with the `NUMAFLOW_LOG_LEVEL` and `NUMAFLOW_LOG_FORMAT` environment
+ * variables, the options passed to `configureLogging()` take precedence.
+ */
+export interface LogOptions {
+    /**
+     * Most verbose level that is logged, one of `off`, `error`, `warn`, `info`, `debug` and
+     * `trace`. Defaults to `info`.
+     */
+    level?: string
+    /** `json` to log a JSON object per line, `text` to log plain lines. Defaults to `json`. */
+    format?: string
+}
+/** A log record of the native layer, as passed to the handler set with `setLogHandler()`. */
+export interface LogRecord {
+    /** Time the record was logged at. */
+    timestamp: Date
+    /** One of `error`, `warn`, `info`, `debug` and `trace`. */
+    level: string
+    /** Module the record was logged from, e.g. `numaflow_js::lifecycle`. */
+    target: string
+    message: string
+    /** Structured fields of the record, e.g. `component` with the server that logged it. */
+    fields: Record<string, any>
+}
+/**
+ * Retry policy for transient failures of a user-defined function.
+ *
+ * Only errors thrown by the JS handler are retried, the request is failed once the attempts are
+ * exhausted.
+ */
+export interface RetryPolicy {
+    /** Total number of attempts, including the first one. Defaults to 3. */
+    maxAttempts?: number
+    /** Delay before the first retry in milliseconds. Defaults to 100. */
+    initialBackoffMs?: number
+    /** Upper bound of the delay between two attempts in milliseconds. Defaults to 10000. */
+    maxBackoffMs?: number
+    /** Factor the delay grows by after every retry. Defaults to 2. */
+    multiplier?: number
+    /** Fraction of the delay, between 0 and 1, that is randomized. Defaults to 0.2. */
+    jitter?: number
+    /**
+     * Names of the JS errors that are retried, e.g. `['FetchError']`. Every error is retried when
+     * not set.
+     */
+    retryOn?: Array<string>
+}
+/** Configuration a server runs with. */
+export interface ServerConfig {
+    /** Container type from `NUMAFLOW_UD_CONTAINER_TYPE`. */
+    containerType?: string
+    /** Config file from `NUMAFLOW_SERVER_CONFIG_FILE`. */
+    configFile?: string
+    /** Options resolved from the `start()` options, the environment and the config file. */
+    options: ServerOptions
+}
+/**
+ * Options accepted by `start()` of every server.
+ *
+ * The same options can be set with `NUMAFLOW_*` environment variables or in a config file, the
+ * options passed to `start()` take precedence.
+ */
+export interface ServerOptions {
+    /**
+     * Path of the Unix domain socket the gRPC server listens on. Defaults to the path numaflow
+     * expects for the server type.
+     */
+    socketPath?: string
+    /**
+     * Path of the file the server information is written to on start up. Defaults to the path
+     * numaflow expects for the server type.
+     */
+    serverInfoPath?: string
+    /** Maximum size of an encoded or decoded gRPC message in bytes. Defaults to 64 MiB. */
+    maxMessageSize?: number
+    /**
+     * Time in milliseconds `stop()` waits for in-flight requests before the server is closed
+     * forcefully. Waits until they are done when not set.
+     */
+    shutdownTimeoutMs?: number
+    /**
+     * Stop the server gracefully, as `stop()` does, when the process receives SIGTERM or SIGINT.
+     * The signals no longer terminate the process while the server runs, and their default
+     * action is not restored afterwards. Defaults to false.
+     */
+    handleSignals?: boolean
+    /**
+     * Port of the HTTP endpoint serving the metrics of the servers in the Prometheus text format
+     * at `/metrics`. Metrics are not served when not set.
+     */
+    metricsPort?: number
+    /**
+     * IP address the metrics endpoint listens on, e.g. `127.0.0.1` to only serve the metrics
+     * within the pod. Defaults to `0.0.0.0`, every IPv4 interface.
+     */
+    metricsAddress?: string
+    /**
+     * Maximum number of requests handed to the JS handler at the same time, further requests
+     * wait until one of them is done. Only applies to the map, map stream and source transform
+     * servers. Unbounded when not set.
+     */
+    maxInFlight?: number
+    /**
+     * Maximum number of requests with the same keys handed to the JS handler at the same time,
+     * in addition to `maxInFlight`. Only applies to the map, map stream and source transform
+     * servers. Unbounded when not set.
+     */
+    maxInFlightPerKey?: number
+    /**
+     * Time in milliseconds a call of the JS handler may take before it is abandoned. A timed out
+     * map or batch map request fails like one whose handler threw, a timed out sink batch is
+     * answered with a failure response for every message. Only applies to the map, batch map and
+     * sink servers. Calls are not timed out when not set.
+     */
+    handlerTimeoutMs?: number
+    /**
+     * Codec the datum values are decoded with into `data`, and the `data` of messages is encoded
+     * with: `json`, `msgpack`, `cbor` or `raw`. Only applies to the map, map stream, batch map,
+     * source transform and sink servers. Defaults to `raw`, values are only handed over as bytes.
+     */
+    codec?: string
+    /**
+     * What happens to a datum whose value the codec can't decode: `fail` fails the request like a
+     * handler that threw, `drop` drops the datum without calling the handler, and `pass` hands it
+     * to the handler with the error in `decodeError`. Defaults to `fail`.
+     */
+    decodeErrorPolicy?: string
+}
+/**
+ * Routes the logs to `handler` instead of stdout and stderr, e.g. into the application's logger.
+ * Passing nothing restores the default output.
+ */
+export declare function setLogHandler(handler?: ((record: LogRecord) => void) | null): void
+/**
+ * [W3C trace context](https://www.w3.org/TR/trace-context/) of the span a datum is handled in.
+ *
+ * The span is a child of the span in the `traceparent` header of the datum, spans created by the
+ * JS handler should be parented on it, e.g. by extracting `traceparent` with the OpenTelemetry
+ * propagation API.
+ */
+export interface TraceContext {
+    /** Id of the trace, 32 lowercase hex digits. */
+    traceId: string
+    /** Id of the span, 16 lowercase hex digits. */
+    spanId: string
+    /**
+     * Id of the parent span from the `traceparent` header, not set for a trace started by the
+     * server.
+     */
+    parentSpanId?: string
+    /** Whether the trace is sampled. */
+    sampled: boolean
+    /** `traceparent` header of the span. */
+    traceparent: string
+    /** `tracestate` header of the datum, passed on as is. */
+    tracestate?: string
+}
 export declare namespace accumulator {
     export class AccumulatorAsyncServer {
         constructor(acc_fn: (datumIterator: DatumIterator) => () => Promise<Message | null>)
-        stop(): void
-        start(sockFile?: string | undefined | null, infoFile?: string | undefined | null): Promise<void>
+        /**
+         * A server that calls `createAccFn` once per key and accumulates the datums of the key with
+         * the function it returned, which is released once the key is closed.
+         */
+        static withFactory(
+            createAccFn: () => (datumIterator: DatumIterator) => () => Promise<Message | null>,
+        ): AccumulatorAsyncServer
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
+        start(options?: ServerOptions | undefined | null): Promise<void>
     }
     export class DatumIterator {
         /**
@@ -23,6 +204,8 @@ export declare namespace accumulator {
         eventTime: Date
         headers: Record<string, string>
         id: string
+        /** Context of the span the datum is handled in, if its headers carry a `traceparent`. */
+        traceContext?: TraceContext
     }
     export interface DatumIteratorResult {
         value?: Datum
@@ -48,7 +231,10 @@ export declare namespace accumulator {
         tags?: Array<string>
         /** ID is used for deduplication. Read-only, set from the input datum. */
         id: string
-        /** Headers for the message. Read-only, set from the input datum. */
+        /**
+         * Headers for the message. Read-only, set from the input datum with its `traceparent`
+         * pointing at the span the datum was handled in.
+         */
         headers: Record<string, string>
         /** Time of the element as seen at source or aligned after a reduce operation. Read-only, set from the input datum. */
         eventTime: Date
@@ -62,7 +248,8 @@ export declare namespace accumulator {
 export declare namespace batchmap {
     export class BatchDatumIterator {
         /**
-         * Returns the next datum from the stream, or None if the stream has ended
+         * Returns the next datum from the stream, or None if the stream has ended. Datums whose value
+         * can't be decoded are skipped unless the decode error policy is `pass`.
          * # SAFETY
          *
          * Async function with &mut self is unsafe in napi because the self is also owned
@@ -71,9 +258,12 @@ export declare namespace batchmap {
         next(): Promise<BatchDatumIteratorResult>
     }
     export class BatchMapAsyncServer {
-        constructor(batchmapFn: (arg: BatchDatumIterator) => Promise<Array<BatchResponse>>)
-        start(sockFile?: string | undefined | null, infoFile?: string | undefined | null): Promise<void>
-        stop(): void
+        constructor(
+            batchmapFn: (arg: BatchDatumIterator) => Promise<Array<BatchResponse>>,
+            retryPolicy?: RetryPolicy | undefined | null,
+        )
+        start(options?: ServerOptions | undefined | null): Promise<void>
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
     }
     export class BatchResponse {
         constructor(id: string)
@@ -102,6 +292,12 @@ export declare namespace batchmap {
         id: string
         /** Headers for the message. */
         headers: Record<string, string>
+        /** Context of the span the datum is handled in, if its headers carry a `traceparent`. */
+        traceContext?: TraceContext
+        /** The value decoded by the codec of the server, not set with the raw codec or an empty value. */
+        data?: any
+        /** Why the value could not be decoded, only set when the decode error policy is `pass`. */
+        decodeError?: string
     }
     export interface BatchDatumIteratorResult {
         value?: BatchDatum
@@ -113,8 +309,10 @@ export declare namespace batchmap {
          * be an empty collection.
          */
         keys?: Array<string>
-        /** Value is the value passed to the next vertex. */
-        value: Buffer
+        /** Value is the value passed to the next vertex, empty when neither it nor `data` is set. */
+        value?: Buffer
+        /** Data encoded by the codec of the server into the value passed on, replaces `value`. */
+        data?: any
         /** Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/). */
         tags?: Array<string>
     }
@@ -134,34 +332,48 @@ export declare namespace map {
             userMetadata?: UserMetadata | undefined | null,
             systemMetadata?: SystemMetadata | undefined | null,
         )
+        /** The value of the datum, the same buffer on every read. */
         get value(): Buffer
         get watermark(): Date
         get eventTime(): Date
         get headers(): Record<string, string>
+        /** Context of the span the datum is handled in, if its headers carry a `traceparent`. */
+        get traceContext(): TraceContext | null
         get userMetadata(): UserMetadata | null
         get systemMetadata(): SystemMetadata | null
+        /** The value decoded by the codec of the server, null with the raw codec or an empty value. */
+        get data(): any | null
+        /** Why the value could not be decoded, only set when the decode error policy is `pass`. */
+        get decodeError(): string | null
         set userMetadata(userMetadata: UserMetadata)
     }
     export class MapAsyncServer {
-        constructor(mapFn: (arg: Datum) => Promise<Array<Message>>)
-        start(sockFile?: string | undefined | null, infoFile?: string | undefined | null): Promise<void>
-        stop(): void
-    }
-    export class SystemMetadata {
-        constructor()
-        getGroups(): Array<string>
-        getKeys(group: string): Array<string>
-        getValue(group: string, key: string): Buffer
-    }
-    export class UserMetadata {
-        constructor()
-        getGroups(): Array<string>
-        getKeys(group: string): Array<string>
-        getValue(group: string, key: string): Buffer
-        createGroup(group: string): void
-        addKv(group: string, key: string, value: Buffer): void
-        removeKey(group: string, key: string): void
-        removeGroup(group: string): void
+        constructor(mapFn: (arg: Datum) => Promise<Array<Message>>, retryPolicy?: RetryPolicy | undefined | null)
+        /**
+         * A server that coalesces the requests arriving concurrently into micro-batches and calls
+         * `mapBatchFn` once per batch. It returns the messages of every datum of the batch, in the
+         * order of the datums. A retry or a failure applies to the whole batch.
+         */
+        static withMicroBatching(
+            mapBatchFn: (datums: Array<Datum>) => Promise<Array<Array<Message>>>,
+            microBatch?: MicroBatchOptions | undefined | null,
+            retryPolicy?: RetryPolicy | undefined | null,
+        ): MapAsyncServer
+        /**
+         * A server that hands every datum to one of the worker threads registered with
+         * `registerWorker` under the id of its pool, in turns. A datum whose worker exits before
+         * settling the call is handed to another worker.
+         */
+        static withWorkerPool(retryPolicy?: RetryPolicy | undefined | null): MapAsyncServer
+        /** Id the worker threads register their handlers with, null without a worker pool. */
+        workerPoolId(): number | null
+        /**
+         * Reports that a worker thread of the pool exited, with the uncaught error it exited with if
+         * any. The datums it was handling are handed to the other workers.
+         */
+        workerExited(workerId: number, exitCode: number, error?: string | undefined | null): void
+        start(options?: ServerOptions | undefined | null): Promise<void>
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
     }
     export interface Message {
         /**
@@ -171,18 +383,40 @@ export declare namespace map {
         keys?: Array<string>
         /** Value is the value passed to the next vertex. */
         value: Buffer
+        /** Data encoded by the codec of the server into the value passed on, replaces `value`. */
+        data?: any
         /** Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/). */
         tags?: Array<string>
         /** User metadata for the message. */
         userMetadata?: Record<string, Record<string, Buffer>>
     }
+    /** Options for coalescing concurrently arriving requests into one call of the JS handler. */
+    export interface MicroBatchOptions {
+        /** Most requests handed to one call of the handler. Defaults to 64. */
+        maxBatchSize?: number
+        /**
+         * Longest time in milliseconds the first request of a batch waits for more requests before
+         * the batch is handed to the handler. Defaults to 5.
+         */
+        maxBatchDelayMs?: number
+    }
+    /**
+     * Registers the handler of a worker thread with the pool of a map server. Called by
+     * `map.registerWorker` in the worker thread, whose wrapper reports the errors of the handler:
+     * any other failure of a call is taken for the thread exiting.
+     */
+    export function registerWorker(
+        poolId: number,
+        workerId: number,
+        mapFn: (arg: Datum) => Promise<Array<Message>>,
+    ): void
 }
 
 export declare namespace mapstream {
     export class MapStreamAsyncServer {
         constructor(map_fn: (datum: Datum) => () => Promise<Message | null>)
-        stop(): void
-        start(sockFile?: string | undefined | null, infoFile?: string | undefined | null): Promise<void>
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
+        start(options?: ServerOptions | undefined | null): Promise<void>
     }
     export interface Datum {
         /** Set of keys in the (key, value) terminology of the map/reduce paradigm. */
@@ -198,6 +432,12 @@ export declare namespace mapstream {
         eventTime: Date
         /** Headers associated with the message. */
         headers: Record<string, string>
+        /** Context of the span the datum is handled in, if its headers carry a `traceparent`. */
+        traceContext?: TraceContext
+        /** The value decoded by the codec of the server, not set with the raw codec or an empty value. */
+        data?: any
+        /** Why the value could not be decoded, only set when the decode error policy is `pass`. */
+        decodeError?: string
     }
     export interface Message {
         /**
@@ -207,20 +447,94 @@ export declare namespace mapstream {
         keys?: Array<string>
         /** Value is the value passed to the next vertex. */
         value: Buffer
+        /** Data encoded by the codec of the server into the value passed on, replaces `value`. */
+        data?: any
         /** Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/). */
         tags?: Array<string>
     }
     export function messageToDrop(): Message
 }
 
+export declare namespace metadata {
+    /** Metadata numaflow attaches to a message, read-only to handlers. */
+    export class SystemMetadata {
+        constructor()
+        getGroups(): Array<string>
+        getKeys(group: string): Array<string>
+        /** Value of the key in the group, empty if there is none. */
+        getValue(group: string, key: string): Buffer
+        /** Value of the key in the group decoded as UTF-8, null if there is none. */
+        getString(group: string, key: string): string | null
+        /** All key/value pairs, ordered by group and key. */
+        entries(): Array<MetadataEntry>
+        /** The groups of key/value pairs with their values base64 encoded, used by `JSON.stringify`. */
+        toJSON(): Record<string, Record<string, string>>
+    }
+    /**
+     * User-defined metadata of a message, groups of key/value pairs that are passed on to the next
+     * vertex.
+     */
+    export class UserMetadata {
+        /** Create metadata, optionally with the given groups of key/value pairs. */
+        constructor(groups?: Record<string, Record<string, Buffer>> | undefined | null)
+        /** Create metadata from the output of `toJSON`, whose values are base64 encoded. */
+        static fromJSON(json: Record<string, Record<string, string>>): UserMetadata
+        getGroups(): Array<string>
+        getKeys(group: string): Array<string>
+        /** Value of the key in the group, empty if there is none. */
+        getValue(group: string, key: string): Buffer
+        /** Value of the key in the group decoded as UTF-8, null if there is none. */
+        getString(group: string, key: string): string | null
+        /** All key/value pairs, ordered by group and key. */
+        entries(): Array<MetadataEntry>
+        createGroup(group: string): void
+        addKv(group: string, key: string, value: Buffer): void
+        /** Add a key/value pair whose value is the UTF-8 encoding of the string. */
+        addString(group: string, key: string, value: string): void
+        removeKey(group: string, key: string): void
+        removeGroup(group: string): void
+        /**
+         * Add the groups and key/value pairs of other metadata, its values replace the values of
+         * the keys both have.
+         */
+        merge(other: UserMetadata): void
+        /** A copy of the metadata with the key/value pair added. */
+        cloneWith(group: string, key: string, value: Buffer): UserMetadata
+        /** The groups of key/value pairs with their values base64 encoded, used by `JSON.stringify`. */
+        toJSON(): Record<string, Record<string, string>>
+    }
+    /** A key/value pair of a metadata group. */
+    export interface MetadataEntry {
+        group: string
+        key: string
+        value: Buffer
+    }
+}
+
 export declare namespace reduce {
     export class ReduceAsyncServer {
         /** Create a new ReduceAsyncServer with the given callback. */
         constructor(reduceFn: (iterator: ReduceCallbackArgs) => Promise<Array<Message>>)
+        /**
+         * A server that calls `createReduceFn` once per window and reduces the window with the
+         * function it returned, which is released once the window is reduced.
+         */
+        static withFactory(
+            createReduceFn: () => (iterator: ReduceCallbackArgs) => Promise<Array<Message>>,
+        ): ReduceAsyncServer
+        /**
+         * A server that calls `createAggregator` once per window and drains the datums of the window
+         * itself, handing them to the `addInputs` of the aggregator in micro-batches. Once the datums
+         * end, `extractOutput` returns the messages of the window.
+         */
+        static withAggregator(
+            createAggregator: (args: ReduceCallbackArgs) => WindowAggregator,
+            microBatch?: map.MicroBatchOptions | undefined | null,
+        ): ReduceAsyncServer
         /** Start the ReduceAsyncServer with the given callback */
-        start(socketPath?: string | undefined | null, serverInfoPath?: string | undefined | null): Promise<void>
+        start(options?: ServerOptions | undefined | null): Promise<void>
         /** Stop the reduce server */
-        stop(): void
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
     }
     /**
      * Arguments passed to the reduce callback
@@ -230,6 +544,11 @@ export declare namespace reduce {
         get keys(): Array<string>
         get takeIterator(): ReduceDatumIterator
         get metadata(): Metadata
+        /**
+         * Watermark of the latest datum of the window, not set before the first datum. The reduce
+         * protocol does not tell the watermark the window closed at.
+         */
+        get latestWatermark(): Date | null
     }
     export class ReduceDatumIterator {
         /** Returns the next datum from the stream, or None if the stream has ended */
@@ -241,6 +560,18 @@ export declare namespace reduce {
         watermark: Date
         eventTime: Date
         headers: Record<string, string>
+        /** Context of the span the datum is handled in, if its headers carry a `traceparent`. */
+        traceContext?: TraceContext
+        /**
+         * Whether the watermark had reached the end of the window when the datum arrived, i.e.
+         * the datum arrived after the window would have closed without allowed lateness.
+         */
+        late: boolean
+        /**
+         * How far the watermark was past the end of the window when the datum arrived, 0 if the
+         * datum is not late.
+         */
+        latenessMs: number
     }
     export interface IntervalWindow {
         start: Date
@@ -256,24 +587,56 @@ export declare namespace reduce {
     }
     /** Drop a Message, do not forward to the next vertex. */
     export function messageToDrop(): Message
-    /** Metadata passed to reducer handler */
+    /**
+     * Metadata passed to reducer handler
+     *
+     * The window of the vertex is read from the vertex spec numaflow sets in
+     * `NUMAFLOW_VERTEX_OBJECT`, its identity from `NUMAFLOW_PIPELINE_NAME`, `NUMAFLOW_VERTEX_NAME`
+     * and `NUMAFLOW_REPLICA`. Fields whose variable is not set are left unset.
+     */
     export interface Metadata {
         intervalWindow: IntervalWindow
+        /** Kind of the window of the vertex. */
+        windowKind?: 'fixed' | 'sliding'
+        /** Length of the windows of the vertex in milliseconds. */
+        windowLengthMs?: number
+        /**
+         * Time between the starts of two consecutive windows in milliseconds, the window length for
+         * fixed windows.
+         */
+        slideMs?: number
+        pipelineName?: string
+        vertexName?: string
+        replica?: number
     }
     export interface ReduceDatumIteratorResult {
         value?: Datum
         done: boolean
     }
+    /** The aggregator of one window, created by the `createAggregator` of the server. */
+    export interface WindowAggregator {
+        /** Adds a micro-batch of datums of the window to its accumulator. */
+        addInputs: (datums: Array<Datum>) => void
+        /** Returns the messages of the window once all its datums were added. */
+        extractOutput: () => Promise<Array<Message>>
+    }
 }
 
 export declare namespace reduceStream {
     export class ReduceStreamAsyncServer {
         /** Create a new ReduceStreamAsyncServer with the given callback. */
         constructor(reduceStreamFn: (iterator: ReduceCallbackArgs) => () => Promise<Message | null>)
+        /**
+         * A server that calls `createReduceStreamFn` once per window and reduces the window with the
+         * function it returned, which is released once the window is reduced.
+         */
+        static withFactory(
+            createReduceStreamFn: () => (iterator: ReduceCallbackArgs) => () => Promise<Message | null>,
+        ): ReduceStreamAsyncServer
         /** Start the ReduceStreamAsyncServer with the given callback */
-        start(socketPath?: string | undefined | null, serverInfoPath?: string | undefined | null): Promise<void>
+        start(options?: ServerOptions | undefined | null): Promise<void>
         /** Stop the reduce stream server */
-        stop(): void
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
     }
 }
 
@@ -285,14 +648,24 @@ export declare namespace sessionReduce {
             accumulator_fn: () => Promise<Buffer>,
             merge_accumulator_fn: (accumulator: Buffer) => Promise<void>,
         )
-        stop(): void
-        start(sockFile?: string | undefined | null, infoFile?: string | undefined | null): Promise<void>
+        /**
+         * A server that calls `createHandlers` once per session, the first time the session is
+         * reduced or merged into, and calls the handlers it returned for that session only.
+         */
+        static withFactory(createHandlers: () => SessionHandlers): SessionReduceAsyncServer
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
+        start(options?: ServerOptions | undefined | null): Promise<void>
     }
     /**
      * Arguments passed to the reduce callback
      * Only to be used as part of internal implementation, not to be exposed to final users
      */
     export class SessionReduceCallbackArgs {
+        /**
+         * Number of sessions merged into this one so far. The sessions are merged before the first
+         * datum of the session is received or its datums end.
+         */
+        get mergedSessions(): number
         get keys(): Array<string>
         get takeIterator(): SessionReduceDatumIterator
     }
@@ -312,6 +685,8 @@ export declare namespace sessionReduce {
         watermark: Date
         eventTime: Date
         headers: Record<string, string>
+        /** Context of the span the datum is handled in, if its headers carry a `traceparent`. */
+        traceContext?: TraceContext
     }
     export interface Message {
         /** optional keys */
@@ -323,6 +698,15 @@ export declare namespace sessionReduce {
     }
     /** Drop a Message, do not forward to the next vertex. */
     export function messageToDrop(): Message
+    /**
+     * The handlers of one session, bound to the state of that session.
+     * Only to be used as part of internal implementation, not to be exposed to final users
+     */
+    export interface SessionHandlers {
+        sessionReduceFn: (args: SessionReduceCallbackArgs) => () => Promise<Message | null>
+        accumulatorFn: () => Promise<Buffer>
+        mergeAccumulatorFn: (accumulator: Buffer) => Promise<void>
+    }
     export interface SessionReduceDatumIteratorResult {
         value?: Datum
         done: boolean
@@ -332,10 +716,66 @@ export declare namespace sessionReduce {
 export declare namespace sideInput {
     export class SideInputAsyncServer {
         constructor(sideInputFn: () => Promise<Buffer | null>)
-        start(sockFile?: string | undefined | null, infoFile?: string | undefined | null): Promise<void>
+        start(options?: ServerOptions | undefined | null): Promise<void>
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
+    }
+    /**
+     * Reads the side inputs numaflow mounts into the containers of the UDFs of a pipeline vertex.
+     *
+     * `start()` reads every side input in the directory and watches it with inotify, caching the
+     * latest value of each side input by name. Handlers read the cache with `get()`, and callbacks
+     * registered with `onChange()` are called whenever a side input is updated or removed.
+     *
+     * Files whose name starts with a dot or ends in `.tmp` are ignored, so that side inputs written
+     * to a temporary file and moved into place are only read once complete.
+     */
+    export class SideInputReader {
+        constructor(options?: SideInputReaderOptions | undefined | null)
+        /**
+         * Reads the side inputs in the directory and starts watching it for changes, until `stop()`
+         * is called. Rejects if the directory can't be watched or read.
+         */
+        start(): Promise<void>
+        /** Stops watching the directory, the cached side inputs can still be read. */
         stop(): void
+        /** The latest value of the side input named `name`, null if there is none. */
+        get(name: string): SideInputValue | null
+        /** Names of the side inputs read, sorted. */
+        names(): Array<string>
+        /** Calls `callback` whenever a side input is updated or removed. Errors it throws are logged. */
+        onChange(callback: (change: SideInputChange) => void): void
     }
     export const DIR_PATH: string
+    /** A side input that was updated or removed, passed to the callbacks registered with `onChange()`. */
+    export interface SideInputChange {
+        /** Name of the side input. */
+        name: string
+        /** The new value, null when the side input was removed. */
+        value?: SideInputValue
+    }
+    /** Options of a `SideInputReader`. */
+    export interface SideInputReaderOptions {
+        /** Directory the side inputs are mounted in, one file per side input. Defaults to `DIR_PATH`. */
+        dirPath?: string
+        /**
+         * Codec the values are decoded with into `data`: `json`, `msgpack`, `cbor` or `raw`. Defaults
+         * to `raw`, values are only handed over as bytes.
+         */
+        codec?: string
+    }
+    /** The latest value of a side input. */
+    export interface SideInputValue {
+        /** Name of the side input, the name of its file. */
+        name: string
+        /** The content of the file. */
+        value: Buffer
+        /** The value decoded by the codec of the reader, null with the raw codec or an empty value. */
+        data?: any
+        /** Why the value could not be decoded by the codec of the reader. */
+        decodeError?: string
+        /** When the reader read the value. */
+        updatedAt: Date
+    }
 }
 
 export declare namespace sink {
@@ -345,27 +785,38 @@ export declare namespace sink {
      */
     export class SinkAsyncServer {
         /** Create a new SinkAsyncServer with the given callback. */
-        constructor(sinkFn: (arg: SinkDatumIterator) => Promise<Array<SinkResponse>>)
+        constructor(
+            sinkFn: (arg: SinkDatumIterator) => Promise<Array<SinkResponse>>,
+            retryPolicy?: RetryPolicy | undefined | null,
+        )
         /** Start the SinkAsyncServer with the given callback */
-        start(socketPath?: string | undefined | null, serverInfoPath?: string | undefined | null): Promise<void>
+        start(options?: ServerOptions | undefined | null): Promise<void>
         /** Stop the sink server */
-        stop(): void
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
     }
     export class SinkDatum {
         /** Set of keys in the (key, value) terminology of map/reduce paradigm. */
         keys: Array<string>
         /** ID is the unique id of the message to be sent to the Sink. */
         id: string
+        /** The value of the datum, the same buffer on every read. */
         getValue(): Buffer
         getWatermark(): Date
         getEventtime(): Date
         getHeaders(): Record<string, string>
-        userMetadata(): SinkUserMetadata
-        systemMetadata(): SinkSystemMetadata
+        userMetadata(): metadata.UserMetadata
+        systemMetadata(): metadata.SystemMetadata
+        /** Context of the span the datum is handled in, if its headers carry a `traceparent`. */
+        traceContext(): TraceContext | null
+        /** The value decoded by the codec of the server, null with the raw codec or an empty value. */
+        data(): any | null
+        /** Why the value could not be decoded, only set when the decode error policy is `pass`. */
+        decodeError(): string | null
     }
     export class SinkDatumIterator {
         /**
-         * Returns the next datum from the stream, or None if the stream has ended
+         * Returns the next datum from the stream, or None if the stream has ended. Datums whose value
+         * can't be decoded are skipped unless the decode error policy is `pass`.
          * # SAFETY
          *
          * Async function with &mut self is unsafe in napi because the self is also owned
@@ -376,9 +827,13 @@ export declare namespace sink {
     export class SinkMessage {
         /**
          * Create a new Message with the given value.
-         * Keys and user_metadata are optional.
+         * Keys and user_metadata are optional, the user metadata maps groups to their key/value pairs.
          */
-        constructor(value: Buffer, keys?: Array<string> | undefined | null)
+        constructor(
+            value: Buffer,
+            keys?: Array<string> | undefined | null,
+            userMetadata?: Record<string, Record<string, Buffer>> | undefined | null,
+        )
     }
     export class SinkResponse {
         static failure(id: string, err: string): SinkResponse
@@ -394,18 +849,6 @@ export declare namespace sink {
         len(): number
         isEmpty(): boolean
     }
-    export class SinkSystemMetadata {
-        constructor()
-        getGroups(): Array<string>
-        getKeys(group: string): Array<string>
-        getValue(group: string, key: string): Buffer
-    }
-    export class SinkUserMetadata {
-        constructor()
-        getGroups(): Array<string>
-        getKeys(group: string): Array<string>
-        getValue(group: string, key: string): Buffer
-    }
 }
 
 export declare namespace source {
@@ -414,6 +857,11 @@ export declare namespace source {
         get numRecords(): number
         /** Get the timeout in milliseconds. */
         get timeoutMs(): number
+        /**
+         * Get the context of the trace started for the read. It is set as the `traceparent` header of
+         * the messages read that don't have one.
+         */
+        get traceContext(): TraceContext
     }
     export class SourceAsyncServer {
         constructor(
@@ -424,19 +872,9 @@ export declare namespace source {
             partition_fn: () => Promise<number[] | null>,
         )
         /** Start the SourceAsyncServer with the given callback */
-        start(socketPath?: string | undefined | null, serverInfoPath?: string | undefined | null): Promise<void>
+        start(options?: ServerOptions | undefined | null): Promise<void>
         /** Stop the SourceAsyncServer server */
-        stop(): void
-    }
-    export class SourceUserMetadata {
-        constructor()
-        getGroups(): Array<string>
-        getKeys(group: string): Array<string>
-        getValue(group: string, key: string): Buffer
-        createGroup(group: string): void
-        addKv(group: string, key: string, value: Buffer): void
-        removeKey(group: string, key: string): void
-        removeGroup(group: string): void
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
     }
     export interface Message {
         /** The payload of the message. */
@@ -462,9 +900,12 @@ export declare namespace source {
 
 export declare namespace sourceTransform {
     export class SourceTransformAsyncServer {
-        constructor(sourceTransformFn: (arg: SourceTransformDatum) => Promise<Array<SourceTransformMessage>>)
-        start(sockFile?: string | undefined | null, infoFile?: string | undefined | null): Promise<void>
-        stop(): void
+        constructor(
+            sourceTransformFn: (arg: SourceTransformDatum) => Promise<Array<SourceTransformMessage>>,
+            retryPolicy?: RetryPolicy | undefined | null,
+        )
+        start(options?: ServerOptions | undefined | null): Promise<void>
+        stop(timeoutMs?: number | undefined | null): Promise<DrainSummary>
     }
     export class SourceTransformDatum {
         /** Set of keys in the (key, value) terminology of map/reduce paradigm. */
@@ -475,32 +916,23 @@ export declare namespace sourceTransform {
             watermark: Date,
             eventTime: Date,
             headers: Record<string, string>,
-            userMetadata?: SourceTransformUserMetadata | undefined | null,
-            systemMetadata?: SourceTransformSystemMetadata | undefined | null,
+            userMetadata?: metadata.UserMetadata | undefined | null,
+            systemMetadata?: metadata.SystemMetadata | undefined | null,
         )
+        /** The value of the datum, the same buffer on every read. */
         get value(): Buffer
         get watermark(): Date
         get eventTime(): Date
         get headers(): Record<string, string>
-        get userMetadata(): SourceTransformUserMetadata | null
-        get systemMetadata(): SourceTransformSystemMetadata | null
-        set userMetadata(userMetadata: SourceTransformUserMetadata)
-    }
-    export class SourceTransformSystemMetadata {
-        constructor()
-        getGroups(): Array<string>
-        getKeys(group: string): Array<string>
-        getValue(group: string, key: string): Buffer
-    }
-    export class SourceTransformUserMetadata {
-        constructor()
-        getGroups(): Array<string>
-        getKeys(group: string): Array<string>
-        getValue(group: string, key: string): Buffer
-        createGroup(group: string): void
-        addKv(group: string, key: string, value: Buffer): void
-        removeKey(group: string, key: string): void
-        removeGroup(group: string): void
+        /** Context of the span the datum is handled in, if its headers carry a `traceparent`. */
+        get traceContext(): TraceContext | null
+        get userMetadata(): metadata.UserMetadata | null
+        get systemMetadata(): metadata.SystemMetadata | null
+        /** The value decoded by the codec of the server, null with the raw codec or an empty value. */
+        get data(): any | null
+        /** Why the value could not be decoded, only set when the decode error policy is `pass`. */
+        get decodeError(): string | null
+        set userMetadata(userMetadata: metadata.UserMetadata)
     }
     export function messageToDrop(eventTime: Date): SourceTransformMessage
     export interface SourceTransformMessage {
@@ -511,6 +943,8 @@ export declare namespace sourceTransform {
         keys?: Array<string>
         /** Value is the value passed to the next vertex. */
         value: Buffer
+        /** Data encoded by the codec of the server into the value passed on, replaces `value`. */
+        data?: any
         /**
          * Time for the given event. This will be used for tracking watermarks. If cannot be derived, set it to the incoming
          * event_time from the [`SourceTransformRequest`].
@@ -574,11 +574,15 @@ if (!nativeBinding) {
 module.exports = nativeBinding
 module.exports.accumulator = nativeBinding.accumulator
 module.exports.batchmap = nativeBinding.batchmap
+module.exports.configureLogging = nativeBinding.configureLogging
+module.exports.loadServerConfig = nativeBinding.loadServerConfig
 module.exports.map = nativeBinding.map
 module.exports.mapstream = nativeBinding.mapstream
+module.exports.metadata = nativeBinding.metadata
 module.exports.reduce = nativeBinding.reduce
 module.exports.reduceStream = nativeBinding.reduceStream
 module.exports.sessionReduce = nativeBinding.sessionReduce
+module.exports.setLogHandler = nativeBinding.setLogHandler
 module.exports.sideInput = nativeBinding.sideInput
 module.exports.sink = nativeBinding.sink
 module.exports.source = nativeBinding.source
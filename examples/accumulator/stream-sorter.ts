@@ -99,15 +99,9 @@ async function main() {
     // bind streamSorter to streamSorter.streamSorter
     const server = new accumulator.AsyncServer(streamSorter.streamSorter.bind(streamSorter))
 
-    const shutdown = () => {
-        server.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
-
     console.log('Starting batchMap server')
     //await server.start("/tmp/var/run/numaflow/accumulator.sock", "/tmp/var/run/numaflow/accumulator-info.info")
-    await server.start()
+    await server.start({ handleSignals: true })
 }
 
 main().catch(console.error)
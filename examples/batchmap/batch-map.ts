@@ -19,14 +19,8 @@ async function batchMapFn(datums: AsyncIterableIterator<batchmap.Datum>): Promis
 async function main() {
     const batchMapper = new batchmap.AsyncServer(batchMapFn)
 
-    const shutdown = () => {
-        batchMapper.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
-
     console.log('Starting batchMap server')
-    await batchMapper.start()
+    await batchMapper.start({ handleSignals: true })
 }
 
 main().catch(console.error)
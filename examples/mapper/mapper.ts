@@ -29,13 +29,7 @@ async function main() {
     console.log('Starting mapper')
     const server = new map.AsyncServer(mapFn)
 
-    const shutdown = () => {
-        server.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
-
-    await server.start('/tmp/mapper.sock', '/tmp/mapper.info')
+    await server.start({ socketPath: '/tmp/mapper.sock', serverInfoPath: '/tmp/mapper.info', handleSignals: true })
     console.log('Mapper finished')
 }
 
@@ -14,14 +14,8 @@ async function* streamMapFn(datum: mapstream.Datum) {
 async function main() {
     const server = new mapstream.AsyncServer(streamMapFn)
 
-    const shutdown = () => {
-        server.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
-
     console.log('Starting map stream server')
-    await server.start()
+    await server.start({ handleSignals: true })
 }
 
 main().catch(console.error)
@@ -17,14 +17,8 @@ async function reduceFn(
 async function main() {
     const server = new reduce.AsyncServer(reduceFn)
 
-    const shutdown = () => {
-        server.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
-
     console.log('Starting reduce async server')
-    await server.start()
+    await server.start({ handleSignals: true })
 }
 
 main().catch(console.error)
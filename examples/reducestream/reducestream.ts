@@ -18,14 +18,8 @@ async function* reduceStreamFn(
 async function main() {
     const server = new reduceStream.AsyncServer(reduceStreamFn)
 
-    const shutdown = () => {
-        server.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
-
     console.log('Starting reduce stream async server')
-    await server.start()
+    await server.start({ handleSignals: true })
 }
 
 main().catch(console.error)
@@ -22,21 +22,15 @@ class SessionReduceCounter implements sessionReduce.SessionReducer {
     }
 
     async mergeAccumulatorFn(accumulator: Buffer): Promise<void> {
-        this.counter += accumulator.readUInt8(0)
+        this.counter += parseInt(accumulator.toString())
     }
 }
 
 async function main() {
-    const server = new sessionReduce.AsyncServer(new SessionReduceCounter())
-
-    const shutdown = () => {
-        server.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
+    const server = sessionReduce.AsyncServer.withFactory(() => new SessionReduceCounter())
 
     console.log('Starting session reduce async server')
-    await server.start()
+    await server.start({ handleSignals: true })
 }
 
 main().catch(console.error)
@@ -19,14 +19,8 @@ async function main() {
     let sideInputer = new SideInputer()
     const server = new sideInput.SideInputAsyncServer(sideInputer.retrieveSideInput.bind(sideInputer))
 
-    const shutdown = () => {
-        server.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
-
     console.log('Starting side-input async server')
-    await server.start()
+    await server.start({ handleSignals: true })
 }
 
 main().catch(console.error)
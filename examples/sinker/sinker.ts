@@ -18,15 +18,8 @@ async function sinkFn(datums: AsyncIterableIterator<sink.Datum>): Promise<sink.R
 async function main() {
     const sinker = new sink.AsyncServer(sinkFn)
 
-    const shutdown = () => {
-        console.log('Received shutdown signal')
-        sinker.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
-
     console.log('Starting sink server')
-    await sinker.start()
+    await sinker.start({ handleSignals: true })
 }
 
 main().catch(console.error)
@@ -14,14 +14,8 @@ async function sourceTransformFn(datum: sourceTransform.Datum): Promise<sourceTr
 async function main() {
     const server = new sourceTransform.AsyncServer(sourceTransformFn)
 
-    const shutdown = () => {
-        server.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
-
     console.log('Starting source transformer server')
-    await server.start()
+    await server.start({ handleSignals: true })
 }
 
 main().catch(console.error)
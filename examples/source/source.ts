@@ -51,14 +51,8 @@ class Sourcer implements source.Sourcer {
 async function main() {
     const server = new source.AsyncServer(new Sourcer())
 
-    const shutdown = () => {
-        server.stop()
-    }
-    process.on('SIGTERM', shutdown)
-    process.on('SIGINT', shutdown)
-
     console.log('Starting source async server')
-    await server.start()
+    await server.start({ handleSignals: true })
 }
 
 main().catch(console.error)
@@ -36,7 +36,291 @@
  *
  * @module @numaproj/numaflow-js
  */
+import { type WorkerOptions } from 'worker_threads';
 import binding from './binding';
+/**
+ * Retry policy for transient failures of a handler, accepted by the map, source transform, batch map
+ * and sink servers.
+ *
+ * Only errors thrown by the handler are retried. Once the attempts are exhausted the request fails
+ * as it would without a policy. The delay between two attempts never exceeds `maxBackoffMs`, and the
+ * servers throw on a `multiplier` or `jitter` that is not a finite number of at least 0.
+ *
+ * @example
+ * ```typescript
+ * const server = new map.AsyncServer(mapFn, {
+ *   maxAttempts: 5,
+ *   initialBackoffMs: 200,
+ *   retryOn: ['FetchError'],
+ * });
+ * ```
+ */
+export interface RetryPolicy {
+    /** Total number of attempts, including the first one. Defaults to 3. */
+    maxAttempts?: number;
+    /** Delay before the first retry in milliseconds. Defaults to 100. */
+    initialBackoffMs?: number;
+    /** Upper bound of the delay between two attempts in milliseconds. Defaults to 10000. */
+    maxBackoffMs?: number;
+    /** Factor the delay grows by after every retry. Defaults to 2. */
+    multiplier?: number;
+    /** Fraction of the delay, between 0 and 1, that is randomized. Defaults to 0.2. */
+    jitter?: number;
+    /** Names of the errors that are retried, e.g. `['FetchError']`. Every error is retried when not set. */
+    retryOn?: string[];
+}
+/**
+ * Options accepted by `start()` of every server.
+ *
+ * The same options can be set with environment variables or in a JSON or YAML config file. Options passed to
+ * `start()` take precedence over the environment, which takes precedence over the config file.
+ *
+ * | Option              | Environment variable             |
+ * | ------------------- | -------------------------------- |
+ * | `socketPath`        | `NUMAFLOW_SERVER_SOCKET_PATH`    |
+ * | `serverInfoPath`    | `NUMAFLOW_SERVER_INFO_PATH`      |
+ * | `maxMessageSize`    | `NUMAFLOW_GRPC_MAX_MESSAGE_SIZE` |
+ * | `shutdownTimeoutMs` | `NUMAFLOW_SHUTDOWN_TIMEOUT_MS`   |
+ * | `handleSignals`     | `NUMAFLOW_HANDLE_SIGNALS`        |
+ * | `metricsPort`       | `NUMAFLOW_METRICS_PORT`          |
+ * | `metricsAddress`    | `NUMAFLOW_METRICS_ADDRESS`       |
+ * | `maxInFlight`       | `NUMAFLOW_MAX_IN_FLIGHT`         |
+ * | `maxInFlightPerKey` | `NUMAFLOW_MAX_IN_FLIGHT_PER_KEY` |
+ * | `handlerTimeoutMs`  | `NUMAFLOW_HANDLER_TIMEOUT_MS`    |
+ * | `codec`             | `NUMAFLOW_CODEC`                 |
+ * | `decodeErrorPolicy` | `NUMAFLOW_DECODE_ERROR_POLICY`   |
+ *
+ * The config file is read from the path in `NUMAFLOW_SERVER_CONFIG_FILE`, it holds the options under the same
+ * names as above. Files ending in `.yaml` or `.yml` are parsed as YAML, all others as JSON.
+ *
+ * @example
+ * ```typescript
+ * await server.start({ maxMessageSize: 128 * 1024 * 1024, shutdownTimeoutMs: 30000 });
+ * ```
+ */
+export interface ServerOptions {
+    /** Path of the Unix domain socket the gRPC server listens on. Defaults to the path Numaflow expects for the server type. */
+    socketPath?: string;
+    /** Path of the file the server information is written to on start up. Defaults to the path Numaflow expects. */
+    serverInfoPath?: string;
+    /** Maximum size of an encoded or decoded gRPC message in bytes. Defaults to 64 MiB. */
+    maxMessageSize?: number;
+    /** Time in milliseconds `stop()` waits for in-flight requests before the server is closed forcefully. */
+    shutdownTimeoutMs?: number;
+    /**
+     * Stop the server gracefully, as `stop()` does, when the process receives SIGTERM or SIGINT. The signals no
+     * longer terminate the process while the server runs, and their default action is not restored afterwards.
+     * Defaults to false.
+     */
+    handleSignals?: boolean;
+    /**
+     * Port of the HTTP endpoint serving the metrics of the servers in the Prometheus text format at `/metrics`.
+     * Metrics are not served when not set.
+     */
+    metricsPort?: number;
+    /**
+     * IP address the metrics endpoint listens on, e.g. `127.0.0.1` to only serve the metrics within the pod. Defaults
+     * to `0.0.0.0`, every IPv4 interface.
+     */
+    metricsAddress?: string;
+    /**
+     * Maximum number of requests handed to the handler at the same time, further requests wait until one of them is
+     * done. Only applies to the map, map stream and source transform servers. Unbounded when not set.
+     */
+    maxInFlight?: number;
+    /**
+     * Maximum number of requests with the same keys handed to the handler at the same time, in addition to
+     * `maxInFlight`. Only applies to the map, map stream and source transform servers. Unbounded when not set.
+     */
+    maxInFlightPerKey?: number;
+    /**
+     * Time in milliseconds a call of the handler may take before it is abandoned. A timed out map or batch map request
+     * fails like one whose handler threw, a timed out sink batch is answered with a failure response for every message.
+     * Only applies to the map, batch map and sink servers. Calls are not timed out when not set.
+     */
+    handlerTimeoutMs?: number;
+    /**
+     * Codec the datum values are decoded with into `data`, and the `data` of messages is encoded with. Only applies
+     * to the map, map stream, batch map, source transform and sink servers. Defaults to `raw`, values are only handed
+     * over as bytes.
+     */
+    codec?: Codec;
+    /**
+     * What happens to a datum whose value the codec can't decode: `fail` fails the request like a handler that threw,
+     * `drop` drops the datum without calling the handler, and `pass` hands it to the handler with the error in
+     * `decodeError`. Defaults to `fail`.
+     */
+    decodeErrorPolicy?: DecodeErrorPolicy;
+}
+/**
+ * Encoding of datum values and message payloads, see {@link ServerOptions.codec}.
+ */
+export type Codec = 'json' | 'msgpack' | 'cbor' | 'raw';
+/**
+ * What happens to a datum whose value can't be decoded, see {@link ServerOptions.decodeErrorPolicy}.
+ */
+export type DecodeErrorPolicy = 'fail' | 'drop' | 'pass';
+/**
+ * Configuration a server runs with, see {@link loadServerConfig}.
+ */
+export type ServerConfig = binding.ServerConfig;
+/**
+ * Returns the configuration a server started with `options` runs with, i.e. the options merged with the
+ * `NUMAFLOW_*` environment variables and the config file. Useful to log the configuration on start up.
+ *
+ * @param options - Options that would be passed to `start()`
+ * @throws If an environment variable has an invalid value or the config file can't be read
+ *
+ * @example
+ * ```typescript
+ * console.log('Starting mapper with', loadServerConfig());
+ * await server.start();
+ * ```
+ */
+export declare function loadServerConfig(options?: ServerOptions): ServerConfig;
+/**
+ * Outcome of stopping a server, resolved by `stop()`.
+ */
+export type DrainSummary = binding.DrainSummary;
+/**
+ * Level and format of the native logs, see {@link configureLogging}.
+ */
+export interface LogOptions {
+    /**
+     * Most verbose level that is logged. Defaults to `NUMAFLOW_LOG_LEVEL`, or `info` when it is not set.
+     */
+    level?: 'off' | 'error' | 'warn' | 'info' | 'debug' | 'trace';
+    /**
+     * `json` to log a JSON object per line, `text` to log plain lines. Defaults to `NUMAFLOW_LOG_FORMAT`, or `json`
+     * when it is not set.
+     */
+    format?: 'json' | 'text';
+}/**
+ * A log record of the native layer, passed to the handler set with {@link setLogHandler}.
+ */
+export type LogRecord = binding.LogRecord;
+/**
+ * [W3C trace context](https://www.w3.org/TR/trace-context/) of the span a datum is handled in. Spans created by a
+ * handler can be parented on it by extracting its `traceparent` with the OpenTelemetry propagation API.
+ */
+export type TraceContext = binding.TraceContext;
+/**
+ * Sets the level and format of the logs written by the servers, options that are not set keep their current value.
+ *
+ * @param options - Log level and format
+ * @throws If the level or the format is not valid
+ *
+ * @example
+ * ```typescript
+ * configureLogging({ level: 'debug', format: 'text' });
+ * ```
+ */
+export declare function configureLogging(options: LogOptions): void;
+/**
+ * Routes the logs of the servers to `handler` instead of stdout and stderr, e.g. into the application's logger.
+ * Passing `null` restores the default output.
+ *
+ * @param handler - Called with every log record, or `null`
+ *
+ * @example
+ * ```typescript
+ * import pino from 'pino';
+ *
+ * const logger = pino();
+ * setLogHandler(({ level, message, fields }) => logger[level](fields, message));
+ * ```
+ */
+export declare function setLogHandler(handler: ((record: LogRecord) => void) | null): void;
+/**
+ * A server a {@link CompositeServer} runs, e.g. the `AsyncServer` of any namespace.
+ */
+export interface UdfServer {
+    start(options?: ServerOptions): Promise<void>;
+    stop(timeoutMs?: number): Promise<DrainSummary>;
+}
+/**
+ * Where a server of a {@link CompositeServer} is: not started yet, serving, stopped, or failed to start or run.
+ */
+export type ServerState = 'idle' | 'running' | 'stopped' | 'failed';
+/**
+ * Status of a server of a {@link CompositeServer}, returned by {@link CompositeServer.status}.
+ */
+export interface ServerStatus {
+    /** Name the server was added with. */
+    name: string;
+    /** Where the server is in its lifecycle. */
+    state: ServerState;
+    /** Why the server failed, only set in the `failed` state. */
+    error?: Error;
+}
+/**
+ * Runs several servers in one process, e.g. a user-defined source together with its source transformer, or a map
+ * together with a side input server.
+ *
+ * The servers share one lifecycle: `start()` starts all of them, and once one of them stops or fails the others are
+ * stopped as well, so the process never keeps serving half a vertex. `stop()` stops all of them at once. The options
+ * passed to `start()` apply to every server, the options a server was added with take precedence, e.g. its
+ * `socketPath`. Servers started with the same `metricsPort` share one metrics endpoint.
+ *
+ * @example
+ * ```typescript
+ * const server = new CompositeServer()
+ *   .add('source', new source.AsyncServer(sourcer))
+ *   .add('transformer', new sourceTransform.AsyncServer(transformFn));
+ *
+ * await server.start({ handleSignals: true, metricsPort: 9090 });
+ * ```
+ */
+export declare class CompositeServer {
+    private readonly entries;
+    private running;
+    /**
+     * Add a server, before the composite server is started.
+     * @param name - Name of the server in {@link status} and the summary of {@link stop}, unique within the composite
+     * @param server - The server, e.g. a `map.AsyncServer`
+     * @param options - Optional options of this server, taking precedence over the options passed to `start()`
+     * @returns The composite server, for chaining
+     * @throws If the composite server is running or a server with the same name was added
+     */
+    add(name: string, server: UdfServer, options?: ServerOptions): this;
+    /**
+     * Start every server and wait until all of them stopped.
+     * @param options - Optional options of every server
+     * @throws The error of the first server that failed, once the others stopped
+     */
+    start(options?: ServerOptions): Promise<void>;
+    /**
+     * Stop every server gracefully, draining them at the same time.
+     * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+     * @returns How many requests each server drained and abandoned, by name
+     */
+    stop(timeoutMs?: number): Promise<Record<string, DrainSummary>>;
+    /**
+     * The status of every server, in the order they were added.
+     */
+    status(): ServerStatus[];
+    private run;
+}
+/**
+ * Metadata namespace with the user and system metadata of messages. The map, source transform, sink and source
+ * namespaces re-export its classes for the metadata of their datums and messages.
+ *
+ * Values are buffers, `getString` and `addString` read and write them as UTF-8 strings. `toJSON` encodes the values
+ * as base64, so `JSON.stringify` can write metadata that `UserMetadata.fromJSON` reads back.
+ *
+ * @example
+ * ```typescript
+ * import { metadata } from '@numaproj/numaflow-js';
+ *
+ * const userMetadata = new metadata.UserMetadata({ tracking: { source: Buffer.from('api') } });
+ * userMetadata.merge(datum.userMetadata);
+ * for (const { group, key, value } of userMetadata.entries()) {
+ *   console.log(group, key, value.toString());
+ * }
+ * const copy = metadata.UserMetadata.fromJSON(JSON.parse(JSON.stringify(userMetadata)));
+ * ```
+ */
+export import metadata = binding.metadata;
 /**
  * Side Input namespace provides functionality for handling side inputs in Numaflow pipelines.
  *
@@ -84,94 +368,22 @@ export declare namespace sourceTransform {
     /** @internal Native message type from the binding layer */
     export type NativeMessage = binding.sourceTransform.SourceTransformMessage;
     /**
-     * System-provided metadata attached to messages.
-     * Contains read-only system information organized by groups.
+     * System-provided metadata attached to messages, read-only to handlers.
+     * Shared by the namespaces that carry metadata, see {@link metadata.SystemMetadata}.
      */
-    export interface SystemMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>;
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>;
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer;
-    }
+    export type SystemMetadata = metadata.SystemMetadata;
     /**
-     * User-defined metadata that can be attached to messages.
-     * Allows storing arbitrary key-value pairs organized by groups.
+     * User-defined metadata that can be attached to messages, groups of key/value pairs.
+     * Shared by the namespaces that carry metadata, see {@link metadata.UserMetadata}.
      *
      * @example
      * ```typescript
-     * const metadata = new sourceTransform.UserMetadata();
-     * metadata.createGroup('tracking');
-     * metadata.addKv('tracking', 'source', Buffer.from('api'));
+     * const userMetadata = new sourceTransform.UserMetadata();
+     * userMetadata.addString('tracking', 'source', 'api');
      * ```
      */
-    export interface UserMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>;
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>;
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer;
-        /**
-         * Create a new group in the metadata.
-         * @param group - The group name to create
-         */
-        createGroup(group: string): void;
-        /**
-         * Add a key-value pair to a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @param value - The value as a Buffer
-         */
-        addKv(group: string, key: string, value: Buffer): void;
-        /**
-         * Remove a key from a group.
-         * @param group - The group name
-         * @param key - The key to remove
-         */
-        removeKey(group: string, key: string): void;
-        /**
-         * Remove an entire group from the metadata.
-         * @param group - The group name to remove
-         */
-        removeGroup(group: string): void;
-    }
-    /**
-     * Constructor for creating UserMetadata instances.
-     *
-     * @example
-     * ```typescript
-     * const metadata = new sourceTransform.UserMetadata();
-     * ```
-     */
-    export const UserMetadata: {
-        new (): UserMetadata;
-    };
+    export type UserMetadata = metadata.UserMetadata;
+    export const UserMetadata: typeof metadata.UserMetadata;
     /**
      * Represents an input datum received by the source transform handler.
      *
@@ -192,6 +404,12 @@ export declare namespace sourceTransform {
         userMetadata: UserMetadata | null;
         /** System-provided metadata, if any */
         systemMetadata: SystemMetadata | null;
+        /** Context of the span the datum is handled in, `null` without a valid `traceparent` header */
+        traceContext: TraceContext | null;
+        /** The value decoded by the codec of the server, `null` with the raw codec or an empty value */
+        data: unknown;
+        /** Why the value could not be decoded, only set when the decode error policy is `pass` */
+        decodeError: string | null;
         /** @internal */
         constructor(nativeDatum: NativeDatum);
     }
@@ -222,6 +440,8 @@ export declare namespace sourceTransform {
     export class Message {
         /** The message payload */
         value: Buffer;
+        /** Data the codec of the server encodes into the payload, replaces `value` when set */
+        data?: unknown;
         /** Event time to assign to this message */
         eventTime: Date;
         /** Optional keys for routing */
@@ -243,6 +463,14 @@ export declare namespace sourceTransform {
          * @returns A message marked for dropping
          */
         static toDrop(eventTime: Date): Message;
+        /**
+         * Create a message whose payload is `data` encoded by the codec of the server.
+         * @param data - The data to encode, e.g. a plain object with the `json` codec
+         * @param eventTime - The event time to assign to this message
+         * @param options - Optional keys, tags, and metadata
+         * @returns A message carrying the data
+         */
+        static fromData(data: unknown, eventTime: Date, options?: MessageOptions): Message;
     }
     /**
      * Async server for handling source transform operations.
@@ -273,18 +501,27 @@ export declare namespace sourceTransform {
         /**
          * Create a new source transform server.
          * @param sourceTransformFn - Async function that transforms input datum to output messages
+         * @param retryPolicy - Optional policy for retrying failed invocations of the function
+         */
+        constructor(sourceTransformFn: (message: Datum) => Promise<Message[]>, retryPolicy?: RetryPolicy);
+        /**
+         * Start the source transform server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
          */
-        constructor(sourceTransformFn: (message: Datum) => Promise<Message[]>);
+        start(options?: ServerOptions): Promise<void>;
         /**
          * Start the source transform server.
-         * @param socketPath - Optional custom Unix socket path (defaults to Numaflow standard)
+         * @param socketPath - Optional custom Unix socket path
          * @param serverInfoPath - Optional path for server info file
          */
         start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>;
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void;
+        stop(timeoutMs?: number): Promise<DrainSummary>;
     }
     export {};
 }
@@ -351,6 +588,11 @@ export declare namespace accumulator {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>;
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext;
         /**
          * Unique identifier for the message.
          * Used for deduplication.
@@ -409,24 +651,57 @@ export declare namespace accumulator {
          */
         static toDrop(): Message;
     }
+    /**
+     * Callback function type for accumulator handlers.
+     * Receives the datums of a key, yields the output messages.
+     */
+    type AccumulatorFnCallback = (datum: AsyncIterableIterator<Datum>) => AsyncIterable<Message>;
+    /**
+     * Handler of one key, created by an AccumulatorFactory.
+     */
+    interface Accumulator {
+        /** Accumulates the datums of the key, yielding the output messages */
+        accumulatorFn: AccumulatorFnCallback;
+    }
+    /**
+     * Creates the Accumulator of a new key.
+     * Called once per key, the Accumulator is released once the key is closed.
+     */
+    type AccumulatorFactory = () => Accumulator;
     /**
      * AsyncServer is a wrapper around a JavaScript callable that will be passed by the user to process the
      * data received by the Sink.
      */
     class AsyncServer {
         private readonly nativeServer;
+        /**
+         * Create a new accumulator server whose keys each get their own Accumulator, so state can be kept on the
+         * Accumulator.
+         * @param createAccumulator - Factory called once per key
+         */
+        static withFactory(createAccumulator: AccumulatorFactory): AsyncServer;
         /**
          * Create a new Sink with the given callback.
          */
-        constructor(accumulatorFn: (datum: AsyncIterableIterator<Datum>) => AsyncIterable<Message>);
+        constructor(accumulatorFn: AccumulatorFnCallback);
         /**
          * Start the AsyncServer server with the given callback
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>;
+        /**
+         * Start the AsyncServer server with the given callback
+         * @param socketPath - Optional custom Unix socket path
+         * @param serverInfoPath - Optional path for server info file
          */
         start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>;
         /**
-         * Stop the AsyncServer server
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void;
+        stop(timeoutMs?: number): Promise<DrainSummary>;
     }
 }
 /**
@@ -463,95 +738,22 @@ export declare namespace accumulator {
  */
 export declare namespace map {
     /**
-     * System-provided metadata attached to messages.
-     * Contains read-only system information organized by groups.
+     * System-provided metadata attached to messages, read-only to handlers.
+     * Shared by the namespaces that carry metadata, see {@link metadata.SystemMetadata}.
      */
-    interface SystemMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>;
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>;
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer;
-    }
-    /**
-     * User-defined metadata that can be attached to messages.
-     * Allows storing arbitrary key-value pairs organized by groups.
-     *
-     * @example
-     * ```typescript
-     * const metadata = new map.UserMetadata();
-     * metadata.createGroup('tracking');
-     * metadata.addKv('tracking', 'source', Buffer.from('api'));
-     * metadata.addKv('tracking', 'version', Buffer.from('1.0'));
-     * ```
-     */
-    interface UserMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>;
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>;
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer;
-        /**
-         * Create a new group in the metadata.
-         * @param group - The group name to create
-         */
-        createGroup(group: string): void;
-        /**
-         * Add a key-value pair to a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @param value - The value as a Buffer
-         */
-        addKv(group: string, key: string, value: Buffer): void;
-        /**
-         * Remove a key from a group.
-         * @param group - The group name
-         * @param key - The key to remove
-         */
-        removeKey(group: string, key: string): void;
-        /**
-         * Remove an entire group from the metadata.
-         * @param group - The group name to remove
-         */
-        removeGroup(group: string): void;
-    }
+    type SystemMetadata = metadata.SystemMetadata;
     /**
-     * Constructor for creating UserMetadata instances.
+     * User-defined metadata that can be attached to messages, groups of key/value pairs.
+     * Shared by the namespaces that carry metadata, see {@link metadata.UserMetadata}.
      *
      * @example
      * ```typescript
-     * const metadata = new map.UserMetadata();
+     * const userMetadata = new map.UserMetadata();
+     * userMetadata.addString('tracking', 'source', 'api');
      * ```
      */
-    const UserMetadata: {
-        new (): UserMetadata;
-    };
+    type UserMetadata = metadata.UserMetadata;
+    const UserMetadata: typeof metadata.UserMetadata;
     /**
      * Input datum received by the map handler.
      * Contains the message data, keys, timestamps, and optional metadata.
@@ -580,6 +782,11 @@ export declare namespace map {
          * HTTP-style headers attached to the message.
          */
         readonly headers: Record<string, string>;
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * `null` when the datum has no valid `traceparent` header.
+         */
+        readonly traceContext: TraceContext | null;
         /**
          * User-defined metadata, if any was attached to this message.
          */
@@ -588,6 +795,15 @@ export declare namespace map {
          * System-provided metadata, if any.
          */
         readonly systemMetadata: SystemMetadata | null;
+        /**
+         * The value decoded by the codec of the server, see {@link ServerOptions.codec}.
+         * `null` with the raw codec or an empty value.
+         */
+        readonly data: unknown;
+        /**
+         * Why the value could not be decoded, only set when the decode error policy is `pass`.
+         */
+        readonly decodeError: string | null;
     }
     /**
      * Options for creating a map output message.
@@ -618,6 +834,8 @@ export declare namespace map {
     class Message {
         /** The message payload */
         value: Buffer;
+        /** Data the codec of the server encodes into the payload, replaces `value` when set */
+        data?: unknown;
         /** Optional keys for routing */
         keys?: string[];
         /** Optional tags for conditional forwarding */
@@ -635,6 +853,13 @@ export declare namespace map {
          * @returns A message marked for dropping
          */
         static toDrop(): Message;
+        /**
+         * Create a message whose payload is `data` encoded by the codec of the server.
+         * @param data - The data to encode, e.g. a plain object with the `json` codec
+         * @param options - Optional keys, tags, and metadata
+         * @returns A message carrying the data
+         */
+        static fromData(data: unknown, options?: MessageOptions): Message;
     }
     /**
      * Async server for handling map operations.
@@ -654,8 +879,14 @@ export declare namespace map {
         /**
          * Create a new map server.
          * @param mapFn - Async function that transforms input datum to output messages
+         * @param retryPolicy - Optional policy for retrying failed invocations of the function
+         */
+        constructor(mapFn: (message: Datum) => Promise<Message[]>, retryPolicy?: RetryPolicy);
+        /**
+         * Start the map server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
          */
-        constructor(mapFn: (message: Datum) => Promise<Message[]>);
+        start(options?: ServerOptions): Promise<void>;
         /**
          * Start the map server.
          * @param socketPath - Optional custom Unix socket path
@@ -663,10 +894,140 @@ export declare namespace map {
          */
         start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>;
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
+         */
+        stop(timeoutMs?: number): Promise<DrainSummary>;
+    }
+    /**
+     * Options for coalescing the requests that arrive concurrently into one call of the handler of a
+     * {@link MicroBatchServer}.
+     */
+    interface MicroBatchOptions {
+        /** Most requests handed to one call of the handler. Defaults to 64. */
+        maxBatchSize?: number;
+        /**
+         * Longest time in milliseconds the first request of a batch waits for more requests before the batch is
+         * handed to the handler. Defaults to 5.
          */
-        stop(): void;
+        maxBatchDelayMs?: number;
     }
+    /**
+     * Handler of a {@link MicroBatchServer}, returns the messages of every datum of the batch in the order of the
+     * datums.
+     */
+    type MapBatchCallback = (datums: Datum[]) => Promise<Message[][]>;
+    /**
+     * Map server that coalesces the requests arriving concurrently into micro-batches and calls the handler once
+     * per batch, which saves crossing into JavaScript for every request at high throughput. The messages returned
+     * for each datum are sent back as the response to its own request.
+     *
+     * A batch is handed to the handler once it has `maxBatchSize` requests or its first request waited for
+     * `maxBatchDelayMs`. When the handler throws, times out or returns a list of messages per datum that doesn't
+     * match the batch, every request of the batch fails. A retry policy retries the whole batch.
+     *
+     * @example
+     * ```typescript
+     * const server = new map.MicroBatchServer(
+     *   async (datums) => datums.map((datum) => [new map.Message(datum.value, { keys: datum.keys })]),
+     *   { maxBatchSize: 128, maxBatchDelayMs: 2 },
+     * );
+     *
+     * await server.start();
+     * ```
+     */
+    class MicroBatchServer {
+        private readonly nativeServer;
+        /**
+         * Create a new micro-batching map server.
+         * @param mapBatchFn - Async function that transforms a batch of datums to the output messages of each
+         * @param microBatch - Optional batch size and delay
+         * @param retryPolicy - Optional policy for retrying failed invocations, a retry receives the whole batch again
+         */
+        constructor(mapBatchFn: MapBatchCallback, microBatch?: MicroBatchOptions, retryPolicy?: RetryPolicy);
+        /**
+         * Start the map server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>;
+        /**
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
+         */
+        stop(timeoutMs?: number): Promise<DrainSummary>;
+    }
+    /**
+     * Options of the worker threads of a {@link WorkerPoolServer}.
+     */
+    interface WorkerPoolOptions {
+        /** Number of worker threads. Defaults to the number of CPUs available to the process. */
+        workers?: number;
+        /** Options every worker thread is created with, e.g. `resourceLimits` or `execArgv`. */
+        workerOptions?: Omit<WorkerOptions, 'workerData'>;
+    }
+    /**
+     * Map server that runs the handler in a pool of `worker_threads`, so that CPU-heavy handlers don't block the main
+     * thread or each other. Every worker thread loads `workerScript`, which registers the handler with
+     * {@link registerWorker}.
+     *
+     * The native server hands the datums to the registered workers in turns and waits for a worker to register
+     * when there is none. A worker that exits is replaced, the datums it was handling are handed to the other
+     * workers, up to 3 times per datum before the request fails. The retry policy and `handlerTimeoutMs` apply to
+     * every call as with {@link AsyncServer}, a timed out handler keeps running on its worker.
+     *
+     * The messages of a datum are sent in the order the handler returned them. Datums with the same keys may be
+     * handled by different workers at the same time; set `maxInFlightPerKey: 1` to handle them one at a time, in
+     * the order they arrived. The value of a datum is copied once into the worker thread that handles it.
+     *
+     * @example
+     * ```typescript
+     * // main.ts
+     * const server = new map.WorkerPoolServer(require.resolve('./mapper'), { workers: 4 });
+     * await server.start();
+     *
+     * // mapper.ts
+     * map.registerWorker(async (datum) => [new map.Message(expensiveTransform(datum.value))]);
+     * ```
+     */
+    class WorkerPoolServer {
+        private readonly workerScript;
+        private readonly poolOptions;
+        private readonly nativeServer;
+        private readonly workers;
+        private nextWorkerId;
+        private terminating;
+        /**
+         * Create a new worker pool map server.
+         * @param workerScript - Path or URL of the script every worker thread runs, it calls {@link registerWorker}
+         * @param poolOptions - Optional number of worker threads and their options
+         * @param retryPolicy - Optional policy for retrying failed invocations of the handler
+         */
+        constructor(workerScript: string | URL, poolOptions?: WorkerPoolOptions, retryPolicy?: RetryPolicy);
+        /**
+         * Start the worker threads and the map server, the worker threads are terminated once the server stopped.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>;
+        /**
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
+         */
+        stop(timeoutMs?: number): Promise<DrainSummary>;
+        private spawnWorker;
+    }
+    /**
+     * Registers the handler of a worker thread of a {@link WorkerPoolServer}, to be called once by its
+     * `workerScript`. The thread keeps running until the server terminates it.
+     * @param mapFn - Async function that transforms input datum to output messages
+     * @throws If called outside of a worker thread of a {@link WorkerPoolServer}
+     */
+    function registerWorker(mapFn: (message: Datum) => Promise<Message[]>): void;
 }
 /**
  * Sink namespace for custom data sinks.
@@ -703,53 +1064,22 @@ export declare namespace map {
  */
 export declare namespace sink {
     /**
-     * System-provided metadata attached to sink messages.
-     * Contains read-only system information organized by groups.
+     * System-provided metadata attached to messages, read-only to handlers.
+     * Shared by the namespaces that carry metadata, see {@link metadata.SystemMetadata}.
      */
-    interface SystemMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>;
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>;
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer;
-    }
+    type SystemMetadata = metadata.SystemMetadata;
     /**
-     * User-defined metadata attached to sink messages.
-     * Contains read-only user information organized by groups.
+     * User-defined metadata that can be attached to messages, groups of key/value pairs.
+     * Shared by the namespaces that carry metadata, see {@link metadata.UserMetadata}.
+     *
+     * @example
+     * ```typescript
+     * const userMetadata = new sink.UserMetadata();
+     * userMetadata.addString('tracking', 'source', 'api');
+     * ```
      */
-    interface UserMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>;
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>;
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer;
-    }
+    type UserMetadata = metadata.UserMetadata;
+    const UserMetadata: typeof metadata.UserMetadata;
     /**
      * Input datum for sink operations.
      * Provides access to message data, metadata, and unique ID.
@@ -785,6 +1115,11 @@ export declare namespace sink {
          * @returns A record of header key-value pairs
          */
         getHeaders(): Record<string, string>;
+        /**
+         * Get the context of the span the message is handled in, a child of the span in its `traceparent` header.
+         * @returns The trace context, or null when the message has no valid `traceparent` header
+         */
+        traceContext(): TraceContext | null;
         /**
          * Get the user-defined metadata for this message.
          * @returns The user metadata object
@@ -795,10 +1130,33 @@ export declare namespace sink {
          * @returns The system metadata object
          */
         systemMetadata(): SystemMetadata;
+        /**
+         * Get the value decoded by the codec of the server, see {@link ServerOptions.codec}.
+         * @returns The decoded value, or null with the raw codec or an empty value
+         */
+        data(): unknown;
+        /**
+         * Get why the value could not be decoded, only set when the decode error policy is `pass`.
+         * @returns The decode error, or null when the value was decoded
+         */
+        decodeError(): string | null;
     }
+    /**
+     * User metadata of an on-success message, either the metadata of a datum or groups of key/value pairs.
+     */
+    type MessageUserMetadata = UserMetadata | Record<string, Record<string, Buffer>>;
     /**
      * Message class for sink serve operations.
      * Used with Response.onSuccess() to include a payload.
+     *
+     * @example
+     * ```typescript
+     * // Forward the datum with its user metadata to the on-success sink
+     * sink.Response.onSuccess(datum.id, new sink.Message(datum.getValue(), datum.keys, datum.userMetadata()))
+     *
+     * // Attach new metadata
+     * new sink.Message(payload, undefined, { audit: { stored: Buffer.from('true') } })
+     * ```
      */
     class Message {
         /** @internal */
@@ -807,8 +1165,9 @@ export declare namespace sink {
          * Create a new sink message.
          * @param value - The message payload
          * @param keys - Optional keys for the message
+         * @param userMetadata - Optional user metadata for the message
          */
-        constructor(value: Buffer, keys?: string[]);
+        constructor(value: Buffer, keys?: string[], userMetadata?: MessageUserMetadata);
     }
     /**
      * Response class for indicating the result of processing each message.
@@ -923,8 +1282,14 @@ export declare namespace sink {
         /**
          * Create a new sink server.
          * @param sinkFn - Async function that processes datums and returns responses
+         * @param retryPolicy - Optional policy for retrying failed invocations, a retry receives the whole batch again
+         */
+        constructor(sinkFn: SinkCallback, retryPolicy?: RetryPolicy);
+        /**
+         * Start the sink server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
          */
-        constructor(sinkFn: SinkCallback);
+        start(options?: ServerOptions): Promise<void>;
         /**
          * Start the sink server.
          * @param socketPath - Optional custom Unix socket path
@@ -932,9 +1297,12 @@ export declare namespace sink {
          */
         start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>;
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void;
+        stop(timeoutMs?: number): Promise<DrainSummary>;
     }
 }
 /**
@@ -1006,6 +1374,20 @@ export declare namespace batchmap {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>;
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext;
+        /**
+         * The value decoded by the codec of the server, see {@link ServerOptions.codec}.
+         * Not set with the raw codec or an empty value.
+         */
+        data?: unknown;
+        /**
+         * Why the value could not be decoded, only set when the decode error policy is `pass`.
+         */
+        decodeError?: string;
     }
     /**
      * Output message for batch map operations.
@@ -1020,7 +1402,11 @@ export declare namespace batchmap {
         /**
          * The message payload passed to the next vertex.
          */
-        value: Buffer;
+        value?: Buffer;
+        /**
+         * Data the codec of the server encodes into the payload, replaces `value` when set.
+         */
+        data?: unknown;
         /**
          * Tags for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
          */
@@ -1030,8 +1416,10 @@ export declare namespace batchmap {
      * Options for appending a message to a batch response.
      */
     interface BatchMessageOptions {
-        /** The message payload */
-        value: Buffer;
+        /** The message payload, empty when neither it nor `data` is set */
+        value?: Buffer;
+        /** Data the codec of the server encodes into the payload, replaces `value` when set */
+        data?: unknown;
         /** Keys for routing the output message */
         keys?: string[];
         /** Tags for conditional forwarding */
@@ -1046,6 +1434,9 @@ export declare namespace batchmap {
      * const response = batchmap.Response.fromId(datum.id);
      * response.append({ value: Buffer.from('result1'), keys: ['key1'] });
      * response.append({ value: Buffer.from('result2'), keys: ['key2'] });
+     *
+     * // With the json codec
+     * response.append({ data: { id: datum.id, ok: true } });
      * ```
      */
     class Response {
@@ -1101,8 +1492,14 @@ export declare namespace batchmap {
         /**
          * Create a new batch map server.
          * @param batchmapFn - Async function that processes a batch of datums
+         * @param retryPolicy - Optional policy for retrying failed invocations, a retry receives the whole batch again
+         */
+        constructor(batchmapFn: BatchMapCallback, retryPolicy?: RetryPolicy);
+        /**
+         * Start the batch map server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
          */
-        constructor(batchmapFn: BatchMapCallback);
+        start(options?: ServerOptions): Promise<void>;
         /**
          * Start the batch map server.
          * @param socketPath - Optional custom Unix socket path
@@ -1110,9 +1507,12 @@ export declare namespace batchmap {
          */
         start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>;
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void;
+        stop(timeoutMs?: number): Promise<DrainSummary>;
     }
 }
 /**
@@ -1170,6 +1570,20 @@ export declare namespace mapstream {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>;
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext;
+        /**
+         * The value decoded by the codec of the server, see {@link ServerOptions.codec}.
+         * Not set with the raw codec or an empty value.
+         */
+        data?: unknown;
+        /**
+         * Why the value could not be decoded, only set when the decode error policy is `pass`.
+         */
+        decodeError?: string;
     }
     /**
      * Callback function type for map stream handlers.
@@ -1197,6 +1611,8 @@ export declare namespace mapstream {
     class Message {
         /** The message payload */
         value: Buffer;
+        /** Data the codec of the server encodes into the payload, replaces `value` when set */
+        data?: unknown;
         /** Optional keys for routing */
         keys?: string[];
         /** Optional tags for conditional forwarding */
@@ -1212,6 +1628,13 @@ export declare namespace mapstream {
          * @returns A message marked for dropping
          */
         static toDrop(): Message;
+        /**
+         * Create a message whose payload is `data` encoded by the codec of the server.
+         * @param data - The data to encode, e.g. a plain object with the `json` codec
+         * @param options - Optional keys and tags
+         * @returns A message carrying the data
+         */
+        static fromData(data: unknown, options?: MessageOptions): Message;
     }
     /**
      * Async server for map stream operations.
@@ -1234,14 +1657,22 @@ export declare namespace mapstream {
         constructor(mapFn: MapStreamCallback);
         /**
          * Start the map stream server.
-         * @param sockFile - Optional custom Unix socket path
-         * @param infoFile - Optional path for server info file
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
          */
-        start(sockFile?: string | null, infoFile?: string | null): Promise<void>;
+        start(options?: ServerOptions): Promise<void>;
+        /**
+         * Start the map stream server.
+         * @param socketPath - Optional custom Unix socket path
+         * @param serverInfoPath - Optional path for server info file
+         */
+        start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>;
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void;
+        stop(timeoutMs?: number): Promise<DrainSummary>;
     }
 }
 /**
@@ -1307,6 +1738,21 @@ export declare namespace reduce {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>;
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext;
+        /**
+         * Whether the watermark had reached the end of the window when the datum arrived, i.e. the datum arrived
+         * after the window would have closed without allowed lateness.
+         */
+        late: boolean;
+        /**
+         * How far the watermark was past the end of the window when the datum arrived in milliseconds, 0 if the
+         * datum is not late.
+         */
+        latenessMs: number;
     }
     /**
      * Represents a time window with start and end timestamps.
@@ -1324,7 +1770,7 @@ export declare namespace reduce {
     }
     /**
      * Metadata provided to reduce handlers.
-     * Contains information about the current time window.
+     * Contains information about the current time window, the windows of the vertex and the vertex itself.
      */
     interface Metadata {
         /**
@@ -1332,6 +1778,33 @@ export declare namespace reduce {
          * Contains the start and end times of the current window.
          */
         intervalWindow: IntervalWindow;
+        /**
+         * Kind of the windows of the vertex.
+         * Read from the vertex spec numaflow sets in `NUMAFLOW_VERTEX_OBJECT`, not set without it.
+         */
+        windowKind?: 'fixed' | 'sliding';
+        /**
+         * Length of the windows of the vertex in milliseconds.
+         * Read from the vertex spec, not set without it.
+         */
+        windowLengthMs?: number;
+        /**
+         * Time between the starts of two consecutive windows in milliseconds, the window length for fixed windows.
+         * Read from the vertex spec, not set without it.
+         */
+        slideMs?: number;
+        /**
+         * Watermark of the latest datum of the window, not set before the first datum. The reduce protocol does not
+         * tell the watermark the window closed at: once the datums are exhausted this is the watermark of the last
+         * datum, which may be behind the watermark that closed the window.
+         */
+        readonly latestWatermark?: Date;
+        /** Name of the pipeline, from `NUMAFLOW_PIPELINE_NAME`. */
+        pipelineName?: string;
+        /** Name of the vertex, from `NUMAFLOW_VERTEX_NAME`. */
+        vertexName?: string;
+        /** Replica of the vertex, from `NUMAFLOW_REPLICA`. */
+        replica?: number;
     }
     /**
      * Create a drop message for reduce.
@@ -1375,16 +1848,78 @@ export declare namespace reduce {
      * Receives keys, an iterator of datums, and window metadata.
      */
     type Callback = (keys: string[], iterator: AsyncIterableIterator<Datum>, metadata: Metadata) => Promise<Message[]>;
+    /**
+     * Handler of one window, created by a ReducerFactory.
+     */
+    interface Reducer {
+        /** Aggregates the datums of the window */
+        reduceFn: Callback;
+    }
+    /**
+     * Creates the Reducer of a new window.
+     * Called once per window, the Reducer is released once the window is reduced.
+     */
+    type ReducerFactory = () => Reducer;
+    /**
+     * Incremental aggregation of the datums of a window into an accumulator, for servers created with
+     * {@link AsyncServer.withAggregator}.
+     *
+     * The server drains the datums of every window itself and hands them over in micro-batches. Each batch is added
+     * to an accumulator of its own, which is then merged into the accumulator of the window, so `addInput` may
+     * modify and return the accumulator it is given.
+     */
+    interface Aggregator<A> {
+        /** Creates an empty accumulator */
+        createAccumulator(): A;
+        /** Adds a datum to the accumulator, returns the resulting accumulator */
+        addInput(accumulator: A, datum: Datum): A;
+        /** Merges accumulators into one, returns the resulting accumulator */
+        mergeAccumulators(accumulators: A[]): A;
+        /** Returns the messages of the window from the accumulator of all its datums */
+        extractOutput(keys: string[], accumulator: A, metadata: Metadata): Message[] | Promise<Message[]>;
+    }
     /**
      * Async server for reduce operations.
+     *
+     * Given a callback, every window calls that same callback. Given a ReducerFactory through
+     * {@link AsyncServer.withFactory}, every window gets its own Reducer, so state can be kept on the Reducer.
+     * Given an Aggregator through {@link AsyncServer.withAggregator}, the server iterates the datums itself and
+     * adds them to an accumulator per window.
      */
     class AsyncServer {
         private readonly nativeServer;
+        /**
+         * Create a new reduce server whose windows each get their own Reducer.
+         * @param createReducer - Factory called once per window
+         */
+        static withFactory(createReducer: ReducerFactory): AsyncServer;
+        /**
+         * Create a new reduce server that aggregates the datums of every window incrementally.
+         *
+         * @example
+         * ```typescript
+         * const server = reduce.AsyncServer.withAggregator<number>({
+         *   createAccumulator: () => 0,
+         *   addInput: (sum, datum) => sum + parseInt(datum.value.toString()),
+         *   mergeAccumulators: (sums) => sums.reduce((a, b) => a + b, 0),
+         *   extractOutput: (keys, sum) => [new reduce.Message(Buffer.from(String(sum)), { keys })],
+         * })
+         * ```
+         *
+         * @param aggregator - Aggregator of the datums of every window
+         * @param microBatch - Optional size and delay of the batches the datums are added in
+         */
+        static withAggregator<A>(aggregator: Aggregator<A>, microBatch?: map.MicroBatchOptions): AsyncServer;
         /**
          * Create a new reduce server.
          * @param reduceFn - Async function that aggregates datums by key
          */
         constructor(reduceFn: Callback);
+        /**
+         * Start the reduce server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>;
         /**
          * Start the reduce server.
          * @param socketPath - Optional custom Unix socket path
@@ -1392,9 +1927,12 @@ export declare namespace reduce {
          */
         start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>;
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void;
+        stop(timeoutMs?: number): Promise<DrainSummary>;
     }
 }
 /**
@@ -1433,7 +1971,8 @@ export declare namespace reduce {
  *   }
  * }
  *
- * const server = new sessionReduce.AsyncServer(new MySessionReducer());
+ * // Every session gets its own reducer, so that its state is not shared with other sessions.
+ * const server = sessionReduce.AsyncServer.withFactory(() => new MySessionReducer());
  * server.start();
  * ```
  */
@@ -1466,6 +2005,11 @@ export declare namespace sessionReduce {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>;
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext;
     }
     /**
      * Create a drop message for session reduce.
@@ -1504,11 +2048,26 @@ export declare namespace sessionReduce {
          */
         static toDrop(): Message;
     }
+    /**
+     * Metadata of the session a session reduce handler runs for.
+     *
+     * The bounds of the session window are not available: the session reduce server of the numaflow SDK keeps them
+     * to itself.
+     */
+    interface SessionMetadata {
+        /**
+         * Number of sessions merged into this one. Sessions are merged into a new session before its first datum is
+         * received or its datums end, so the count is final from then on.
+         */
+        readonly mergedSessions: number;
+        /** Whether this session was created by merging other sessions. */
+        readonly merged: boolean;
+    }
     /**
      * Callback type for the main session reduce function.
      * Processes messages and yields output as an async iterable.
      */
-    type SessionReduceFnCallback = (keys: string[], iterator: AsyncIterableIterator<Datum>) => AsyncIterable<Message>;
+    type SessionReduceFnCallback = (keys: string[], iterator: AsyncIterableIterator<Datum>, metadata: SessionMetadata) => AsyncIterable<Message>;
     /**
      * Callback type for serializing the current accumulator state.
      * Used for checkpointing and recovery.
@@ -1535,19 +2094,39 @@ export declare namespace sessionReduce {
         /** Merge incoming state from another session */
         mergeAccumulatorFn: MergeAccumulatorFnCallback;
     }
+    /**
+     * Creates the SessionReducer of a new session.
+     * Called once per session, the first time it is reduced or another session is merged into it.
+     */
+    type SessionReducerFactory = () => SessionReducer;
     /**
      * Async server for session reduce operations.
      *
      * Requires a SessionReducer implementation that provides
      * the reduce function, accumulator serialization, and merge logic.
+     *
+     * Given a SessionReducer instance, every session calls the methods of that same instance. Given a
+     * SessionReducerFactory through {@link AsyncServer.withFactory}, every session gets its own instance: the
+     * accumulator of a session is serialized by its own instance and merged into the instance of the session it is
+     * merged into.
      */
     class AsyncServer {
         private readonly nativeServer;
         /**
-         * Create a new session reduce server.
+         * Create a new session reduce server whose sessions each get their own SessionReducer.
+         * @param createSessionReducer - Factory called once per session
+         */
+        static withFactory(createSessionReducer: SessionReducerFactory): AsyncServer;
+        /**
+         * Create a new session reduce server whose sessions share one SessionReducer.
          * @param sessionReducerImpl - Implementation of SessionReducer interface
          */
         constructor(sessionReducerImpl: SessionReducer);
+        /**
+         * Start the session reduce server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>;
         /**
          * Start the session reduce server.
          * @param socketPath - Optional custom Unix socket path
@@ -1555,9 +2134,12 @@ export declare namespace sessionReduce {
          */
         start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>;
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void;
+        stop(timeoutMs?: number): Promise<DrainSummary>;
     }
 }
 /**
@@ -1620,6 +2202,21 @@ export declare namespace reduceStream {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>;
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext;
+        /**
+         * Whether the watermark had reached the end of the window when the datum arrived, i.e. the datum arrived
+         * after the window would have closed without allowed lateness.
+         */
+        late: boolean;
+        /**
+         * How far the watermark was past the end of the window when the datum arrived in milliseconds, 0 if the
+         * datum is not late.
+         */
+        latenessMs: number;
     }
     /**
      * Represents a time window with start and end timestamps.
@@ -1637,7 +2234,7 @@ export declare namespace reduceStream {
     }
     /**
      * Metadata provided to reduce stream handlers.
-     * Contains information about the current time window.
+     * Contains information about the current time window, the windows of the vertex and the vertex itself.
      */
     interface Metadata {
         /**
@@ -1645,6 +2242,33 @@ export declare namespace reduceStream {
          * Contains the start and end times of the current window.
          */
         intervalWindow: IntervalWindow;
+        /**
+         * Kind of the windows of the vertex.
+         * Read from the vertex spec numaflow sets in `NUMAFLOW_VERTEX_OBJECT`, not set without it.
+         */
+        windowKind?: 'fixed' | 'sliding';
+        /**
+         * Length of the windows of the vertex in milliseconds.
+         * Read from the vertex spec, not set without it.
+         */
+        windowLengthMs?: number;
+        /**
+         * Time between the starts of two consecutive windows in milliseconds, the window length for fixed windows.
+         * Read from the vertex spec, not set without it.
+         */
+        slideMs?: number;
+        /**
+         * Watermark of the latest datum of the window, not set before the first datum. The reduce protocol does not
+         * tell the watermark the window closed at: once the datums are exhausted this is the watermark of the last
+         * datum, which may be behind the watermark that closed the window.
+         */
+        readonly latestWatermark?: Date;
+        /** Name of the pipeline, from `NUMAFLOW_PIPELINE_NAME`. */
+        pipelineName?: string;
+        /** Name of the vertex, from `NUMAFLOW_VERTEX_NAME`. */
+        vertexName?: string;
+        /** Replica of the vertex, from `NUMAFLOW_REPLICA`. */
+        replica?: number;
     }
     /**
      * Create a drop message for reduce stream.
@@ -1688,6 +2312,18 @@ export declare namespace reduceStream {
      * Returns an async iterable of output messages.
      */
     type CallbackFn = (keys: string[], iterator: AsyncIterableIterator<Datum>, metadata: Metadata) => AsyncIterable<Message>;
+    /**
+     * Handler of one window, created by a ReduceStreamerFactory.
+     */
+    interface ReduceStreamer {
+        /** Aggregates the datums of the window, yielding the output messages */
+        reduceStreamFn: CallbackFn;
+    }
+    /**
+     * Creates the ReduceStreamer of a new window.
+     * Called once per window, the ReduceStreamer is released once the window is reduced.
+     */
+    type ReduceStreamerFactory = () => ReduceStreamer;
     /**
      * Async server for reduce stream operations.
      *
@@ -1702,11 +2338,22 @@ export declare namespace reduceStream {
      */
     class AsyncServer {
         private readonly nativeServer;
+        /**
+         * Create a new reduce stream server whose windows each get their own ReduceStreamer, so state can be kept
+         * on the ReduceStreamer.
+         * @param createReduceStreamer - Factory called once per window
+         */
+        static withFactory(createReduceStreamer: ReduceStreamerFactory): AsyncServer;
         /**
          * Create a new reduce stream server.
          * @param callbackFn - Async generator function that yields output messages
          */
         constructor(callbackFn: CallbackFn);
+        /**
+         * Start the reduce stream server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>;
         /**
          * Start the reduce stream server.
          * @param socketPath - Optional custom Unix socket path
@@ -1714,9 +2361,12 @@ export declare namespace reduceStream {
          */
         start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>;
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void;
+        stop(timeoutMs?: number): Promise<DrainSummary>;
     }
 }
 /**
@@ -1789,6 +2439,11 @@ export declare namespace source {
          * The timeout in milliseconds for the read operation.
          */
         readonly timeoutMs: number;
+        /**
+         * Context of the trace started for the read.
+         * Its `traceparent` is set as the header of the messages read that don't have one.
+         */
+        readonly traceContext: TraceContext;
     }
     /**
      * Represents the position of a message in the source.
@@ -1806,70 +2461,22 @@ export declare namespace source {
         partitionId: number;
     }
     /**
-     * User-defined metadata that can be attached to source messages.
-     * Allows storing arbitrary key-value pairs organized by groups.
-     *
-     * @example
-     * ```typescript
-     * const metadata = new source.UserMetadata();
-     * metadata.createGroup('tracking');
-     * metadata.addKv('tracking', 'origin', Buffer.from('sensor-1'));
-     * ```
+     * System-provided metadata attached to messages, read-only to handlers.
+     * Shared by the namespaces that carry metadata, see {@link metadata.SystemMetadata}.
      */
-    interface UserMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>;
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>;
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer;
-        /**
-         * Create a new group in the metadata.
-         * @param group - The group name to create
-         */
-        createGroup(group: string): void;
-        /**
-         * Add a key-value pair to a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @param value - The value as a Buffer
-         */
-        addKv(group: string, key: string, value: Buffer): void;
-        /**
-         * Remove a key from a group.
-         * @param group - The group name
-         * @param key - The key to remove
-         */
-        removeKey(group: string, key: string): void;
-        /**
-         * Remove an entire group from the metadata.
-         * @param group - The group name to remove
-         */
-        removeGroup(group: string): void;
-    }
+    type SystemMetadata = metadata.SystemMetadata;
     /**
-     * Constructor for creating UserMetadata instances.
+     * User-defined metadata that can be attached to messages, groups of key/value pairs.
+     * Shared by the namespaces that carry metadata, see {@link metadata.UserMetadata}.
      *
      * @example
      * ```typescript
-     * const metadata = new source.UserMetadata();
+     * const userMetadata = new source.UserMetadata();
+     * userMetadata.addString('tracking', 'source', 'api');
      * ```
      */
-    const UserMetadata: {
-        new (): UserMetadata;
-    };
+    type UserMetadata = metadata.UserMetadata;
+    const UserMetadata: typeof metadata.UserMetadata;
     /**
      * Interface that must be implemented for custom sources.
      *
@@ -1944,6 +2551,11 @@ export declare namespace source {
          * @param sourcer - Implementation of the Sourcer interface
          */
         constructor(sourcer: Sourcer);
+        /**
+         * Start the source server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>;
         /**
          * Start the source server.
          * @param socketPath - Optional custom Unix socket path
@@ -1951,8 +2563,11 @@ export declare namespace source {
          */
         start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>;
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void;
+        stop(timeoutMs?: number): Promise<DrainSummary>;
     }
 }
@@ -39,6 +39,9 @@
 
 /* eslint-disable */
 
+import { availableParallelism } from 'os'
+import { Worker, type WorkerOptions, parentPort, workerData } from 'worker_threads'
+
 import binding from './binding'
 
 /**
@@ -48,6 +51,459 @@ import binding from './binding'
  */
 const DROP = 'U+005C__DROP__'
 
+/**
+ * Prefix of the reason a handler failure is rethrown with, so that the native layer can decode the
+ * error name and stack from it. Must match `JS_ERROR_PREFIX` in `src/error.rs`.
+ * @internal
+ */
+const UDF_ERROR_PREFIX = 'U+005C__UDF_ERROR__'
+
+/**
+ * Wraps an async user handler so that a throw or rejection reaches the native layer with its name and stack,
+ * only the message of a rejection makes it across the binding otherwise.
+ * @internal
+ */
+function reportErrors<A extends unknown[], R>(fn: (...args: A) => Promise<R>): (...args: A) => Promise<R> {
+    return async (...args: A): Promise<R> => {
+        try {
+            return await fn(...args)
+        } catch (err) {
+            throw toReportedError(err)
+        }
+    }
+}
+
+/**
+ * Wraps a sync user handler like {@link reportErrors}, for the handlers the native layer calls without awaiting.
+ * @internal
+ */
+function reportErrorsSync<A extends unknown[], R>(fn: (...args: A) => R): (...args: A) => R {
+    return (...args: A): R => {
+        try {
+            return fn(...args)
+        } catch (err) {
+            throw toReportedError(err)
+        }
+    }
+}
+
+/**
+ * The error a handler failure is rethrown with, its name, message and stack encoded into the message.
+ * @internal
+ */
+function toReportedError(err: unknown): Error {
+    const error = err instanceof Error ? err : new Error(String(err))
+    const encoded = JSON.stringify({ name: error.name, message: error.message, stack: error.stack ?? null })
+    return new Error(UDF_ERROR_PREFIX + encoded)
+}
+
+/**
+ * Retry policy for transient failures of a handler, accepted by the map, source transform, batch map
+ * and sink servers.
+ *
+ * Only errors thrown by the handler are retried. Once the attempts are exhausted the request fails
+ * as it would without a policy. The delay between two attempts never exceeds `maxBackoffMs`, and the
+ * servers throw on a `multiplier` or `jitter` that is not a finite number of at least 0.
+ *
+ * @example
+ * ```typescript
+ * const server = new map.AsyncServer(mapFn, {
+ *   maxAttempts: 5,
+ *   initialBackoffMs: 200,
+ *   retryOn: ['FetchError'],
+ * });
+ * ```
+ */
+export interface RetryPolicy {
+    /** Total number of attempts, including the first one. Defaults to 3. */
+    maxAttempts?: number
+    /** Delay before the first retry in milliseconds. Defaults to 100. */
+    initialBackoffMs?: number
+    /** Upper bound of the delay between two attempts in milliseconds. Defaults to 10000. */
+    maxBackoffMs?: number
+    /** Factor the delay grows by after every retry. Defaults to 2. */
+    multiplier?: number
+    /** Fraction of the delay, between 0 and 1, that is randomized. Defaults to 0.2. */
+    jitter?: number
+    /** Names of the errors that are retried, e.g. `['FetchError']`. Every error is retried when not set. */
+    retryOn?: string[]
+}
+
+/**
+ * Options accepted by `start()` of every server.
+ *
+ * The same options can be set with environment variables or in a JSON or YAML config file. Options passed to
+ * `start()` take precedence over the environment, which takes precedence over the config file.
+ *
+ * | Option              | Environment variable             |
+ * | ------------------- | -------------------------------- |
+ * | `socketPath`        | `NUMAFLOW_SERVER_SOCKET_PATH`    |
+ * | `serverInfoPath`    | `NUMAFLOW_SERVER_INFO_PATH`      |
+ * | `maxMessageSize`    | `NUMAFLOW_GRPC_MAX_MESSAGE_SIZE` |
+ * | `shutdownTimeoutMs` | `NUMAFLOW_SHUTDOWN_TIMEOUT_MS`   |
+ * | `handleSignals`     | `NUMAFLOW_HANDLE_SIGNALS`        |
+ * | `metricsPort`       | `NUMAFLOW_METRICS_PORT`          |
+ * | `metricsAddress`    | `NUMAFLOW_METRICS_ADDRESS`       |
+ * | `maxInFlight`       | `NUMAFLOW_MAX_IN_FLIGHT`         |
+ * | `maxInFlightPerKey` | `NUMAFLOW_MAX_IN_FLIGHT_PER_KEY` |
+ * | `handlerTimeoutMs`  | `NUMAFLOW_HANDLER_TIMEOUT_MS`    |
+ * | `codec`             | `NUMAFLOW_CODEC`                 |
+ * | `decodeErrorPolicy` | `NUMAFLOW_DECODE_ERROR_POLICY`   |
+ *
+ * The config file is read from the path in `NUMAFLOW_SERVER_CONFIG_FILE`, it holds the options under the same
+ * names as above. Files ending in `.yaml` or `.yml` are parsed as YAML, all others as JSON.
+ *
+ * @example
+ * ```typescript
+ * await server.start({ maxMessageSize: 128 * 1024 * 1024, shutdownTimeoutMs: 30000 });
+ * ```
+ */
+export interface ServerOptions {
+    /** Path of the Unix domain socket the gRPC server listens on. Defaults to the path Numaflow expects for the server type. */
+    socketPath?: string
+    /** Path of the file the server information is written to on start up. Defaults to the path Numaflow expects. */
+    serverInfoPath?: string
+    /** Maximum size of an encoded or decoded gRPC message in bytes. Defaults to 64 MiB. */
+    maxMessageSize?: number
+    /** Time in milliseconds `stop()` waits for in-flight requests before the server is closed forcefully. */
+    shutdownTimeoutMs?: number
+    /**
+     * Stop the server gracefully, as `stop()` does, when the process receives SIGTERM or SIGINT. The signals no
+     * longer terminate the process while the server runs, and their default action is not restored afterwards.
+     * Defaults to false.
+     */
+    handleSignals?: boolean
+    /**
+     * Port of the HTTP endpoint serving the metrics of the servers in the Prometheus text format at `/metrics`.
+     * Metrics are not served when not set.
+     */
+    metricsPort?: number
+    /**
+     * IP address the metrics endpoint listens on, e.g. `127.0.0.1` to only serve the metrics within the pod. Defaults
+     * to `0.0.0.0`, every IPv4 interface.
+     */
+    metricsAddress?: string
+    /**
+     * Maximum number of requests handed to the handler at the same time, further requests wait until one of them is
+     * done. Only applies to the map, map stream and source transform servers. Unbounded when not set.
+     */
+    maxInFlight?: number
+    /**
+     * Maximum number of requests with the same keys handed to the handler at the same time, in addition to
+     * `maxInFlight`. Only applies to the map, map stream and source transform servers. Unbounded when not set.
+     */
+    maxInFlightPerKey?: number
+    /**
+     * Time in milliseconds a call of the handler may take before it is abandoned. A timed out map or batch map request
+     * fails like one whose handler threw, a timed out sink batch is answered with a failure response for every message.
+     * Only applies to the map, batch map and sink servers. Calls are not timed out when not set.
+     */
+    handlerTimeoutMs?: number
+    /**
+     * Codec the datum values are decoded with into `data`, and the `data` of messages is encoded with. Only applies
+     * to the map, map stream, batch map, source transform and sink servers. Defaults to `raw`, values are only handed
+     * over as bytes.
+     */
+    codec?: Codec
+    /**
+     * What happens to a datum whose value the codec can't decode: `fail` fails the request like a handler that threw,
+     * `drop` drops the datum without calling the handler, and `pass` hands it to the handler with the error in
+     * `decodeError`. Defaults to `fail`.
+     */
+    decodeErrorPolicy?: DecodeErrorPolicy
+}
+
+/**
+ * Encoding of datum values and message payloads, see {@link ServerOptions.codec}.
+ */
+export type Codec = 'json' | 'msgpack' | 'cbor' | 'raw'
+
+/**
+ * What happens to a datum whose value can't be decoded, see {@link ServerOptions.decodeErrorPolicy}.
+ */
+export type DecodeErrorPolicy = 'fail' | 'drop' | 'pass'
+
+/**
+ * Configuration a server runs with, see {@link loadServerConfig}.
+ */
+export type ServerConfig = binding.ServerConfig
+
+/**
+ * Returns the configuration a server started with `options` runs with, i.e. the options merged with the
+ * `NUMAFLOW_*` environment variables and the config file. Useful to log the configuration on start up.
+ *
+ * @param options - Options that would be passed to `start()`
+ * @throws If an environment variable has an invalid value or the config file can't be read
+ *
+ * @example
+ * ```typescript
+ * console.log('Starting mapper with', loadServerConfig());
+ * await server.start();
+ * ```
+ */
+export function loadServerConfig(options?: ServerOptions): ServerConfig {
+    return binding.loadServerConfig(options)
+}
+
+/**
+ * Outcome of stopping a server, resolved by `stop()`.
+ */
+export type DrainSummary = binding.DrainSummary
+
+/**
+ * Level and format of the native logs, see {@link configureLogging}.
+ */
+export interface LogOptions {
+    /**
+     * Most verbose level that is logged. Defaults to `NUMAFLOW_LOG_LEVEL`, or `info` when it is not set.
+     */
+    level?: 'off' | 'error' | 'warn' | 'info' | 'debug' | 'trace'
+    /**
+     * `json` to log a JSON object per line, `text` to log plain lines. Defaults to `NUMAFLOW_LOG_FORMAT`, or `json`
+     * when it is not set.
+     */
+    format?: 'json' | 'text'
+}
+
+/**
+ * A log record of the native layer, passed to the handler set with {@link setLogHandler}.
+ */
+export type LogRecord = binding.LogRecord
+
+/**
+ * [W3C trace context](https://www.w3.org/TR/trace-context/) of the span a datum is handled in. Spans created by a
+ * handler can be parented on it by extracting its `traceparent` with the OpenTelemetry propagation API.
+ */
+export type TraceContext = binding.TraceContext
+
+/**
+ * Sets the level and format of the logs written by the servers, options that are not set keep their current value.
+ *
+ * @param options - Log level and format
+ * @throws If the level or the format is not valid
+ *
+ * @example
+ * ```typescript
+ * configureLogging({ level: 'debug', format: 'text' });
+ * ```
+ */
+export function configureLogging(options: LogOptions): void {
+    binding.configureLogging(options)
+}
+
+/**
+ * Routes the logs of the servers to `handler` instead of stdout and stderr, e.g. into the application's logger.
+ * Passing `null` restores the default output.
+ *
+ * @param handler - Called with every log record, or `null`
+ *
+ * @example
+ * ```typescript
+ * import pino from 'pino';
+ *
+ * const logger = pino();
+ * setLogHandler(({ level, message, fields }) => logger[level](fields, message));
+ * ```
+ */
+export function setLogHandler(handler: ((record: LogRecord) => void) | null): void {
+    binding.setLogHandler(handler)
+}
+
+/**
+ * Builds the options for a native `start()` from either the options object or the positional socket and
+ * server info paths `start()` used to take.
+ * @internal
+ */
+function toServerOptions(options?: ServerOptions | string | null, serverInfoPath?: string | null): ServerOptions {
+    if (typeof options === 'object' && options !== null) {
+        return options
+    }
+    return { socketPath: options ?? undefined, serverInfoPath: serverInfoPath ?? undefined }
+}
+
+/**
+ * A server a {@link CompositeServer} runs, e.g. the `AsyncServer` of any namespace.
+ */
+export interface UdfServer {
+    start(options?: ServerOptions): Promise<void>
+    stop(timeoutMs?: number): Promise<DrainSummary>
+}
+
+/**
+ * Where a server of a {@link CompositeServer} is: not started yet, serving, stopped, or failed to start or run.
+ */
+export type ServerState = 'idle' | 'running' | 'stopped' | 'failed'
+
+/**
+ * Status of a server of a {@link CompositeServer}, returned by {@link CompositeServer.status}.
+ */
+export interface ServerStatus {
+    /** Name the server was added with. */
+    name: string
+    /** Where the server is in its lifecycle. */
+    state: ServerState
+    /** Why the server failed, only set in the `failed` state. */
+    error?: Error
+}
+
+/**
+ * Delay before a {@link CompositeServer} stops again the servers that were not serving yet when it stopped them.
+ * @internal
+ */
+const COMPOSITE_STOP_RETRY_MS = 50
+
+/** @internal */
+interface CompositeEntry {
+    name: string
+    server: UdfServer
+    options?: ServerOptions
+    state: ServerState
+    error?: Error
+}
+
+/**
+ * Runs several servers in one process, e.g. a user-defined source together with its source transformer, or a map
+ * together with a side input server.
+ *
+ * The servers share one lifecycle: `start()` starts all of them, and once one of them stops or fails the others are
+ * stopped as well, so the process never keeps serving half a vertex. `stop()` stops all of them at once. The options
+ * passed to `start()` apply to every server, the options a server was added with take precedence, e.g. its
+ * `socketPath`. Servers started with the same `metricsPort` share one metrics endpoint.
+ *
+ * @example
+ * ```typescript
+ * const server = new CompositeServer()
+ *   .add('source', new source.AsyncServer(sourcer))
+ *   .add('transformer', new sourceTransform.AsyncServer(transformFn));
+ *
+ * await server.start({ handleSignals: true, metricsPort: 9090 });
+ * ```
+ */
+export class CompositeServer {
+    private readonly entries: CompositeEntry[] = []
+    private running = false
+
+    /**
+     * Add a server, before the composite server is started.
+     * @param name - Name of the server in {@link status} and the summary of {@link stop}, unique within the composite
+     * @param server - The server, e.g. a `map.AsyncServer`
+     * @param options - Optional options of this server, taking precedence over the options passed to `start()`
+     * @returns The composite server, for chaining
+     * @throws If the composite server is running or a server with the same name was added
+     */
+    public add(name: string, server: UdfServer, options?: ServerOptions): this {
+        if (this.running) {
+            throw new Error('Servers cannot be added to a running CompositeServer')
+        }
+        if (this.entries.some((entry) => entry.name === name)) {
+            throw new Error(`A server named ${name} was already added to the CompositeServer`)
+        }
+        this.entries.push({ name, server, options, state: 'idle' })
+        return this
+    }
+
+    /**
+     * Start every server and wait until all of them stopped.
+     * @param options - Optional options of every server
+     * @throws The error of the first server that failed, once the others stopped
+     */
+    public async start(options?: ServerOptions): Promise<void> {
+        if (this.entries.length === 0) {
+            throw new Error('CompositeServer has no servers to start')
+        }
+        if (this.running) {
+            throw new Error('CompositeServer is already running')
+        }
+        this.running = true
+        try {
+            await Promise.all(this.entries.map((entry) => this.run(entry, options)))
+        } finally {
+            this.running = false
+        }
+        const failed = this.entries.find((entry) => entry.state === 'failed')
+        if (failed !== undefined) {
+            throw failed.error
+        }
+    }
+
+    /**
+     * Stop every server gracefully, draining them at the same time.
+     * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+     * @returns How many requests each server drained and abandoned, by name
+     */
+    public async stop(timeoutMs?: number): Promise<Record<string, DrainSummary>> {
+        const summaries = await Promise.all(this.entries.map((entry) => entry.server.stop(timeoutMs)))
+        return Object.fromEntries(this.entries.map((entry, i) => [entry.name, summaries[i]]))
+    }
+
+    /**
+     * The status of every server, in the order they were added.
+     */
+    public status(): ServerStatus[] {
+        return this.entries.map(({ name, state, error }) => (error === undefined ? { name, state } : { name, state, error }))
+    }
+
+    private async run(entry: CompositeEntry, options?: ServerOptions): Promise<void> {
+        entry.state = 'running'
+        entry.error = undefined
+        try {
+            await entry.server.start({ ...options, ...entry.options })
+            entry.state = 'stopped'
+        } catch (err) {
+            entry.state = 'failed'
+            entry.error = err instanceof Error ? err : new Error(String(err))
+        }
+        // A server that stopped on its own or failed takes the others with it. A server that had not started serving
+        // yet ignores the stop, it is stopped again until its start settled.
+        while (this.entries.some((other) => other.state === 'running')) {
+            await this.stop()
+            await new Promise((resolve) => setTimeout(resolve, COMPOSITE_STOP_RETRY_MS))
+        }
+    }
+}
+
+/**
+ * Metadata namespace with the user and system metadata of messages. The map, source transform, sink and source
+ * namespaces re-export its classes for the metadata of their datums and messages.
+ *
+ * Values are buffers, `getString` and `addString` read and write them as UTF-8 strings. `toJSON` encodes the values
+ * as base64, so `JSON.stringify` can write metadata that `UserMetadata.fromJSON` reads back.
+ *
+ * @example
+ * ```typescript
+ * import { metadata } from '@numaproj/numaflow-js';
+ *
+ * const userMetadata = new metadata.UserMetadata({ tracking: { source: Buffer.from('api') } });
+ * userMetadata.merge(datum.userMetadata);
+ * for (const { group, key, value } of userMetadata.entries()) {
+ *   console.log(group, key, value.toString());
+ * }
+ * const copy = metadata.UserMetadata.fromJSON(JSON.parse(JSON.stringify(userMetadata)));
+ * ```
+ */
+export import metadata = binding.metadata
+
+/**
+ * User metadata of a message as the groups of key/value pairs the native layer takes.
+ * @internal
+ */
+function toNativeMetadata(
+    userMetadata: metadata.UserMetadata | Record<string, Record<string, Buffer>>,
+): Record<string, Record<string, Buffer>> {
+    if (typeof userMetadata.getGroups !== 'function') {
+        return userMetadata as Record<string, Record<string, Buffer>>
+    }
+    const groups = userMetadata as metadata.UserMetadata
+    const nativeMetadata: Record<string, Record<string, Buffer>> = {}
+    for (const group of groups.getGroups()) {
+        nativeMetadata[group] = {}
+        for (const key of groups.getKeys(group)) {
+            nativeMetadata[group][key] = groups.getValue(group, key)
+        }
+    }
+    return nativeMetadata
+}
+
 /**
  * Side Input namespace provides functionality for handling side inputs in Numaflow pipelines.
  *
@@ -97,96 +553,24 @@ export namespace sourceTransform {
     export type NativeMessage = binding.sourceTransform.SourceTransformMessage
 
     /**
-     * System-provided metadata attached to messages.
-     * Contains read-only system information organized by groups.
+     * System-provided metadata attached to messages, read-only to handlers.
+     * Shared by the namespaces that carry metadata, see {@link metadata.SystemMetadata}.
      */
-    export interface SystemMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer
-    }
+    export type SystemMetadata = metadata.SystemMetadata
 
     /**
-     * User-defined metadata that can be attached to messages.
-     * Allows storing arbitrary key-value pairs organized by groups.
+     * User-defined metadata that can be attached to messages, groups of key/value pairs.
+     * Shared by the namespaces that carry metadata, see {@link metadata.UserMetadata}.
      *
      * @example
      * ```typescript
-     * const metadata = new sourceTransform.UserMetadata();
-     * metadata.createGroup('tracking');
-     * metadata.addKv('tracking', 'source', Buffer.from('api'));
+     * const userMetadata = new sourceTransform.UserMetadata();
+     * userMetadata.addString('tracking', 'source', 'api');
      * ```
      */
-    export interface UserMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer
-        /**
-         * Create a new group in the metadata.
-         * @param group - The group name to create
-         */
-        createGroup(group: string): void
-        /**
-         * Add a key-value pair to a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @param value - The value as a Buffer
-         */
-        addKv(group: string, key: string, value: Buffer): void
-        /**
-         * Remove a key from a group.
-         * @param group - The group name
-         * @param key - The key to remove
-         */
-        removeKey(group: string, key: string): void
-        /**
-         * Remove an entire group from the metadata.
-         * @param group - The group name to remove
-         */
-        removeGroup(group: string): void
-    }
+    export type UserMetadata = metadata.UserMetadata
+    export const UserMetadata = metadata.UserMetadata
 
-    /**
-     * Constructor for creating UserMetadata instances.
-     *
-     * @example
-     * ```typescript
-     * const metadata = new sourceTransform.UserMetadata();
-     * ```
-     */
-    export const UserMetadata: {
-        new (): UserMetadata
-    } = binding.sourceTransform.SourceTransformUserMetadata
 
     /**
      * Represents an input datum received by the source transform handler.
@@ -208,6 +592,12 @@ export namespace sourceTransform {
         userMetadata: UserMetadata | null
         /** System-provided metadata, if any */
         systemMetadata: SystemMetadata | null
+        /** Context of the span the datum is handled in, `null` without a valid `traceparent` header */
+        traceContext: TraceContext | null
+        /** The value decoded by the codec of the server, `null` with the raw codec or an empty value */
+        data: unknown
+        /** Why the value could not be decoded, only set when the decode error policy is `pass` */
+        decodeError: string | null
 
         /** @internal */
         constructor(nativeDatum: NativeDatum) {
@@ -218,6 +608,9 @@ export namespace sourceTransform {
             this.headers = nativeDatum.headers
             this.userMetadata = nativeDatum.userMetadata
             this.systemMetadata = nativeDatum.systemMetadata
+            this.traceContext = nativeDatum.traceContext
+            this.data = nativeDatum.data
+            this.decodeError = nativeDatum.decodeError
         }
     }
 
@@ -249,6 +642,8 @@ export namespace sourceTransform {
     export class Message {
         /** The message payload */
         value: Buffer
+        /** Data the codec of the server encodes into the payload, replaces `value` when set */
+        data?: unknown
         /** Event time to assign to this message */
         eventTime: Date
         /** Optional keys for routing */
@@ -280,18 +675,19 @@ export namespace sourceTransform {
         public static toDrop(eventTime: Date): Message {
             return new Message(Buffer.from([]), eventTime, { tags: [DROP] })
         }
-    }
 
-    /** @internal */
-    function toNativeMetadata(metadata: UserMetadata): Record<string, Record<string, Buffer>> {
-        let nativeMetadata: Record<string, Record<string, Buffer>> = {}
-        for (const group of metadata.getGroups()) {
-            nativeMetadata[group] = {}
-            for (const key of metadata.getKeys(group)) {
-                nativeMetadata[group][key] = metadata.getValue(group, key)
-            }
+        /**
+         * Create a message whose payload is `data` encoded by the codec of the server.
+         * @param data - The data to encode, e.g. a plain object with the `json` codec
+         * @param eventTime - The event time to assign to this message
+         * @param options - Optional keys, tags, and metadata
+         * @returns A message carrying the data
+         */
+        public static fromData(data: unknown, eventTime: Date, options?: MessageOptions): Message {
+            const message = new Message(Buffer.from([]), eventTime, options)
+            message.data = data
+            return message
         }
-        return nativeMetadata
     }
 
     /**
@@ -324,37 +720,48 @@ export namespace sourceTransform {
         /**
          * Create a new source transform server.
          * @param sourceTransformFn - Async function that transforms input datum to output messages
+         * @param retryPolicy - Optional policy for retrying failed invocations of the function
          */
-        constructor(sourceTransformFn: (message: Datum) => Promise<Message[]>) {
-            const wrappedCallback = async (datum: NativeDatum) => {
+        constructor(sourceTransformFn: (message: Datum) => Promise<Message[]>, retryPolicy?: RetryPolicy) {
+            const wrappedCallback = reportErrors(async (datum: NativeDatum) => {
                 let messages = await sourceTransformFn(new Datum(datum))
                 return messages.map((message: Message): NativeMessage => {
                     return {
                         value: message.value,
+                        data: message.data,
                         keys: message.keys,
                         tags: message.tags,
                         eventTime: message.eventTime,
                         userMetadata: message.userMetadata ? toNativeMetadata(message.userMetadata) : undefined,
                     } satisfies NativeMessage
                 })
-            }
-            this.nativeServer = new binding.sourceTransform.SourceTransformAsyncServer(wrappedCallback)
+            })
+            this.nativeServer = new binding.sourceTransform.SourceTransformAsyncServer(wrappedCallback, retryPolicy)
         }
 
         /**
          * Start the source transform server.
-         * @param socketPath - Optional custom Unix socket path (defaults to Numaflow standard)
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        public start(options?: ServerOptions): Promise<void>
+        /**
+         * Start the source transform server.
+         * @param socketPath - Optional custom Unix socket path
          * @param serverInfoPath - Optional path for server info file
          */
-        public async start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void> {
-            return this.nativeServer.start(socketPath, serverInfoPath)
+        public start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>
+        public async start(options?: ServerOptions | string | null, serverInfoPath?: string | null): Promise<void> {
+            return this.nativeServer.start(toServerOptions(options, serverInfoPath))
         }
 
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        public stop(): void {
-            this.nativeServer.stop()
+        public stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
         }
     }
 }
@@ -422,6 +829,11 @@ export namespace accumulator {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext
         /**
          * Unique identifier for the message.
          * Used for deduplication.
@@ -534,48 +946,105 @@ export namespace accumulator {
         }
     }
 
+    /**
+     * Callback function type for accumulator handlers.
+     * Receives the datums of a key, yields the output messages.
+     */
+    export type AccumulatorFnCallback = (datum: AsyncIterableIterator<Datum>) => AsyncIterable<Message>
+
+    /**
+     * Handler of one key, created by an AccumulatorFactory.
+     */
+    export interface Accumulator {
+        /** Accumulates the datums of the key, yielding the output messages */
+        accumulatorFn: AccumulatorFnCallback
+    }
+    /**
+     * Creates the Accumulator of a new key.
+     * Called once per key, the Accumulator is released once the key is closed.
+     */
+    export type AccumulatorFactory = () => Accumulator
+
+    /**
+     * Wraps an accumulator callback into the handler of the native server.
+     * @internal
+     */
+    function toNativeAccumulatorFn(
+        accumulatorFn: AccumulatorFnCallback,
+    ): (nativeDatumIterator: binding.accumulator.DatumIterator) => () => Promise<NativeMessage | null> {
+        return (nativeDatumIterator: binding.accumulator.DatumIterator): (() => Promise<NativeMessage | null>) => {
+            const iterator = new DatumIterator(nativeDatumIterator)
+            const wrappedIterator = accumulatorFn(iterator)[Symbol.asyncIterator]()
+
+            // Return a function that pulls the next message from the iterator
+            return reportErrors(async () => {
+                const result = await wrappedIterator.next()
+                if (result.done) {
+                    return null
+                }
+
+                return result.value satisfies NativeMessage
+            })
+        }
+    }
+
     /**
      * AsyncServer is a wrapper around a JavaScript callable that will be passed by the user to process the
      * data received by the Sink.
      */
     export class AsyncServer {
         private readonly nativeServer: binding.accumulator.AccumulatorAsyncServer
+
         /**
-         * Create a new Sink with the given callback.
+         * Create a new accumulator server whose keys each get their own Accumulator, so state can be kept on the
+         * Accumulator.
+         * @param createAccumulator - Factory called once per key
          */
-        constructor(accumulatorFn: (datum: AsyncIterableIterator<Datum>) => AsyncIterable<Message>) {
-            const wrapperMapFn = (
-                nativeDatumIterator: binding.accumulator.DatumIterator,
-            ): (() => Promise<NativeMessage | null>) => {
-                const iterator = new DatumIterator(nativeDatumIterator)
-                const wrappedIterator = accumulatorFn(iterator)[Symbol.asyncIterator]()
-
-                // Return a function that pulls the next message from the iterator
-                return async () => {
-                    const result = await wrappedIterator.next()
-                    if (result.done) {
-                        return null
-                    }
-
-                    return result.value satisfies NativeMessage
-                }
-            }
+        static withFactory(createAccumulator: AccumulatorFactory): AsyncServer {
+            return new AsyncServer(
+                binding.accumulator.AccumulatorAsyncServer.withFactory(() => {
+                    const accumulator = createAccumulator()
+                    return toNativeAccumulatorFn(accumulator.accumulatorFn.bind(accumulator))
+                }),
+            )
+        }
 
-            this.nativeServer = new binding.accumulator.AccumulatorAsyncServer(wrapperMapFn)
+        /**
+         * Create a new Sink with the given callback.
+         */
+        constructor(accumulatorFn: AccumulatorFnCallback)
+        /** @internal */
+        constructor(nativeServer: binding.accumulator.AccumulatorAsyncServer)
+        constructor(accumulatorFn: AccumulatorFnCallback | binding.accumulator.AccumulatorAsyncServer) {
+            this.nativeServer =
+                accumulatorFn instanceof binding.accumulator.AccumulatorAsyncServer
+                    ? accumulatorFn
+                    : new binding.accumulator.AccumulatorAsyncServer(toNativeAccumulatorFn(accumulatorFn))
         }
 
         /**
          * Start the AsyncServer server with the given callback
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
          */
-        async start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void> {
-            return await this.nativeServer.start(socketPath, serverInfoPath)
+        start(options?: ServerOptions): Promise<void>
+        /**
+         * Start the AsyncServer server with the given callback
+         * @param socketPath - Optional custom Unix socket path
+         * @param serverInfoPath - Optional path for server info file
+         */
+        start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>
+        async start(options?: ServerOptions | string | null, serverInfoPath?: string | null): Promise<void> {
+            return await this.nativeServer.start(toServerOptions(options, serverInfoPath))
         }
 
         /**
-         * Stop the AsyncServer server
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop() {
-            return this.nativeServer.stop()
+        stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
         }
     }
 }
@@ -614,97 +1083,24 @@ export namespace accumulator {
  */
 export namespace map {
     /**
-     * System-provided metadata attached to messages.
-     * Contains read-only system information organized by groups.
+     * System-provided metadata attached to messages, read-only to handlers.
+     * Shared by the namespaces that carry metadata, see {@link metadata.SystemMetadata}.
      */
-    export interface SystemMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer
-    }
+    export type SystemMetadata = metadata.SystemMetadata
 
     /**
-     * User-defined metadata that can be attached to messages.
-     * Allows storing arbitrary key-value pairs organized by groups.
+     * User-defined metadata that can be attached to messages, groups of key/value pairs.
+     * Shared by the namespaces that carry metadata, see {@link metadata.UserMetadata}.
      *
      * @example
      * ```typescript
-     * const metadata = new map.UserMetadata();
-     * metadata.createGroup('tracking');
-     * metadata.addKv('tracking', 'source', Buffer.from('api'));
-     * metadata.addKv('tracking', 'version', Buffer.from('1.0'));
+     * const userMetadata = new map.UserMetadata();
+     * userMetadata.addString('tracking', 'source', 'api');
      * ```
      */
-    export interface UserMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer
-        /**
-         * Create a new group in the metadata.
-         * @param group - The group name to create
-         */
-        createGroup(group: string): void
-        /**
-         * Add a key-value pair to a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @param value - The value as a Buffer
-         */
-        addKv(group: string, key: string, value: Buffer): void
-        /**
-         * Remove a key from a group.
-         * @param group - The group name
-         * @param key - The key to remove
-         */
-        removeKey(group: string, key: string): void
-        /**
-         * Remove an entire group from the metadata.
-         * @param group - The group name to remove
-         */
-        removeGroup(group: string): void
-    }
+    export type UserMetadata = metadata.UserMetadata
+    export const UserMetadata = metadata.UserMetadata
 
-    /**
-     * Constructor for creating UserMetadata instances.
-     *
-     * @example
-     * ```typescript
-     * const metadata = new map.UserMetadata();
-     * ```
-     */
-    export const UserMetadata: {
-        new (): UserMetadata
-    } = binding.map.UserMetadata
 
     /**
      * Input datum received by the map handler.
@@ -734,6 +1130,11 @@ export namespace map {
          * HTTP-style headers attached to the message.
          */
         readonly headers: Record<string, string>
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * `null` when the datum has no valid `traceparent` header.
+         */
+        readonly traceContext: TraceContext | null
         /**
          * User-defined metadata, if any was attached to this message.
          */
@@ -742,11 +1143,31 @@ export namespace map {
          * System-provided metadata, if any.
          */
         readonly systemMetadata: SystemMetadata | null
+        /**
+         * The value decoded by the codec of the server, see {@link ServerOptions.codec}.
+         * `null` with the raw codec or an empty value.
+         */
+        readonly data: unknown
+        /**
+         * Why the value could not be decoded, only set when the decode error policy is `pass`.
+         */
+        readonly decodeError: string | null
     }
 
     /** @internal Native message type for internal use */
     type NativeMessage = binding.map.Message
 
+    /** @internal Converts a message returned by a handler to its native form */
+    function toNativeMessage(message: Message): NativeMessage {
+        return {
+            value: message.value,
+            data: message.data,
+            keys: message.keys,
+            tags: message.tags,
+            userMetadata: message.userMetadata ? toNativeMetadata(message.userMetadata) : undefined,
+        } satisfies NativeMessage
+    }
+
     /**
      * Options for creating a map output message.
      */
@@ -777,6 +1198,8 @@ export namespace map {
     export class Message {
         /** The message payload */
         value: Buffer
+        /** Data the codec of the server encodes into the payload, replaces `value` when set */
+        data?: unknown
         /** Optional keys for routing */
         keys?: string[]
         /** Optional tags for conditional forwarding */
@@ -803,18 +1226,18 @@ export namespace map {
         public static toDrop(): Message {
             return new Message(Buffer.from([]), { tags: [DROP] })
         }
-    }
 
-    /** @internal */
-    function toNativeMetadata(metadata: UserMetadata): Record<string, Record<string, Buffer>> {
-        let nativeMetadata: Record<string, Record<string, Buffer>> = {}
-        for (const group of metadata.getGroups()) {
-            nativeMetadata[group] = {}
-            for (const key of metadata.getKeys(group)) {
-                nativeMetadata[group][key] = metadata.getValue(group, key)
-            }
+        /**
+         * Create a message whose payload is `data` encoded by the codec of the server.
+         * @param data - The data to encode, e.g. a plain object with the `json` codec
+         * @param options - Optional keys, tags, and metadata
+         * @returns A message carrying the data
+         */
+        public static fromData(data: unknown, options?: MessageOptions): Message {
+            const message = new Message(Buffer.from([]), options)
+            message.data = data
+            return message
         }
-        return nativeMetadata
     }
 
     /**
@@ -836,39 +1259,275 @@ export namespace map {
         /**
          * Create a new map server.
          * @param mapFn - Async function that transforms input datum to output messages
+         * @param retryPolicy - Optional policy for retrying failed invocations of the function
          */
-        constructor(mapFn: (message: Datum) => Promise<Message[]>) {
-            const wrappedCallback = async (datum: Datum): Promise<NativeMessage[]> => {
+        constructor(mapFn: (message: Datum) => Promise<Message[]>, retryPolicy?: RetryPolicy) {
+            const wrappedCallback = reportErrors(async (datum: Datum): Promise<NativeMessage[]> => {
                 let messages = await mapFn(datum)
-                return messages.map((message): NativeMessage => {
-                    return {
-                        value: message.value,
-                        keys: message.keys,
-                        tags: message.tags,
-                        userMetadata: message.userMetadata ? toNativeMetadata(message.userMetadata) : undefined,
-                    } satisfies NativeMessage
-                })
-            }
+                return messages.map(toNativeMessage)
+            })
             this.nativeServer = new binding.map.MapAsyncServer(
                 wrappedCallback as (arg: binding.map.Datum) => Promise<NativeMessage[]>,
+                retryPolicy,
             )
         }
 
+        /**
+         * Start the map server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        public start(options?: ServerOptions): Promise<void>
         /**
          * Start the map server.
          * @param socketPath - Optional custom Unix socket path
          * @param serverInfoPath - Optional path for server info file
          */
-        public async start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void> {
-            return this.nativeServer.start(socketPath, serverInfoPath)
+        public start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>
+        public async start(options?: ServerOptions | string | null, serverInfoPath?: string | null): Promise<void> {
+            return this.nativeServer.start(toServerOptions(options, serverInfoPath))
+        }
+
+        /**
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
+         */
+        public stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
+        }
+    }
+
+    /**
+     * Options for coalescing the requests that arrive concurrently into one call of the handler of a
+     * {@link MicroBatchServer}.
+     */
+    export interface MicroBatchOptions {
+        /** Most requests handed to one call of the handler. Defaults to 64. */
+        maxBatchSize?: number
+        /**
+         * Longest time in milliseconds the first request of a batch waits for more requests before the batch is
+         * handed to the handler. Defaults to 5.
+         */
+        maxBatchDelayMs?: number
+    }
+
+    /**
+     * Handler of a {@link MicroBatchServer}, returns the messages of every datum of the batch in the order of the
+     * datums.
+     */
+    export type MapBatchCallback = (datums: Datum[]) => Promise<Message[][]>
+
+    /**
+     * Map server that coalesces the requests arriving concurrently into micro-batches and calls the handler once
+     * per batch, which saves crossing into JavaScript for every request at high throughput. The messages returned
+     * for each datum are sent back as the response to its own request.
+     *
+     * A batch is handed to the handler once it has `maxBatchSize` requests or its first request waited for
+     * `maxBatchDelayMs`. When the handler throws, times out or returns a list of messages per datum that doesn't
+     * match the batch, every request of the batch fails. A retry policy retries the whole batch.
+     *
+     * @example
+     * ```typescript
+     * const server = new map.MicroBatchServer(
+     *   async (datums) => datums.map((datum) => [new map.Message(datum.value, { keys: datum.keys })]),
+     *   { maxBatchSize: 128, maxBatchDelayMs: 2 },
+     * );
+     *
+     * await server.start();
+     * ```
+     */
+    export class MicroBatchServer {
+        private readonly nativeServer: binding.map.MapAsyncServer
+
+        /**
+         * Create a new micro-batching map server.
+         * @param mapBatchFn - Async function that transforms a batch of datums to the output messages of each
+         * @param microBatch - Optional batch size and delay
+         * @param retryPolicy - Optional policy for retrying failed invocations, a retry receives the whole batch again
+         */
+        constructor(mapBatchFn: MapBatchCallback, microBatch?: MicroBatchOptions, retryPolicy?: RetryPolicy) {
+            const wrappedCallback = reportErrors(async (datums: Datum[]): Promise<NativeMessage[][]> => {
+                const messages = await mapBatchFn(datums)
+                return messages.map((datumMessages) => datumMessages.map(toNativeMessage))
+            })
+            this.nativeServer = binding.map.MapAsyncServer.withMicroBatching(
+                wrappedCallback as (datums: binding.map.Datum[]) => Promise<NativeMessage[][]>,
+                microBatch,
+                retryPolicy,
+            )
+        }
+
+        /**
+         * Start the map server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        public start(options?: ServerOptions): Promise<void> {
+            return this.nativeServer.start(options)
+        }
+
+        /**
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
+         */
+        public stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
+        }
+    }
+
+    /**
+     * Key of the `workerData` a {@link WorkerPoolServer} identifies its pool and each worker thread with.
+     * @internal
+     */
+    const WORKER_POOL_DATA = '__numaflowWorkerPool'
+
+    /**
+     * Delay before a worker thread that exited is replaced, so that a script that fails on load doesn't spin.
+     * @internal
+     */
+    const WORKER_RESPAWN_DELAY_MS = 1000
+
+    /** @internal */
+    interface WorkerPoolData {
+        poolId: number
+        workerId: number
+    }
+
+    /**
+     * Options of the worker threads of a {@link WorkerPoolServer}.
+     */
+    export interface WorkerPoolOptions {
+        /** Number of worker threads. Defaults to the number of CPUs available to the process. */
+        workers?: number
+        /** Options every worker thread is created with, e.g. `resourceLimits` or `execArgv`. */
+        workerOptions?: Omit<WorkerOptions, 'workerData'>
+    }
+
+    /**
+     * Map server that runs the handler in a pool of `worker_threads`, so that CPU-heavy handlers don't block the main
+     * thread or each other. Every worker thread loads `workerScript`, which registers the handler with
+     * {@link registerWorker}.
+     *
+     * The native server hands the datums to the registered workers in turns and waits for a worker to register
+     * when there is none. A worker that exits is replaced, the datums it was handling are handed to the other
+     * workers, up to 3 times per datum before the request fails. The retry policy and `handlerTimeoutMs` apply to
+     * every call as with {@link AsyncServer}, a timed out handler keeps running on its worker.
+     *
+     * The messages of a datum are sent in the order the handler returned them. Datums with the same keys may be
+     * handled by different workers at the same time; set `maxInFlightPerKey: 1` to handle them one at a time, in
+     * the order they arrived. The value of a datum is copied once into the worker thread that handles it.
+     *
+     * @example
+     * ```typescript
+     * // main.ts
+     * const server = new map.WorkerPoolServer(require.resolve('./mapper'), { workers: 4 });
+     * await server.start();
+     *
+     * // mapper.ts
+     * map.registerWorker(async (datum) => [new map.Message(expensiveTransform(datum.value))]);
+     * ```
+     */
+    export class WorkerPoolServer {
+        private readonly nativeServer: binding.map.MapAsyncServer
+        private readonly workers = new Set<Worker>()
+        private nextWorkerId = 0
+        private terminating = false
+
+        /**
+         * Create a new worker pool map server.
+         * @param workerScript - Path or URL of the script every worker thread runs, it calls {@link registerWorker}
+         * @param poolOptions - Optional number of worker threads and their options
+         * @param retryPolicy - Optional policy for retrying failed invocations of the handler
+         */
+        constructor(
+            private readonly workerScript: string | URL,
+            private readonly poolOptions: WorkerPoolOptions = {},
+            retryPolicy?: RetryPolicy,
+        ) {
+            this.nativeServer = binding.map.MapAsyncServer.withWorkerPool(retryPolicy)
         }
 
         /**
-         * Stop the server gracefully.
+         * Start the worker threads and the map server, the worker threads are terminated once the server stopped.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
          */
-        public stop(): void {
-            this.nativeServer.stop()
+        public async start(options?: ServerOptions): Promise<void> {
+            this.terminating = false
+            const workers = this.poolOptions.workers ?? availableParallelism()
+            for (let i = 0; i < workers; i++) {
+                this.spawnWorker()
+            }
+            try {
+                await this.nativeServer.start(options)
+            } finally {
+                this.terminating = true
+                await Promise.all([...this.workers].map((worker) => worker.terminate()))
+            }
+        }
+
+        /**
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
+         */
+        public stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
+        }
+
+        private spawnWorker(): void {
+            const data: WorkerPoolData = { poolId: this.nativeServer.workerPoolId()!, workerId: this.nextWorkerId++ }
+            const worker = new Worker(this.workerScript, {
+                ...this.poolOptions.workerOptions,
+                workerData: { [WORKER_POOL_DATA]: data },
+            })
+            let error: string | undefined
+            worker.on('error', (err) => {
+                error = err instanceof Error ? (err.stack ?? err.message) : String(err)
+            })
+            worker.on('exit', (exitCode) => {
+                this.workers.delete(worker)
+                // Also deregisters the workers terminated with the server, which may be started again.
+                this.nativeServer.workerExited(data.workerId, exitCode, error)
+                if (this.terminating) {
+                    return
+                }
+                // Workers keep being replaced while the server drains, the requests in flight may need them.
+                setTimeout(() => {
+                    if (!this.terminating) {
+                        this.spawnWorker()
+                    }
+                }, WORKER_RESPAWN_DELAY_MS)
+            })
+            this.workers.add(worker)
+        }
+    }
+
+    /**
+     * Registers the handler of a worker thread of a {@link WorkerPoolServer}, to be called once by its
+     * `workerScript`. The thread keeps running until the server terminates it.
+     * @param mapFn - Async function that transforms input datum to output messages
+     * @throws If called outside of a worker thread of a {@link WorkerPoolServer}
+     */
+    export function registerWorker(mapFn: (message: Datum) => Promise<Message[]>): void {
+        const data = (workerData as Record<string, WorkerPoolData | undefined> | null)?.[WORKER_POOL_DATA]
+        if (parentPort === null || data === undefined) {
+            throw new Error('map.registerWorker must be called in a worker thread of a map.WorkerPoolServer')
         }
+        const wrappedCallback = reportErrors(async (datum: Datum): Promise<NativeMessage[]> => {
+            const messages = await mapFn(datum)
+            return messages.map(toNativeMessage)
+        })
+        binding.map.registerWorker(
+            data.poolId,
+            data.workerId,
+            wrappedCallback as (arg: binding.map.Datum) => Promise<NativeMessage[]>,
+        )
+        // The handler is called through a handle that doesn't keep the thread alive, listening to the main thread
+        // does until the server terminates the worker.
+        parentPort.on('message', () => {})
     }
 }
 
@@ -905,56 +1564,25 @@ export namespace map {
  * server.start();
  * ```
  */
-export namespace sink {
-    /**
-     * System-provided metadata attached to sink messages.
-     * Contains read-only system information organized by groups.
-     */
-    export interface SystemMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer
-    }
+export namespace sink {
+    /**
+     * System-provided metadata attached to messages, read-only to handlers.
+     * Shared by the namespaces that carry metadata, see {@link metadata.SystemMetadata}.
+     */
+    export type SystemMetadata = metadata.SystemMetadata
 
     /**
-     * User-defined metadata attached to sink messages.
-     * Contains read-only user information organized by groups.
+     * User-defined metadata that can be attached to messages, groups of key/value pairs.
+     * Shared by the namespaces that carry metadata, see {@link metadata.UserMetadata}.
+     *
+     * @example
+     * ```typescript
+     * const userMetadata = new sink.UserMetadata();
+     * userMetadata.addString('tracking', 'source', 'api');
+     * ```
      */
-    export interface UserMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer
-    }
+    export type UserMetadata = metadata.UserMetadata
+    export const UserMetadata = metadata.UserMetadata
 
     /**
      * Input datum for sink operations.
@@ -991,6 +1619,11 @@ export namespace sink {
          * @returns A record of header key-value pairs
          */
         getHeaders(): Record<string, string>
+        /**
+         * Get the context of the span the message is handled in, a child of the span in its `traceparent` header.
+         * @returns The trace context, or null when the message has no valid `traceparent` header
+         */
+        traceContext(): TraceContext | null
         /**
          * Get the user-defined metadata for this message.
          * @returns The user metadata object
@@ -1001,11 +1634,35 @@ export namespace sink {
          * @returns The system metadata object
          */
         systemMetadata(): SystemMetadata
+        /**
+         * Get the value decoded by the codec of the server, see {@link ServerOptions.codec}.
+         * @returns The decoded value, or null with the raw codec or an empty value
+         */
+        data(): unknown
+        /**
+         * Get why the value could not be decoded, only set when the decode error policy is `pass`.
+         * @returns The decode error, or null when the value was decoded
+         */
+        decodeError(): string | null
     }
 
+    /**
+     * User metadata of an on-success message, either the metadata of a datum or groups of key/value pairs.
+     */
+    export type MessageUserMetadata = UserMetadata | Record<string, Record<string, Buffer>>
+
     /**
      * Message class for sink serve operations.
      * Used with Response.onSuccess() to include a payload.
+     *
+     * @example
+     * ```typescript
+     * // Forward the datum with its user metadata to the on-success sink
+     * sink.Response.onSuccess(datum.id, new sink.Message(datum.getValue(), datum.keys, datum.userMetadata()))
+     *
+     * // Attach new metadata
+     * new sink.Message(payload, undefined, { audit: { stored: Buffer.from('true') } })
+     * ```
      */
     export class Message {
         /** @internal */
@@ -1015,9 +1672,14 @@ export namespace sink {
          * Create a new sink message.
          * @param value - The message payload
          * @param keys - Optional keys for the message
+         * @param userMetadata - Optional user metadata for the message
          */
-        constructor(value: Buffer, keys?: string[]) {
-            this._nativeMessage = new binding.sink.SinkMessage(value, keys)
+        constructor(value: Buffer, keys?: string[], userMetadata?: MessageUserMetadata) {
+            this._nativeMessage = new binding.sink.SinkMessage(
+                value,
+                keys,
+                userMetadata ? toNativeMetadata(userMetadata) : undefined,
+            )
         }
     }
 
@@ -1190,34 +1852,44 @@ export namespace sink {
         /**
          * Create a new sink server.
          * @param sinkFn - Async function that processes datums and returns responses
-         */
-        constructor(sinkFn: SinkCallback) {
-            const wrappedCallback = async (
-                nativeIterator: SinkDatumIteratorNative,
-            ): Promise<binding.sink.SinkResponse[]> => {
-                const iterator = new SinkDatumIteratorImpl(nativeIterator)
-                const responses = await sinkFn(iterator)
-                // Extract native responses from wrapper Response objects
-                return responses.map((r) => r._nativeResponse)
-            }
+         * @param retryPolicy - Optional policy for retrying failed invocations, a retry receives the whole batch again
+         */
+        constructor(sinkFn: SinkCallback, retryPolicy?: RetryPolicy) {
+            const wrappedCallback = reportErrors(
+                async (nativeIterator: SinkDatumIteratorNative): Promise<binding.sink.SinkResponse[]> => {
+                    const iterator = new SinkDatumIteratorImpl(nativeIterator)
+                    const responses = await sinkFn(iterator)
+                    // Extract native responses from wrapper Response objects
+                    return responses.map((r) => r._nativeResponse)
+                },
+            )
 
-            this.nativeServer = new binding.sink.SinkAsyncServer(wrappedCallback)
+            this.nativeServer = new binding.sink.SinkAsyncServer(wrappedCallback, retryPolicy)
         }
 
+        /**
+         * Start the sink server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>
         /**
          * Start the sink server.
          * @param socketPath - Optional custom Unix socket path
          * @param serverInfoPath - Optional path for server info file
          */
-        async start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void> {
-            return this.nativeServer.start(socketPath, serverInfoPath)
+        start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>
+        async start(options?: ServerOptions | string | null, serverInfoPath?: string | null): Promise<void> {
+            return this.nativeServer.start(toServerOptions(options, serverInfoPath))
         }
 
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void {
-            this.nativeServer.stop()
+        stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
         }
     }
 }
@@ -1291,6 +1963,20 @@ export namespace batchmap {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext
+        /**
+         * The value decoded by the codec of the server, see {@link ServerOptions.codec}.
+         * Not set with the raw codec or an empty value.
+         */
+        data?: unknown
+        /**
+         * Why the value could not be decoded, only set when the decode error policy is `pass`.
+         */
+        decodeError?: string
     }
 
     /**
@@ -1306,7 +1992,11 @@ export namespace batchmap {
         /**
          * The message payload passed to the next vertex.
          */
-        value: Buffer
+        value?: Buffer
+        /**
+         * Data the codec of the server encodes into the payload, replaces `value` when set.
+         */
+        data?: unknown
         /**
          * Tags for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
          */
@@ -1317,8 +2007,10 @@ export namespace batchmap {
      * Options for appending a message to a batch response.
      */
     export interface BatchMessageOptions {
-        /** The message payload */
-        value: Buffer
+        /** The message payload, empty when neither it nor `data` is set */
+        value?: Buffer
+        /** Data the codec of the server encodes into the payload, replaces `value` when set */
+        data?: unknown
         /** Keys for routing the output message */
         keys?: string[]
         /** Tags for conditional forwarding */
@@ -1334,6 +2026,9 @@ export namespace batchmap {
      * const response = batchmap.Response.fromId(datum.id);
      * response.append({ value: Buffer.from('result1'), keys: ['key1'] });
      * response.append({ value: Buffer.from('result2'), keys: ['key2'] });
+     *
+     * // With the json codec
+     * response.append({ data: { id: datum.id, ok: true } });
      * ```
      */
     export class Response {
@@ -1428,34 +2123,44 @@ export namespace batchmap {
         /**
          * Create a new batch map server.
          * @param batchmapFn - Async function that processes a batch of datums
-         */
-        constructor(batchmapFn: BatchMapCallback) {
-            const wrappedCallback = async (
-                nativeIterator: BatchDatumIteratorNative,
-            ): Promise<binding.batchmap.BatchResponse[]> => {
-                const iterator = new BatchDatumIteratorImpl(nativeIterator)
-                const responses = await batchmapFn(iterator)
-                // Extract native responses from wrapper Response objects
-                return responses.map((r) => r._nativeResponse)
-            }
+         * @param retryPolicy - Optional policy for retrying failed invocations, a retry receives the whole batch again
+         */
+        constructor(batchmapFn: BatchMapCallback, retryPolicy?: RetryPolicy) {
+            const wrappedCallback = reportErrors(
+                async (nativeIterator: BatchDatumIteratorNative): Promise<binding.batchmap.BatchResponse[]> => {
+                    const iterator = new BatchDatumIteratorImpl(nativeIterator)
+                    const responses = await batchmapFn(iterator)
+                    // Extract native responses from wrapper Response objects
+                    return responses.map((r) => r._nativeResponse)
+                },
+            )
 
-            this.nativeServer = new binding.batchmap.BatchMapAsyncServer(wrappedCallback)
+            this.nativeServer = new binding.batchmap.BatchMapAsyncServer(wrappedCallback, retryPolicy)
         }
 
+        /**
+         * Start the batch map server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>
         /**
          * Start the batch map server.
          * @param socketPath - Optional custom Unix socket path
          * @param serverInfoPath - Optional path for server info file
          */
-        async start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void> {
-            return this.nativeServer.start(socketPath, serverInfoPath)
+        start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>
+        async start(options?: ServerOptions | string | null, serverInfoPath?: string | null): Promise<void> {
+            return this.nativeServer.start(toServerOptions(options, serverInfoPath))
         }
 
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void {
-            this.nativeServer.stop()
+        stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
         }
     }
 }
@@ -1515,6 +2220,20 @@ export namespace mapstream {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext
+        /**
+         * The value decoded by the codec of the server, see {@link ServerOptions.codec}.
+         * Not set with the raw codec or an empty value.
+         */
+        data?: unknown
+        /**
+         * Why the value could not be decoded, only set when the decode error policy is `pass`.
+         */
+        decodeError?: string
     }
 
     /** @internal */
@@ -1548,6 +2267,8 @@ export namespace mapstream {
     export class Message {
         /** The message payload */
         value: Buffer
+        /** Data the codec of the server encodes into the payload, replaces `value` when set */
+        data?: unknown
         /** Optional keys for routing */
         keys?: string[]
         /** Optional tags for conditional forwarding */
@@ -1571,6 +2292,18 @@ export namespace mapstream {
         public static toDrop(): Message {
             return messageToDrop()
         }
+
+        /**
+         * Create a message whose payload is `data` encoded by the codec of the server.
+         * @param data - The data to encode, e.g. a plain object with the `json` codec
+         * @param options - Optional keys and tags
+         * @returns A message carrying the data
+         */
+        public static fromData(data: unknown, options?: MessageOptions): Message {
+            const message = new Message(Buffer.from([]), options)
+            message.data = data
+            return message
+        }
     }
 
     /**
@@ -1596,13 +2329,13 @@ export namespace mapstream {
             const wrapperMapFn = (datum: Datum): (() => Promise<NativeMessage | null>) => {
                 const iterator = mapFn(datum)[Symbol.asyncIterator]()
 
-                return async () => {
+                return reportErrors(async () => {
                     const result = await iterator.next()
                     if (result.done) {
                         return null
                     }
                     return result.value satisfies NativeMessage
-                }
+                })
             }
 
             this.mapper = new binding.mapstream.MapStreamAsyncServer(wrapperMapFn)
@@ -1610,18 +2343,27 @@ export namespace mapstream {
 
         /**
          * Start the map stream server.
-         * @param sockFile - Optional custom Unix socket path
-         * @param infoFile - Optional path for server info file
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>
+        /**
+         * Start the map stream server.
+         * @param socketPath - Optional custom Unix socket path
+         * @param serverInfoPath - Optional path for server info file
          */
-        async start(sockFile?: string | null, infoFile?: string | null): Promise<void> {
-            return this.mapper.start(sockFile, infoFile)
+        start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>
+        async start(options?: ServerOptions | string | null, serverInfoPath?: string | null): Promise<void> {
+            return this.mapper.start(toServerOptions(options, serverInfoPath))
         }
 
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void {
-            this.mapper.stop()
+        stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.mapper.stop(timeoutMs)
         }
     }
 }
@@ -1689,6 +2431,21 @@ export namespace reduce {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext
+        /**
+         * Whether the watermark had reached the end of the window when the datum arrived, i.e. the datum arrived
+         * after the window would have closed without allowed lateness.
+         */
+        late: boolean
+        /**
+         * How far the watermark was past the end of the window when the datum arrived in milliseconds, 0 if the
+         * datum is not late.
+         */
+        latenessMs: number
     }
 
     /**
@@ -1708,7 +2465,7 @@ export namespace reduce {
 
     /**
      * Metadata provided to reduce handlers.
-     * Contains information about the current time window.
+     * Contains information about the current time window, the windows of the vertex and the vertex itself.
      */
     export interface Metadata {
         /**
@@ -1716,6 +2473,33 @@ export namespace reduce {
          * Contains the start and end times of the current window.
          */
         intervalWindow: IntervalWindow
+        /**
+         * Kind of the windows of the vertex.
+         * Read from the vertex spec numaflow sets in `NUMAFLOW_VERTEX_OBJECT`, not set without it.
+         */
+        windowKind?: 'fixed' | 'sliding'
+        /**
+         * Length of the windows of the vertex in milliseconds.
+         * Read from the vertex spec, not set without it.
+         */
+        windowLengthMs?: number
+        /**
+         * Time between the starts of two consecutive windows in milliseconds, the window length for fixed windows.
+         * Read from the vertex spec, not set without it.
+         */
+        slideMs?: number
+        /**
+         * Watermark of the latest datum of the window, not set before the first datum. The reduce protocol does not
+         * tell the watermark the window closed at: once the datums are exhausted this is the watermark of the last
+         * datum, which may be behind the watermark that closed the window.
+         */
+        readonly latestWatermark?: Date
+        /** Name of the pipeline, from `NUMAFLOW_PIPELINE_NAME`. */
+        pipelineName?: string
+        /** Name of the vertex, from `NUMAFLOW_VERTEX_NAME`. */
+        vertexName?: string
+        /** Replica of the vertex, from `NUMAFLOW_REPLICA`. */
+        replica?: number
     }
 
     /** @internal */
@@ -1782,6 +2566,40 @@ export namespace reduce {
     /** @internal */
     type ReduceCallbackArgs = binding.reduce.ReduceCallbackArgs
 
+    /**
+     * Handler of one window, created by a ReducerFactory.
+     */
+    export interface Reducer {
+        /** Aggregates the datums of the window */
+        reduceFn: Callback
+    }
+    /**
+     * Creates the Reducer of a new window.
+     * Called once per window, the Reducer is released once the window is reduced.
+     */
+    export type ReducerFactory = () => Reducer
+
+    /**
+     * Incremental aggregation of the datums of a window into an accumulator, for servers created with
+     * {@link AsyncServer.withAggregator}.
+     *
+     * The server drains the datums of every window itself and hands them over in micro-batches. Each batch is added
+     * to an accumulator of its own, which is then merged into the accumulator of the window, so `addInput` may
+     * modify and return the accumulator it is given.
+     */
+    export interface Aggregator<A> {
+        /** Creates an empty accumulator */
+        createAccumulator(): A
+        /** Adds a datum to the accumulator, returns the resulting accumulator */
+        addInput(accumulator: A, datum: Datum): A
+        /** Merges accumulators into one, returns the resulting accumulator */
+        mergeAccumulators(accumulators: A[]): A
+        /** Returns the messages of the window from the accumulator of all its datums */
+        extractOutput(keys: string[], accumulator: A, metadata: Metadata): Message[] | Promise<Message[]>
+    }
+    /** @internal */
+    type NativeWindowAggregator = binding.reduce.WindowAggregator
+
     /** @internal */
     class DatumIteratorImpl implements AsyncIterableIterator<Datum> {
         constructor(private readonly nativeIterator: DatumIteratorNative) {}
@@ -1799,39 +2617,136 @@ export namespace reduce {
         }
     }
 
+    /**
+     * Metadata of the window of the callback arguments, reading the watermark as the datums arrive.
+     * @internal
+     */
+    function toMetadata(args: ReduceCallbackArgs): Metadata {
+        return {
+            ...args.metadata,
+            get latestWatermark() {
+                return args.latestWatermark ?? undefined
+            },
+        }
+    }
+
+    /**
+     * Wraps an aggregator into the aggregator factory of the native server, which creates the accumulator of every
+     * window.
+     * @internal
+     */
+    function toNativeAggregator<A>(aggregator: Aggregator<A>): (args: ReduceCallbackArgs) => NativeWindowAggregator {
+        return reportErrorsSync((args: ReduceCallbackArgs): NativeWindowAggregator => {
+            let accumulator = aggregator.createAccumulator()
+            return {
+                addInputs: reportErrorsSync((datums: Datum[]) => {
+                    let batch = aggregator.createAccumulator()
+                    for (const datum of datums) {
+                        batch = aggregator.addInput(batch, datum)
+                    }
+                    accumulator = aggregator.mergeAccumulators([accumulator, batch])
+                }),
+                extractOutput: reportErrors(async (): Promise<NativeMessage[]> => {
+                    return aggregator.extractOutput(args.keys, accumulator, toMetadata(args))
+                }),
+            }
+        })
+    }
+
+    /**
+     * Wraps a reduce callback into the handler of the native server.
+     * @internal
+     */
+    function toNativeReduceFn(reduceFn: Callback): (args: ReduceCallbackArgs) => Promise<NativeMessage[]> {
+        return reportErrors(async (args: ReduceCallbackArgs): Promise<NativeMessage[]> => {
+            const iterator = new DatumIteratorImpl(args.takeIterator)
+            return reduceFn(args.keys, iterator, toMetadata(args))
+        })
+    }
+
     /**
      * Async server for reduce operations.
+     *
+     * Given a callback, every window calls that same callback. Given a ReducerFactory through
+     * {@link AsyncServer.withFactory}, every window gets its own Reducer, so state can be kept on the Reducer.
+     * Given an Aggregator through {@link AsyncServer.withAggregator}, the server iterates the datums itself and
+     * adds them to an accumulator per window.
      */
     export class AsyncServer {
         private readonly nativeServer: binding.reduce.ReduceAsyncServer
 
+        /**
+         * Create a new reduce server whose windows each get their own Reducer.
+         * @param createReducer - Factory called once per window
+         */
+        static withFactory(createReducer: ReducerFactory): AsyncServer {
+            return new AsyncServer(
+                binding.reduce.ReduceAsyncServer.withFactory(() => {
+                    const reducer = createReducer()
+                    return toNativeReduceFn(reducer.reduceFn.bind(reducer))
+                }),
+            )
+        }
+
+        /**
+         * Create a new reduce server that aggregates the datums of every window incrementally.
+         *
+         * @example
+         * ```typescript
+         * const server = reduce.AsyncServer.withAggregator<number>({
+         *   createAccumulator: () => 0,
+         *   addInput: (sum, datum) => sum + parseInt(datum.value.toString()),
+         *   mergeAccumulators: (sums) => sums.reduce((a, b) => a + b, 0),
+         *   extractOutput: (keys, sum) => [new reduce.Message(Buffer.from(String(sum)), { keys })],
+         * })
+         * ```
+         *
+         * @param aggregator - Aggregator of the datums of every window
+         * @param microBatch - Optional size and delay of the batches the datums are added in
+         */
+        static withAggregator<A>(aggregator: Aggregator<A>, microBatch?: map.MicroBatchOptions): AsyncServer {
+            return new AsyncServer(
+                binding.reduce.ReduceAsyncServer.withAggregator(toNativeAggregator(aggregator), microBatch),
+            )
+        }
+
         /**
          * Create a new reduce server.
          * @param reduceFn - Async function that aggregates datums by key
          */
-        constructor(reduceFn: Callback) {
-            const wrappedCallback = async (args: ReduceCallbackArgs): Promise<NativeMessage[]> => {
-                const iterator = new DatumIteratorImpl(args.takeIterator)
-                return reduceFn(args.keys, iterator, args.metadata)
-            }
-
-            this.nativeServer = new binding.reduce.ReduceAsyncServer(wrappedCallback)
+        constructor(reduceFn: Callback)
+        /** @internal */
+        constructor(nativeServer: binding.reduce.ReduceAsyncServer)
+        constructor(reduceFn: Callback | binding.reduce.ReduceAsyncServer) {
+            this.nativeServer =
+                reduceFn instanceof binding.reduce.ReduceAsyncServer
+                    ? reduceFn
+                    : new binding.reduce.ReduceAsyncServer(toNativeReduceFn(reduceFn))
         }
 
+        /**
+         * Start the reduce server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>
         /**
          * Start the reduce server.
          * @param socketPath - Optional custom Unix socket path
          * @param serverInfoPath - Optional path for server info file
          */
-        async start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void> {
-            return this.nativeServer.start(socketPath, serverInfoPath)
+        start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>
+        async start(options?: ServerOptions | string | null, serverInfoPath?: string | null): Promise<void> {
+            return this.nativeServer.start(toServerOptions(options, serverInfoPath))
         }
 
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop(): void {
-            this.nativeServer.stop()
+        stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
         }
     }
 }
@@ -1872,7 +2787,8 @@ export namespace reduce {
  *   }
  * }
  *
- * const server = new sessionReduce.AsyncServer(new MySessionReducer());
+ * // Every session gets its own reducer, so that its state is not shared with other sessions.
+ * const server = sessionReduce.AsyncServer.withFactory(() => new MySessionReducer());
  * server.start();
  * ```
  */
@@ -1905,6 +2821,11 @@ export namespace sessionReduce {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext
     }
 
     /** @internal */
@@ -1957,6 +2878,22 @@ export namespace sessionReduce {
         }
     }
 
+    /**
+     * Metadata of the session a session reduce handler runs for.
+     *
+     * The bounds of the session window are not available: the session reduce server of the numaflow SDK keeps them
+     * to itself.
+     */
+    export interface SessionMetadata {
+        /**
+         * Number of sessions merged into this one. Sessions are merged into a new session before its first datum is
+         * received or its datums end, so the count is final from then on.
+         */
+        readonly mergedSessions: number
+        /** Whether this session was created by merging other sessions. */
+        readonly merged: boolean
+    }
+
     /**
      * Callback type for the main session reduce function.
      * Processes messages and yields output as an async iterable.
@@ -1964,6 +2901,7 @@ export namespace sessionReduce {
     export type SessionReduceFnCallback = (
         keys: string[],
         iterator: AsyncIterableIterator<Datum>,
+        metadata: SessionMetadata,
     ) => AsyncIterable<Message>
     /**
      * Callback type for serializing the current accumulator state.
@@ -1977,6 +2915,8 @@ export namespace sessionReduce {
     export type MergeAccumulatorFnCallback = (accumulator: Buffer) => Promise<void>
     /** @internal */
     type SessionReduceCallbackArgs = binding.sessionReduce.SessionReduceCallbackArgs
+    /** @internal */
+    type SessionHandlers = binding.sessionReduce.SessionHandlers
 
     /** @internal */
     class DatumIteratorImpl implements AsyncIterableIterator<Datum> {
@@ -2016,59 +2956,125 @@ export namespace sessionReduce {
         mergeAccumulatorFn: MergeAccumulatorFnCallback
     }
 
+    /**
+     * Creates the SessionReducer of a new session.
+     * Called once per session, the first time it is reduced or another session is merged into it.
+     */
+    export type SessionReducerFactory = () => SessionReducer
+
+    /**
+     * Wraps the methods of a SessionReducer into the handlers of the native server.
+     * @internal
+     */
+    function toSessionHandlers(sessionReducerImpl: SessionReducer): SessionHandlers {
+        const wrapperSessionReduceFnCallback = (
+            callbackArgs: SessionReduceCallbackArgs,
+        ): (() => Promise<NativeMessage | null>) => {
+            const iterator = new DatumIteratorImpl(callbackArgs.takeIterator)
+            const metadata: SessionMetadata = {
+                get mergedSessions() {
+                    return callbackArgs.mergedSessions
+                },
+                get merged() {
+                    return callbackArgs.mergedSessions > 0
+                },
+            }
+            const wrappedIterator = sessionReducerImpl
+                .sessionReduceFn(callbackArgs.keys, iterator, metadata)
+                [Symbol.asyncIterator]()
+
+            return reportErrors(async () => {
+                const result = await wrappedIterator.next()
+                if (result.done) {
+                    return null
+                }
+
+                return result.value satisfies NativeMessage
+            })
+        }
+
+        return {
+            sessionReduceFn: wrapperSessionReduceFnCallback,
+            accumulatorFn: reportErrors(sessionReducerImpl.accumulatorFn.bind(sessionReducerImpl)),
+            mergeAccumulatorFn: reportErrors(sessionReducerImpl.mergeAccumulatorFn.bind(sessionReducerImpl)),
+        }
+    }
+
     /**
      * Async server for session reduce operations.
      *
      * Requires a SessionReducer implementation that provides
      * the reduce function, accumulator serialization, and merge logic.
+     *
+     * Given a SessionReducer instance, every session calls the methods of that same instance. Given a
+     * SessionReducerFactory through {@link AsyncServer.withFactory}, every session gets its own instance: the
+     * accumulator of a session is serialized by its own instance and merged into the instance of the session it is
+     * merged into.
      */
     export class AsyncServer {
         private readonly nativeServer: binding.sessionReduce.SessionReduceAsyncServer
 
         /**
-         * Create a new session reduce server.
-         * @param sessionReducerImpl - Implementation of SessionReducer interface
+         * Create a new session reduce server whose sessions each get their own SessionReducer.
+         * @param createSessionReducer - Factory called once per session
          */
-        constructor(sessionReducerImpl: SessionReducer) {
-            const wrapperSessionReduceFnCallback = (
-                callbackArgs: SessionReduceCallbackArgs,
-            ): (() => Promise<NativeMessage | null>) => {
-                const iterator = new DatumIteratorImpl(callbackArgs.takeIterator)
-                const wrappedIterator = sessionReducerImpl
-                    .sessionReduceFn(callbackArgs.keys, iterator)
-                    [Symbol.asyncIterator]()
-
-                return async () => {
-                    const result = await wrappedIterator.next()
-                    if (result.done) {
-                        return null
-                    }
+        static withFactory(createSessionReducer: SessionReducerFactory): AsyncServer {
+            return new AsyncServer(
+                binding.sessionReduce.SessionReduceAsyncServer.withFactory(() =>
+                    toSessionHandlers(createSessionReducer()),
+                ),
+            )
+        }
 
-                    return result.value satisfies NativeMessage
-                }
+        /**
+         * Create a new session reduce server whose sessions share one SessionReducer.
+         * @param sessionReducerImpl - Implementation of SessionReducer interface
+         */
+        constructor(sessionReducerImpl: SessionReducer)
+        /** @internal */
+        constructor(nativeServer: binding.sessionReduce.SessionReduceAsyncServer)
+        constructor(sessionReducer: SessionReducer | binding.sessionReduce.SessionReduceAsyncServer) {
+            if (sessionReducer instanceof binding.sessionReduce.SessionReduceAsyncServer) {
+                this.nativeServer = sessionReducer
+                return
             }
-
+            if (typeof sessionReducer === 'function') {
+                throw new TypeError(
+                    'sessionReduce.AsyncServer takes a SessionReducer instance, ' +
+                        'use sessionReduce.AsyncServer.withFactory() to create one per session',
+                )
+            }
+            const handlers = toSessionHandlers(sessionReducer)
             this.nativeServer = new binding.sessionReduce.SessionReduceAsyncServer(
-                wrapperSessionReduceFnCallback.bind(sessionReducerImpl),
-                sessionReducerImpl.accumulatorFn.bind(sessionReducerImpl),
-                sessionReducerImpl.mergeAccumulatorFn.bind(sessionReducerImpl),
+                handlers.sessionReduceFn,
+                handlers.accumulatorFn,
+                handlers.mergeAccumulatorFn,
             )
         }
 
+        /**
+         * Start the session reduce server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>
         /**
          * Start the session reduce server.
          * @param socketPath - Optional custom Unix socket path
          * @param serverInfoPath - Optional path for server info file
          */
-        async start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void> {
-            return await this.nativeServer.start(socketPath, serverInfoPath)
+        start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>
+        async start(options?: ServerOptions | string | null, serverInfoPath?: string | null): Promise<void> {
+            return await this.nativeServer.start(toServerOptions(options, serverInfoPath))
         }
 
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop() {
-            return this.nativeServer.stop()
+        stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
         }
     }
 }
@@ -2133,6 +3139,21 @@ export namespace reduceStream {
          * HTTP-style headers attached to the message.
          */
         headers: Record<string, string>
+        /**
+         * Context of the span the datum is handled in, a child of the span in its `traceparent` header.
+         * Not set when the datum has no valid `traceparent` header.
+         */
+        traceContext?: TraceContext
+        /**
+         * Whether the watermark had reached the end of the window when the datum arrived, i.e. the datum arrived
+         * after the window would have closed without allowed lateness.
+         */
+        late: boolean
+        /**
+         * How far the watermark was past the end of the window when the datum arrived in milliseconds, 0 if the
+         * datum is not late.
+         */
+        latenessMs: number
     }
 
     /**
@@ -2152,7 +3173,7 @@ export namespace reduceStream {
 
     /**
      * Metadata provided to reduce stream handlers.
-     * Contains information about the current time window.
+     * Contains information about the current time window, the windows of the vertex and the vertex itself.
      */
     export interface Metadata {
         /**
@@ -2160,6 +3181,33 @@ export namespace reduceStream {
          * Contains the start and end times of the current window.
          */
         intervalWindow: IntervalWindow
+        /**
+         * Kind of the windows of the vertex.
+         * Read from the vertex spec numaflow sets in `NUMAFLOW_VERTEX_OBJECT`, not set without it.
+         */
+        windowKind?: 'fixed' | 'sliding'
+        /**
+         * Length of the windows of the vertex in milliseconds.
+         * Read from the vertex spec, not set without it.
+         */
+        windowLengthMs?: number
+        /**
+         * Time between the starts of two consecutive windows in milliseconds, the window length for fixed windows.
+         * Read from the vertex spec, not set without it.
+         */
+        slideMs?: number
+        /**
+         * Watermark of the latest datum of the window, not set before the first datum. The reduce protocol does not
+         * tell the watermark the window closed at: once the datums are exhausted this is the watermark of the last
+         * datum, which may be behind the watermark that closed the window.
+         */
+        readonly latestWatermark?: Date
+        /** Name of the pipeline, from `NUMAFLOW_PIPELINE_NAME`. */
+        pipelineName?: string
+        /** Name of the vertex, from `NUMAFLOW_VERTEX_NAME`. */
+        vertexName?: string
+        /** Replica of the vertex, from `NUMAFLOW_REPLICA`. */
+        replica?: number
     }
 
     /** @internal */
@@ -2224,6 +3272,19 @@ export namespace reduceStream {
     /** @internal */
     type CallbackArgs = binding.reduce.ReduceCallbackArgs
 
+    /**
+     * Handler of one window, created by a ReduceStreamerFactory.
+     */
+    export interface ReduceStreamer {
+        /** Aggregates the datums of the window, yielding the output messages */
+        reduceStreamFn: CallbackFn
+    }
+    /**
+     * Creates the ReduceStreamer of a new window.
+     * Called once per window, the ReduceStreamer is released once the window is reduced.
+     */
+    export type ReduceStreamerFactory = () => ReduceStreamer
+
     /** @internal */
     class DatumIteratorImpl implements AsyncIterableIterator<Datum> {
         private readonly nativeDatumIterator: binding.reduce.ReduceDatumIterator
@@ -2245,6 +3306,43 @@ export namespace reduceStream {
         }
     }
 
+    /**
+     * Metadata of the window of the callback arguments, reading the watermark as the datums arrive.
+     * @internal
+     */
+    function toMetadata(callbackArgs: CallbackArgs): Metadata {
+        return {
+            ...callbackArgs.metadata,
+            get latestWatermark() {
+                return callbackArgs.latestWatermark ?? undefined
+            },
+        }
+    }
+
+    /**
+     * Wraps a reduce stream callback into the handler of the native server.
+     * @internal
+     */
+    function toNativeCallbackFn(
+        callbackFn: CallbackFn,
+    ): (callbackArgs: CallbackArgs) => () => Promise<NativeMessage | null> {
+        return (callbackArgs: CallbackArgs): (() => Promise<NativeMessage | null>) => {
+            const iterator = new DatumIteratorImpl(callbackArgs.takeIterator)
+            const wrappedIterator = callbackFn(callbackArgs.keys, iterator, toMetadata(callbackArgs))[
+                Symbol.asyncIterator
+            ]()
+
+            return reportErrors(async () => {
+                const result = await wrappedIterator.next()
+                if (result.done) {
+                    return null
+                }
+
+                return result.value satisfies NativeMessage
+            })
+        }
+    }
+
     /**
      * Async server for reduce stream operations.
      *
@@ -2260,44 +3358,57 @@ export namespace reduceStream {
     export class AsyncServer {
         private readonly nativeServer: binding.reduceStream.ReduceStreamAsyncServer
 
+        /**
+         * Create a new reduce stream server whose windows each get their own ReduceStreamer, so state can be kept
+         * on the ReduceStreamer.
+         * @param createReduceStreamer - Factory called once per window
+         */
+        static withFactory(createReduceStreamer: ReduceStreamerFactory): AsyncServer {
+            return new AsyncServer(
+                binding.reduceStream.ReduceStreamAsyncServer.withFactory(() => {
+                    const reduceStreamer = createReduceStreamer()
+                    return toNativeCallbackFn(reduceStreamer.reduceStreamFn.bind(reduceStreamer))
+                }),
+            )
+        }
+
         /**
          * Create a new reduce stream server.
          * @param callbackFn - Async generator function that yields output messages
          */
-        constructor(callbackFn: CallbackFn) {
-            const wrapperCallbackFn = (callbackArgs: CallbackArgs): (() => Promise<NativeMessage | null>) => {
-                const iterator = new DatumIteratorImpl(callbackArgs.takeIterator)
-                const wrappedIterator = callbackFn(callbackArgs.keys, iterator, callbackArgs.metadata)[
-                    Symbol.asyncIterator
-                ]()
-
-                return async () => {
-                    const result = await wrappedIterator.next()
-                    if (result.done) {
-                        return null
-                    }
-
-                    return result.value satisfies NativeMessage
-                }
-            }
-
-            this.nativeServer = new binding.reduceStream.ReduceStreamAsyncServer(wrapperCallbackFn)
+        constructor(callbackFn: CallbackFn)
+        /** @internal */
+        constructor(nativeServer: binding.reduceStream.ReduceStreamAsyncServer)
+        constructor(callbackFn: CallbackFn | binding.reduceStream.ReduceStreamAsyncServer) {
+            this.nativeServer =
+                callbackFn instanceof binding.reduceStream.ReduceStreamAsyncServer
+                    ? callbackFn
+                    : new binding.reduceStream.ReduceStreamAsyncServer(toNativeCallbackFn(callbackFn))
         }
 
+        /**
+         * Start the reduce stream server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>
         /**
          * Start the reduce stream server.
          * @param socketPath - Optional custom Unix socket path
          * @param serverInfoPath - Optional path for server info file
          */
-        async start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void> {
-            return await this.nativeServer.start(socketPath, serverInfoPath)
+        start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>
+        async start(options?: ServerOptions | string | null, serverInfoPath?: string | null): Promise<void> {
+            return await this.nativeServer.start(toServerOptions(options, serverInfoPath))
         }
 
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop() {
-            return this.nativeServer.stop()
+        stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
         }
     }
 }
@@ -2372,6 +3483,11 @@ export namespace source {
          * The timeout in milliseconds for the read operation.
          */
         readonly timeoutMs: number
+        /**
+         * Context of the trace started for the read.
+         * Its `traceparent` is set as the header of the messages read that don't have one.
+         */
+        readonly traceContext: TraceContext
     }
 
     /**
@@ -2391,71 +3507,24 @@ export namespace source {
     }
 
     /**
-     * User-defined metadata that can be attached to source messages.
-     * Allows storing arbitrary key-value pairs organized by groups.
-     *
-     * @example
-     * ```typescript
-     * const metadata = new source.UserMetadata();
-     * metadata.createGroup('tracking');
-     * metadata.addKv('tracking', 'origin', Buffer.from('sensor-1'));
-     * ```
+     * System-provided metadata attached to messages, read-only to handlers.
+     * Shared by the namespaces that carry metadata, see {@link metadata.SystemMetadata}.
      */
-    export interface UserMetadata {
-        /**
-         * Get all group names in the metadata.
-         * @returns Array of group names
-         */
-        getGroups(): Array<string>
-        /**
-         * Get all keys within a specific group.
-         * @param group - The group name
-         * @returns Array of key names in the group
-         */
-        getKeys(group: string): Array<string>
-        /**
-         * Get the value for a specific key within a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @returns The value as a Buffer
-         */
-        getValue(group: string, key: string): Buffer
-        /**
-         * Create a new group in the metadata.
-         * @param group - The group name to create
-         */
-        createGroup(group: string): void
-        /**
-         * Add a key-value pair to a group.
-         * @param group - The group name
-         * @param key - The key name
-         * @param value - The value as a Buffer
-         */
-        addKv(group: string, key: string, value: Buffer): void
-        /**
-         * Remove a key from a group.
-         * @param group - The group name
-         * @param key - The key to remove
-         */
-        removeKey(group: string, key: string): void
-        /**
-         * Remove an entire group from the metadata.
-         * @param group - The group name to remove
-         */
-        removeGroup(group: string): void
-    }
+    export type SystemMetadata = metadata.SystemMetadata
 
     /**
-     * Constructor for creating UserMetadata instances.
+     * User-defined metadata that can be attached to messages, groups of key/value pairs.
+     * Shared by the namespaces that carry metadata, see {@link metadata.UserMetadata}.
      *
      * @example
      * ```typescript
-     * const metadata = new source.UserMetadata();
+     * const userMetadata = new source.UserMetadata();
+     * userMetadata.addString('tracking', 'source', 'api');
      * ```
      */
-    export const UserMetadata: {
-        new (): UserMetadata
-    } = binding.source.SourceUserMetadata
+    export type UserMetadata = metadata.UserMetadata
+    export const UserMetadata = metadata.UserMetadata
+
 
     /** @internal Native message type from the binding layer */
     type NativeMessage = binding.source.Message
@@ -2538,18 +3607,6 @@ export namespace source {
         }
     }
 
-    /** @internal */
-    function toNativeMetadata(metadata: UserMetadata): Record<string, Record<string, Buffer>> {
-        let nativeMetadata: Record<string, Record<string, Buffer>> = {}
-        for (const group of metadata.getGroups()) {
-            nativeMetadata[group] = {}
-            for (const key of metadata.getKeys(group)) {
-                nativeMetadata[group][key] = metadata.getValue(group, key)
-            }
-        }
-        return nativeMetadata
-    }
-
     /**
      * Async server for source operations.
      *
@@ -2567,7 +3624,7 @@ export namespace source {
             const wrapperReadFn = (request: ReadRequest): (() => Promise<NativeMessage | null>) => {
                 const iterator: AsyncIterator<Message> = sourcer.read(request)[Symbol.asyncIterator]()
 
-                return async (): Promise<NativeMessage | null> => {
+                return reportErrors(async (): Promise<NativeMessage | null> => {
                     const result: IteratorResult<Message> = await iterator.next()
                     if (result.done) {
                         return null
@@ -2581,32 +3638,41 @@ export namespace source {
                         headers: message.headers,
                         userMetadata: message.userMetadata ? toNativeMetadata(message.userMetadata) : undefined,
                     } satisfies NativeMessage
-                }
+                })
             }
 
             this.nativeServer = new binding.source.SourceAsyncServer(
                 wrapperReadFn.bind(sourcer),
-                sourcer.ack.bind(sourcer),
-                sourcer.nack.bind(sourcer),
-                sourcer.pending.bind(sourcer),
-                sourcer.partitions.bind(sourcer),
+                reportErrors(sourcer.ack.bind(sourcer)),
+                reportErrors(sourcer.nack.bind(sourcer)),
+                reportErrors(sourcer.pending.bind(sourcer)),
+                reportErrors(sourcer.partitions.bind(sourcer)),
             )
         }
 
+        /**
+         * Start the source server.
+         * @param options - Optional socket paths, gRPC message size limit and shutdown timeout
+         */
+        start(options?: ServerOptions): Promise<void>
         /**
          * Start the source server.
          * @param socketPath - Optional custom Unix socket path
          * @param serverInfoPath - Optional path for server info file
          */
-        async start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void> {
-            return await this.nativeServer.start(socketPath, serverInfoPath)
+        start(socketPath?: string | null, serverInfoPath?: string | null): Promise<void>
+        async start(options?: ServerOptions | string | null, serverInfoPath?: string | null): Promise<void> {
+            return await this.nativeServer.start(toServerOptions(options, serverInfoPath))
         }
 
         /**
-         * Stop the server gracefully.
+         * Stop the server gracefully: new connections are refused and the requests in flight are waited for until
+         * they settle or the shutdown timeout passes, whichever comes first.
+         * @param timeoutMs - Optional shutdown timeout overriding `shutdownTimeoutMs` of the server options
+         * @returns How many requests settled while draining and how many were abandoned
          */
-        stop() {
-            return this.nativeServer.stop()
+        stop(timeoutMs?: number): Promise<DrainSummary> {
+            return this.nativeServer.stop(timeoutMs)
         }
     }
 }
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex, Once};
+use std::sync::{Arc, Once};
 
 use chrono::{DateTime, Utc};
 use napi::bindgen_prelude::{Buffer, Promise};
@@ -9,6 +9,14 @@ use napi_derive::napi;
 use numaflow::accumulator;
 use numaflow::shared::ServerExtras;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::{Instrument, info, warn};
+
+use crate::config;
+use crate::error;
+use crate::factory::{FactoryFn, Handler};
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metrics::{self, MetricsServer};
+use crate::trace::{self, TraceContext};
 
 /// A message to be sent to the next vertex from an accumulator handler.
 #[napi(object, namespace = "accumulator")]
@@ -22,7 +30,8 @@ pub struct Message {
     pub tags: Option<Vec<String>>,
     /// ID is used for deduplication. Read-only, set from the input datum.
     pub id: String,
-    /// Headers for the message. Read-only, set from the input datum.
+    /// Headers for the message. Read-only, set from the input datum with its `traceparent`
+    /// pointing at the span the datum was handled in.
     pub headers: HashMap<String, String>,
     /// Time of the element as seen at source or aligned after a reduce operation. Read-only, set from the input datum.
     pub event_time: DateTime<Utc>,
@@ -52,12 +61,16 @@ fn from_datum(
     keys: Option<Vec<String>>,
     tags: Option<Vec<String>>,
 ) -> Message {
+    let mut headers = datum.headers.clone();
+    if let Some(trace_context) = &datum.trace_context {
+        trace_context.inject(&mut headers);
+    }
     Message {
         keys: keys.or_else(|| Some(datum.keys.clone())),
         value: value.unwrap_or(datum.value),
         tags,
         id: datum.id.clone(),
-        headers: datum.headers.clone(),
+        headers,
         event_time: datum.event_time,
         watermark: datum.watermark,
     }
@@ -85,6 +98,8 @@ pub struct Datum {
     pub event_time: DateTime<Utc>,
     pub headers: HashMap<String, String>,
     pub id: String,
+    /// Context of the span the datum is handled in, if its headers carry a `traceparent`.
+    pub trace_context: Option<TraceContext>,
 }
 
 impl Datum {
@@ -101,6 +116,7 @@ impl Datum {
             value: value.into(),
             watermark,
             event_time,
+            trace_context: TraceContext::child_of(&headers),
             headers,
             id,
         }
@@ -122,10 +138,12 @@ impl From<accumulator::AccumulatorRequest> for Datum {
 
 type AccFn = ThreadsafeFunction<(), Promise<Option<Message>>, (), Status, false, true>;
 
+type AccumulatorFn = ThreadsafeFunction<DatumIterator, AccFn, DatumIterator, Status, false, true>;
+
 #[napi(namespace = "accumulator")]
 pub struct AccumulatorAsyncServer {
-    acc_fn: Arc<ThreadsafeFunction<DatumIterator, AccFn, DatumIterator, Status, false, true>>,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    handler: Handler<AccumulatorFn>,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "accumulator")]
@@ -134,63 +152,67 @@ impl AccumulatorAsyncServer {
         constructor,
         ts_args_type = "acc_fn: (datumIterator: DatumIterator) => () => Promise<Message | null>"
     )]
-    pub fn new(
-        acc_fn: Arc<ThreadsafeFunction<DatumIterator, AccFn, DatumIterator, Status, false, true>>,
-    ) -> Self {
+    pub fn new(acc_fn: Arc<AccumulatorFn>) -> Self {
         Self {
-            acc_fn,
-            shutdown_tx: Mutex::new(None),
+            handler: Handler::Shared(acc_fn),
+            lifecycle: Lifecycle::new("accumulator"),
         }
     }
 
-    #[napi]
-    pub fn stop(&self) -> napi::Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
+    /// A server that calls `createAccFn` once per key and accumulates the datums of the key with
+    /// the function it returned, which is released once the key is closed.
+    #[napi(
+        factory,
+        ts_args_type = "createAccFn: () => (datumIterator: DatumIterator) => () => Promise<Message | null>"
+    )]
+    pub fn with_factory(create_acc_fn: FactoryFn<AccumulatorFn>) -> Self {
+        Self {
+            handler: Handler::Factory(Arc::new(create_acc_fn)),
+            lifecycle: Lifecycle::new("accumulator"),
         }
-        Ok(())
     }
 
     #[napi]
-    pub async fn start(
-        &self,
-        sock_file: Option<String>,
-        info_file: Option<String>,
-    ) -> napi::Result<()> {
-        let accumulator = AccumulatorCreator::new(self.acc_fn.clone());
-        let mut server = accumulator::Server::new(accumulator);
-        if let Some(sock_file) = sock_file {
-            server = server.with_socket_file(sock_file.clone());
-        }
-        if let Some(info_file) = info_file {
-            server = server.with_server_info_file(info_file);
-        }
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        {
-            self.shutdown_tx.lock().unwrap().replace(tx);
-        }
-        println!("Starting Accumulator server at {:?}", server.socket_file());
-        server.start_with_shutdown(rx).await.map_err(|e| {
-            Error::new(
-                Status::GenericFailure,
-                format!("Error running AccumulatorAsyncServer: {e:?}"),
-            )
-        })?;
-        println!("AccumulatorAsyncServer has shutdown...");
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
+    }
+
+    #[napi]
+    pub async fn start(&self, options: Option<ServerOptions>) -> napi::Result<()> {
+        let options = config::resolve(options)?;
+        let accumulator = AccumulatorCreator::new(self.handler.clone(), self.lifecycle.in_flight());
+        let server = options.configure(accumulator::Server::new(accumulator));
+        info!(
+            component = "accumulator",
+            socket_file = ?server.socket_file(),
+            "Starting AccumulatorAsyncServer"
+        );
+        let _metrics_server = MetricsServer::start(&options)?;
+        self.lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    Status::GenericFailure,
+                    format!("Error running AccumulatorAsyncServer: {e:?}"),
+                )
+            })?;
+        info!(
+            component = "accumulator",
+            "AccumulatorAsyncServer has shut down"
+        );
         Ok(())
     }
 }
 
 struct AccumulatorCreator {
-    acc_fn: Arc<ThreadsafeFunction<DatumIterator, AccFn, DatumIterator, Status, false, true>>,
+    handler: Handler<AccumulatorFn>,
+    in_flight: InFlight,
 }
 
 impl AccumulatorCreator {
-    fn new(
-        acc_fn: Arc<ThreadsafeFunction<DatumIterator, AccFn, DatumIterator, Status, false, true>>,
-    ) -> Self {
-        Self { acc_fn }
+    fn new(handler: Handler<AccumulatorFn>, in_flight: InFlight) -> Self {
+        Self { handler, in_flight }
     }
 }
 
@@ -198,24 +220,24 @@ impl AccumulatorCreator {
 impl accumulator::AccumulatorCreator for AccumulatorCreator {
     type A = Accumulator;
     fn create(&self) -> Self::A {
-        Accumulator::new(self.acc_fn.clone())
+        Accumulator::new(self.handler.clone(), self.in_flight.clone())
     }
 }
 
 struct Accumulator {
-    acc_fn: Arc<ThreadsafeFunction<DatumIterator, AccFn, DatumIterator, Status, false, true>>,
+    handler: Handler<AccumulatorFn>,
     /// Used to ensure the channel send error is only logged once, since subsequent errors
     /// are a consequence of the receiver terminating due to a prior error.
     send_error_once: Once,
+    in_flight: InFlight,
 }
 
 impl Accumulator {
-    fn new(
-        acc_fn: Arc<ThreadsafeFunction<DatumIterator, AccFn, DatumIterator, Status, false, true>>,
-    ) -> Self {
+    fn new(handler: Handler<AccumulatorFn>, in_flight: InFlight) -> Self {
         Self {
-            acc_fn,
+            handler,
             send_error_once: Once::new(),
+            in_flight,
         }
     }
 }
@@ -227,45 +249,39 @@ impl accumulator::Accumulator for Accumulator {
         input: Receiver<accumulator::AccumulatorRequest>,
         tx: Sender<accumulator::Message>,
     ) {
-        let requests = DatumIterator::new(input);
-        match self.acc_fn.call_async(requests).await {
-            Ok(messages_fn) => loop {
-                match messages_fn.call_async(()).await {
-                    Ok(promise) => match promise.await {
-                        Ok(Some(message)) => {
-                            if let Err(_e) = tx.send(message.into()).await {
-                                self.send_error_once.call_once(|| {
-                                    // printing SendError will only show "SendError { .. }"
-                                    eprintln!("[WARN] Failed to send accumulator message to numa. This means the numa has terminated. Please check the numa logs for more details");
-                                });
-                            }
-                        }
-                        Ok(None) => break,
-                        Err(e) => {
-                            eprintln!(
-                                "[ERROR] User-defined accumulator function returned an error: {:?}",
-                                e
-                            );
-                            panic!(
-                                "User-defined accumulator function returned an error: {:?}",
-                                e
-                            );
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("accumulator", None);
+        async {
+            let acc_fn = match self.handler.create("accumulator factory").await {
+                Ok(acc_fn) => acc_fn,
+                Err(e) => e.raise(),
+            };
+            let requests = DatumIterator::new(input);
+            let messages_fn = match error::call("accumulator", &acc_fn, requests).await {
+                Ok(messages_fn) => messages_fn,
+                Err(e) => e.raise(),
+            };
+            loop {
+                match error::call_promise("accumulator iterator", &messages_fn, ()).await {
+                    Ok(Some(message)) => {
+                        metrics::message_emitted("accumulator", message.tags.as_deref());
+                        if let Err(_e) = tx.send(message.into()).await {
+                            self.send_error_once.call_once(|| {
+                                // printing SendError will only show "SendError { .. }"
+                                warn!(
+                                    component = "accumulator",
+                                    "Failed to send accumulator message to numa. This means the numa has terminated. Please check the numa logs for more details"
+                                );
+                            });
                         }
-                    },
-                    Err(e) => {
-                        eprintln!(
-                            "[ERROR] Executing user-defined accumulator function: {:?}",
-                            e
-                        );
-                        panic!("Error executing user-defined accumulator function: {:?}", e);
                     }
+                    Ok(None) => break,
+                    Err(e) => e.raise(),
                 }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Executing accumulator function: {:?}", e);
-                panic!("Error executing accumulator function: {:?}", e);
             }
         }
+        .instrument(span)
+        .await
     }
 }
 
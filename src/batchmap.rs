@@ -1,13 +1,23 @@
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::{collections::HashMap, sync::Mutex};
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
-use napi::bindgen_prelude::{Buffer, Promise};
+use napi::bindgen_prelude::{Buffer, ClassInstance, FromNapiValue, Promise};
 use napi::threadsafe_function::ThreadsafeFunction;
-use napi::{Error, Result, Status};
+use napi::{Error, Result, Status, sys};
 use napi_derive::napi;
 use numaflow::batchmap;
-use numaflow::shared::ServerExtras;
+use tracing::{Instrument, info};
+
+use crate::codec::{Codec, Skipped};
+use crate::config;
+use crate::error::{self, UdfError};
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metrics::{self, MetricsServer};
+use crate::retry::{ReplayStream, Retry, RetryPolicy};
+use crate::trace::{self, TraceContext};
+use crate::value::DatumValue;
 
 #[derive(Default)]
 #[napi(object, namespace = "batchmap")]
@@ -15,8 +25,10 @@ pub struct BatchMessage {
     /// Keys are a collection of strings which will be passed on to the next vertex as is. It can
     /// be an empty collection.
     pub keys: Option<Vec<String>>,
-    /// Value is the value passed to the next vertex.
-    pub value: Buffer,
+    /// Value is the value passed to the next vertex, empty when neither it nor `data` is set.
+    pub value: Option<Buffer>,
+    /// Data encoded by the codec of the server into the value passed on, replaces `value`.
+    pub data: Option<serde_json::Value>,
     /// Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
     pub tags: Option<Vec<String>>,
 }
@@ -25,28 +37,19 @@ pub struct BatchMessage {
 pub fn message_to_drop() -> BatchMessage {
     BatchMessage {
         keys: None,
-        value: vec![].into(),
+        value: Some(vec![].into()),
+        data: None,
         tags: Some(vec![numaflow::shared::DROP.to_string()]),
     }
 }
 
-impl From<BatchMessage> for batchmap::Message {
-    fn from(value: BatchMessage) -> Self {
-        Self {
-            keys: value.keys,
-            value: value.value.into(),
-            tags: value.tags,
-        }
-    }
-}
-
-impl From<&BatchMessage> for batchmap::Message {
-    fn from(value: &BatchMessage) -> Self {
-        Self {
-            keys: value.keys.clone(),
-            value: value.value.iter().copied().collect(),
-            tags: value.tags.clone(),
-        }
+impl BatchMessage {
+    fn encode(self, codec: &Codec) -> std::result::Result<batchmap::Message, String> {
+        Ok(batchmap::Message {
+            keys: self.keys,
+            value: codec.encode(self.value, self.data)?,
+            tags: self.tags,
+        })
     }
 }
 
@@ -55,7 +58,8 @@ pub struct BatchDatum {
     /// Set of keys in the (key, value) terminology of map/reduce paradigm.
     pub keys: Vec<String>,
     /// The value in the (key, value) terminology of map/reduce paradigm.
-    pub value: Buffer,
+    #[napi(ts_type = "Buffer")]
+    pub value: DatumValue,
     /// [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a
     /// guarantee that we will not see an element older than this time.
     pub watermark: DateTime<Utc>,
@@ -65,17 +69,26 @@ pub struct BatchDatum {
     pub id: String,
     /// Headers for the message.
     pub headers: HashMap<String, String>,
+    /// Context of the span the datum is handled in, if its headers carry a `traceparent`.
+    pub trace_context: Option<TraceContext>,
+    /// The value decoded by the codec of the server, not set with the raw codec or an empty value.
+    pub data: Option<serde_json::Value>,
+    /// Why the value could not be decoded, only set when the decode error policy is `pass`.
+    pub decode_error: Option<String>,
 }
 
 impl Clone for BatchDatum {
     fn clone(&self) -> Self {
         Self {
             keys: self.keys.clone(),
-            value: Buffer::from(self.value.to_vec()),
+            value: self.value.clone(),
             watermark: self.watermark,
             event_time: self.event_time,
             id: self.id.clone(),
             headers: self.headers.clone(),
+            trace_context: self.trace_context.clone(),
+            data: self.data.clone(),
+            decode_error: self.decode_error.clone(),
         }
     }
 }
@@ -88,7 +101,10 @@ impl From<batchmap::Datum> for BatchDatum {
             watermark: value.watermark,
             event_time: value.event_time,
             id: value.id,
+            trace_context: TraceContext::child_of(&value.headers),
             headers: value.headers,
+            data: None,
+            decode_error: None,
         }
     }
 }
@@ -124,26 +140,38 @@ impl BatchResponse {
     }
 }
 
-impl From<BatchResponse> for batchmap::BatchResponse {
-    fn from(value: BatchResponse) -> Self {
-        let mut resp = batchmap::BatchResponse::from_id(value.id);
-        for m in value.messages.into_iter() {
-            resp.append(m.into());
+impl BatchResponse {
+    fn encode(self, codec: &Codec) -> std::result::Result<batchmap::BatchResponse, String> {
+        let mut resp = batchmap::BatchResponse::from_id(self.id);
+        for m in self.messages.into_iter() {
+            resp.append(m.encode(codec)?);
         }
-        resp
+        Ok(resp)
     }
 }
 
-impl From<&BatchResponse> for batchmap::BatchResponse {
-    fn from(value: &BatchResponse) -> Self {
-        let mut resp = batchmap::BatchResponse::from_id(value.id.clone());
-        for m in value.messages.iter() {
-            resp.append(m.into());
-        }
-        resp
+/// A response resolved by the batch map handler. Its messages are moved out of the
+/// `BatchResponse` instance without copying their values, a reference to the instance can't
+/// outlive the promise callback it is converted in.
+pub struct ResolvedResponse(BatchResponse);
+
+impl FromNapiValue for ResolvedResponse {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> Result<Self> {
+        let mut response =
+            unsafe { ClassInstance::<BatchResponse>::from_napi_value(env, napi_val)? };
+        Ok(Self(std::mem::take(&mut *response)))
     }
 }
 
+type BatchMapFn = ThreadsafeFunction<
+    BatchDatumIterator,
+    Promise<Vec<ResolvedResponse>>,
+    BatchDatumIterator,
+    Status,
+    false,
+    true,
+>;
+
 /// A collection of BatchResponse objects for a batch.
 #[derive(Clone, Default)]
 #[napi(namespace = "batchmap")]
@@ -167,101 +195,82 @@ impl BatchResponses {
 
 #[napi(namespace = "batchmap")]
 pub struct BatchMapAsyncServer {
-    batchmap_fn: Arc<
-        ThreadsafeFunction<
-            BatchDatumIterator,
-            Promise<Vec<&'static BatchResponse>>,
-            BatchDatumIterator,
-            Status,
-            false,
-            true,
-        >,
-    >,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    batchmap_fn: Arc<BatchMapFn>,
+    retry: Retry,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "batchmap")]
 impl BatchMapAsyncServer {
-    #[napi(constructor)]
-    pub fn new(
-        batchmap_fn: Arc<
-            ThreadsafeFunction<
-                BatchDatumIterator,
-                Promise<Vec<&'static BatchResponse>>,
-                BatchDatumIterator,
-                Status,
-                false,
-                true,
-            >,
-        >,
-    ) -> Self {
-        Self {
+    #[napi(
+        constructor,
+        ts_args_type = "batchmapFn: (arg: BatchDatumIterator) => Promise<Array<BatchResponse>>, retryPolicy?: RetryPolicy | undefined | null"
+    )]
+    pub fn new(batchmap_fn: Arc<BatchMapFn>, retry_policy: Option<RetryPolicy>) -> Result<Self> {
+        Ok(Self {
             batchmap_fn,
-            shutdown_tx: Mutex::new(None),
-        }
+            retry: retry_policy.try_into()?,
+            lifecycle: Lifecycle::new("batchmap"),
+        })
     }
 
     #[napi]
-    pub async fn start(&self, sock_file: Option<String>, info_file: Option<String>) -> Result<()> {
-        let batch_mapper = BatchMapper::new(Arc::clone(&self.batchmap_fn));
+    pub async fn start(&self, options: Option<ServerOptions>) -> Result<()> {
+        let options = config::resolve(options)?;
+        let batch_mapper = BatchMapper::new(
+            Arc::clone(&self.batchmap_fn),
+            self.retry.clone(),
+            options.handler_timeout(),
+            self.lifecycle.in_flight(),
+            Codec::new("batchmap", &options)?,
+        );
 
-        let mut server = batchmap::Server::new(batch_mapper);
-        if let Some(sock_file) = sock_file {
-            server = server.with_socket_file(sock_file);
-        }
-        if let Some(info_file) = info_file {
-            server = server.with_server_info_file(info_file);
-        }
+        let server = options.configure(batchmap::Server::new(batch_mapper));
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        self.shutdown_tx.lock().unwrap().replace(tx);
-        if let Err(e) = server.start_with_shutdown(rx).await {
+        let _metrics_server = MetricsServer::start(&options)?;
+        if let Err(e) = self
+            .lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+        {
             return Err(Error::new(
                 Status::GenericFailure,
                 format!("Error running BatchMapAsyncServer: {e:?}"),
             ));
         }
-        println!("BatchMapAsyncServer has shutdown...");
+        info!(component = "batchmap", "BatchMapAsyncServer has shut down");
         Ok(())
     }
 
     #[napi]
-    pub fn stop(&self) -> Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
-        }
-        Ok(())
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
     }
 }
 
 struct BatchMapper {
-    batchmap_fn: Arc<
-        ThreadsafeFunction<
-            BatchDatumIterator,
-            Promise<Vec<&'static BatchResponse>>,
-            BatchDatumIterator,
-            Status,
-            false,
-            true,
-        >,
-    >,
+    batchmap_fn: Arc<BatchMapFn>,
+    retry: Retry,
+    timeout: Option<Duration>,
+    in_flight: InFlight,
+    codec: Codec,
 }
 
 impl BatchMapper {
     fn new(
-        batchmap_fn: Arc<
-            ThreadsafeFunction<
-                BatchDatumIterator,
-                Promise<Vec<&'static BatchResponse>>,
-                BatchDatumIterator,
-                Status,
-                false,
-                true,
-            >,
-        >,
+        batchmap_fn: Arc<BatchMapFn>,
+        retry: Retry,
+        timeout: Option<Duration>,
+        in_flight: InFlight,
+        codec: Codec,
     ) -> Self {
-        Self { batchmap_fn }
+        Self {
+            batchmap_fn,
+            retry,
+            timeout,
+            in_flight,
+            codec,
+        }
     }
 }
 
@@ -271,31 +280,63 @@ impl batchmap::BatchMapper for BatchMapper {
         &self,
         input: tokio::sync::mpsc::Receiver<batchmap::Datum>,
     ) -> Vec<batchmap::BatchResponse> {
-        let requests = BatchDatumIterator::new(input);
-
-        // Call the JavaScript callback
-        match self.batchmap_fn.call_async(requests).await {
-            Ok(promise) => match promise.await {
-                Ok(responses) => responses.into_iter().map(|resp| resp.into()).collect(),
-                Err(e) => {
-                    eprintln!(
-                        "[ERROR] User-defined batchmap function returned an error: {:?}",
-                        e
-                    );
-                    panic!("User-defined batchmap function returned an error: {:?}", e);
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("batchmap", None);
+        async {
+            let requests = ReplayStream::new(input, self.retry.enabled());
+            let skipped = Skipped::default();
+
+            // Call the JavaScript callback, a retry reads the batch again from the start
+            let responses = self
+                .retry
+                .run(requests, |requests| {
+                    error::within(
+                        "batchmap",
+                        self.timeout,
+                        error::call_promise(
+                            "batchmap",
+                            &self.batchmap_fn,
+                            BatchDatumIterator::new(requests, self.codec, skipped.clone()),
+                        ),
+                    )
+                })
+                .await;
+            let responses = match responses {
+                Ok(responses) => responses,
+                Err(e) => e.raise(),
+            };
+            let mut responses: Vec<_> = responses
+                .into_iter()
+                .map(|ResolvedResponse(resp)| {
+                    for message in &resp.messages {
+                        metrics::message_emitted("batchmap", message.tags.as_deref());
+                    }
+                    resp.encode(&self.codec)
+                        .unwrap_or_else(|e| UdfError::forward("batchmap", e).raise())
+                })
+                .collect();
+            // Datums the codec skipped fail the batch, or are answered with a message to drop.
+            for (id, error) in skipped.take() {
+                if let Some(error) = error {
+                    UdfError::decode("batchmap", format!("datum {id}: {error}")).raise();
                 }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Executing user-defined batchmap function: {:?}", e);
-                panic!("Error executing user-defined batchmap function: {:?}", e);
+                metrics::datum_dropped("batchmap");
+                let mut resp = batchmap::BatchResponse::from_id(id);
+                resp.append(batchmap::Message::message_to_drop());
+                responses.push(resp);
             }
+            responses
         }
+        .instrument(span)
+        .await
     }
 }
 
 #[napi(namespace = "batchmap")]
 pub struct BatchDatumIterator {
-    datum_rx: tokio::sync::mpsc::Receiver<batchmap::Datum>,
+    datum_rx: ReplayStream<batchmap::Datum, BatchDatum>,
+    codec: Codec,
+    skipped: Skipped,
 }
 
 #[napi(object, namespace = "batchmap")]
@@ -307,18 +348,38 @@ pub struct BatchDatumIteratorResult {
 #[napi(namespace = "batchmap")]
 impl BatchDatumIterator {
     /// Internal constructor - not exposed to JavaScript
-    pub(crate) fn new(datum_rx: tokio::sync::mpsc::Receiver<batchmap::Datum>) -> Self {
-        Self { datum_rx }
+    pub(crate) fn new(
+        datum_rx: ReplayStream<batchmap::Datum, BatchDatum>,
+        codec: Codec,
+        skipped: Skipped,
+    ) -> Self {
+        Self {
+            datum_rx,
+            codec,
+            skipped,
+        }
     }
 
-    /// Returns the next datum from the stream, or None if the stream has ended
+    /// Returns the next datum from the stream, or None if the stream has ended. Datums whose value
+    /// can't be decoded are skipped unless the decode error policy is `pass`.
     /// # SAFETY
     ///
     /// Async function with &mut self is unsafe in napi because the self is also owned
     /// by the Node.js runtime. You cannot ensure that the self is only owned by Rust.
     #[napi]
     pub async unsafe fn next(&mut self) -> BatchDatumIteratorResult {
-        let value = self.datum_rx.recv().await.map(BatchDatum::from);
+        let mut value = None;
+        while let Some(mut datum) = self.datum_rx.next().await {
+            if let Some((data, decode_error)) = datum
+                .value
+                .with(|value| self.skipped.decode(&self.codec, &datum.id, value))
+            {
+                datum.data = data;
+                datum.decode_error = decode_error;
+                value = Some(datum);
+                break;
+            }
+        }
         let done = value.is_none();
         BatchDatumIteratorResult { value, done }
     }
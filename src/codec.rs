@@ -0,0 +1,215 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result, Status};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::lifecycle::ServerOptions;
+use crate::metrics;
+
+/// Encoding of the datum values and message payloads a server exchanges as `data`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Format {
+    /// Values are only handed over as bytes.
+    #[default]
+    Raw,
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(Self::Raw),
+            "json" => Ok(Self::Json),
+            "msgpack" => Ok(Self::MessagePack),
+            "cbor" => Ok(Self::Cbor),
+            _ => Err("expected json, msgpack, cbor or raw".to_string()),
+        }
+    }
+}
+
+/// What happens to a datum whose value can't be decoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum DecodeErrorPolicy {
+    /// The request fails like one whose handler threw.
+    #[default]
+    Fail,
+    /// The datum is dropped without calling the handler.
+    Drop,
+    /// The datum is handed to the handler without `data`, with the error in `decodeError`.
+    Pass,
+}
+
+impl FromStr for DecodeErrorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(Self::Fail),
+            "drop" => Ok(Self::Drop),
+            "pass" => Ok(Self::Pass),
+            _ => Err("expected fail, drop or pass".to_string()),
+        }
+    }
+}
+
+/// Outcome of decoding the value of a datum.
+pub(crate) enum Decoded {
+    /// The datum is handed to the handler with its data, and the decode error under the `pass`
+    /// policy.
+    Datum {
+        data: Option<Value>,
+        error: Option<String>,
+    },
+    /// The datum is dropped without calling the handler.
+    Drop,
+    /// The request fails.
+    Fail(String),
+}
+
+/// Decodes datum values and encodes message data with the codec a server is configured with.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Codec {
+    /// Name of the server in the metrics and logs.
+    server: &'static str,
+    format: Format,
+    policy: DecodeErrorPolicy,
+}
+
+impl Codec {
+    pub(crate) fn new(server: &'static str, options: &ServerOptions) -> Result<Self> {
+        let format = match &options.codec {
+            Some(codec) => parse(codec, "codec")?,
+            None => Format::default(),
+        };
+        let policy = match &options.decode_error_policy {
+            Some(policy) => parse(policy, "decode error policy")?,
+            None => DecodeErrorPolicy::default(),
+        };
+        Ok(Self {
+            server,
+            format,
+            policy,
+        })
+    }
+
+    /// Decodes the value of a datum. An empty value, and any value with the raw codec, has no
+    /// data.
+    pub(crate) fn decode(&self, value: &[u8]) -> Decoded {
+        if self.format == Format::Raw || value.is_empty() {
+            return Decoded::Datum {
+                data: None,
+                error: None,
+            };
+        }
+        let data = match self.format {
+            Format::Raw => unreachable!(),
+            Format::Json => serde_json::from_slice(value).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::from_slice(value).map_err(|e| e.to_string()),
+            Format::Cbor => ciborium::from_reader(value).map_err(|e| e.to_string()),
+        };
+        let error = match data {
+            Ok(data) => {
+                return Decoded::Datum {
+                    data: Some(data),
+                    error: None,
+                };
+            }
+            Err(e) => e,
+        };
+        metrics::decode_failed(self.server);
+        match self.policy {
+            DecodeErrorPolicy::Fail => Decoded::Fail(error),
+            DecodeErrorPolicy::Drop => {
+                warn!(
+                    component = self.server,
+                    "Dropping datum that can't be decoded: {error}"
+                );
+                Decoded::Drop
+            }
+            DecodeErrorPolicy::Pass => Decoded::Datum {
+                data: None,
+                error: Some(error),
+            },
+        }
+    }
+
+    /// The payload of a message, its data encoded when it has any and its value otherwise.
+    ///
+    /// The value is copied out of the JS buffer: the messages of the numaflow SDK own their payload
+    /// as a `Vec<u8>`, which can't borrow the memory of a JS buffer.
+    pub(crate) fn encode(
+        &self,
+        value: Option<Buffer>,
+        data: Option<Value>,
+    ) -> std::result::Result<Vec<u8>, String> {
+        let Some(data) = data else {
+            return Ok(value.map(Vec::from).unwrap_or_default());
+        };
+        match self.format {
+            Format::Raw => {
+                Err("Message has data, but the server has no codec to encode it".to_string())
+            }
+            Format::Json => serde_json::to_vec(&data).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::to_vec_named(&data).map_err(|e| e.to_string()),
+            Format::Cbor => {
+                let mut payload = Vec::new();
+                ciborium::into_writer(&data, &mut payload).map_err(|e| e.to_string())?;
+                Ok(payload)
+            }
+        }
+    }
+}
+
+fn parse<T>(value: &str, what: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| Error::new(Status::InvalidArg, format!("Invalid {what} {value:?}: {e}")))
+}
+
+/// Datums of a batch that are not handed to the handler because their value can't be decoded,
+/// with the decode error of the ones that fail. Shared by the iterators of every attempt to handle
+/// the batch, which skip the same datums.
+#[derive(Clone, Default)]
+pub(crate) struct Skipped(Arc<Mutex<Vec<SkippedDatum>>>);
+
+/// Id of a skipped datum, with its decode error when it fails the batch.
+pub(crate) type SkippedDatum = (String, Option<String>);
+
+impl Skipped {
+    /// Decodes the value of a datum of the batch, recording it when it is skipped. Returns the
+    /// data and decode error the datum is handed to the handler with.
+    pub(crate) fn decode(
+        &self,
+        codec: &Codec,
+        id: &str,
+        value: &[u8],
+    ) -> Option<(Option<Value>, Option<String>)> {
+        let error = match codec.decode(value) {
+            Decoded::Datum { data, error } => return Some((data, error)),
+            Decoded::Drop => None,
+            Decoded::Fail(error) => Some(error),
+        };
+        let mut skipped = self.0.lock().unwrap();
+        if !skipped.iter().any(|(skipped_id, _)| skipped_id == id) {
+            skipped.push((id.to_string(), error));
+        }
+        None
+    }
+
+    /// The ids of the skipped datums, with the decode error of the ones that fail.
+    pub(crate) fn take(&self) -> Vec<SkippedDatum> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
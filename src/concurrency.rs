@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use napi::{Error, Result, Status};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+use crate::lifecycle::ServerOptions;
+use crate::metrics;
+
+/// Bounds the requests a server hands to its JS handler at the same time, in total and per key.
+/// Requests over the limit wait until one of the requests in flight is done.
+#[derive(Clone)]
+pub(crate) struct ConcurrencyLimit {
+    /// Name of the server in the metrics.
+    server: &'static str,
+    permits: Option<Arc<Semaphore>>,
+    per_key: Option<Arc<KeyPermits>>,
+}
+
+impl ConcurrencyLimit {
+    /// Limit of the server named `server` in the metrics, from `maxInFlight` and
+    /// `maxInFlightPerKey` of its options.
+    pub(crate) fn new(server: &'static str, options: &ServerOptions) -> Result<Self> {
+        let max_in_flight = validate("maxInFlight", options.max_in_flight)?;
+        let max_in_flight_per_key = validate("maxInFlightPerKey", options.max_in_flight_per_key)?;
+        Ok(Self {
+            server,
+            permits: max_in_flight.map(|max| Arc::new(Semaphore::new(max))),
+            per_key: max_in_flight_per_key.map(|max| {
+                Arc::new(KeyPermits {
+                    max,
+                    keys: Mutex::new(HashMap::new()),
+                })
+            }),
+        })
+    }
+
+    /// Waits until a request with `keys` is within the limit. The request counts against the
+    /// limit until the returned permit is dropped.
+    pub(crate) async fn acquire(&self, keys: &[String]) -> ConcurrencyPermit {
+        if self.permits.is_none() && self.per_key.is_none() {
+            return ConcurrencyPermit::default();
+        }
+        let _queued = Queued::new(self.server);
+        // The key is waited for first, a request of a busy key would otherwise hold one of the
+        // permits of the server that requests of other keys could use.
+        let key = match &self.per_key {
+            Some(per_key) => Some(per_key.acquire(keys).await),
+            None => None,
+        };
+        let server = match &self.permits {
+            Some(permits) => Some(
+                Arc::clone(permits)
+                    .acquire_owned()
+                    .await
+                    .expect("semaphore is never closed"),
+            ),
+            None => None,
+        };
+        ConcurrencyPermit {
+            _server: server,
+            _key: key,
+        }
+    }
+}
+
+fn validate(option: &str, max: Option<u32>) -> Result<Option<usize>> {
+    match max {
+        Some(0) => Err(Error::new(
+            Status::InvalidArg,
+            format!("{option} must be at least 1"),
+        )),
+        max => Ok(max.map(|max| max as usize)),
+    }
+}
+
+/// Permits of the keys that have requests in flight or waiting.
+struct KeyPermits {
+    max: usize,
+    keys: Mutex<HashMap<Vec<String>, KeyEntry>>,
+}
+
+struct KeyEntry {
+    permits: Arc<Semaphore>,
+    /// Requests holding or waiting for a permit, the entry is removed once there are none.
+    requests: usize,
+}
+
+impl KeyPermits {
+    async fn acquire(self: &Arc<Self>, keys: &[String]) -> KeyPermit {
+        let permits = {
+            let mut entries = self.keys.lock().unwrap();
+            let entry = entries.entry(keys.to_vec()).or_insert_with(|| KeyEntry {
+                permits: Arc::new(Semaphore::new(self.max)),
+                requests: 0,
+            });
+            entry.requests += 1;
+            Arc::clone(&entry.permits)
+        };
+        // Created before waiting, so that a cancelled wait releases the entry as well.
+        let mut permit = KeyPermit {
+            key_permits: Arc::clone(self),
+            keys: keys.to_vec(),
+            permit: None,
+        };
+        permit.permit = Some(
+            permits
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed"),
+        );
+        permit
+    }
+}
+
+struct KeyPermit {
+    key_permits: Arc<KeyPermits>,
+    keys: Vec<String>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for KeyPermit {
+    fn drop(&mut self) {
+        self.permit.take();
+        let mut entries = self.key_permits.keys.lock().unwrap();
+        if let Some(entry) = entries.get_mut(&self.keys) {
+            entry.requests -= 1;
+            if entry.requests == 0 {
+                entries.remove(&self.keys);
+            }
+        }
+    }
+}
+
+/// Holds a request within the concurrency limit until it is dropped.
+#[derive(Default)]
+pub(crate) struct ConcurrencyPermit {
+    _server: Option<OwnedSemaphorePermit>,
+    _key: Option<KeyPermit>,
+}
+
+/// A request waiting for the limit, recorded in the metrics until it is dropped.
+struct Queued {
+    server: &'static str,
+    since: Instant,
+}
+
+impl Queued {
+    fn new(server: &'static str) -> Self {
+        metrics::request_queued(server);
+        Self {
+            server,
+            since: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Queued {
+    fn drop(&mut self) {
+        metrics::request_dequeued(self.server, self.since.elapsed());
+    }
+}
@@ -0,0 +1,114 @@
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+
+use crate::lifecycle::ServerOptions;
+
+/// Type of the container numaflow runs the server in, e.g. `udf` or `udsink`. Set by numaflow.
+const ENV_CONTAINER_TYPE: &str = "NUMAFLOW_UD_CONTAINER_TYPE";
+/// Path of a JSON or YAML file with the server options.
+const ENV_CONFIG_FILE: &str = "NUMAFLOW_SERVER_CONFIG_FILE";
+const ENV_SOCKET_PATH: &str = "NUMAFLOW_SERVER_SOCKET_PATH";
+const ENV_SERVER_INFO_PATH: &str = "NUMAFLOW_SERVER_INFO_PATH";
+const ENV_MAX_MESSAGE_SIZE: &str = "NUMAFLOW_GRPC_MAX_MESSAGE_SIZE";
+const ENV_SHUTDOWN_TIMEOUT_MS: &str = "NUMAFLOW_SHUTDOWN_TIMEOUT_MS";
+const ENV_HANDLE_SIGNALS: &str = "NUMAFLOW_HANDLE_SIGNALS";
+const ENV_METRICS_PORT: &str = "NUMAFLOW_METRICS_PORT";
+const ENV_METRICS_ADDRESS: &str = "NUMAFLOW_METRICS_ADDRESS";
+const ENV_MAX_IN_FLIGHT: &str = "NUMAFLOW_MAX_IN_FLIGHT";
+const ENV_MAX_IN_FLIGHT_PER_KEY: &str = "NUMAFLOW_MAX_IN_FLIGHT_PER_KEY";
+const ENV_HANDLER_TIMEOUT_MS: &str = "NUMAFLOW_HANDLER_TIMEOUT_MS";
+const ENV_CODEC: &str = "NUMAFLOW_CODEC";
+const ENV_DECODE_ERROR_POLICY: &str = "NUMAFLOW_DECODE_ERROR_POLICY";
+
+/// Configuration a server runs with.
+#[napi(object)]
+pub struct ServerConfig {
+    /// Container type from `NUMAFLOW_UD_CONTAINER_TYPE`.
+    pub container_type: Option<String>,
+    /// Config file from `NUMAFLOW_SERVER_CONFIG_FILE`.
+    pub config_file: Option<String>,
+    /// Options resolved from the `start()` options, the environment and the config file.
+    pub options: ServerOptions,
+}
+
+/// Returns the configuration a server started with `options` runs with, e.g. to log it on start
+/// up.
+#[napi]
+pub fn load_server_config(options: Option<ServerOptions>) -> Result<ServerConfig> {
+    Ok(ServerConfig {
+        container_type: env_var(ENV_CONTAINER_TYPE),
+        config_file: env_var(ENV_CONFIG_FILE),
+        options: resolve(options)?,
+    })
+}
+
+/// Resolves the options a server is started with. Explicit `start()` options take precedence
+/// over the `NUMAFLOW_*` environment variables, which take precedence over the config file.
+pub(crate) fn resolve(options: Option<ServerOptions>) -> Result<ServerOptions> {
+    let options = options.unwrap_or_default().or(from_env()?);
+    match env_var(ENV_CONFIG_FILE) {
+        Some(path) => Ok(options.or(from_file(&path)?)),
+        None => Ok(options),
+    }
+}
+
+fn from_env() -> Result<ServerOptions> {
+    Ok(ServerOptions {
+        socket_path: env_var(ENV_SOCKET_PATH),
+        server_info_path: env_var(ENV_SERVER_INFO_PATH),
+        max_message_size: parse_env_var(ENV_MAX_MESSAGE_SIZE)?,
+        shutdown_timeout_ms: parse_env_var(ENV_SHUTDOWN_TIMEOUT_MS)?,
+        handle_signals: parse_env_var(ENV_HANDLE_SIGNALS)?,
+        metrics_port: parse_env_var(ENV_METRICS_PORT)?,
+        metrics_address: env_var(ENV_METRICS_ADDRESS),
+        max_in_flight: parse_env_var(ENV_MAX_IN_FLIGHT)?,
+        max_in_flight_per_key: parse_env_var(ENV_MAX_IN_FLIGHT_PER_KEY)?,
+        handler_timeout_ms: parse_env_var(ENV_HANDLER_TIMEOUT_MS)?,
+        codec: env_var(ENV_CODEC),
+        decode_error_policy: env_var(ENV_DECODE_ERROR_POLICY),
+    })
+}
+
+fn from_file(path: &str) -> Result<ServerOptions> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        Error::new(
+            Status::InvalidArg,
+            format!("Error reading config file {path}: {e}"),
+        )
+    })?;
+    let options = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("yaml" | "yml") => serde_yaml::from_str(&contents).map_err(|e| e.to_string()),
+        _ => serde_json::from_str(&contents).map_err(|e| e.to_string()),
+    };
+    options.map_err(|e| {
+        Error::new(
+            Status::InvalidArg,
+            format!("Invalid config file {path}: {e}"),
+        )
+    })
+}
+
+/// An environment variable that is set to an empty value counts as unset.
+pub(crate) fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn parse_env_var<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    env_var(name)
+        .map(|value| {
+            value.parse().map_err(|e| {
+                Error::new(Status::InvalidArg, format!("Invalid {name}={value:?}: {e}"))
+            })
+        })
+        .transpose()
+}
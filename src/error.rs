@@ -0,0 +1,332 @@
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use napi::Status;
+use napi::bindgen_prelude::{FromNapiValue, JsValuesTupleIntoVec, Promise};
+use napi::threadsafe_function::ThreadsafeFunction;
+use serde::Deserialize;
+use tracing::error;
+
+use crate::metrics;
+
+/// Prefix put in front of a JSON encoded `{ name, message, stack }` by the JS wrapper layer
+/// (`index.ts`) when it rethrows a handler error. Only the `message` of a rejected promise makes it
+/// across the N-API boundary, so this is how the error name and stack reach the native side.
+const JS_ERROR_PREFIX: &str = "U+005C__UDF_ERROR__";
+
+/// An error thrown by a JS handler, or the reason the promise it returned was rejected with.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub(crate) struct JsError {
+    /// The JS error name, e.g. `TypeError`.
+    pub(crate) name: String,
+    /// The JS error message.
+    pub(crate) message: String,
+    /// The JS stack trace, when the thrown value carried one.
+    pub(crate) stack: Option<String>,
+    /// Whether the JS wrapper layer rethrew the error, i.e. the handler itself failed with it.
+    #[serde(skip)]
+    pub(crate) reported: bool,
+}
+
+impl From<napi::Error> for JsError {
+    fn from(value: napi::Error) -> Self {
+        let reason = value.reason;
+        if let Some(encoded) = reason.strip_prefix(JS_ERROR_PREFIX)
+            && let Ok(error) = serde_json::from_str::<JsError>(encoded)
+        {
+            return Self {
+                reported: true,
+                ..error
+            };
+        }
+
+        // Values thrown synchronously are coerced to a string, which renders errors as `Name: message`.
+        if let Some((name, message)) = reason.split_once(": ")
+            && name.ends_with("Error")
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Self {
+                name: name.to_string(),
+                message: message.to_string(),
+                stack: None,
+                reported: false,
+            };
+        }
+
+        Self {
+            name: "Error".to_string(),
+            message: reason,
+            stack: None,
+            reported: false,
+        }
+    }
+}
+
+impl fmt::Display for JsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = format!("{}: {}", self.name, self.message);
+        match &self.stack {
+            // V8 stack traces already start with the `Name: message` header.
+            Some(stack) if stack.starts_with(&header) => f.write_str(stack),
+            Some(stack) => write!(f, "{header}\n{stack}"),
+            None => f.write_str(&header),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum UdfErrorKind {
+    /// The JS function could not be invoked, e.g. because its threadsafe function was released.
+    Call(String),
+    /// The JS function threw, or the promise it returned was rejected.
+    Js(JsError),
+    /// The output of the JS function could not be forwarded to numaflow.
+    Forward(String),
+    /// The promise returned by the JS function did not settle within the handler timeout.
+    Timeout(Duration),
+    /// The value of a datum could not be decoded by the codec of the server.
+    Decode(String),
+    /// The worker threads the datum was handed to exited this many times before settling the call.
+    WorkerExited(u32),
+}
+
+/// A failure of a user-defined function, tagged with the handler it came from.
+#[derive(Clone, Debug)]
+pub(crate) struct UdfError {
+    /// Name of the failed handler as shown to the user, e.g. `map` or `source ack`.
+    pub(crate) handler: &'static str,
+    pub(crate) kind: UdfErrorKind,
+}
+
+impl UdfError {
+    pub(crate) fn call(handler: &'static str, error: napi::Error) -> Self {
+        // `call_async_catch` reports a JS throw as a pending exception, anything else means the
+        // function never ran.
+        let kind = if error.status == Status::PendingException {
+            UdfErrorKind::Js(error.into())
+        } else {
+            UdfErrorKind::Call(error.reason)
+        };
+        Self { handler, kind }
+    }
+
+    pub(crate) fn rejected(handler: &'static str, error: napi::Error) -> Self {
+        Self {
+            handler,
+            kind: UdfErrorKind::Js(error.into()),
+        }
+    }
+
+    pub(crate) fn forward(handler: &'static str, reason: impl fmt::Display) -> Self {
+        Self {
+            handler,
+            kind: UdfErrorKind::Forward(reason.to_string()),
+        }
+    }
+
+    pub(crate) fn timeout(handler: &'static str, timeout: Duration) -> Self {
+        Self {
+            handler,
+            kind: UdfErrorKind::Timeout(timeout),
+        }
+    }
+
+    pub(crate) fn decode(handler: &'static str, reason: impl fmt::Display) -> Self {
+        Self {
+            handler,
+            kind: UdfErrorKind::Decode(reason.to_string()),
+        }
+    }
+
+    pub(crate) fn worker_exited(handler: &'static str, exits: u32) -> Self {
+        Self {
+            handler,
+            kind: UdfErrorKind::WorkerExited(exits),
+        }
+    }
+
+    /// Whether the JS function was cut short rather than settled: it could not be invoked, or it
+    /// failed without the JS wrapper layer reporting the failure, as happens when the thread it
+    /// runs on exits during the call.
+    pub(crate) fn is_interrupted(&self) -> bool {
+        match &self.kind {
+            UdfErrorKind::Call(_) => true,
+            UdfErrorKind::Js(error) => !error.reported,
+            _ => false,
+        }
+    }
+
+    /// Whether the JS function did not settle within the handler timeout.
+    pub(crate) fn is_timeout(&self) -> bool {
+        matches!(self.kind, UdfErrorKind::Timeout(_))
+    }
+
+    /// The error thrown by the JS function, if it got to run.
+    pub(crate) fn js_error(&self) -> Option<&JsError> {
+        match &self.kind {
+            UdfErrorKind::Js(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Reports the error to numaflow-rs and aborts the request that is being handled.
+    ///
+    /// The numaflow-rs handler traits have no error return. A UDF failure is signalled by panicking
+    /// in the task numaflow-rs spawned for the request: its panic hook records the message, the
+    /// stream is answered with a `UDF_EXECUTION_ERROR` status and the server shuts down.
+    pub(crate) fn raise(self) -> ! {
+        error!(handler = self.handler, "{self}");
+        panic!("{self}");
+    }
+}
+
+impl fmt::Display for UdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            UdfErrorKind::Call(reason) => {
+                write!(
+                    f,
+                    "Failed to call user-defined {} function: {reason}",
+                    self.handler
+                )
+            }
+            UdfErrorKind::Js(error) => {
+                write!(
+                    f,
+                    "User-defined {} function failed with {error}",
+                    self.handler
+                )
+            }
+            UdfErrorKind::Forward(reason) => write!(
+                f,
+                "Failed to forward the output of user-defined {} function: {reason}",
+                self.handler
+            ),
+            UdfErrorKind::Timeout(timeout) => write!(
+                f,
+                "User-defined {} function did not settle within {timeout:?}",
+                self.handler
+            ),
+            UdfErrorKind::Decode(reason) => write!(
+                f,
+                "Failed to decode the input of user-defined {} function: {reason}",
+                self.handler
+            ),
+            UdfErrorKind::WorkerExited(exits) => write!(
+                f,
+                "Worker threads running user-defined {} function exited {exits} times before settling the call",
+                self.handler
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UdfError {}
+
+/// Invokes a JS handler, turning a synchronous throw into a [`UdfError`].
+pub(crate) async fn call<T, R>(
+    handler: &'static str,
+    function: &ThreadsafeFunction<T, R, T, Status, false, true>,
+    args: T,
+) -> Result<R, UdfError>
+where
+    T: 'static + JsValuesTupleIntoVec,
+    R: 'static + FromNapiValue,
+{
+    function
+        .call_async_catch(args)
+        .await
+        .map_err(|e| UdfError::call(handler, e))
+}
+
+/// Invokes a JS handler and waits for the promise it returns to settle, recording how long that
+/// took in the handler metrics.
+pub(crate) async fn call_promise<T, R>(
+    handler: &'static str,
+    function: &ThreadsafeFunction<T, Promise<R>, T, Status, false, true>,
+    args: T,
+) -> Result<R, UdfError>
+where
+    T: 'static + JsValuesTupleIntoVec,
+    R: 'static + FromNapiValue,
+{
+    let started = Instant::now();
+    let result = match call(handler, function, args).await {
+        Ok(promise) => promise.await.map_err(|e| UdfError::rejected(handler, e)),
+        Err(e) => Err(e),
+    };
+    let rejected = result.as_ref().is_err_and(|e| e.js_error().is_some());
+    metrics::callback_settled(handler, started.elapsed(), rejected);
+    result
+}
+
+/// Awaits a call of a JS handler for at most `timeout`, if there is one. The JS handler keeps
+/// running after a timeout, whatever it returns is discarded.
+pub(crate) async fn within<T>(
+    handler: &'static str,
+    timeout: Option<Duration>,
+    call: impl Future<Output = Result<T, UdfError>>,
+) -> Result<T, UdfError> {
+    let Some(timeout) = timeout else {
+        return call.await;
+    };
+    match tokio::time::timeout(timeout, call).await {
+        Ok(result) => result,
+        Err(_) => {
+            metrics::callback_timed_out(handler);
+            Err(UdfError::timeout(handler, timeout))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn js_error(reason: &str) -> JsError {
+        napi::Error::from_reason(reason).into()
+    }
+
+    #[test]
+    fn decodes_reported_errors() {
+        let error = js_error(&format!(
+            r#"{JS_ERROR_PREFIX}{{"name":"RangeError","message":"out of range","stack":"RangeError: out of range\n    at f"}}"#
+        ));
+        assert_eq!(
+            error,
+            JsError {
+                name: "RangeError".to_string(),
+                message: "out of range".to_string(),
+                stack: Some("RangeError: out of range\n    at f".to_string()),
+                reported: true,
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_malformed_reports_as_message() {
+        let reason = format!("{JS_ERROR_PREFIX}{{not json");
+        let error = js_error(&reason);
+        assert_eq!(error.name, "Error");
+        assert_eq!(error.message, reason);
+        assert!(!error.reported);
+    }
+
+    #[test]
+    fn parses_error_names_of_thrown_values() {
+        let error = js_error("TypeError: x is not a function");
+        assert_eq!(error.name, "TypeError");
+        assert_eq!(error.message, "x is not a function");
+        assert_eq!(error.stack, None);
+        assert!(!error.reported);
+    }
+
+    #[test]
+    fn names_messages_without_a_name_error() {
+        let error = js_error("connection refused: retry later");
+        assert_eq!(error.name, "Error");
+        assert_eq!(error.message, "connection refused: retry later");
+        assert_eq!(error.to_string(), "Error: connection refused: retry later");
+    }
+}
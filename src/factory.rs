@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use napi::Status;
+use napi::bindgen_prelude::FromNapiValue;
+use napi::threadsafe_function::ThreadsafeFunction;
+
+use crate::error::{self, UdfError};
+
+/// A JS function creating the handler of a new window, key or session.
+pub(crate) type FactoryFn<F> = ThreadsafeFunction<(), F, (), Status, false, true>;
+
+/// The JS handler of a server that reduces windows, keys or sessions.
+pub(crate) enum Handler<F: FromNapiValue + 'static> {
+    /// Every window calls the same handler.
+    Shared(Arc<F>),
+    /// Every window calls the handler the factory created for it, which is released along with
+    /// the window.
+    Factory(Arc<FactoryFn<F>>),
+}
+
+impl<F: FromNapiValue + 'static> Clone for Handler<F> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Shared(handler) => Self::Shared(Arc::clone(handler)),
+            Self::Factory(create) => Self::Factory(Arc::clone(create)),
+        }
+    }
+}
+
+impl<F: FromNapiValue + 'static> Handler<F> {
+    /// The handler of a new window, calling the factory if there is one.
+    pub(crate) async fn create(&self, handler: &'static str) -> Result<Arc<F>, UdfError> {
+        match self {
+            Self::Shared(handler) => Ok(Arc::clone(handler)),
+            Self::Factory(create) => error::call(handler, create, ()).await.map(Arc::new),
+        }
+    }
+}
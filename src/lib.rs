@@ -1,11 +1,30 @@
+// napi registration is compiled out of the unit-test target, so exported items look unused there.
+#![cfg_attr(test, allow(dead_code))]
+
 mod accumulator;
 mod batchmap;
+mod codec;
+mod concurrency;
+mod config;
+mod error;
+mod factory;
+mod lifecycle;
+mod logging;
 mod map;
 mod mapstream;
+mod metadata;
+mod metrics;
+mod microbatch;
 mod reduce;
 mod reducestream;
+mod retry;
 mod session_reduce;
 mod sideinput;
+mod sideinput_reader;
 mod sink;
 mod source;
 mod source_transform;
+mod trace;
+mod value;
+mod vertex;
+mod worker_pool;
@@ -0,0 +1,339 @@
+use std::error::Error;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use napi_derive::napi;
+use numaflow::shared::ServerExtras;
+use serde::Deserialize;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::{oneshot, watch};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::metrics;
+
+type ServerResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+/// Options accepted by `start()` of every server.
+///
+/// The same options can be set with `NUMAFLOW_*` environment variables or in a config file, the
+/// options passed to `start()` take precedence.
+#[napi(object)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ServerOptions {
+    /// Path of the Unix domain socket the gRPC server listens on. Defaults to the path numaflow
+    /// expects for the server type.
+    pub socket_path: Option<String>,
+    /// Path of the file the server information is written to on start up. Defaults to the path
+    /// numaflow expects for the server type.
+    pub server_info_path: Option<String>,
+    /// Maximum size of an encoded or decoded gRPC message in bytes. Defaults to 64 MiB.
+    pub max_message_size: Option<u32>,
+    /// Time in milliseconds `stop()` waits for in-flight requests before the server is closed
+    /// forcefully. Waits until they are done when not set.
+    pub shutdown_timeout_ms: Option<u32>,
+    /// Stop the server gracefully, as `stop()` does, when the process receives SIGTERM or SIGINT.
+    /// The signals no longer terminate the process while the server runs, and their default
+    /// action is not restored afterwards. Defaults to false.
+    pub handle_signals: Option<bool>,
+    /// Port of the HTTP endpoint serving the metrics of the servers in the Prometheus text format
+    /// at `/metrics`. Metrics are not served when not set.
+    pub metrics_port: Option<u16>,
+    /// IP address the metrics endpoint listens on, e.g. `127.0.0.1` to only serve the metrics
+    /// within the pod. Defaults to `0.0.0.0`, every IPv4 interface.
+    pub metrics_address: Option<String>,
+    /// Maximum number of requests handed to the JS handler at the same time, further requests
+    /// wait until one of them is done. Only applies to the map, map stream and source transform
+    /// servers. Unbounded when not set.
+    pub max_in_flight: Option<u32>,
+    /// Maximum number of requests with the same keys handed to the JS handler at the same time,
+    /// in addition to `maxInFlight`. Only applies to the map, map stream and source transform
+    /// servers. Unbounded when not set.
+    pub max_in_flight_per_key: Option<u32>,
+    /// Time in milliseconds a call of the JS handler may take before it is abandoned. A timed out
+    /// map or batch map request fails like one whose handler threw, a timed out sink batch is
+    /// answered with a failure response for every message. Only applies to the map, batch map and
+    /// sink servers. Calls are not timed out when not set.
+    pub handler_timeout_ms: Option<u32>,
+    /// Codec the datum values are decoded with into `data`, and the `data` of messages is encoded
+    /// with: `json`, `msgpack`, `cbor` or `raw`. Only applies to the map, map stream, batch map,
+    /// source transform and sink servers. Defaults to `raw`, values are only handed over as bytes.
+    pub codec: Option<String>,
+    /// What happens to a datum whose value the codec can't decode: `fail` fails the request like a
+    /// handler that threw, `drop` drops the datum without calling the handler, and `pass` hands it
+    /// to the handler with the error in `decodeError`. Defaults to `fail`.
+    pub decode_error_policy: Option<String>,
+}
+
+impl ServerOptions {
+    /// Fills the options that are not set from `fallback`.
+    pub(crate) fn or(self, fallback: ServerOptions) -> ServerOptions {
+        ServerOptions {
+            socket_path: self.socket_path.or(fallback.socket_path),
+            server_info_path: self.server_info_path.or(fallback.server_info_path),
+            max_message_size: self.max_message_size.or(fallback.max_message_size),
+            shutdown_timeout_ms: self.shutdown_timeout_ms.or(fallback.shutdown_timeout_ms),
+            handle_signals: self.handle_signals.or(fallback.handle_signals),
+            metrics_port: self.metrics_port.or(fallback.metrics_port),
+            metrics_address: self.metrics_address.or(fallback.metrics_address),
+            max_in_flight: self.max_in_flight.or(fallback.max_in_flight),
+            max_in_flight_per_key: self
+                .max_in_flight_per_key
+                .or(fallback.max_in_flight_per_key),
+            handler_timeout_ms: self.handler_timeout_ms.or(fallback.handler_timeout_ms),
+            codec: self.codec.or(fallback.codec),
+            decode_error_policy: self.decode_error_policy.or(fallback.decode_error_policy),
+        }
+    }
+
+    /// Applies the gRPC server settings to a numaflow server.
+    pub(crate) fn configure<S: ServerExtras<T>, T>(&self, mut server: S) -> S {
+        if let Some(socket_path) = &self.socket_path {
+            server = server.with_socket_file(socket_path);
+        }
+        if let Some(server_info_path) = &self.server_info_path {
+            server = server.with_server_info_file(server_info_path);
+        }
+        if let Some(max_message_size) = self.max_message_size {
+            server = server.with_max_message_size(max_message_size as usize);
+        }
+        server
+    }
+
+    fn shutdown_timeout(&self) -> Option<Duration> {
+        self.shutdown_timeout_ms
+            .map(|timeout| Duration::from_millis(timeout as u64))
+    }
+
+    pub(crate) fn handler_timeout(&self) -> Option<Duration> {
+        self.handler_timeout_ms
+            .map(|timeout| Duration::from_millis(timeout as u64))
+    }
+}
+
+/// Outcome of draining a server on `stop()`.
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct DrainSummary {
+    /// Requests that settled while the server was draining.
+    pub drained: u32,
+    /// Requests that were still in flight when the server was closed.
+    pub abandoned: u32,
+}
+
+/// Counts the requests a server is handling, i.e. the calls of the JS handlers that have not
+/// settled yet.
+#[derive(Clone)]
+pub(crate) struct InFlight {
+    /// Name of the server in the metrics.
+    server: &'static str,
+    active: Arc<watch::Sender<u32>>,
+    settled: Arc<AtomicU32>,
+}
+
+impl InFlight {
+    fn new(server: &'static str) -> Self {
+        Self {
+            server,
+            active: Arc::new(watch::Sender::new(0)),
+            settled: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Tracks a request until the returned guard is dropped.
+    pub(crate) fn track(&self) -> InFlightGuard {
+        self.active.send_modify(|active| *active += 1);
+        InFlightGuard(self.clone())
+    }
+
+    fn active(&self) -> u32 {
+        *self.active.borrow()
+    }
+
+    fn settled(&self) -> u32 {
+        self.settled.load(Ordering::Relaxed)
+    }
+
+    async fn idle(&self) {
+        let _ = self
+            .active
+            .subscribe()
+            .wait_for(|active| *active == 0)
+            .await;
+    }
+}
+
+pub(crate) struct InFlightGuard(InFlight);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.settled.fetch_add(1, Ordering::Relaxed);
+        self.0.active.send_modify(|active| *active -= 1);
+        metrics::request_handled(self.0.server);
+    }
+}
+
+/// A `stop()` call waiting for the server to drain.
+struct Stop {
+    timeout: Option<Duration>,
+    done_tx: oneshot::Sender<DrainSummary>,
+}
+
+/// Start and stop of a server, shared by all of them.
+pub(crate) struct Lifecycle {
+    stop_tx: Mutex<Option<oneshot::Sender<Stop>>>,
+    in_flight: InFlight,
+}
+
+impl Lifecycle {
+    /// Lifecycle of the server named `server` in the metrics, e.g. `map`.
+    pub(crate) fn new(server: &'static str) -> Self {
+        Self {
+            stop_tx: Mutex::new(None),
+            in_flight: InFlight::new(server),
+        }
+    }
+
+    /// Tracker for the requests of the server, handed to its handler adapter.
+    pub(crate) fn in_flight(&self) -> InFlight {
+        self.in_flight.clone()
+    }
+
+    /// Runs a numaflow server started by `start` until it exits on its own or `stop()` is called.
+    ///
+    /// On stop the server no longer accepts connections and the requests in flight are waited for
+    /// until the shutdown timeout, after which the server is closed.
+    pub(crate) async fn serve<F>(
+        &self,
+        options: &ServerOptions,
+        start: impl FnOnce(oneshot::Receiver<()>) -> F,
+    ) -> ServerResult
+    where
+        F: Future<Output = ServerResult>,
+    {
+        let (stop_tx, stop_rx) = oneshot::channel();
+        self.stop_tx.lock().unwrap().replace(stop_tx);
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let server = start(shutdown_rx);
+        tokio::pin!(server);
+
+        let stop = tokio::select! {
+            result = &mut server => return result,
+            // A dropped sender means the JS server object is gone, which is a stop as well.
+            stop = stop_rx => stop.ok(),
+            signal = termination_signal(), if options.handle_signals.unwrap_or(false) => {
+                info!(
+                    component = self.in_flight.server,
+                    signal,
+                    "Received a termination signal, stopping the server"
+                );
+                None
+            }
+        };
+        let (timeout, done_tx) = match stop {
+            Some(stop) => (
+                stop.timeout.or(options.shutdown_timeout()),
+                Some(stop.done_tx),
+            ),
+            None => (options.shutdown_timeout(), None),
+        };
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        let _ = shutdown_tx.send(());
+        let settled = self.in_flight.settled();
+        let finished = tokio::select! {
+            result = &mut server => Some(result),
+            _ = until(deadline, self.in_flight.idle()) => None,
+        };
+        let summary = DrainSummary {
+            drained: self.in_flight.settled() - settled,
+            abandoned: self.in_flight.active(),
+        };
+        if summary.abandoned > 0 {
+            warn!(
+                component = self.in_flight.server,
+                abandoned = summary.abandoned,
+                "Server stopped with requests in flight"
+            );
+        }
+
+        let result = match finished {
+            Some(result) => result,
+            None => until(deadline, server).await.unwrap_or_else(|| {
+                warn!(
+                    component = self.in_flight.server,
+                    "Server did not shut down within the shutdown timeout, closing it"
+                );
+                Ok(())
+            }),
+        };
+        if let Some(done_tx) = done_tx {
+            let _ = done_tx.send(summary);
+        }
+        result
+    }
+
+    /// Stops the server and waits until it drained, `timeout_ms` overrides the shutdown timeout of
+    /// the server options.
+    pub(crate) async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        let stop_tx = { self.stop_tx.lock().unwrap().take() };
+        let Some(stop_tx) = stop_tx else {
+            return DrainSummary::default();
+        };
+        let (done_tx, done_rx) = oneshot::channel();
+        let stop = Stop {
+            timeout: timeout_ms.map(|timeout| Duration::from_millis(timeout as u64)),
+            done_tx,
+        };
+        if stop_tx.send(stop).is_err() {
+            return DrainSummary::default();
+        }
+        done_rx.await.unwrap_or_default()
+    }
+}
+
+/// Resolves with the name of the first SIGTERM or SIGINT the process receives.
+async fn termination_signal() -> &'static str {
+    reset_one_shot_handler(libc::SIGTERM);
+    reset_one_shot_handler(libc::SIGINT);
+    let (Ok(mut terminate), Ok(mut interrupt)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) else {
+        warn!("Failed to install the SIGTERM and SIGINT handlers");
+        return std::future::pending().await;
+    };
+    tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        _ = interrupt.recv() => "SIGINT",
+    }
+}
+
+/// Node handles SIGTERM and SIGINT by resetting the terminal and raising the signal again, with
+/// the default action restored through `SA_RESETHAND`. Called by tokio's handler, which doesn't
+/// reset anything, the handler would raise the signal forever, so it is replaced with the default
+/// action before tokio installs its own. Handlers installed from JS are kept.
+fn reset_one_shot_handler(signum: libc::c_int) {
+    // SAFETY: the current action is only read into a zeroed struct, and replaced with the default
+    // action, which doesn't run any code in the signal context.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        if libc::sigaction(signum, std::ptr::null(), &mut action) == 0
+            && action.sa_flags & libc::SA_RESETHAND != 0
+        {
+            libc::signal(signum, libc::SIG_DFL);
+        }
+    }
+}
+
+/// Awaits `future` until the deadline, if there is one.
+async fn until<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
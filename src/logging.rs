@@ -0,0 +1,341 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config;
+
+const ENV_LOG_LEVEL: &str = "NUMAFLOW_LOG_LEVEL";
+const ENV_LOG_FORMAT: &str = "NUMAFLOW_LOG_FORMAT";
+
+type LogHandler = ThreadsafeFunction<LogRecord, (), LogRecord, Status, false, true>;
+
+/// Level and format of the logs of the servers.
+///
+/// The same options can be set with the `NUMAFLOW_LOG_LEVEL` and `NUMAFLOW_LOG_FORMAT` environment
+/// variables, the options passed to `configureLogging()` take precedence.
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct LogOptions {
+    /// Most verbose level that is logged, one of `off`, `error`, `warn`, `info`, `debug` and
+    /// `trace`. Defaults to `info`.
+    pub level: Option<String>,
+    /// `json` to log a JSON object per line, `text` to log plain lines. Defaults to `json`.
+    pub format: Option<String>,
+}
+
+/// A log record of the native layer, as passed to the handler set with `setLogHandler()`.
+#[napi(object)]
+pub struct LogRecord {
+    /// Time the record was logged at.
+    pub timestamp: DateTime<Utc>,
+    /// One of `error`, `warn`, `info`, `debug` and `trace`.
+    pub level: String,
+    /// Module the record was logged from, e.g. `numaflow_js::lifecycle`.
+    pub target: String,
+    pub message: String,
+    /// Structured fields of the record, e.g. `component` with the server that logged it.
+    pub fields: Map<String, Value>,
+}
+
+/// Sets the level and format of the logs, options that are not set keep their current value.
+#[napi]
+pub fn configure_logging(options: LogOptions) -> Result<()> {
+    let level = options
+        .level
+        .map(|level| parse(&level, "log level"))
+        .transpose()?;
+    let format = options
+        .format
+        .map(|format| parse(&format, "log format"))
+        .transpose()?;
+    {
+        let mut settings = SETTINGS.write().unwrap();
+        if let Some(level) = level {
+            settings.level = level;
+        }
+        if let Some(format) = format {
+            settings.format = format;
+        }
+    }
+    // Callsites cache whether they are enabled, which depends on the level.
+    tracing::callsite::rebuild_interest_cache();
+    Ok(())
+}
+
+/// Routes the logs to `handler` instead of stdout and stderr, e.g. into the application's logger.
+/// Passing nothing restores the default output.
+#[napi(ts_args_type = "handler?: ((record: LogRecord) => void) | null")]
+pub fn set_log_handler(handler: Option<LogHandler>) {
+    SETTINGS.write().unwrap().handler = handler.map(Arc::new);
+}
+
+/// Installs the logger when the addon is loaded.
+#[napi_derive::module_init]
+fn init() {
+    let subscriber = tracing_subscriber::registry().with(LogLayer);
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        eprintln!(
+            "[WARN] A tracing subscriber is already installed, numaflow-js logs are not written"
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum LogFormat {
+    Json,
+    Text,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            _ => Err("expected json or text".to_string()),
+        }
+    }
+}
+
+struct Settings {
+    level: LevelFilter,
+    format: LogFormat,
+    handler: Option<Arc<LogHandler>>,
+}
+
+static SETTINGS: LazyLock<RwLock<Settings>> = LazyLock::new(|| {
+    RwLock::new(Settings {
+        level: env_setting(ENV_LOG_LEVEL).unwrap_or(LevelFilter::INFO),
+        format: env_setting(ENV_LOG_FORMAT).unwrap_or(LogFormat::Json),
+        handler: None,
+    })
+});
+
+/// Reads a setting from the environment. The logger is installed on load, when there is no caller
+/// to report an invalid value to, so it is ignored with a warning.
+fn env_setting<T>(name: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = config::env_var(name)?;
+    match value.parse() {
+        Ok(setting) => Some(setting),
+        Err(e) => {
+            eprintln!("[WARN] Ignoring invalid {name}={value:?}: {e}");
+            None
+        }
+    }
+}
+
+fn parse<T>(value: &str, what: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| Error::new(Status::InvalidArg, format!("Invalid {what} {value:?}: {e}")))
+}
+
+/// Writes the events to stdout and stderr, or hands them to the JS log handler. Events get the
+/// fields of the spans they are in, e.g. the trace ids of the datum that is handled.
+struct LogLayer;
+
+/// Fields of a span, kept in its extensions.
+struct SpanFields(Map<String, Value>);
+
+impl<S> Layer<S> for LogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.enabled(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        self.enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(SETTINGS.read().unwrap().level)
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(visitor.fields));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        values.record(&mut visitor);
+        if let Some(span) = ctx.span(id)
+            && let Some(fields) = span.extensions_mut().get_mut::<SpanFields>()
+        {
+            fields.0.extend(visitor.fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(span_fields) = span.extensions().get::<SpanFields>() {
+                    fields.extend(span_fields.0.clone());
+                }
+            }
+        }
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        fields.extend(visitor.fields);
+        let metadata = event.metadata();
+        let record = LogRecord {
+            timestamp: Utc::now(),
+            level: metadata.level().as_str().to_ascii_lowercase(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+            fields,
+        };
+
+        let (format, handler) = {
+            let settings = SETTINGS.read().unwrap();
+            (settings.format, settings.handler.clone())
+        };
+        if let Some(handler) = handler {
+            handler.call(record, ThreadsafeFunctionCallMode::NonBlocking);
+            return;
+        }
+        let line = match format {
+            LogFormat::Json => json_line(record),
+            LogFormat::Text => text_line(record),
+        };
+        // Like the servers used to, warnings and errors go to stderr and the rest to stdout.
+        let _ = if *metadata.level() <= Level::WARN {
+            writeln!(io::stderr().lock(), "{line}")
+        } else {
+            writeln!(io::stdout().lock(), "{line}")
+        };
+    }
+}
+
+impl LogLayer {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= SETTINGS.read().unwrap().level
+    }
+}
+
+fn json_line(record: LogRecord) -> String {
+    let line = JsonLine {
+        timestamp: record
+            .timestamp
+            .to_rfc3339_opts(SecondsFormat::Millis, true),
+        level: record.level,
+        target: record.target,
+        message: record.message,
+        fields: record.fields,
+    };
+    serde_json::to_string(&line).expect("log line is serializable")
+}
+
+/// A record in the JSON format, the fields follow the message.
+#[derive(Serialize)]
+struct JsonLine {
+    timestamp: String,
+    level: String,
+    target: String,
+    message: String,
+    #[serde(flatten)]
+    fields: Map<String, Value>,
+}
+
+fn text_line(record: LogRecord) -> String {
+    let mut line = format!(
+        "{} {:>5} {}: {}",
+        record
+            .timestamp
+            .to_rfc3339_opts(SecondsFormat::Millis, true),
+        record.level.to_ascii_uppercase(),
+        record.target,
+        record.message
+    );
+    for (name, value) in record.fields {
+        let value = match value {
+            Value::String(value) => value,
+            value => value.to_string(),
+        };
+        line.push_str(&format!(" {name}={value}"));
+    }
+    line
+}
+
+/// Collects the message and the fields of an event.
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: Map<String, Value>,
+}
+
+impl FieldVisitor {
+    fn insert(&mut self, field: &Field, value: Value) {
+        match (field.name(), value) {
+            ("message", Value::String(message)) => self.message = message,
+            (name, value) => {
+                self.fields.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.into());
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.insert(field, value.to_string().into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{value:?}").into());
+    }
+}
@@ -1,93 +1,34 @@
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::{collections::HashMap, sync::Mutex};
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 use napi::Result;
 use napi::{bindgen_prelude::*, threadsafe_function::ThreadsafeFunction};
 use napi_derive::napi;
 use numaflow::map;
-use numaflow::shared::ServerExtras;
-
-#[derive(Clone, Default)]
-#[napi(namespace = "map")]
-pub struct UserMetadata(map::UserMetadata);
-
-#[napi(namespace = "map")]
-impl UserMetadata {
-    #[napi(constructor)]
-    pub fn new() -> Self {
-        Self(map::UserMetadata::default())
-    }
-
-    #[napi]
-    pub fn get_groups(&self) -> Vec<String> {
-        self.0.groups()
-    }
-
-    #[napi]
-    pub fn get_keys(&self, group: String) -> Vec<String> {
-        self.0.keys(group.as_str())
-    }
-
-    #[napi]
-    pub fn get_value(&self, group: String, key: String) -> Buffer {
-        Buffer::from(self.0.value(group.as_str(), key.as_str()))
-    }
-
-    #[napi]
-    pub fn create_group(&mut self, group: String) {
-        self.0.create_group(group);
-    }
-
-    #[napi]
-    pub fn add_kv(&mut self, group: String, key: String, value: Buffer) {
-        self.0.add_kv(group, key, value.into())
-    }
-
-    #[napi]
-    pub fn remove_key(&mut self, group: String, key: String) {
-        self.0.remove_key(group.as_str(), key.as_str());
-    }
-
-    #[napi]
-    pub fn remove_group(&mut self, group: String) {
-        self.0.remove_group(group.as_str());
-    }
-}
-
-#[derive(Clone, Default)]
-#[napi(namespace = "map")]
-pub struct SystemMetadata(map::SystemMetadata);
-
-#[napi(namespace = "map")]
-impl SystemMetadata {
-    #[napi(constructor)]
-    pub fn new() -> Self {
-        Self(map::SystemMetadata::default())
-    }
-
-    #[napi]
-    pub fn get_groups(&self) -> Vec<String> {
-        self.0.groups()
-    }
-
-    #[napi]
-    pub fn get_keys(&self, group: String) -> Vec<String> {
-        self.0.keys(group.as_str())
-    }
-
-    #[napi]
-    pub fn get_value(&self, group: String, key: String) -> Buffer {
-        Buffer::from(self.0.value(group.as_str(), key.as_str()))
-    }
-}
-
+use tracing::{Instrument, error, info};
+
+use crate::codec::{Codec, Decoded};
+use crate::concurrency::ConcurrencyLimit;
+use crate::config;
+use crate::error::{self, UdfError};
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metadata::{self, SystemMetadata, UserMetadata};
+use crate::metrics::{self, MetricsServer};
+use crate::microbatch::{MicroBatch, MicroBatchOptions, MicroBatcher};
+use crate::retry::{Retry, RetryPolicy};
+use crate::trace::{self, TraceContext};
+use crate::value::DatumValue;
+use crate::worker_pool::WorkerPool;
+
+#[derive(Clone)]
 #[napi(namespace = "map")]
 pub struct Datum {
     /// Set of keys in the (key, value) terminology of map/reduce paradigm.
     pub keys: Vec<String>,
     /// The value in the (key, value) terminology of map/reduce paradigm.
-    value: Vec<u8>,
+    value: DatumValue,
     /// [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a
     /// guarantee that we will not see an element older than this time.
     watermark: DateTime<Utc>,
@@ -99,6 +40,12 @@ pub struct Datum {
     user_metadata: Option<UserMetadata>,
     /// System metadata for the message.
     system_metadata: Option<SystemMetadata>,
+    /// Trace context from the `traceparent` header.
+    trace_context: Option<TraceContext>,
+    /// The value decoded by the codec of the server.
+    data: Option<serde_json::Value>,
+    /// Why the value could not be decoded, when the decode error policy is `pass`.
+    decode_error: Option<String>,
 }
 
 #[napi(namespace = "map")]
@@ -118,15 +65,19 @@ impl Datum {
             value: value.into(),
             watermark,
             event_time,
+            trace_context: TraceContext::child_of(&headers),
             headers,
-            user_metadata: user_metadata.map(|metadata| UserMetadata(metadata.0.clone())),
-            system_metadata: system_metadata.map(|metadata| SystemMetadata(metadata.0.clone())),
+            user_metadata: user_metadata.cloned(),
+            system_metadata: system_metadata.cloned(),
+            data: None,
+            decode_error: None,
         }
     }
 
-    #[napi(getter)]
-    pub fn get_value(&self) -> Buffer {
-        Buffer::from(self.value.clone())
+    /// The value of the datum, the same buffer on every read.
+    #[napi(getter, ts_return_type = "Buffer")]
+    pub fn get_value(&self) -> DatumValue {
+        self.value.clone()
     }
 
     #[napi(getter)]
@@ -144,6 +95,12 @@ impl Datum {
         self.headers.clone()
     }
 
+    /// Context of the span the datum is handled in, if its headers carry a `traceparent`.
+    #[napi(getter)]
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        self.trace_context.clone()
+    }
+
     #[napi(getter)]
     pub fn user_metadata(&self) -> Option<UserMetadata> {
         self.user_metadata.clone()
@@ -154,9 +111,31 @@ impl Datum {
         self.system_metadata.clone()
     }
 
+    /// The value decoded by the codec of the server, null with the raw codec or an empty value.
+    #[napi(getter)]
+    pub fn data(&self) -> Option<serde_json::Value> {
+        self.data.clone()
+    }
+
+    /// Why the value could not be decoded, only set when the decode error policy is `pass`.
+    #[napi(getter)]
+    pub fn decode_error(&self) -> Option<String> {
+        self.decode_error.clone()
+    }
+
     #[napi(setter)]
     pub fn set_user_metadata(&mut self, user_metadata: &UserMetadata) {
-        self.user_metadata = Some(UserMetadata(user_metadata.0.clone()));
+        self.user_metadata = Some(user_metadata.clone());
+    }
+}
+
+impl Datum {
+    /// A copy of the datum whose value shares no buffer with this one.
+    pub(crate) fn detached(&self) -> Self {
+        Self {
+            value: self.value.detach(),
+            ..self.clone()
+        }
     }
 }
 
@@ -164,12 +143,15 @@ impl From<map::MapRequest> for Datum {
     fn from(value: map::MapRequest) -> Self {
         Self {
             keys: value.keys,
-            value: value.value,
+            value: value.value.into(),
             watermark: value.watermark,
             event_time: value.eventtime,
+            trace_context: TraceContext::child_of(&value.headers),
             headers: value.headers,
-            user_metadata: Some(UserMetadata(value.user_metadata)),
-            system_metadata: Some(SystemMetadata(value.system_metadata)),
+            user_metadata: Some(UserMetadata::from_numaflow(&value.user_metadata)),
+            system_metadata: Some(SystemMetadata::from_numaflow(&value.system_metadata)),
+            data: None,
+            decode_error: None,
         }
     }
 }
@@ -181,113 +163,281 @@ pub struct Message {
     pub keys: Option<Vec<String>>,
     /// Value is the value passed to the next vertex.
     pub value: Buffer,
+    /// Data encoded by the codec of the server into the value passed on, replaces `value`.
+    pub data: Option<serde_json::Value>,
     /// Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
     pub tags: Option<Vec<String>>,
     /// User metadata for the message.
     pub user_metadata: Option<HashMap<String, HashMap<String, Buffer>>>,
 }
 
-impl From<Message> for map::Message {
-    fn from(value: Message) -> Self {
-        let mut user_metadata = None;
-        if let Some(user_metadata_map) = value.user_metadata {
-            let mut metadata = map::UserMetadata::new();
-            for (group, keys) in user_metadata_map.iter() {
-                for (key, value) in keys.iter() {
-                    metadata.add_kv(group.clone(), key.clone(), value.to_vec());
-                }
-            }
-            user_metadata = Some(metadata);
-        }
-        Self {
-            keys: value.keys,
-            value: value.value.into(),
-            tags: value.tags,
-            user_metadata,
-        }
+impl Message {
+    fn encode(self, codec: &Codec) -> std::result::Result<map::Message, String> {
+        Ok(map::Message {
+            keys: self.keys,
+            value: codec.encode(Some(self.value), self.data)?,
+            tags: self.tags,
+            user_metadata: self.user_metadata.map(metadata::from_record),
+        })
     }
 }
 
+pub(crate) type MapFn =
+    ThreadsafeFunction<Datum, Promise<Vec<Message>>, Datum, Status, false, true>;
+
+type MapBatchFn =
+    ThreadsafeFunction<Vec<Datum>, Promise<Vec<Vec<Message>>>, Vec<Datum>, Status, false, true>;
+
+/// The JS handler of a map server.
+#[derive(Clone)]
+enum Handler {
+    /// Called once per request.
+    Unary(Arc<MapFn>),
+    /// Called once per micro-batch of requests, with one list of messages per datum.
+    MicroBatch(Arc<MapBatchFn>, MicroBatch),
+    /// Called in the worker threads of the pool, each of which registered its own handler.
+    WorkerPool(Arc<WorkerPool>),
+}
+
 #[napi(namespace = "map")]
 pub struct MapAsyncServer {
-    map_fn: Arc<ThreadsafeFunction<Datum, Promise<Vec<Message>>, Datum, Status, false, true>>,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    handler: Handler,
+    retry: Retry,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "map")]
 impl MapAsyncServer {
-    #[napi(constructor, namespace = "map")]
-    pub fn new(
-        map_fn: Arc<ThreadsafeFunction<Datum, Promise<Vec<Message>>, Datum, Status, false, true>>,
-    ) -> Self {
-        Self {
-            map_fn,
-            shutdown_tx: Mutex::new(None),
-        }
+    #[napi(
+        constructor,
+        namespace = "map",
+        ts_args_type = "mapFn: (arg: Datum) => Promise<Array<Message>>, retryPolicy?: RetryPolicy | undefined | null"
+    )]
+    pub fn new(map_fn: Arc<MapFn>, retry_policy: Option<RetryPolicy>) -> Result<Self> {
+        Ok(Self {
+            handler: Handler::Unary(map_fn),
+            retry: retry_policy.try_into()?,
+            lifecycle: Lifecycle::new("map"),
+        })
     }
 
-    #[napi(namespace = "map")]
-    pub async fn start(&self, sock_file: Option<String>, info_file: Option<String>) -> Result<()> {
-        let js_mapper = JsMapper::new(Arc::clone(&self.map_fn));
+    /// A server that coalesces the requests arriving concurrently into micro-batches and calls
+    /// `mapBatchFn` once per batch. It returns the messages of every datum of the batch, in the
+    /// order of the datums. A retry or a failure applies to the whole batch.
+    #[napi(
+        factory,
+        namespace = "map",
+        ts_args_type = "mapBatchFn: (datums: Array<Datum>) => Promise<Array<Array<Message>>>, microBatch?: MicroBatchOptions | undefined | null, retryPolicy?: RetryPolicy | undefined | null"
+    )]
+    pub fn with_micro_batching(
+        map_batch_fn: Arc<MapBatchFn>,
+        micro_batch: Option<MicroBatchOptions>,
+        retry_policy: Option<RetryPolicy>,
+    ) -> Result<Self> {
+        Ok(Self {
+            handler: Handler::MicroBatch(map_batch_fn, micro_batch.into()),
+            retry: retry_policy.try_into()?,
+            lifecycle: Lifecycle::new("map"),
+        })
+    }
 
-        let mut server = map::Server::new(js_mapper);
-        if let Some(sock_file) = sock_file {
-            server = server.with_socket_file(sock_file);
-        }
-        if let Some(info_file) = info_file {
-            server = server.with_server_info_file(info_file);
+    /// A server that hands every datum to one of the worker threads registered with
+    /// `registerWorker` under the id of its pool, in turns. A datum whose worker exits before
+    /// settling the call is handed to another worker.
+    #[napi(factory, namespace = "map")]
+    pub fn with_worker_pool(retry_policy: Option<RetryPolicy>) -> Result<Self> {
+        Ok(Self {
+            handler: Handler::WorkerPool(WorkerPool::new()),
+            retry: retry_policy.try_into()?,
+            lifecycle: Lifecycle::new("map"),
+        })
+    }
+
+    /// Id the worker threads register their handlers with, null without a worker pool.
+    #[napi(namespace = "map")]
+    pub fn worker_pool_id(&self) -> Option<u32> {
+        match &self.handler {
+            Handler::WorkerPool(pool) => Some(pool.id()),
+            _ => None,
         }
+    }
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        self.shutdown_tx.lock().unwrap().replace(tx);
-        if let Err(e) = server.start_with_shutdown(rx).await {
-            println!("Error running MapAsyncServer: {e:?}");
+    /// Reports that a worker thread of the pool exited, with the uncaught error it exited with if
+    /// any. The datums it was handling are handed to the other workers.
+    #[napi(namespace = "map")]
+    pub fn worker_exited(&self, worker_id: u32, exit_code: i32, error: Option<String>) {
+        if let Handler::WorkerPool(pool) = &self.handler {
+            pool.worker_exited(worker_id, exit_code, error);
         }
-        println!("MapAsyncServer has shutdown...");
-        Ok(())
     }
 
     #[napi(namespace = "map")]
-    pub fn stop(&self) -> Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
+    pub async fn start(&self, options: Option<ServerOptions>) -> Result<()> {
+        let options = config::resolve(options)?;
+        let js_mapper = JsMapper::new(
+            self.handler.clone(),
+            self.retry.clone(),
+            options.handler_timeout(),
+            ConcurrencyLimit::new("map", &options)?,
+            self.lifecycle.in_flight(),
+            Codec::new("map", &options)?,
+        );
+
+        let server = options.configure(map::Server::new(js_mapper));
+
+        let _metrics_server = MetricsServer::start(&options)?;
+        if let Err(e) = self
+            .lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+        {
+            error!(component = "map", error = ?e, "Error running MapAsyncServer");
         }
+        info!(component = "map", "MapAsyncServer has shut down");
         Ok(())
     }
+
+    #[napi(namespace = "map")]
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
+    }
+}
+
+/// How [`JsMapper`] calls the JS handler.
+enum Dispatch {
+    Unary(Arc<MapFn>),
+    MicroBatch(MicroBatcher<Datum, std::result::Result<Vec<Message>, UdfError>>),
+    WorkerPool(Arc<WorkerPool>),
 }
 
 struct JsMapper {
-    map_fn: Arc<ThreadsafeFunction<Datum, Promise<Vec<Message>>, Datum, Status, false, true>>,
+    dispatch: Dispatch,
+    retry: Retry,
+    timeout: Option<Duration>,
+    limit: ConcurrencyLimit,
+    in_flight: InFlight,
+    codec: Codec,
 }
 
 impl JsMapper {
     fn new(
-        map_fn: Arc<ThreadsafeFunction<Datum, Promise<Vec<Message>>, Datum, Status, false, true>>,
+        handler: Handler,
+        retry: Retry,
+        timeout: Option<Duration>,
+        limit: ConcurrencyLimit,
+        in_flight: InFlight,
+        codec: Codec,
     ) -> Self {
-        Self { map_fn }
+        let dispatch = match handler {
+            Handler::Unary(map_fn) => Dispatch::Unary(map_fn),
+            Handler::MicroBatch(map_batch_fn, micro_batch) => {
+                let retry = retry.clone();
+                Dispatch::MicroBatch(MicroBatcher::new(micro_batch, move |datums| {
+                    map_batch(Arc::clone(&map_batch_fn), retry.clone(), timeout, datums)
+                }))
+            }
+            Handler::WorkerPool(pool) => Dispatch::WorkerPool(pool),
+        };
+        Self {
+            dispatch,
+            retry,
+            timeout,
+            limit,
+            in_flight,
+            codec,
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl map::Mapper for JsMapper {
     async fn map(&self, datum: map::MapRequest) -> Vec<map::Message> {
-        let datum: Datum = datum.into();
-        match self.map_fn.call_async(datum).await {
-            Ok(promise) => match promise.await {
-                Ok(messages) => messages.into_iter().map(|message| message.into()).collect(),
-                Err(e) => {
-                    eprintln!(
-                        "[ERROR] User-defined map function returned an error: {:?}",
-                        e
-                    );
-                    panic!("User-defined map function returned an error: {:?}", e);
+        let _in_flight = self.in_flight.track();
+        let mut datum: Datum = datum.into();
+        match datum.value.with(|value| self.codec.decode(value)) {
+            Decoded::Datum { data, error } => {
+                datum.data = data;
+                datum.decode_error = error;
+            }
+            Decoded::Drop => {
+                metrics::datum_dropped("map");
+                return vec![map::Message::message_to_drop()];
+            }
+            Decoded::Fail(reason) => UdfError::decode("map", reason).raise(),
+        }
+        let _permit = self.limit.acquire(&datum.keys).await;
+        let span = trace::handler_span("map", datum.trace_context.as_ref());
+        async {
+            let messages = match &self.dispatch {
+                Dispatch::Unary(map_fn) => {
+                    self.retry
+                        .run(datum, |datum| {
+                            error::within(
+                                "map",
+                                self.timeout,
+                                error::call_promise("map", map_fn, datum),
+                            )
+                        })
+                        .await
                 }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Executing user-defined map function: {:?}", e);
-                panic!("Error executing user-defined map function: {:?}", e);
+                Dispatch::MicroBatch(batcher) => batcher.submit(datum).await,
+                Dispatch::WorkerPool(pool) => {
+                    self.retry
+                        .run(datum, |datum| pool.map(datum, self.timeout))
+                        .await
+                }
+            };
+            match messages {
+                Ok(messages) => messages
+                    .into_iter()
+                    .map(|message| {
+                        metrics::message_emitted("map", message.tags.as_deref());
+                        message
+                            .encode(&self.codec)
+                            .unwrap_or_else(|e| UdfError::forward("map", e).raise())
+                    })
+                    .collect(),
+                Err(e) => e.raise(),
             }
         }
+        .instrument(span)
+        .await
+    }
+}
+
+/// Calls the JS handler with a micro-batch of datums, returning the messages of every datum. A
+/// failure of the call fails every datum of the batch.
+async fn map_batch(
+    map_batch_fn: Arc<MapBatchFn>,
+    retry: Retry,
+    timeout: Option<Duration>,
+    datums: Vec<Datum>,
+) -> Vec<std::result::Result<Vec<Message>, UdfError>> {
+    let count = datums.len();
+    let result = retry
+        .run(datums, |datums| {
+            error::within(
+                "map",
+                timeout,
+                error::call_promise("map", &map_batch_fn, datums),
+            )
+        })
+        .await
+        .and_then(|messages| {
+            if messages.len() == count {
+                Ok(messages)
+            } else {
+                Err(UdfError::forward(
+                    "map",
+                    format!(
+                        "Returned {} lists of messages for a batch of {count} datums",
+                        messages.len()
+                    ),
+                ))
+            }
+        });
+    match result {
+        Ok(messages) => messages.into_iter().map(Ok).collect(),
+        Err(e) => (0..count).map(|_| Err(e.clone())).collect(),
     }
 }
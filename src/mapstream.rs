@@ -1,13 +1,20 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-};
+use std::{collections::HashMap, sync::Arc};
 
 use chrono::{DateTime, Utc};
 use napi::{bindgen_prelude::*, threadsafe_function::ThreadsafeFunction};
 use napi_derive::napi;
 use numaflow::{mapstream, shared::ServerExtras};
 use tokio::sync::mpsc::Sender;
+use tracing::{Instrument, info};
+
+use crate::codec::{Codec, Decoded};
+use crate::concurrency::ConcurrencyLimit;
+use crate::config;
+use crate::error::{self, UdfError};
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metrics::{self, MetricsServer};
+use crate::trace::{self, TraceContext};
+use crate::value::DatumValue;
 
 #[napi(object, namespace = "mapstream")]
 pub struct Message {
@@ -16,17 +23,19 @@ pub struct Message {
     pub keys: Option<Vec<String>>,
     /// Value is the value passed to the next vertex.
     pub value: Buffer,
+    /// Data encoded by the codec of the server into the value passed on, replaces `value`.
+    pub data: Option<serde_json::Value>,
     /// Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
     pub tags: Option<Vec<String>>,
 }
 
-impl From<Message> for mapstream::Message {
-    fn from(value: Message) -> Self {
-        Self {
-            keys: value.keys,
-            value: value.value.into(),
-            tags: value.tags,
-        }
+impl Message {
+    fn encode(self, codec: &Codec) -> std::result::Result<mapstream::Message, String> {
+        Ok(mapstream::Message {
+            keys: self.keys,
+            value: codec.encode(Some(self.value), self.data)?,
+            tags: self.tags,
+        })
     }
 }
 
@@ -35,7 +44,8 @@ pub struct Datum {
     /// Set of keys in the (key, value) terminology of the map/reduce paradigm.
     pub keys: Vec<String>,
     /// The value in the (key, value) terminology of the map/reduce paradigm.
-    pub value: Buffer,
+    #[napi(ts_type = "Buffer")]
+    pub value: DatumValue,
     /// [Watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a
     /// guarantee that we will not see an element older than this time.
     pub watermark: DateTime<Utc>,
@@ -43,16 +53,25 @@ pub struct Datum {
     pub event_time: DateTime<Utc>,
     /// Headers associated with the message.
     pub headers: HashMap<String, String>,
+    /// Context of the span the datum is handled in, if its headers carry a `traceparent`.
+    pub trace_context: Option<TraceContext>,
+    /// The value decoded by the codec of the server, not set with the raw codec or an empty value.
+    pub data: Option<serde_json::Value>,
+    /// Why the value could not be decoded, only set when the decode error policy is `pass`.
+    pub decode_error: Option<String>,
 }
 
 impl Clone for Datum {
     fn clone(&self) -> Self {
         Self {
             keys: self.keys.clone(),
-            value: Buffer::from(self.value.to_vec()),
+            value: self.value.clone(),
             watermark: self.watermark,
             event_time: self.event_time,
             headers: self.headers.clone(),
+            trace_context: self.trace_context.clone(),
+            data: self.data.clone(),
+            decode_error: self.decode_error.clone(),
         }
     }
 }
@@ -64,7 +83,10 @@ impl From<numaflow::mapstream::MapStreamRequest> for Datum {
             value: value.value.into(),
             watermark: value.watermark,
             event_time: value.eventtime,
+            trace_context: TraceContext::child_of(&value.headers),
             headers: value.headers,
+            data: None,
+            decode_error: None,
         }
     }
 }
@@ -74,6 +96,7 @@ pub fn message_to_drop() -> Message {
     Message {
         keys: None,
         value: Buffer::from(vec![]),
+        data: None,
         tags: Some(vec![numaflow::shared::DROP.to_string()]),
     }
 }
@@ -81,7 +104,7 @@ pub fn message_to_drop() -> Message {
 #[napi(namespace = "mapstream")]
 pub struct MapStreamAsyncServer {
     map_fn: Arc<ThreadsafeFunction<Datum, MapFn, Datum, Status, false, true>>,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "mapstream")]
@@ -93,108 +116,116 @@ impl MapStreamAsyncServer {
     pub fn new(map_fn: Arc<ThreadsafeFunction<Datum, MapFn, Datum, Status, false, true>>) -> Self {
         Self {
             map_fn,
-            shutdown_tx: Mutex::new(None),
+            lifecycle: Lifecycle::new("mapstream"),
         }
     }
 
     #[napi]
-    pub fn stop(&self) -> Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
-        }
-        Ok(())
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
     }
 
     #[napi]
-    pub async fn start(&self, sock_file: Option<String>, info_file: Option<String>) -> Result<()> {
-        let mapper = JsMapper::new(self.map_fn.clone());
-        let mut server = mapstream::Server::new(mapper);
-        if let Some(sock_file) = sock_file {
-            server = server.with_socket_file(sock_file.clone());
-        }
-        if let Some(info_file) = info_file {
-            server = server.with_server_info_file(info_file);
-        }
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        {
-            self.shutdown_tx.lock().unwrap().replace(tx);
-        }
-        println!("Starting MapStream server at {:?}", server.socket_file());
-        server.start_with_shutdown(rx).await.map_err(|e| {
-            Error::new(
-                Status::GenericFailure,
-                format!("Error running MapStreamAsyncServer: {e:?}"),
-            )
-        })?;
-        println!("MapStreamAsyncServer has shutdown...");
+    pub async fn start(&self, options: Option<ServerOptions>) -> Result<()> {
+        let options = config::resolve(options)?;
+        let mapper = JsMapper::new(
+            self.map_fn.clone(),
+            ConcurrencyLimit::new("mapstream", &options)?,
+            self.lifecycle.in_flight(),
+            Codec::new("mapstream", &options)?,
+        );
+        let server = options.configure(mapstream::Server::new(mapper));
+        info!(
+            component = "mapstream",
+            socket_file = ?server.socket_file(),
+            "Starting MapStreamAsyncServer"
+        );
+        let _metrics_server = MetricsServer::start(&options)?;
+        self.lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    Status::GenericFailure,
+                    format!("Error running MapStreamAsyncServer: {e:?}"),
+                )
+            })?;
+        info!(
+            component = "mapstream",
+            "MapStreamAsyncServer has shut down"
+        );
         Ok(())
     }
 }
 
 struct JsMapper {
     map_fn: Arc<ThreadsafeFunction<Datum, MapFn, Datum, Status, false, true>>,
+    /// Held until the stream of a request has ended.
+    limit: ConcurrencyLimit,
+    in_flight: InFlight,
+    codec: Codec,
 }
 
 type MapFn = ThreadsafeFunction<(), Promise<Option<Message>>, (), Status, false, true>;
 
 impl JsMapper {
-    fn new(map_fn: Arc<ThreadsafeFunction<Datum, MapFn, Datum, Status, false, true>>) -> Self {
-        Self { map_fn }
+    fn new(
+        map_fn: Arc<ThreadsafeFunction<Datum, MapFn, Datum, Status, false, true>>,
+        limit: ConcurrencyLimit,
+        in_flight: InFlight,
+        codec: Codec,
+    ) -> Self {
+        Self {
+            map_fn,
+            limit,
+            in_flight,
+            codec,
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl mapstream::MapStreamer for JsMapper {
     async fn map_stream(&self, input: mapstream::MapStreamRequest, tx: Sender<mapstream::Message>) {
-        let datum = Datum {
-            keys: input.keys,
-            value: Buffer::from(input.value.to_vec()),
-            watermark: input.watermark,
-            event_time: input.eventtime,
-            headers: input.headers,
-        };
-        match self.map_fn.call_async(datum).await {
-            Ok(messages_fn) => loop {
-                match messages_fn.call_async(()).await {
-                    Ok(promise) => match promise.await {
-                        Ok(Some(message)) => {
-                            if let Err(e) = tx.send(message.into()).await {
-                                eprintln!(
-                                    "[ERROR] Failed to send mapstream message to grpc client: {:?}",
-                                    e
-                                );
-                                panic!("Failed to send mapstream message to grpc client: {:?}", e);
-                            }
-                        }
-                        Ok(None) => break,
-                        Err(e) => {
-                            eprintln!(
-                                "[ERROR] Error executing iterator returned by user-defined mapstream function: {:?}",
-                                e
-                            );
-                            panic!(
-                                "Error executing iterator returned by user-defined mapstream function: {:?}",
-                                e
-                            );
+        let _in_flight = self.in_flight.track();
+        let mut datum: Datum = input.into();
+        match datum.value.with(|value| self.codec.decode(value)) {
+            Decoded::Datum { data, error } => {
+                datum.data = data;
+                datum.decode_error = error;
+            }
+            // Nothing is sent for a dropped datum.
+            Decoded::Drop => {
+                metrics::datum_dropped("mapstream");
+                return;
+            }
+            Decoded::Fail(reason) => UdfError::decode("mapstream", reason).raise(),
+        }
+        let _permit = self.limit.acquire(&datum.keys).await;
+        let span = trace::handler_span("mapstream", datum.trace_context.as_ref());
+        async {
+            let messages_fn = match error::call("mapstream", &self.map_fn, datum).await {
+                Ok(messages_fn) => messages_fn,
+                Err(e) => e.raise(),
+            };
+            loop {
+                match error::call_promise("mapstream iterator", &messages_fn, ()).await {
+                    Ok(Some(message)) => {
+                        metrics::message_emitted("mapstream", message.tags.as_deref());
+                        let message = match message.encode(&self.codec) {
+                            Ok(message) => message,
+                            Err(e) => UdfError::forward("mapstream", e).raise(),
+                        };
+                        if let Err(e) = tx.send(message).await {
+                            UdfError::forward("mapstream", e).raise();
                         }
-                    },
-                    Err(e) => {
-                        eprintln!(
-                            "[ERROR] User-defined map-stream function retured an error: {:?}",
-                            e
-                        );
-                        panic!("User-defined map-stream function retured an error: {:?}", e);
                     }
+                    Ok(None) => break,
+                    Err(e) => e.raise(),
                 }
-            },
-            Err(e) => {
-                eprintln!(
-                    "[ERROR] Error executing user-defined mapstream function: {:?}",
-                    e
-                );
-                panic!("Error executing user-defined mapstream function: {:?}", e);
             }
         }
+        .instrument(span)
+        .await
     }
 }
@@ -0,0 +1,361 @@
+use std::collections::{BTreeMap, HashMap};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+use numaflow::{map, sink, source, sourcetransform};
+
+/// Groups of key/value pairs, ordered by name so that they are listed the same way every time.
+type Groups = BTreeMap<String, BTreeMap<String, Vec<u8>>>;
+
+/// Read access to the metadata types of the numaflow modules, which all hold groups of key/value
+/// pairs.
+pub(crate) trait GroupedMetadata {
+    fn groups(&self) -> Vec<String>;
+    fn keys(&self, group: &str) -> Vec<String>;
+    fn value(&self, group: &str, key: &str) -> Vec<u8>;
+}
+
+/// User metadata types of the numaflow modules that the messages of a handler carry.
+pub(crate) trait GroupedMetadataMut: Default {
+    fn create_group(&mut self, group: String);
+    fn add_kv(&mut self, group: String, key: String, value: Vec<u8>);
+}
+
+macro_rules! grouped_metadata {
+    ($($metadata:ty),* $(,)?) => {$(
+        impl GroupedMetadata for $metadata {
+            fn groups(&self) -> Vec<String> {
+                <$metadata>::groups(self)
+            }
+
+            fn keys(&self, group: &str) -> Vec<String> {
+                <$metadata>::keys(self, group)
+            }
+
+            fn value(&self, group: &str, key: &str) -> Vec<u8> {
+                <$metadata>::value(self, group, key)
+            }
+        }
+    )*};
+}
+
+macro_rules! grouped_metadata_mut {
+    ($($metadata:ty),* $(,)?) => {$(
+        impl GroupedMetadataMut for $metadata {
+            fn create_group(&mut self, group: String) {
+                <$metadata>::create_group(self, group)
+            }
+
+            fn add_kv(&mut self, group: String, key: String, value: Vec<u8>) {
+                <$metadata>::add_kv(self, group, key, value)
+            }
+        }
+    )*};
+}
+
+grouped_metadata!(
+    map::UserMetadata,
+    map::SystemMetadata,
+    sink::UserMetadata,
+    sink::SystemMetadata,
+    source::UserMetadata,
+    sourcetransform::UserMetadata,
+    sourcetransform::SystemMetadata,
+);
+
+grouped_metadata_mut!(
+    map::UserMetadata,
+    source::UserMetadata,
+    sourcetransform::UserMetadata,
+);
+
+fn groups_of(metadata: &impl GroupedMetadata) -> Groups {
+    metadata
+        .groups()
+        .into_iter()
+        .map(|group| {
+            let kvs = metadata
+                .keys(&group)
+                .into_iter()
+                .map(|key| {
+                    let value = metadata.value(&group, &key);
+                    (key, value)
+                })
+                .collect();
+            (group, kvs)
+        })
+        .collect()
+}
+
+fn groups_of_record(record: HashMap<String, HashMap<String, Buffer>>) -> Groups {
+    record
+        .into_iter()
+        .map(|(group, kvs)| {
+            let kvs = kvs.into_iter().map(|(k, v)| (k, v.into())).collect();
+            (group, kvs)
+        })
+        .collect()
+}
+
+/// User metadata of a message, the groups of key/value pairs the JS layer sends, as the user
+/// metadata type of a numaflow module.
+pub(crate) fn from_record<T: GroupedMetadataMut>(
+    record: HashMap<String, HashMap<String, Buffer>>,
+) -> T {
+    UserMetadata(groups_of_record(record)).to_numaflow()
+}
+
+/// A key/value pair of a metadata group.
+#[napi(object, namespace = "metadata")]
+pub struct MetadataEntry {
+    pub group: String,
+    pub key: String,
+    pub value: Buffer,
+}
+
+fn keys(groups: &Groups, group: &str) -> Vec<String> {
+    groups
+        .get(group)
+        .map(|kvs| kvs.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn value<'a>(groups: &'a Groups, group: &str, key: &str) -> Option<&'a Vec<u8>> {
+    groups.get(group).and_then(|kvs| kvs.get(key))
+}
+
+fn entries(groups: &Groups) -> Vec<MetadataEntry> {
+    groups
+        .iter()
+        .flat_map(|(group, kvs)| {
+            kvs.iter().map(|(key, value)| MetadataEntry {
+                group: group.clone(),
+                key: key.clone(),
+                value: value.clone().into(),
+            })
+        })
+        .collect()
+}
+
+fn to_json(groups: &Groups) -> HashMap<String, HashMap<String, String>> {
+    groups
+        .iter()
+        .map(|(group, kvs)| {
+            let kvs = kvs
+                .iter()
+                .map(|(key, value)| (key.clone(), BASE64.encode(value)))
+                .collect();
+            (group.clone(), kvs)
+        })
+        .collect()
+}
+
+fn from_json(json: HashMap<String, HashMap<String, String>>) -> Result<Groups> {
+    json.into_iter()
+        .map(|(group, kvs)| {
+            let kvs = kvs
+                .into_iter()
+                .map(|(key, value)| match BASE64.decode(&value) {
+                    Ok(value) => Ok((key, value)),
+                    Err(e) => Err(Error::new(
+                        Status::InvalidArg,
+                        format!("Value of key {key} in group {group} is not valid base64: {e}"),
+                    )),
+                })
+                .collect::<Result<_>>()?;
+            Ok((group, kvs))
+        })
+        .collect()
+}
+
+/// User-defined metadata of a message, groups of key/value pairs that are passed on to the next
+/// vertex.
+#[derive(Clone, Debug, Default)]
+#[napi(namespace = "metadata")]
+pub struct UserMetadata(Groups);
+
+#[napi(namespace = "metadata")]
+impl UserMetadata {
+    /// Create metadata, optionally with the given groups of key/value pairs.
+    #[napi(constructor)]
+    pub fn new(groups: Option<HashMap<String, HashMap<String, Buffer>>>) -> Self {
+        Self(groups.map(groups_of_record).unwrap_or_default())
+    }
+
+    /// Create metadata from the output of `toJSON`, whose values are base64 encoded.
+    #[napi(factory, js_name = "fromJSON")]
+    pub fn from_json(json: HashMap<String, HashMap<String, String>>) -> Result<Self> {
+        from_json(json).map(Self)
+    }
+
+    #[napi]
+    pub fn get_groups(&self) -> Vec<String> {
+        self.0.keys().cloned().collect()
+    }
+
+    #[napi]
+    pub fn get_keys(&self, group: String) -> Vec<String> {
+        keys(&self.0, &group)
+    }
+
+    /// Value of the key in the group, empty if there is none.
+    #[napi]
+    pub fn get_value(&self, group: String, key: String) -> Buffer {
+        value(&self.0, &group, &key)
+            .cloned()
+            .unwrap_or_default()
+            .into()
+    }
+
+    /// Value of the key in the group decoded as UTF-8, null if there is none.
+    #[napi]
+    pub fn get_string(&self, group: String, key: String) -> Option<String> {
+        value(&self.0, &group, &key).map(|value| String::from_utf8_lossy(value).into_owned())
+    }
+
+    /// All key/value pairs, ordered by group and key.
+    #[napi]
+    pub fn entries(&self) -> Vec<MetadataEntry> {
+        entries(&self.0)
+    }
+
+    #[napi]
+    pub fn create_group(&mut self, group: String) {
+        self.0.entry(group).or_default();
+    }
+
+    #[napi]
+    pub fn add_kv(&mut self, group: String, key: String, value: Buffer) {
+        self.0.entry(group).or_default().insert(key, value.into());
+    }
+
+    /// Add a key/value pair whose value is the UTF-8 encoding of the string.
+    #[napi]
+    pub fn add_string(&mut self, group: String, key: String, value: String) {
+        self.0
+            .entry(group)
+            .or_default()
+            .insert(key, value.into_bytes());
+    }
+
+    #[napi]
+    pub fn remove_key(&mut self, group: String, key: String) {
+        if let Some(kvs) = self.0.get_mut(&group) {
+            kvs.remove(&key);
+        }
+    }
+
+    #[napi]
+    pub fn remove_group(&mut self, group: String) {
+        self.0.remove(&group);
+    }
+
+    /// Add the groups and key/value pairs of other metadata, its values replace the values of
+    /// the keys both have.
+    #[napi]
+    pub fn merge(&mut self, other: &UserMetadata) {
+        for (group, kvs) in &other.0 {
+            let entry = self.0.entry(group.clone()).or_default();
+            entry.extend(kvs.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+    }
+
+    /// A copy of the metadata with the key/value pair added.
+    #[napi]
+    pub fn clone_with(&self, group: String, key: String, value: Buffer) -> UserMetadata {
+        let mut metadata = self.clone();
+        metadata.add_kv(group, key, value);
+        metadata
+    }
+
+    /// The groups of key/value pairs with their values base64 encoded, used by `JSON.stringify`.
+    #[napi(js_name = "toJSON")]
+    pub fn to_json(&self) -> HashMap<String, HashMap<String, String>> {
+        to_json(&self.0)
+    }
+}
+
+impl UserMetadata {
+    /// The user metadata of a numaflow module as `UserMetadata`.
+    pub(crate) fn from_numaflow(metadata: &impl GroupedMetadata) -> Self {
+        Self(groups_of(metadata))
+    }
+
+    /// The metadata as the user metadata type of a numaflow module.
+    pub(crate) fn to_numaflow<T: GroupedMetadataMut>(&self) -> T {
+        let mut metadata = T::default();
+        for (group, kvs) in &self.0 {
+            metadata.create_group(group.clone());
+            for (key, value) in kvs {
+                metadata.add_kv(group.clone(), key.clone(), value.clone());
+            }
+        }
+        metadata
+    }
+
+    pub(crate) fn into_groups(self) -> impl Iterator<Item = (String, HashMap<String, Vec<u8>>)> {
+        self.0
+            .into_iter()
+            .map(|(group, kvs)| (group, kvs.into_iter().collect()))
+    }
+}
+
+/// Metadata numaflow attaches to a message, read-only to handlers.
+#[derive(Clone, Debug, Default)]
+#[napi(namespace = "metadata")]
+pub struct SystemMetadata(Groups);
+
+#[napi(namespace = "metadata")]
+impl SystemMetadata {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[napi]
+    pub fn get_groups(&self) -> Vec<String> {
+        self.0.keys().cloned().collect()
+    }
+
+    #[napi]
+    pub fn get_keys(&self, group: String) -> Vec<String> {
+        keys(&self.0, &group)
+    }
+
+    /// Value of the key in the group, empty if there is none.
+    #[napi]
+    pub fn get_value(&self, group: String, key: String) -> Buffer {
+        value(&self.0, &group, &key)
+            .cloned()
+            .unwrap_or_default()
+            .into()
+    }
+
+    /// Value of the key in the group decoded as UTF-8, null if there is none.
+    #[napi]
+    pub fn get_string(&self, group: String, key: String) -> Option<String> {
+        value(&self.0, &group, &key).map(|value| String::from_utf8_lossy(value).into_owned())
+    }
+
+    /// All key/value pairs, ordered by group and key.
+    #[napi]
+    pub fn entries(&self) -> Vec<MetadataEntry> {
+        entries(&self.0)
+    }
+
+    /// The groups of key/value pairs with their values base64 encoded, used by `JSON.stringify`.
+    #[napi(js_name = "toJSON")]
+    pub fn to_json(&self) -> HashMap<String, HashMap<String, String>> {
+        to_json(&self.0)
+    }
+}
+
+impl SystemMetadata {
+    /// The system metadata of a numaflow module as `SystemMetadata`.
+    pub(crate) fn from_numaflow(metadata: &impl GroupedMetadata) -> Self {
+        Self(groups_of(metadata))
+    }
+}
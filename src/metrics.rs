@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, LazyLock, Mutex, Weak};
+use std::time::Duration;
+
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use napi::{Error, Status};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use tokio::net::TcpListener;
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{error, info, warn};
+
+use crate::lifecycle::ServerOptions;
+
+const METRICS_PATH: &str = "/metrics";
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The metrics endpoints being served, by address.
+static ENDPOINTS: LazyLock<Mutex<HashMap<SocketAddr, Weak<Endpoint>>>> =
+    LazyLock::new(Default::default);
+
+/// Metrics of all the servers of the process, labelled by server or JS handler.
+struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    callback_duration: HistogramVec,
+    callback_rejections: IntCounterVec,
+    callback_timeouts: IntCounterVec,
+    messages_emitted: IntCounterVec,
+    messages_dropped: IntCounterVec,
+    decode_errors: IntCounterVec,
+    sink_responses: IntCounterVec,
+    queue_wait: HistogramVec,
+    queued_requests: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let counter = |name: &str, help: &str, label: &str| {
+            let counter = IntCounterVec::new(Opts::new(name, help), &[label])
+                .expect("metric options are valid");
+            registry
+                .register(Box::new(counter.clone()))
+                .expect("metric is registered once");
+            counter
+        };
+        let requests = counter(
+            "numaflow_js_requests_total",
+            "Requests handled by the server",
+            "server",
+        );
+        let callback_rejections = counter(
+            "numaflow_js_callback_rejections_total",
+            "Calls of the JS handler that threw or returned a rejected promise",
+            "handler",
+        );
+        let callback_timeouts = counter(
+            "numaflow_js_callback_timeouts_total",
+            "Calls of the JS handler that did not settle within the handler timeout",
+            "handler",
+        );
+        let messages_emitted = counter(
+            "numaflow_js_messages_emitted_total",
+            "Messages returned by the JS handler and forwarded to numaflow",
+            "server",
+        );
+        let messages_dropped = counter(
+            "numaflow_js_messages_dropped_total",
+            "Messages returned by the JS handler that are dropped",
+            "server",
+        );
+        let decode_errors = counter(
+            "numaflow_js_decode_errors_total",
+            "Datums whose value the codec of the server could not decode",
+            "server",
+        );
+        let sink_responses = counter(
+            "numaflow_js_sink_responses_total",
+            "Responses of the sink handler by response type",
+            "type",
+        );
+
+        let histogram = |name: &str, help: &str, label: &str| {
+            let histogram = HistogramVec::new(
+                HistogramOpts::new(name, help).buckets(
+                    prometheus::exponential_buckets(0.001, 2.0, 15).expect("buckets are valid"),
+                ),
+                &[label],
+            )
+            .expect("metric options are valid");
+            registry
+                .register(Box::new(histogram.clone()))
+                .expect("metric is registered once");
+            histogram
+        };
+        let callback_duration = histogram(
+            "numaflow_js_callback_duration_seconds",
+            "Time until the promise returned by the JS handler settled",
+            "handler",
+        );
+        let queue_wait = histogram(
+            "numaflow_js_queue_wait_seconds",
+            "Time requests waited for the concurrency limit before the JS handler was called",
+            "server",
+        );
+
+        let queued_requests = IntGaugeVec::new(
+            Opts::new(
+                "numaflow_js_queued_requests",
+                "Requests waiting for the concurrency limit",
+            ),
+            &["server"],
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(queued_requests.clone()))
+            .expect("metric is registered once");
+
+        Self {
+            registry,
+            requests,
+            callback_duration,
+            callback_rejections,
+            callback_timeouts,
+            messages_emitted,
+            messages_dropped,
+            decode_errors,
+            sink_responses,
+            queue_wait,
+            queued_requests,
+        }
+    }
+}
+
+/// Records a request the server finished handling.
+pub(crate) fn request_handled(server: &str) {
+    METRICS.requests.with_label_values(&[server]).inc();
+}
+
+/// Records a call of a JS handler that settled after `duration`.
+pub(crate) fn callback_settled(handler: &str, duration: Duration, rejected: bool) {
+    METRICS
+        .callback_duration
+        .with_label_values(&[handler])
+        .observe(duration.as_secs_f64());
+    if rejected {
+        METRICS
+            .callback_rejections
+            .with_label_values(&[handler])
+            .inc();
+    }
+}
+
+/// Records a call of a JS handler that was abandoned after the handler timeout.
+pub(crate) fn callback_timed_out(handler: &str) {
+    METRICS
+        .callback_timeouts
+        .with_label_values(&[handler])
+        .inc();
+}
+
+/// Records a message returned by a JS handler, messages tagged with `DROP` count as dropped.
+pub(crate) fn message_emitted(server: &str, tags: Option<&[String]>) {
+    let dropped = tags.is_some_and(|tags| tags.iter().any(|tag| tag == numaflow::shared::DROP));
+    let counter = if dropped {
+        &METRICS.messages_dropped
+    } else {
+        &METRICS.messages_emitted
+    };
+    counter.with_label_values(&[server]).inc();
+}
+
+/// Records a datum the server dropped without calling the JS handler, e.g. because the decode
+/// error policy drops the datums it can't decode.
+pub(crate) fn datum_dropped(server: &str) {
+    METRICS.messages_dropped.with_label_values(&[server]).inc();
+}
+
+/// Records a datum whose value the codec of the server could not decode.
+pub(crate) fn decode_failed(server: &str) {
+    METRICS.decode_errors.with_label_values(&[server]).inc();
+}
+
+/// Records a response of the sink handler.
+pub(crate) fn sink_response(response_type: &str) {
+    METRICS
+        .sink_responses
+        .with_label_values(&[response_type])
+        .inc();
+}
+
+/// Records a request that started waiting for the concurrency limit of the server.
+pub(crate) fn request_queued(server: &str) {
+    METRICS.queued_requests.with_label_values(&[server]).inc();
+}
+
+/// Records a request that waited `duration` for the concurrency limit of the server.
+pub(crate) fn request_dequeued(server: &str, duration: Duration) {
+    METRICS.queued_requests.with_label_values(&[server]).dec();
+    METRICS
+        .queue_wait
+        .with_label_values(&[server])
+        .observe(duration.as_secs_f64());
+}
+
+/// HTTP endpoint serving the metrics in the Prometheus text format. The servers of the process
+/// started with the same address and port share one endpoint, which is stopped once the last of
+/// them dropped it.
+pub(crate) struct MetricsServer {
+    _endpoint: Option<Arc<Endpoint>>,
+}
+
+impl MetricsServer {
+    /// Serves the metrics at the `metricsAddress` and `metricsPort` of the options, or not at all
+    /// when the port is not set. Fails when the address can't be bound, e.g. because the port is
+    /// taken, so that a server is never started without the endpoint it was configured with. The
+    /// server's `start()` holds the returned value until the server stopped.
+    pub(crate) fn start(options: &ServerOptions) -> napi::Result<Self> {
+        let Some(port) = options.metrics_port else {
+            return Ok(Self { _endpoint: None });
+        };
+        let ip = match &options.metrics_address {
+            Some(address) => address.parse::<IpAddr>().map_err(|e| {
+                Error::new(
+                    Status::InvalidArg,
+                    format!("Invalid metricsAddress {address:?}: {e}"),
+                )
+            })?,
+            None => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        };
+        let addr = SocketAddr::new(ip, port);
+        let mut endpoints = ENDPOINTS.lock().unwrap();
+        if let Some(endpoint) = endpoints.get(&addr).and_then(Weak::upgrade) {
+            return Ok(Self {
+                _endpoint: Some(endpoint),
+            });
+        }
+        let listener = bind(addr).map_err(|e| {
+            Error::new(
+                Status::GenericFailure,
+                format!("Failed to serve metrics at {addr}: {e}"),
+            )
+        })?;
+        info!("Serving metrics at http://{addr}{METRICS_PATH}");
+        let endpoint = Arc::new(Endpoint(tokio::spawn(serve(listener))));
+        endpoints.insert(addr, Arc::downgrade(&endpoint));
+        Ok(Self {
+            _endpoint: Some(endpoint),
+        })
+    }
+}
+
+struct Endpoint(JoinHandle<()>);
+
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Binds the listener of an endpoint while the endpoints are locked, binding doesn't wait on the
+/// network.
+fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+    let listener = std::net::TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener)
+}
+
+async fn serve(listener: TcpListener) {
+    // Connections are aborted together with the endpoint, kept-alive ones would outlive it.
+    let mut connections = JoinSet::new();
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!(error = %e, "Failed to accept a metrics connection");
+                continue;
+            }
+        };
+        while connections.try_join_next().is_some() {}
+        connections.spawn(async move {
+            let connection = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service_fn(handle))
+                .await;
+            if let Err(e) = connection {
+                warn!(error = %e, "Failed to serve a metrics request");
+            }
+        });
+    }
+}
+
+async fn handle(request: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+    if request.method() != Method::GET || request.uri().path() != METRICS_PATH {
+        let mut response = Response::new(Full::default());
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return Ok(response);
+    }
+
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(e) = encoder.encode(&METRICS.registry.gather(), &mut body) {
+        error!(error = %e, "Failed to encode the metrics");
+        let mut response = Response::new(Full::default());
+        *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        return Ok(response);
+    }
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        encoder
+            .format_type()
+            .parse()
+            .expect("content type is a valid header"),
+    );
+    Ok(response)
+}
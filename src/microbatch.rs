@@ -0,0 +1,147 @@
+use std::future::Future;
+use std::time::Duration;
+
+use napi_derive::napi;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+const DEFAULT_MAX_BATCH_SIZE: u32 = 64;
+const DEFAULT_MAX_BATCH_DELAY_MS: u32 = 5;
+
+/// Options for coalescing concurrently arriving requests into one call of the JS handler.
+#[napi(object, namespace = "map")]
+#[derive(Clone, Debug, Default)]
+pub struct MicroBatchOptions {
+    /// Most requests handed to one call of the handler. Defaults to 64.
+    pub max_batch_size: Option<u32>,
+    /// Longest time in milliseconds the first request of a batch waits for more requests before
+    /// the batch is handed to the handler. Defaults to 5.
+    pub max_batch_delay_ms: Option<u32>,
+}
+
+/// A resolved [`MicroBatchOptions`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct MicroBatch {
+    max_batch_size: usize,
+    max_batch_delay: Duration,
+}
+
+impl From<MicroBatchOptions> for MicroBatch {
+    fn from(value: MicroBatchOptions) -> Self {
+        Self {
+            max_batch_size: value
+                .max_batch_size
+                .unwrap_or(DEFAULT_MAX_BATCH_SIZE)
+                .max(1) as usize,
+            max_batch_delay: Duration::from_millis(
+                value
+                    .max_batch_delay_ms
+                    .unwrap_or(DEFAULT_MAX_BATCH_DELAY_MS) as u64,
+            ),
+        }
+    }
+}
+
+impl MicroBatch {
+    /// Receives the next batch of items from `rx`: waits for the first item, then for more until
+    /// the batch is full or the first item waited for the maximum delay. Empty once `rx` closed.
+    pub(crate) async fn recv<T>(&self, rx: &mut impl Receive<T>) -> Vec<T> {
+        let Some(first) = rx.recv().await else {
+            return Vec::new();
+        };
+        let deadline = Instant::now() + self.max_batch_delay;
+        let mut batch = vec![first];
+        while batch.len() < self.max_batch_size {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(item)) => batch.push(item),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        batch
+    }
+}
+
+/// A channel a [`MicroBatch`] receives its items from.
+pub(crate) trait Receive<T> {
+    async fn recv(&mut self) -> Option<T>;
+}
+
+impl<T> Receive<T> for mpsc::Receiver<T> {
+    async fn recv(&mut self) -> Option<T> {
+        mpsc::Receiver::recv(self).await
+    }
+}
+
+impl<T> Receive<T> for mpsc::UnboundedReceiver<T> {
+    async fn recv(&mut self) -> Option<T> {
+        mpsc::UnboundedReceiver::recv(self).await
+    }
+}
+
+impl From<Option<MicroBatchOptions>> for MicroBatch {
+    fn from(value: Option<MicroBatchOptions>) -> Self {
+        value.unwrap_or_default().into()
+    }
+}
+
+/// Coalesces the items submitted concurrently into batches, which are handled by one call of
+/// `handle` each. A batch is handled once it is full or its first item waited for the maximum
+/// delay, while the next batch is collected.
+pub(crate) struct MicroBatcher<T, R> {
+    tx: mpsc::UnboundedSender<(T, oneshot::Sender<R>)>,
+}
+
+impl<T, R> MicroBatcher<T, R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+{
+    /// Starts collecting batches, until the batcher is dropped. `handle` returns one result per
+    /// item of the batch, in the same order.
+    pub(crate) fn new<F, Fut>(options: MicroBatch, handle: F) -> Self
+    where
+        F: Fn(Vec<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Vec<R>> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(collect(options, rx, handle));
+        Self { tx }
+    }
+
+    /// Adds `item` to the batch being collected and waits for its result.
+    pub(crate) async fn submit(&self, item: T) -> R {
+        let (result_tx, result_rx) = oneshot::channel();
+        self.tx
+            .send((item, result_tx))
+            .unwrap_or_else(|_| panic!("The micro-batches are no longer collected"));
+        result_rx
+            .await
+            .expect("The micro-batch was dropped before it was handled")
+    }
+}
+
+async fn collect<T, R, F, Fut>(
+    options: MicroBatch,
+    mut rx: mpsc::UnboundedReceiver<(T, oneshot::Sender<R>)>,
+    handle: F,
+) where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(Vec<T>) -> Fut,
+    Fut: Future<Output = Vec<R>> + Send + 'static,
+{
+    loop {
+        let batch = options.recv(&mut rx).await;
+        if batch.is_empty() {
+            break;
+        }
+        let (items, senders): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+        let results = handle(items);
+        tokio::spawn(async move {
+            for (sender, result) in senders.into_iter().zip(results.await) {
+                // The request was abandoned if its receiver is gone.
+                let _ = sender.send(result);
+            }
+        });
+    }
+}
@@ -7,6 +7,16 @@ use numaflow::reduce;
 use numaflow::shared::ServerExtras;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use tracing::{Instrument, info};
+
+use crate::config;
+use crate::error::{self, UdfError};
+use crate::factory::{FactoryFn, Handler};
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metrics::{self, MetricsServer};
+use crate::microbatch::{MicroBatch, MicroBatchOptions};
+use crate::trace::{self, TraceContext};
+use crate::vertex;
 
 #[derive(Default)]
 #[napi(object, namespace = "reduce")]
@@ -46,6 +56,14 @@ pub struct Datum {
     pub watermark: DateTime<Utc>,
     pub event_time: DateTime<Utc>,
     pub headers: HashMap<String, String>,
+    /// Context of the span the datum is handled in, if its headers carry a `traceparent`.
+    pub trace_context: Option<TraceContext>,
+    /// Whether the watermark had reached the end of the window when the datum arrived, i.e.
+    /// the datum arrived after the window would have closed without allowed lateness.
+    pub late: bool,
+    /// How far the watermark was past the end of the window when the datum arrived, 0 if the
+    /// datum is not late.
+    pub lateness_ms: i64,
 }
 
 impl From<Datum> for reduce::ReduceRequest {
@@ -60,14 +78,19 @@ impl From<Datum> for reduce::ReduceRequest {
     }
 }
 
-impl From<reduce::ReduceRequest> for Datum {
-    fn from(value: reduce::ReduceRequest) -> Self {
+impl Datum {
+    /// A datum of the window ending at `window_end`.
+    fn new(value: reduce::ReduceRequest, window_end: DateTime<Utc>) -> Self {
+        let lateness_ms = (value.watermark - window_end).num_milliseconds();
         Datum {
             keys: value.keys,
             value: value.value.into(),
             watermark: value.watermark,
             event_time: value.eventtime,
+            trace_context: TraceContext::child_of(&value.headers),
             headers: value.headers,
+            late: value.watermark >= window_end,
+            lateness_ms: lateness_ms.max(0),
         }
     }
 }
@@ -92,15 +115,39 @@ impl From<reduce::IntervalWindow> for IntervalWindow {
 }
 
 /// Metadata passed to reducer handler
+///
+/// The window of the vertex is read from the vertex spec numaflow sets in
+/// `NUMAFLOW_VERTEX_OBJECT`, its identity from `NUMAFLOW_PIPELINE_NAME`, `NUMAFLOW_VERTEX_NAME`
+/// and `NUMAFLOW_REPLICA`. Fields whose variable is not set are left unset.
 #[derive(Clone)]
 #[napi(object, namespace = "reduce")]
 pub struct Metadata {
     pub interval_window: IntervalWindow,
+    /// Kind of the window of the vertex.
+    #[napi(ts_type = "'fixed' | 'sliding'")]
+    pub window_kind: Option<String>,
+    /// Length of the windows of the vertex in milliseconds.
+    pub window_length_ms: Option<i64>,
+    /// Time between the starts of two consecutive windows in milliseconds, the window length for
+    /// fixed windows.
+    pub slide_ms: Option<i64>,
+    pub pipeline_name: Option<String>,
+    pub vertex_name: Option<String>,
+    pub replica: Option<u32>,
 }
 
 impl Metadata {
     pub(crate) fn new(interval_window: IntervalWindow) -> Self {
-        Self { interval_window }
+        let vertex = vertex::get();
+        Self {
+            interval_window,
+            window_kind: vertex.window.map(|window| window.kind().to_string()),
+            window_length_ms: vertex.window.map(|window| window.length_ms()),
+            slide_ms: vertex.window.map(|window| window.slide_ms()),
+            pipeline_name: vertex.pipeline_name.clone(),
+            vertex_name: vertex.vertex_name.clone(),
+            replica: vertex.replica,
+        }
     }
 }
 
@@ -110,9 +157,14 @@ impl From<reduce::Metadata> for Metadata {
     }
 }
 
+/// The watermark of the latest datum of a window, shared by its iterator and callback args.
+type LatestWatermark = Arc<Mutex<Option<DateTime<Utc>>>>;
+
 #[napi(namespace = "reduce")]
 pub struct ReduceDatumIterator {
     source: tokio::sync::mpsc::Receiver<reduce::ReduceRequest>,
+    window_end: DateTime<Utc>,
+    watermark: LatestWatermark,
 }
 
 #[napi(object, namespace = "reduce")]
@@ -124,8 +176,16 @@ pub struct ReduceDatumIteratorResult {
 #[napi(namespace = "reduce")]
 impl ReduceDatumIterator {
     /// Internal constructor - not exposed to JavaScript
-    pub(crate) fn new(source: tokio::sync::mpsc::Receiver<reduce::ReduceRequest>) -> Self {
-        Self { source }
+    fn new(
+        source: tokio::sync::mpsc::Receiver<reduce::ReduceRequest>,
+        window_end: DateTime<Utc>,
+        watermark: LatestWatermark,
+    ) -> Self {
+        Self {
+            source,
+            window_end,
+            watermark,
+        }
     }
 
     /// Returns the next datum from the stream, or None if the stream has ended
@@ -135,12 +195,33 @@ impl ReduceDatumIterator {
     // by the Node.js runtime. You cannot ensure that the self is only owned by Rust.
     #[napi(namespace = "reduce")]
     pub async unsafe fn next(&mut self) -> ReduceDatumIteratorResult {
-        let value = self.source.recv().await.map(Datum::from);
+        let value = self
+            .source
+            .recv()
+            .await
+            .map(|request| self.to_datum(request));
         let done = value.is_none();
         ReduceDatumIteratorResult { value, done }
     }
 }
 
+impl ReduceDatumIterator {
+    /// Returns the next micro-batch of datums, empty once the stream has ended.
+    async fn next_batch(&mut self, micro_batch: MicroBatch) -> Vec<Datum> {
+        let requests = micro_batch.recv(&mut self.source).await;
+        requests
+            .into_iter()
+            .map(|request| self.to_datum(request))
+            .collect()
+    }
+
+    fn to_datum(&self, request: reduce::ReduceRequest) -> Datum {
+        let mut watermark = self.watermark.lock().unwrap();
+        *watermark = (*watermark).max(Some(request.watermark));
+        Datum::new(request, self.window_end)
+    }
+}
+
 /// Arguments passed to the reduce callback
 /// Only to be used as part of internal implementation, not to be exposed to final users
 #[napi(namespace = "reduce")]
@@ -148,19 +229,26 @@ pub struct ReduceCallbackArgs {
     keys: Vec<String>,
     iterator: Option<ReduceDatumIterator>,
     metadata: Metadata,
+    watermark: LatestWatermark,
 }
 
 #[napi(namespace = "reduce")]
 impl ReduceCallbackArgs {
+    /// Arguments of the window of `metadata` whose datums are received from `source`.
     pub(crate) fn new(
         keys: Vec<String>,
-        iterator: ReduceDatumIterator,
-        metadata: Metadata,
+        source: tokio::sync::mpsc::Receiver<reduce::ReduceRequest>,
+        metadata: &reduce::Metadata,
     ) -> Self {
+        let metadata: Metadata = metadata.clone().into();
+        let watermark = LatestWatermark::default();
+        let iterator =
+            ReduceDatumIterator::new(source, metadata.interval_window.end, watermark.clone());
         Self {
             keys,
             iterator: Some(iterator),
             metadata,
+            watermark,
         }
     }
 
@@ -178,6 +266,13 @@ impl ReduceCallbackArgs {
     pub fn get_metadata(&self) -> Metadata {
         self.metadata.clone()
     }
+
+    /// Watermark of the latest datum of the window, not set before the first datum. The reduce
+    /// protocol does not tell the watermark the window closed at.
+    #[napi(getter)]
+    pub fn get_latest_watermark(&self) -> Option<DateTime<Utc>> {
+        *self.watermark.lock().unwrap()
+    }
 }
 
 type ReduceFn = ThreadsafeFunction<
@@ -189,10 +284,44 @@ type ReduceFn = ThreadsafeFunction<
     true,
 >;
 
+type CreateAggregatorFn = ThreadsafeFunction<
+    ReduceCallbackArgs,
+    WindowAggregator,
+    ReduceCallbackArgs,
+    Status,
+    false,
+    true,
+>;
+
+type AddInputsFn = ThreadsafeFunction<Vec<Datum>, (), Vec<Datum>, Status, false, true>;
+
+type ExtractOutputFn = ThreadsafeFunction<(), Promise<Vec<Message>>, (), Status, false, true>;
+
+/// The aggregator of one window, created by the `createAggregator` of the server.
+#[napi(object, object_to_js = false, namespace = "reduce")]
+pub struct WindowAggregator {
+    /// Adds a micro-batch of datums of the window to its accumulator.
+    #[napi(ts_type = "(datums: Array<Datum>) => void")]
+    pub add_inputs: AddInputsFn,
+    /// Returns the messages of the window once all its datums were added.
+    #[napi(ts_type = "() => Promise<Array<Message>>")]
+    pub extract_output: ExtractOutputFn,
+}
+
+/// The JS handler of a reduce server.
+#[derive(Clone)]
+enum ReduceHandler {
+    /// Called once per window with an iterator of its datums.
+    Iterator(Handler<ReduceFn>),
+    /// Creates an aggregator per window, which is handed the datums of the window in
+    /// micro-batches.
+    Aggregator(Arc<CreateAggregatorFn>, MicroBatch),
+}
+
 #[napi(namespace = "reduce")]
 pub struct ReduceAsyncServer {
-    reduce_fn: Arc<ReduceFn>,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    handler: ReduceHandler,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "reduce")]
@@ -204,59 +333,81 @@ impl ReduceAsyncServer {
     )]
     pub fn new(reduce_fn: ReduceFn) -> napi::Result<Self> {
         Ok(Self {
-            reduce_fn: Arc::new(reduce_fn),
-            shutdown_tx: Mutex::new(None),
+            handler: ReduceHandler::Iterator(Handler::Shared(Arc::new(reduce_fn))),
+            lifecycle: Lifecycle::new("reduce"),
         })
     }
 
-    /// Start the ReduceAsyncServer with the given callback
-    #[napi]
-    pub async fn start(
-        &self,
-        socket_path: Option<String>,
-        server_info_path: Option<String>,
-    ) -> napi::Result<()> {
-        let reducer_creator = ReducerCreator::new(self.reduce_fn.clone());
-        let mut server = reduce::Server::new(reducer_creator);
-        if let Some(sock_file) = socket_path {
-            server = server.with_socket_file(sock_file.clone());
-        }
-        if let Some(info_file) = server_info_path {
-            server = server.with_server_info_file(info_file);
+    /// A server that calls `createReduceFn` once per window and reduces the window with the
+    /// function it returned, which is released once the window is reduced.
+    #[napi(
+        factory,
+        ts_args_type = "createReduceFn: () => (iterator: ReduceCallbackArgs) => Promise<Array<Message>>"
+    )]
+    pub fn with_factory(create_reduce_fn: FactoryFn<ReduceFn>) -> Self {
+        Self {
+            handler: ReduceHandler::Iterator(Handler::Factory(Arc::new(create_reduce_fn))),
+            lifecycle: Lifecycle::new("reduce"),
         }
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        {
-            self.shutdown_tx.lock().unwrap().replace(tx);
+    }
+
+    /// A server that calls `createAggregator` once per window and drains the datums of the window
+    /// itself, handing them to the `addInputs` of the aggregator in micro-batches. Once the datums
+    /// end, `extractOutput` returns the messages of the window.
+    #[napi(
+        factory,
+        ts_args_type = "createAggregator: (args: ReduceCallbackArgs) => WindowAggregator, microBatch?: map.MicroBatchOptions | undefined | null"
+    )]
+    pub fn with_aggregator(
+        create_aggregator: CreateAggregatorFn,
+        micro_batch: Option<MicroBatchOptions>,
+    ) -> Self {
+        Self {
+            handler: ReduceHandler::Aggregator(Arc::new(create_aggregator), micro_batch.into()),
+            lifecycle: Lifecycle::new("reduce"),
         }
-        println!("Starting Reduce server at {:?}", server.socket_file());
-        server.start_with_shutdown(rx).await.map_err(|e| {
-            Error::new(
-                Status::GenericFailure,
-                format!("Error running ReduceAsyncServer: {e:?}"),
-            )
-        })?;
-        println!("ReduceAsyncServer has shutdown...");
+    }
+
+    /// Start the ReduceAsyncServer with the given callback
+    #[napi]
+    pub async fn start(&self, options: Option<ServerOptions>) -> napi::Result<()> {
+        let options = config::resolve(options)?;
+        let reducer_creator = ReducerCreator::new(self.handler.clone(), self.lifecycle.in_flight());
+        let server = options.configure(reduce::Server::new(reducer_creator));
+        info!(
+            component = "reduce",
+            socket_file = ?server.socket_file(),
+            "Starting ReduceAsyncServer"
+        );
+        let _metrics_server = MetricsServer::start(&options)?;
+        self.lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    Status::GenericFailure,
+                    format!("Error running ReduceAsyncServer: {e:?}"),
+                )
+            })?;
+        info!(component = "reduce", "ReduceAsyncServer has shut down");
         Ok(())
     }
 
     /// Stop the reduce server
     #[napi]
-    pub fn stop(&self) -> napi::Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
-        }
-        Ok(())
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
     }
 }
 
 struct ReducerCreator {
-    reduce_fn: Arc<ReduceFn>,
+    handler: ReduceHandler,
+    in_flight: InFlight,
 }
 
 impl ReducerCreator {
-    fn new(reduce_fn: Arc<ReduceFn>) -> Self {
-        Self { reduce_fn }
+    fn new(handler: ReduceHandler, in_flight: InFlight) -> Self {
+        Self { handler, in_flight }
     }
 }
 
@@ -265,17 +416,38 @@ impl reduce::ReducerCreator for ReducerCreator {
     type R = Reducer;
 
     fn create(&self) -> Self::R {
-        Reducer::new(self.reduce_fn.clone())
+        Reducer::new(self.handler.clone(), self.in_flight.clone())
     }
 }
 
 struct Reducer {
-    reduce_fn: Arc<ReduceFn>,
+    handler: ReduceHandler,
+    in_flight: InFlight,
 }
 
 impl Reducer {
-    fn new(reduce_fn: Arc<ReduceFn>) -> Self {
-        Self { reduce_fn }
+    fn new(handler: ReduceHandler, in_flight: InFlight) -> Self {
+        Self { handler, in_flight }
+    }
+
+    /// Hands the datums of the window to the aggregator created for it, collecting the next
+    /// micro-batch while the aggregator adds the previous one.
+    async fn aggregate(
+        create_aggregator: &CreateAggregatorFn,
+        micro_batch: MicroBatch,
+        mut args: ReduceCallbackArgs,
+    ) -> Result<Vec<Message>, UdfError> {
+        let mut datums = args.take_iterator();
+        let aggregator = error::call("reduce aggregator", create_aggregator, args).await?;
+        let mut batch = datums.next_batch(micro_batch).await;
+        while !batch.is_empty() {
+            let ((), next) = tokio::try_join!(
+                error::call("reduce addInputs", &aggregator.add_inputs, batch),
+                async { Ok(datums.next_batch(micro_batch).await) },
+            )?;
+            batch = next;
+        }
+        error::call_promise("reduce extractOutput", &aggregator.extract_output, ()).await
     }
 }
 
@@ -287,31 +459,32 @@ impl reduce::Reducer for Reducer {
         input: tokio::sync::mpsc::Receiver<reduce::ReduceRequest>,
         md: &reduce::Metadata,
     ) -> Vec<reduce::Message> {
-        let request_iterator = ReduceDatumIterator::new(input);
-        // Call the JavaScript callback
-        match self
-            .reduce_fn
-            .call_async(ReduceCallbackArgs::new(
-                keys,
-                request_iterator,
-                md.clone().into(),
-            ))
-            .await
-        {
-            Ok(promise) => match promise.await {
-                Ok(responses) => responses.into_iter().map(|m| m.into()).collect(),
-                Err(e) => {
-                    eprintln!(
-                        "[ERROR] User-defined reduce function returned an error: {:?}",
-                        e
-                    );
-                    panic!("User-defined reduce function returned an error: {:?}", e);
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("reduce", None);
+        async {
+            // Call the JavaScript callback
+            let args = ReduceCallbackArgs::new(keys, input, md);
+            let responses = match &self.handler {
+                ReduceHandler::Iterator(handler) => match handler.create("reduce factory").await {
+                    Ok(reduce_fn) => error::call_promise("reduce", &reduce_fn, args).await,
+                    Err(e) => Err(e),
+                },
+                ReduceHandler::Aggregator(create_aggregator, micro_batch) => {
+                    Self::aggregate(create_aggregator, *micro_batch, args).await
                 }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Executing user-defined reduce function: {:?}", e);
-                panic!("Error executing user-defined reduce function: {:?}", e);
+            };
+            match responses {
+                Ok(responses) => responses
+                    .into_iter()
+                    .map(|m| {
+                        metrics::message_emitted("reduce", m.tags.as_deref());
+                        m.into()
+                    })
+                    .collect(),
+                Err(e) => e.raise(),
             }
         }
+        .instrument(span)
+        .await
     }
 }
@@ -1,4 +1,10 @@
-use crate::reduce::{Message, ReduceCallbackArgs, ReduceDatumIterator};
+use crate::config;
+use crate::error::{self, UdfError};
+use crate::factory::{FactoryFn, Handler};
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metrics::{self, MetricsServer};
+use crate::reduce::{Message, ReduceCallbackArgs};
+use crate::trace;
 use napi::bindgen_prelude::Promise;
 use napi::threadsafe_function::ThreadsafeFunction;
 use napi::{Error, Status};
@@ -6,8 +12,9 @@ use napi_derive::napi;
 use numaflow::reducestream::ReduceStreamRequest;
 use numaflow::shared::ServerExtras;
 use numaflow::{reduce, reducestream};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::{Instrument, info};
 
 type AsyncIteratorFn = ThreadsafeFunction<(), Promise<Option<Message>>, (), Status, false, true>;
 
@@ -22,8 +29,8 @@ type ReduceStreamFn = ThreadsafeFunction<
 
 #[napi(namespace = "reduceStream")]
 pub struct ReduceStreamAsyncServer {
-    reduce_stream_fn: Arc<ReduceStreamFn>,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    handler: Handler<ReduceStreamFn>,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "reduceStream")]
@@ -35,62 +42,68 @@ impl ReduceStreamAsyncServer {
     )]
     pub fn new(reduce_stream_fn: ReduceStreamFn) -> napi::Result<Self> {
         Ok(Self {
-            reduce_stream_fn: Arc::new(reduce_stream_fn),
-            shutdown_tx: Mutex::new(None),
+            handler: Handler::Shared(Arc::new(reduce_stream_fn)),
+            lifecycle: Lifecycle::new("reduceStream"),
         })
     }
 
+    /// A server that calls `createReduceStreamFn` once per window and reduces the window with the
+    /// function it returned, which is released once the window is reduced.
+    #[napi(
+        factory,
+        ts_args_type = "createReduceStreamFn: () => (iterator: ReduceCallbackArgs) => () => Promise<Message | null>"
+    )]
+    pub fn with_factory(create_reduce_stream_fn: FactoryFn<ReduceStreamFn>) -> Self {
+        Self {
+            handler: Handler::Factory(Arc::new(create_reduce_stream_fn)),
+            lifecycle: Lifecycle::new("reduceStream"),
+        }
+    }
+
     /// Start the ReduceStreamAsyncServer with the given callback
     #[napi]
-    pub async fn start(
-        &self,
-        socket_path: Option<String>,
-        server_info_path: Option<String>,
-    ) -> napi::Result<()> {
-        let reducer_creator = ReduceStreamerCreator::new(self.reduce_stream_fn.clone());
-        let mut server = reducestream::Server::new(reducer_creator);
-        if let Some(sock_file) = socket_path {
-            server = server.with_socket_file(sock_file.clone());
-        }
-        if let Some(info_file) = server_info_path {
-            server = server.with_server_info_file(info_file);
-        }
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        {
-            self.shutdown_tx.lock().unwrap().replace(tx);
-        }
-        println!(
-            "Starting Reduce stream server at {:?}",
-            server.socket_file()
+    pub async fn start(&self, options: Option<ServerOptions>) -> napi::Result<()> {
+        let options = config::resolve(options)?;
+        let reducer_creator =
+            ReduceStreamerCreator::new(self.handler.clone(), self.lifecycle.in_flight());
+        let server = options.configure(reducestream::Server::new(reducer_creator));
+        info!(
+            component = "reduceStream",
+            socket_file = ?server.socket_file(),
+            "Starting ReduceStreamAsyncServer"
+        );
+        let _metrics_server = MetricsServer::start(&options)?;
+        self.lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    Status::GenericFailure,
+                    format!("Error running ReduceAsyncServer: {e:?}"),
+                )
+            })?;
+        info!(
+            component = "reduceStream",
+            "ReduceStreamAsyncServer has shut down"
         );
-        server.start_with_shutdown(rx).await.map_err(|e| {
-            Error::new(
-                Status::GenericFailure,
-                format!("Error running ReduceAsyncServer: {e:?}"),
-            )
-        })?;
-        println!("ReduceAsyncServer has shutdown...");
         Ok(())
     }
 
     /// Stop the reduce stream server
     #[napi]
-    pub fn stop(&self) -> napi::Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
-        }
-        Ok(())
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
     }
 }
 
 struct ReduceStreamerCreator {
-    reduce_stream_fn: Arc<ReduceStreamFn>,
+    handler: Handler<ReduceStreamFn>,
+    in_flight: InFlight,
 }
 
 impl ReduceStreamerCreator {
-    fn new(reduce_stream_fn: Arc<ReduceStreamFn>) -> Self {
-        Self { reduce_stream_fn }
+    fn new(handler: Handler<ReduceStreamFn>, in_flight: InFlight) -> Self {
+        Self { handler, in_flight }
     }
 }
 
@@ -99,17 +112,18 @@ impl reducestream::ReduceStreamerCreator for ReduceStreamerCreator {
     type R = ReduceStreamer;
 
     fn create(&self) -> Self::R {
-        ReduceStreamer::new(self.reduce_stream_fn.clone())
+        ReduceStreamer::new(self.handler.clone(), self.in_flight.clone())
     }
 }
 
 struct ReduceStreamer {
-    reduce_stream_fn: Arc<ReduceStreamFn>,
+    handler: Handler<ReduceStreamFn>,
+    in_flight: InFlight,
 }
 
 impl ReduceStreamer {
-    fn new(reduce_stream_fn: Arc<ReduceStreamFn>) -> Self {
-        Self { reduce_stream_fn }
+    fn new(handler: Handler<ReduceStreamFn>, in_flight: InFlight) -> Self {
+        Self { handler, in_flight }
     }
 }
 
@@ -122,66 +136,34 @@ impl reducestream::ReduceStreamer for ReduceStreamer {
         output: Sender<reduce::Message>,
         md: &reduce::Metadata,
     ) {
-        let request_iterator = ReduceDatumIterator::new(input);
-        // Call the JavaScript callback
-        match self
-            .reduce_stream_fn
-            .call_async(ReduceCallbackArgs::new(
-                keys,
-                request_iterator,
-                md.clone().into(),
-            ))
-            .await
-        {
-            Ok(async_iterator_fn) => loop {
-                match async_iterator_fn.call_async(()).await {
-                    Ok(promise) => match promise.await {
-                        Ok(Some(message)) => {
-                            if let Err(e) = output.send(message.into()).await {
-                                eprintln!(
-                                    "[ERROR] Failed to send reduce-stream message to grpc client: {:?}",
-                                    e
-                                );
-                                panic!(
-                                    "Failed to send reduce-stream message to grpc client: {:?}",
-                                    e
-                                );
-                            }
-                        }
-                        Ok(None) => break,
-                        Err(e) => {
-                            eprintln!(
-                                "[ERROR] Error executing iterator returned by user-defined reduce-stream function: {:?}",
-                                e
-                            );
-                            panic!(
-                                "Error executing iterator returned by user-defined reduce-stream function: {:?}",
-                                e
-                            );
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("reduceStream", None);
+        async {
+            let reduce_stream_fn = match self.handler.create("reduceStream factory").await {
+                Ok(reduce_stream_fn) => reduce_stream_fn,
+                Err(e) => e.raise(),
+            };
+            // Call the JavaScript callback
+            let args = ReduceCallbackArgs::new(keys, input, md);
+            let async_iterator_fn = match error::call("reduceStream", &reduce_stream_fn, args).await
+            {
+                Ok(async_iterator_fn) => async_iterator_fn,
+                Err(e) => e.raise(),
+            };
+            loop {
+                match error::call_promise("reduceStream iterator", &async_iterator_fn, ()).await {
+                    Ok(Some(message)) => {
+                        metrics::message_emitted("reduceStream", message.tags.as_deref());
+                        if let Err(e) = output.send(message.into()).await {
+                            UdfError::forward("reduceStream", e).raise();
                         }
-                    },
-                    Err(e) => {
-                        eprintln!(
-                            "[ERROR] User-defined reduce-stream function returned an error: {:?}",
-                            e
-                        );
-                        panic!(
-                            "User-defined reduce-stream function returned an error: {:?}",
-                            e
-                        );
                     }
+                    Ok(None) => break,
+                    Err(e) => e.raise(),
                 }
-            },
-            Err(e) => {
-                eprintln!(
-                    "[ERROR] Executing user-defined reduce-stream function: {:?}",
-                    e
-                );
-                panic!(
-                    "Error executing user-defined reduce-stream function: {:?}",
-                    e
-                );
             }
         }
+        .instrument(span)
+        .await
     }
 }
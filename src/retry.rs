@@ -0,0 +1,294 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use napi::{Error, Status};
+use napi_derive::napi;
+use tokio::sync::mpsc::Receiver;
+use tracing::warn;
+
+use crate::error::UdfError;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_INITIAL_BACKOFF_MS: u32 = 100;
+const DEFAULT_MAX_BACKOFF_MS: u32 = 10_000;
+const DEFAULT_MULTIPLIER: f64 = 2.0;
+const DEFAULT_JITTER: f64 = 0.2;
+
+/// Retry policy for transient failures of a user-defined function.
+///
+/// Only errors thrown by the JS handler are retried, the request is failed once the attempts are
+/// exhausted.
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. Defaults to 3.
+    pub max_attempts: Option<u32>,
+    /// Delay before the first retry in milliseconds. Defaults to 100.
+    pub initial_backoff_ms: Option<u32>,
+    /// Upper bound of the delay between two attempts in milliseconds. Defaults to 10000.
+    pub max_backoff_ms: Option<u32>,
+    /// Factor the delay grows by after every retry. Defaults to 2.
+    pub multiplier: Option<f64>,
+    /// Fraction of the delay, between 0 and 1, that is randomized. Defaults to 0.2.
+    pub jitter: Option<f64>,
+    /// Names of the JS errors that are retried, e.g. `['FetchError']`. Every error is retried when
+    /// not set.
+    pub retry_on: Option<Vec<String>>,
+}
+
+/// A resolved [`RetryPolicy`]. The default makes a single attempt.
+#[derive(Clone, Debug)]
+pub(crate) struct Retry {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+    retry_on: Option<Vec<String>>,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            multiplier: 1.0,
+            jitter: 0.0,
+            retry_on: None,
+        }
+    }
+}
+
+impl TryFrom<RetryPolicy> for Retry {
+    type Error = Error;
+
+    fn try_from(value: RetryPolicy) -> napi::Result<Self> {
+        let multiplier =
+            non_negative("multiplier", value.multiplier.unwrap_or(DEFAULT_MULTIPLIER))?;
+        let jitter = non_negative("jitter", value.jitter.unwrap_or(DEFAULT_JITTER))?;
+        Ok(Self {
+            max_attempts: value.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS).max(1),
+            initial_backoff: Duration::from_millis(
+                value
+                    .initial_backoff_ms
+                    .unwrap_or(DEFAULT_INITIAL_BACKOFF_MS) as u64,
+            ),
+            max_backoff: Duration::from_millis(
+                value.max_backoff_ms.unwrap_or(DEFAULT_MAX_BACKOFF_MS) as u64,
+            ),
+            multiplier: multiplier.max(1.0),
+            jitter: jitter.min(1.0),
+            retry_on: value.retry_on,
+        })
+    }
+}
+
+impl TryFrom<Option<RetryPolicy>> for Retry {
+    type Error = Error;
+
+    fn try_from(value: Option<RetryPolicy>) -> napi::Result<Self> {
+        value.map_or_else(|| Ok(Retry::default()), Retry::try_from)
+    }
+}
+
+/// Checks a factor of a retry policy, which JS may pass as NaN, an infinity or a negative number.
+fn non_negative(name: &str, value: f64) -> napi::Result<f64> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(Error::new(
+            Status::InvalidArg,
+            format!("Invalid retry policy {name} {value}: expected a finite number of at least 0"),
+        ))
+    }
+}
+
+impl Retry {
+    /// Whether the retries are enabled at all, streaming handlers only record their input if so.
+    pub(crate) fn enabled(&self) -> bool {
+        self.max_attempts > 1
+    }
+
+    fn should_retry(&self, error: &UdfError) -> bool {
+        match (error.js_error(), &self.retry_on) {
+            (Some(_), None) => true,
+            (Some(js_error), Some(names)) => names.contains(&js_error.name),
+            (None, _) => false,
+        }
+    }
+
+    fn backoff(&self, retry: u32) -> Duration {
+        // In seconds as f64, which saturates to infinity where a `Duration` would overflow.
+        let backoff = self.initial_backoff.as_secs_f64()
+            * self.multiplier.powf(f64::from(retry.saturating_sub(1)));
+        // Spread the delay over [1 - jitter, 1 + jitter] so that failing replicas don't retry in lockstep.
+        let backoff = backoff * (1.0 + self.jitter * (2.0 * fastrand::f64() - 1.0));
+        // `min` also replaces the NaN of a zero initial backoff times an infinite growth.
+        Duration::try_from_secs_f64(backoff.min(self.max_backoff.as_secs_f64()))
+            .unwrap_or(self.max_backoff)
+    }
+
+    /// Runs `attempt` until it succeeds, fails with an error that is not retried or runs out of attempts.
+    ///
+    /// Every attempt gets its own copy of `args`, the last possible attempt takes the original.
+    pub(crate) async fn run<A, T, F, Fut>(&self, args: A, mut attempt: F) -> Result<T, UdfError>
+    where
+        A: Clone,
+        F: FnMut(A) -> Fut,
+        Fut: Future<Output = Result<T, UdfError>>,
+    {
+        let mut args = Some(args);
+        let mut attempts = 1;
+        loop {
+            let current = if attempts < self.max_attempts {
+                args.clone()
+            } else {
+                args.take()
+            };
+            let current = current.expect("arguments are only taken by the last attempt");
+            match attempt(current).await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempts < self.max_attempts && self.should_retry(&e) => {
+                    let backoff = self.backoff(attempts);
+                    warn!(
+                        handler = e.handler,
+                        attempt = attempts,
+                        max_attempts = self.max_attempts,
+                        ?backoff,
+                        "{e}, retrying"
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempts += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Request stream of a streaming handler (sink, batchmap) that can be read again from the start
+/// when the handler is retried or timed out. Requests of type `S` are handed out converted to `T`.
+pub(crate) struct ReplayStream<S, T> {
+    inner: Arc<tokio::sync::Mutex<ReplayInner<S, T>>>,
+    position: usize,
+}
+
+struct ReplayInner<S, T> {
+    source: Receiver<S>,
+    /// Items read so far, only kept when they may be replayed.
+    history: Option<Vec<T>>,
+}
+
+impl<S, T: Clone + From<S>> ReplayStream<S, T> {
+    /// The items are only kept for another read when `replay` is set.
+    pub(crate) fn new(source: Receiver<S>, replay: bool) -> Self {
+        Self {
+            inner: Arc::new(tokio::sync::Mutex::new(ReplayInner {
+                source,
+                history: replay.then(Vec::new),
+            })),
+            position: 0,
+        }
+    }
+
+    pub(crate) async fn next(&mut self) -> Option<T> {
+        let mut inner = self.inner.lock().await;
+        if let Some(item) = inner
+            .history
+            .as_ref()
+            .and_then(|history| history.get(self.position))
+        {
+            self.position += 1;
+            return Some(item.clone());
+        }
+        let item = T::from(inner.source.recv().await?);
+        if let Some(history) = inner.history.as_mut() {
+            history.push(item.clone());
+        }
+        self.position += 1;
+        Some(item)
+    }
+}
+
+/// A clone is a new reader of the same stream that starts over at the first item.
+impl<S, T> Clone for ReplayStream<S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            position: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retry(policy: RetryPolicy) -> Retry {
+        Retry::try_from(policy).expect("valid retry policy")
+    }
+
+    #[test]
+    fn grows_the_backoff_up_to_the_maximum() {
+        let retry = retry(RetryPolicy {
+            initial_backoff_ms: Some(100),
+            max_backoff_ms: Some(1_000),
+            jitter: Some(0.0),
+            ..Default::default()
+        });
+        assert_eq!(retry.backoff(1), Duration::from_millis(100));
+        assert_eq!(retry.backoff(3), Duration::from_millis(400));
+        assert_eq!(retry.backoff(5), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn caps_overflowing_backoffs() {
+        let retry = retry(RetryPolicy {
+            max_attempts: Some(u32::MAX),
+            initial_backoff_ms: Some(u32::MAX),
+            max_backoff_ms: Some(u32::MAX),
+            multiplier: Some(f64::MAX),
+            jitter: Some(1.0),
+            ..Default::default()
+        });
+        for attempt in [2, 1_000, u32::MAX - 1] {
+            assert!(retry.backoff(attempt) <= Duration::from_millis(u32::MAX as u64));
+        }
+        let retry = Retry {
+            initial_backoff: Duration::ZERO,
+            ..retry
+        };
+        assert_eq!(retry.backoff(1_000), Duration::from_millis(u32::MAX as u64));
+    }
+
+    #[test]
+    fn keeps_the_jittered_backoff_within_the_maximum() {
+        let retry = retry(RetryPolicy {
+            initial_backoff_ms: Some(1_000),
+            max_backoff_ms: Some(1_000),
+            jitter: Some(1.0),
+            ..Default::default()
+        });
+        for _ in 0..100 {
+            assert!(retry.backoff(1) <= Duration::from_millis(1_000));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_factors() {
+        for value in [f64::NAN, f64::INFINITY, -1.0] {
+            let multiplier = RetryPolicy {
+                multiplier: Some(value),
+                ..Default::default()
+            };
+            assert!(Retry::try_from(multiplier).is_err(), "multiplier {value}");
+            let jitter = RetryPolicy {
+                jitter: Some(value),
+                ..Default::default()
+            };
+            assert!(Retry::try_from(jitter).is_err(), "jitter {value}");
+        }
+    }
+}
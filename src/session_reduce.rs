@@ -6,8 +6,18 @@ use napi_derive::napi;
 use numaflow::session_reduce;
 use numaflow::shared::ServerExtras;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::sync::OnceCell;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::{Instrument, info};
+
+use crate::config;
+use crate::error::{self, UdfError};
+use crate::factory::{FactoryFn, Handler};
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metrics::{self, MetricsServer};
+use crate::trace::{self, TraceContext};
 
 #[derive(Default)]
 #[napi(object, namespace = "sessionReduce")]
@@ -47,6 +57,8 @@ pub struct Datum {
     pub watermark: DateTime<Utc>,
     pub event_time: DateTime<Utc>,
     pub headers: HashMap<String, String>,
+    /// Context of the span the datum is handled in, if its headers carry a `traceparent`.
+    pub trace_context: Option<TraceContext>,
 }
 
 impl Datum {
@@ -62,6 +74,7 @@ impl Datum {
             value: value.into(),
             watermark,
             event_time,
+            trace_context: TraceContext::child_of(&headers),
             headers,
         }
     }
@@ -118,17 +131,30 @@ pub struct SessionReduceDatumIteratorResult {
 pub struct SessionReduceCallbackArgs {
     keys: Vec<String>,
     iterator: Option<SessionReduceDatumIterator>,
+    merged_sessions: Arc<AtomicU32>,
 }
 
 #[napi(namespace = "sessionReduce")]
 impl SessionReduceCallbackArgs {
-    pub(crate) fn new(keys: Vec<String>, iterator: SessionReduceDatumIterator) -> Self {
+    pub(crate) fn new(
+        keys: Vec<String>,
+        iterator: SessionReduceDatumIterator,
+        merged_sessions: Arc<AtomicU32>,
+    ) -> Self {
         Self {
             keys,
             iterator: Some(iterator),
+            merged_sessions,
         }
     }
 
+    /// Number of sessions merged into this one so far. The sessions are merged before the first
+    /// datum of the session is received or its datums end.
+    #[napi(getter)]
+    pub fn get_merged_sessions(&self) -> u32 {
+        self.merged_sessions.load(Ordering::Relaxed)
+    }
+
     #[napi(getter)]
     pub fn get_keys(&self) -> Vec<String> {
         self.keys.clone()
@@ -155,12 +181,22 @@ type AccumulatorFn = ThreadsafeFunction<(), Promise<Buffer>, (), Status, false,
 
 type MergeAccumulatorFn = ThreadsafeFunction<Buffer, Promise<()>, Buffer, Status, false, true>;
 
+/// The handlers of one session, bound to the state of that session.
+/// Only to be used as part of internal implementation, not to be exposed to final users
+#[napi(object, object_to_js = false, namespace = "sessionReduce")]
+pub struct SessionHandlers {
+    #[napi(ts_type = "(args: SessionReduceCallbackArgs) => () => Promise<Message | null>")]
+    pub session_reduce_fn: SessionReduceFn,
+    #[napi(ts_type = "() => Promise<Buffer>")]
+    pub accumulator_fn: AccumulatorFn,
+    #[napi(ts_type = "(accumulator: Buffer) => Promise<void>")]
+    pub merge_accumulator_fn: MergeAccumulatorFn,
+}
+
 #[napi(namespace = "sessionReduce")]
 pub struct SessionReduceAsyncServer {
-    session_reduce_fn: Arc<SessionReduceFn>,
-    accumulator_fn: Arc<AccumulatorFn>,
-    merge_accumulator_fn: Arc<MergeAccumulatorFn>,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    handler: Handler<SessionHandlers>,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "sessionReduce")]
@@ -178,76 +214,67 @@ impl SessionReduceAsyncServer {
         merge_accumulator_fn: MergeAccumulatorFn,
     ) -> napi::Result<Self> {
         Ok(Self {
-            session_reduce_fn: Arc::new(session_reduce_fn),
-            accumulator_fn: Arc::new(accumulator_fn),
-            merge_accumulator_fn: Arc::new(merge_accumulator_fn),
-            shutdown_tx: Mutex::new(None),
+            handler: Handler::Shared(Arc::new(SessionHandlers {
+                session_reduce_fn,
+                accumulator_fn,
+                merge_accumulator_fn,
+            })),
+            lifecycle: Lifecycle::new("sessionReduce"),
         })
     }
 
-    #[napi]
-    pub fn stop(&self) -> napi::Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
+    /// A server that calls `createHandlers` once per session, the first time the session is
+    /// reduced or merged into, and calls the handlers it returned for that session only.
+    #[napi(factory, ts_args_type = "createHandlers: () => SessionHandlers")]
+    pub fn with_factory(create_handlers: FactoryFn<SessionHandlers>) -> Self {
+        Self {
+            handler: Handler::Factory(Arc::new(create_handlers)),
+            lifecycle: Lifecycle::new("sessionReduce"),
         }
-        Ok(())
     }
 
     #[napi]
-    pub async fn start(
-        &self,
-        sock_file: Option<String>,
-        info_file: Option<String>,
-    ) -> napi::Result<()> {
-        let session_reducer = SessionReduceCreator::new(
-            self.session_reduce_fn.clone(),
-            self.accumulator_fn.clone(),
-            self.merge_accumulator_fn.clone(),
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
+    }
+
+    #[napi]
+    pub async fn start(&self, options: Option<ServerOptions>) -> napi::Result<()> {
+        let options = config::resolve(options)?;
+        let session_reducer =
+            SessionReduceCreator::new(self.handler.clone(), self.lifecycle.in_flight());
+        let server = options.configure(session_reduce::Server::new(session_reducer));
+        info!(
+            component = "sessionReduce",
+            socket_file = ?server.socket_file(),
+            "Starting SessionReduceAsyncServer"
         );
-        let mut server = session_reduce::Server::new(session_reducer);
-        if let Some(sock_file) = sock_file {
-            server = server.with_socket_file(sock_file.clone());
-        }
-        if let Some(info_file) = info_file {
-            server = server.with_server_info_file(info_file);
-        }
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        {
-            self.shutdown_tx.lock().unwrap().replace(tx);
-        }
-        println!(
-            "Starting session reduce server at {:?}",
-            server.socket_file()
+        let _metrics_server = MetricsServer::start(&options)?;
+        self.lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    Status::GenericFailure,
+                    format!("Error running SessionReduceAsyncServer: {e:?}"),
+                )
+            })?;
+        info!(
+            component = "sessionReduce",
+            "SessionReduceAsyncServer has shut down"
         );
-        server.start_with_shutdown(rx).await.map_err(|e| {
-            Error::new(
-                Status::GenericFailure,
-                format!("Error running SessionReduceAsyncServer: {e:?}"),
-            )
-        })?;
-        println!("SessionReduceAsyncServer has shutdown...");
         Ok(())
     }
 }
 
 struct SessionReduceCreator {
-    session_reduce_fn: Arc<SessionReduceFn>,
-    accumulator_fn: Arc<AccumulatorFn>,
-    merge_accumulator_fn: Arc<MergeAccumulatorFn>,
+    handler: Handler<SessionHandlers>,
+    in_flight: InFlight,
 }
 
 impl SessionReduceCreator {
-    fn new(
-        session_reduce_fn: Arc<SessionReduceFn>,
-        accumulator_fn: Arc<AccumulatorFn>,
-        merge_accumulator_fn: Arc<MergeAccumulatorFn>,
-    ) -> Self {
-        Self {
-            session_reduce_fn,
-            accumulator_fn,
-            merge_accumulator_fn,
-        }
+    fn new(handler: Handler<SessionHandlers>, in_flight: InFlight) -> Self {
+        Self { handler, in_flight }
     }
 }
 
@@ -256,32 +283,42 @@ impl session_reduce::SessionReducerCreator for SessionReduceCreator {
     type R = SessionReducer;
 
     fn create(&self) -> Self::R {
-        SessionReducer::new(
-            self.session_reduce_fn.clone(),
-            self.accumulator_fn.clone(),
-            self.merge_accumulator_fn.clone(),
-        )
+        SessionReducer::new(self.handler.clone(), self.in_flight.clone())
     }
 }
 
 struct SessionReducer {
-    session_reduce_fn: Arc<SessionReduceFn>,
-    accumulator_fn: Arc<AccumulatorFn>,
-    merge_accumulator_fn: Arc<MergeAccumulatorFn>,
+    handler: Handler<SessionHandlers>,
+    /// The handlers of this session, created on first use: the factory is a JS function, which
+    /// can't be called from `create`.
+    session_handlers: OnceCell<Arc<SessionHandlers>>,
+    /// Number of accumulators merged into this session.
+    merged_sessions: Arc<AtomicU32>,
+    in_flight: InFlight,
 }
 
 impl SessionReducer {
-    fn new(
-        session_reduce_fn: Arc<SessionReduceFn>,
-        accumulator_fn: Arc<AccumulatorFn>,
-        merge_accumulator_fn: Arc<MergeAccumulatorFn>,
-    ) -> Self {
+    fn new(handler: Handler<SessionHandlers>, in_flight: InFlight) -> Self {
         Self {
-            session_reduce_fn,
-            accumulator_fn,
-            merge_accumulator_fn,
+            handler,
+            session_handlers: OnceCell::new(),
+            merged_sessions: Arc::default(),
+            in_flight,
         }
     }
+
+    /// The handlers of this session. A session that is merged into is handed the accumulators
+    /// while it is being reduced, both get the same handlers.
+    async fn handlers(&self) -> &SessionHandlers {
+        self.session_handlers
+            .get_or_init(|| async {
+                match self.handler.create("sessionReduce factory").await {
+                    Ok(handlers) => handlers,
+                    Err(e) => e.raise(),
+                }
+            })
+            .await
+    }
 }
 
 #[async_trait::async_trait]
@@ -292,90 +329,68 @@ impl session_reduce::SessionReducer for SessionReducer {
         request_stream: Receiver<session_reduce::SessionReduceRequest>,
         response_stream: Sender<session_reduce::Message>,
     ) {
-        let requests = SessionReduceDatumIterator::new(request_stream);
-        match self
-            .session_reduce_fn
-            .call_async(SessionReduceCallbackArgs::new(keys, requests))
-            .await
-        {
-            Ok(messages_fn) => loop {
-                match messages_fn.call_async(()).await {
-                    Ok(promise) => match promise.await {
-                        Ok(Some(message)) => {
-                            if let Err(e) = response_stream.send(message.into()).await {
-                                eprintln!(
-                                    "[ERROR] Failed to send session reduce message to grpc client: {:?}",
-                                    e
-                                );
-                                panic!(
-                                    "Failed to send session reduce message to grpc client: {:?}",
-                                    e
-                                );
-                            }
-                        }
-                        Ok(None) => break,
-                        Err(e) => {
-                            eprintln!(
-                                "[ERROR] Error executing iterator returned by user-defined session reduce function: {:?}",
-                                e
-                            );
-                            panic!(
-                                "Error executing iterator returned by user-defined session reduce function: {:?}",
-                                e
-                            );
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("sessionReduce", None);
+        async {
+            let handlers = self.handlers().await;
+            let requests = SessionReduceDatumIterator::new(request_stream);
+            let args =
+                SessionReduceCallbackArgs::new(keys, requests, Arc::clone(&self.merged_sessions));
+            let messages_fn =
+                match error::call("sessionReduce", &handlers.session_reduce_fn, args).await {
+                    Ok(messages_fn) => messages_fn,
+                    Err(e) => e.raise(),
+                };
+            loop {
+                match error::call_promise("sessionReduce iterator", &messages_fn, ()).await {
+                    Ok(Some(message)) => {
+                        metrics::message_emitted("sessionReduce", message.tags.as_deref());
+                        if let Err(e) = response_stream.send(message.into()).await {
+                            UdfError::forward("sessionReduce", e).raise();
                         }
-                    },
-                    Err(e) => {
-                        eprintln!(
-                            "[ERROR] User-defined session reduce function returned an error: {:?}",
-                            e
-                        );
-                        panic!(
-                            "User-defined session reduce function returned an error: {:?}",
-                            e
-                        );
                     }
+                    Ok(None) => break,
+                    Err(e) => e.raise(),
                 }
-            },
-            Err(e) => {
-                eprintln!(
-                    "[ERROR] Failed to call user-defined session reduce function: {:?}",
-                    e
-                );
-                panic!(
-                    "Failed to call user-defined session reduce function: {:?}",
-                    e
-                );
             }
         }
+        .instrument(span)
+        .await
     }
 
     async fn accumulator(&self) -> Vec<u8> {
-        match self.accumulator_fn.call_async(()).await {
-            Ok(promise) => promise
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("sessionReduce accumulator", None);
+        async {
+            let handlers = self.handlers().await;
+            match error::call_promise("sessionReduce accumulator", &handlers.accumulator_fn, ())
                 .await
-                .map(|buffer| buffer.into())
-                .unwrap_or_else(|e| {
-                    eprintln!("[ERROR] Unable to unwrap response from session reduce's accumulator method: {:?}", e);
-                    panic!("Unable to unwrap response from session reduce's accumulator method: {:?}", e);
-                }),
-            Err(e) => {
-                eprintln!("[ERROR] Failed to call user-defined session reduce's accumulator method: {:?}", e);
-                panic!("Failed to call user-defined session reduce's accumulator method: {:?}", e);
+            {
+                Ok(buffer) => buffer.into(),
+                Err(e) => e.raise(),
             }
         }
+        .instrument(span)
+        .await
     }
 
     async fn merge_accumulator(&self, accumulator: Vec<u8>) {
-        match self.merge_accumulator_fn.call_async(accumulator.into()).await {
-            Ok(promise) => promise.await.unwrap_or_else(|e| {
-                eprintln!("[ERROR] Unable to unwrap response from session reduce's merge accumulator method: {:?}", e);
-                panic!("Unable to unwrap response from session reduce's merge accumulator method: {:?}", e);
-            }),
-            Err(e) => {
-                eprintln!("[ERROR] Failed to call user-defined session reduce's merge accumulator method: {:?}", e);
-                panic!("Failed to call user-defined session reduce's merge accumulator method: {:?}", e);
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("sessionReduce merge accumulator", None);
+        async {
+            let handlers = self.handlers().await;
+            if let Err(e) = error::call_promise(
+                "sessionReduce merge accumulator",
+                &handlers.merge_accumulator_fn,
+                accumulator.into(),
+            )
+            .await
+            {
+                e.raise();
             }
+            self.merged_sessions.fetch_add(1, Ordering::Relaxed);
         }
+        .instrument(span)
+        .await
     }
 }
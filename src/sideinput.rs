@@ -3,7 +3,14 @@ use napi::bindgen_prelude::{Buffer, Promise};
 use napi::threadsafe_function::ThreadsafeFunction;
 use napi_derive::napi;
 use numaflow::sideinput;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use tracing::{Instrument, error, info};
+
+use crate::config;
+use crate::error;
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metrics::MetricsServer;
+use crate::trace;
 
 type SideInputFn = ThreadsafeFunction<(), Promise<Option<Buffer>>, (), Status, false, true>;
 
@@ -13,7 +20,7 @@ pub const DIR_PATH: &str = sideinput::DIR_PATH;
 #[napi(namespace = "sideInput")]
 pub struct SideInputAsyncServer {
     side_input_fn: Arc<SideInputFn>,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "sideInput")]
@@ -25,71 +32,80 @@ impl SideInputAsyncServer {
     pub fn new(side_input_fn: Arc<SideInputFn>) -> Self {
         Self {
             side_input_fn,
-            shutdown_tx: Mutex::new(None),
+            lifecycle: Lifecycle::new("sideInput"),
         }
     }
 
     #[napi]
-    pub async fn start(
-        &self,
-        sock_file: Option<String>,
-        info_file: Option<String>,
-    ) -> napi::Result<()> {
-        let side_inputer = SideInputer::new(Arc::clone(&self.side_input_fn));
+    pub async fn start(&self, options: Option<ServerOptions>) -> napi::Result<()> {
+        let options = config::resolve(options)?;
+        let side_inputer =
+            SideInputer::new(Arc::clone(&self.side_input_fn), self.lifecycle.in_flight());
 
+        // The side input server is not a `ServerExtras`, so the options are applied one by one.
         let mut server = sideinput::Server::new(side_inputer);
-        if let Some(sock_file) = sock_file {
-            server = server.with_socket_file(sock_file);
+        if let Some(socket_path) = &options.socket_path {
+            server = server.with_socket_file(socket_path);
+        }
+        if let Some(server_info_path) = &options.server_info_path {
+            server = server.with_server_info_file(server_info_path);
         }
-        if let Some(info_file) = info_file {
-            server = server.with_server_info_file(info_file);
+        if let Some(max_message_size) = options.max_message_size {
+            server = server.with_max_message_size(max_message_size as usize);
         }
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        self.shutdown_tx.lock().unwrap().replace(tx);
-        if let Err(e) = server.start_with_shutdown(rx).await {
-            println!("Error running SideInputAsyncServer: {e:?}");
+        let _metrics_server = MetricsServer::start(&options)?;
+        if let Err(e) = self
+            .lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+        {
+            error!(component = "sideInput", error = ?e, "Error running SideInputAsyncServer");
         }
-        println!("SideInputAsyncServer has shutdown...");
+        info!(
+            component = "sideInput",
+            "SideInputAsyncServer has shut down"
+        );
         Ok(())
     }
 
     #[napi]
-    pub fn stop(&self) -> napi::Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
-        }
-        Ok(())
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
     }
 }
 
 struct SideInputer {
     side_input_fn: Arc<SideInputFn>,
+    in_flight: InFlight,
 }
 
 impl SideInputer {
-    fn new(side_input_fn: Arc<SideInputFn>) -> Self {
-        Self { side_input_fn }
+    fn new(side_input_fn: Arc<SideInputFn>, in_flight: InFlight) -> Self {
+        Self {
+            side_input_fn,
+            in_flight,
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl sideinput::SideInputer for SideInputer {
     async fn retrieve_sideinput(&self) -> Option<Vec<u8>> {
-        match self.side_input_fn.call_async(()).await {
-            Ok(promise) => match promise.await {
-                Ok(Some(buffer)) => Some(buffer.into()),
-                Ok(None) => None,
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("sideInput", None);
+        async {
+            // A failed retrieval is skipped rather than raised, the manager retries on its next
+            // tick.
+            match error::call_promise("sideInput", &self.side_input_fn, ()).await {
+                Ok(buffer) => buffer.map(|buffer| buffer.into()),
                 Err(e) => {
-                    eprintln!("Error awaiting for side input buffer: {:?}", e);
+                    error!(component = "sideInput", "{e}");
                     None
                 }
-            },
-            Err(e) => {
-                eprintln!("Error calling JS side input function: {:?}", e);
-                None
             }
         }
+        .instrument(span)
+        .await
     }
 }
@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use napi::bindgen_prelude::Buffer;
+use napi::threadsafe_function::ThreadsafeFunction;
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+use numaflow::sideinput;
+use tokio::io::unix::AsyncFd;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::codec::{Codec, Decoded};
+use crate::error;
+use crate::lifecycle::ServerOptions;
+
+type ChangeFn = ThreadsafeFunction<SideInputChange, (), SideInputChange, Status, false, true>;
+
+/// Events that (re)write or remove a side input file. Files are only read once they are closed
+/// or moved into the directory, so a value is never read half written.
+const WATCH_MASK: u32 =
+    libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_DELETE | libc::IN_MOVED_FROM;
+
+/// Options of a `SideInputReader`.
+#[napi(object, namespace = "sideInput")]
+#[derive(Clone, Debug, Default)]
+pub struct SideInputReaderOptions {
+    /// Directory the side inputs are mounted in, one file per side input. Defaults to `DIR_PATH`.
+    pub dir_path: Option<String>,
+    /// Codec the values are decoded with into `data`: `json`, `msgpack`, `cbor` or `raw`. Defaults
+    /// to `raw`, values are only handed over as bytes.
+    pub codec: Option<String>,
+}
+
+/// The latest value of a side input.
+#[napi(object, namespace = "sideInput")]
+pub struct SideInputValue {
+    /// Name of the side input, the name of its file.
+    pub name: String,
+    /// The content of the file.
+    pub value: Buffer,
+    /// The value decoded by the codec of the reader, null with the raw codec or an empty value.
+    pub data: Option<serde_json::Value>,
+    /// Why the value could not be decoded by the codec of the reader.
+    pub decode_error: Option<String>,
+    /// When the reader read the value.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A side input that was updated or removed, passed to the callbacks registered with `onChange()`.
+#[napi(object, namespace = "sideInput")]
+pub struct SideInputChange {
+    /// Name of the side input.
+    pub name: String,
+    /// The new value, null when the side input was removed.
+    pub value: Option<SideInputValue>,
+}
+
+/// A side input as cached by the reader.
+#[derive(Clone)]
+struct Entry {
+    value: Arc<Vec<u8>>,
+    data: Option<serde_json::Value>,
+    decode_error: Option<String>,
+    updated_at: DateTime<Utc>,
+}
+
+impl Entry {
+    fn to_value(&self, name: &str) -> SideInputValue {
+        SideInputValue {
+            name: name.to_string(),
+            value: self.value.to_vec().into(),
+            data: self.data.clone(),
+            decode_error: self.decode_error.clone(),
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+/// Reads the side inputs numaflow mounts into the containers of the UDFs of a pipeline vertex.
+///
+/// `start()` reads every side input in the directory and watches it with inotify, caching the
+/// latest value of each side input by name. Handlers read the cache with `get()`, and callbacks
+/// registered with `onChange()` are called whenever a side input is updated or removed.
+///
+/// Files whose name starts with a dot or ends in `.tmp` are ignored, so that side inputs written
+/// to a temporary file and moved into place are only read once complete.
+#[napi(namespace = "sideInput")]
+pub struct SideInputReader {
+    side_inputs: Arc<SideInputs>,
+    watcher: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[napi(namespace = "sideInput")]
+impl SideInputReader {
+    #[napi(constructor)]
+    pub fn new(options: Option<SideInputReaderOptions>) -> Result<Self> {
+        let options = options.unwrap_or_default();
+        // Undecodable side inputs are kept with their error, there is no request to fail.
+        let codec = Codec::new(
+            "sideInput",
+            &ServerOptions {
+                codec: options.codec,
+                decode_error_policy: Some("pass".to_string()),
+                ..Default::default()
+            },
+        )?;
+        Ok(Self {
+            side_inputs: Arc::new(SideInputs {
+                dir: options
+                    .dir_path
+                    .unwrap_or_else(|| sideinput::DIR_PATH.to_string())
+                    .into(),
+                codec,
+                entries: Mutex::default(),
+                listeners: Mutex::default(),
+            }),
+            watcher: Mutex::new(None),
+        })
+    }
+
+    /// Reads the side inputs in the directory and starts watching it for changes, until `stop()`
+    /// is called. Rejects if the directory can't be watched or read.
+    #[napi]
+    pub async fn start(&self) -> Result<()> {
+        if self.watcher.lock().unwrap().is_some() {
+            return Err(Error::from_reason(
+                "The side input reader is already started",
+            ));
+        }
+        let side_inputs = Arc::clone(&self.side_inputs);
+        let dir = &side_inputs.dir;
+        // The directory is watched before it is read, an update in between is read twice rather
+        // than missed.
+        let inotify = Inotify::watch(dir)
+            .map_err(|e| Error::from_reason(format!("Failed to watch {}: {e}", dir.display())))?;
+        let names = list(dir)
+            .await
+            .map_err(|e| Error::from_reason(format!("Failed to read {}: {e}", dir.display())))?;
+        for name in names {
+            side_inputs.reload(&name).await;
+        }
+        info!(
+            component = "sideInput",
+            dir = %dir.display(),
+            "Watching the side inputs"
+        );
+
+        let watcher = tokio::spawn(async move { side_inputs.watch(inotify).await });
+        self.watcher.lock().unwrap().replace(watcher);
+        Ok(())
+    }
+
+    /// Stops watching the directory, the cached side inputs can still be read.
+    #[napi]
+    pub fn stop(&self) {
+        if let Some(watcher) = self.watcher.lock().unwrap().take() {
+            watcher.abort();
+        }
+    }
+
+    /// The latest value of the side input named `name`, null if there is none.
+    #[napi]
+    pub fn get(&self, name: String) -> Option<SideInputValue> {
+        let entries = self.side_inputs.entries.lock().unwrap();
+        entries.get(&name).map(|entry| entry.to_value(&name))
+    }
+
+    /// Names of the side inputs read, sorted.
+    #[napi]
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .side_inputs
+            .entries
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Calls `callback` whenever a side input is updated or removed. Errors it throws are logged.
+    #[napi(ts_args_type = "callback: (change: SideInputChange) => void")]
+    pub fn on_change(&self, callback: Arc<ChangeFn>) {
+        self.side_inputs.listeners.lock().unwrap().push(callback);
+    }
+}
+
+impl Drop for SideInputReader {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The side inputs read so far and the callbacks to notify of their changes.
+struct SideInputs {
+    dir: PathBuf,
+    codec: Codec,
+    entries: Mutex<HashMap<String, Entry>>,
+    listeners: Mutex<Vec<Arc<ChangeFn>>>,
+}
+
+impl SideInputs {
+    async fn watch(&self, inotify: Inotify) {
+        loop {
+            match inotify.read().await {
+                Ok(Events::Names(names)) => {
+                    for name in names {
+                        self.reload(&name).await;
+                    }
+                }
+                // Events were lost, every side input is read again.
+                Ok(Events::Overflow) => {
+                    warn!(
+                        component = "sideInput",
+                        "Side input events were dropped, reading every side input again"
+                    );
+                    let mut names = list(&self.dir).await.unwrap_or_default();
+                    names.extend(self.entries.lock().unwrap().keys().cloned());
+                    names.sort();
+                    names.dedup();
+                    for name in names {
+                        self.reload(&name).await;
+                    }
+                }
+                Err(e) => {
+                    error!(component = "sideInput", error = %e, "Failed to watch the side inputs");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Reads the side input named `name` again, notifying the listeners if it changed.
+    async fn reload(&self, name: &str) {
+        if is_ignored(name) {
+            return;
+        }
+        let path = self.dir.join(name);
+        let value = match tokio::fs::read(&path).await {
+            Ok(value) => Some(value),
+            // A directory is not a side input.
+            Err(e) if e.kind() == io::ErrorKind::NotFound || path.is_dir() => None,
+            Err(e) => {
+                error!(component = "sideInput", name, error = %e, "Failed to read the side input");
+                return;
+            }
+        };
+
+        let entry = {
+            let mut entries = self.entries.lock().unwrap();
+            match value {
+                Some(value)
+                    if entries
+                        .get(name)
+                        .is_some_and(|entry| entry.value.as_slice() == value.as_slice()) =>
+                {
+                    return;
+                }
+                Some(value) => {
+                    let (data, decode_error) = match self.codec.decode(&value) {
+                        Decoded::Datum { data, error } => (data, error),
+                        Decoded::Drop | Decoded::Fail(_) => unreachable!("decode errors pass"),
+                    };
+                    let entry = Entry {
+                        value: Arc::new(value),
+                        data,
+                        decode_error,
+                        updated_at: Utc::now(),
+                    };
+                    entries.insert(name.to_string(), entry.clone());
+                    Some(entry)
+                }
+                None => match entries.remove(name) {
+                    Some(_) => None,
+                    None => return,
+                },
+            }
+        };
+        info!(
+            component = "sideInput",
+            name,
+            removed = entry.is_none(),
+            "Side input changed"
+        );
+
+        let listeners = self.listeners.lock().unwrap().clone();
+        for listener in listeners {
+            let change = SideInputChange {
+                name: name.to_string(),
+                value: entry.as_ref().map(|entry| entry.to_value(name)),
+            };
+            // Awaited one by one, so the callbacks see the changes in order.
+            if let Err(e) = error::call("sideInput change", &listener, change).await {
+                error!(component = "sideInput", "{e}");
+            }
+        }
+    }
+}
+
+fn is_ignored(name: &str) -> bool {
+    name.starts_with('.') || name.ends_with(".tmp") || is_synchronized_value(name)
+}
+
+/// Whether `name` is a value written by the side input synchronizer of numaflow, which writes
+/// every value to `<name>_<unix time in nanoseconds>` and points the `<name>` symlink to it.
+fn is_synchronized_value(name: &str) -> bool {
+    name.rsplit_once('_')
+        .is_some_and(|(side_input, timestamp)| {
+            !side_input.is_empty()
+                && timestamp.len() >= 19
+                && timestamp.bytes().all(|b| b.is_ascii_digit())
+        })
+}
+
+/// Names of the files in `dir`, including the symlinks to files.
+async fn list(dir: &Path) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        // Unlike the file type of the entry, its metadata follows symlinks.
+        let is_file = match tokio::fs::metadata(entry.path()).await {
+            Ok(metadata) => metadata.is_file(),
+            // A dangling symlink, or a file removed since it was listed.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+            Err(e) => return Err(e),
+        };
+        if is_file && let Ok(name) = entry.file_name().into_string() {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// What a read of the inotify instance returned.
+enum Events {
+    /// Names of the files the events were about.
+    Names(Vec<String>),
+    /// The event queue overflowed, events were lost.
+    Overflow,
+}
+
+/// An inotify instance watching one directory.
+struct Inotify(AsyncFd<OwnedFd>);
+
+impl Inotify {
+    fn watch(dir: &Path) -> io::Result<Self> {
+        let path = CString::new(dir.as_os_str().as_bytes())?;
+        // SAFETY: a successful `inotify_init1` returns a new file descriptor the `OwnedFd` takes
+        // ownership of, and `path` is a NUL-terminated string that outlives the call.
+        let fd = unsafe {
+            let fd = libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = OwnedFd::from_raw_fd(fd);
+            if libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), WATCH_MASK) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            fd
+        };
+        Ok(Self(AsyncFd::new(fd)?))
+    }
+
+    /// Waits for events and returns them.
+    async fn read(&self) -> io::Result<Events> {
+        // Room for many events, a single event with the longest name fits.
+        let mut buffer = [0u8; 64 * 1024];
+        let len = loop {
+            let mut guard = self.0.readable().await?;
+            // SAFETY: the buffer is valid for writes of its whole length.
+            let read = guard.try_io(|fd| {
+                let len =
+                    unsafe { libc::read(fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) };
+                if len < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(len as usize)
+                }
+            });
+            match read {
+                Ok(result) => break result?,
+                Err(_would_block) => continue,
+            }
+        };
+
+        let mut names = Vec::new();
+        let mut offset = 0;
+        while offset + mem::size_of::<libc::inotify_event>() <= len {
+            // SAFETY: the kernel wrote a whole event header at `offset`, which may be unaligned.
+            let event = unsafe {
+                buffer
+                    .as_ptr()
+                    .add(offset)
+                    .cast::<libc::inotify_event>()
+                    .read_unaligned()
+            };
+            if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                return Ok(Events::Overflow);
+            }
+            let start = offset + mem::size_of::<libc::inotify_event>();
+            let name = &buffer[start..start + event.len as usize];
+            // The name is padded with NUL bytes.
+            let name = name.split(|&byte| byte == 0).next().unwrap_or_default();
+            if let Ok(name) = std::str::from_utf8(name)
+                && !name.is_empty()
+            {
+                names.push(name.to_string());
+            }
+            offset = start + event.len as usize;
+        }
+        names.dedup();
+        Ok(Events::Names(names))
+    }
+}
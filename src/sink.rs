@@ -1,68 +1,24 @@
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
-use napi::bindgen_prelude::{Buffer, Promise};
+use napi::bindgen_prelude::{Buffer, ClassInstance, FromNapiValue, Promise};
 use napi::threadsafe_function::ThreadsafeFunction;
-use napi::{Error, Status};
+use napi::{Error, Status, sys};
 use napi_derive::napi;
-use numaflow::shared::ServerExtras;
 use numaflow::sink;
-
-#[derive(Clone, Default)]
-#[napi(namespace = "sink")]
-pub struct SinkSystemMetadata(sink::SystemMetadata);
-
-#[napi(namespace = "sink")]
-impl SinkSystemMetadata {
-    #[napi(constructor)]
-    pub fn new() -> Self {
-        Self(sink::SystemMetadata::default())
-    }
-
-    #[napi]
-    pub fn get_groups(&self) -> Vec<String> {
-        self.0.groups()
-    }
-
-    #[napi]
-    pub fn get_keys(&self, group: String) -> Vec<String> {
-        self.0.keys(group.as_str())
-    }
-
-    #[napi]
-    pub fn get_value(&self, group: String, key: String) -> Buffer {
-        Buffer::from(self.0.value(group.as_str(), key.as_str()))
-    }
-}
-
-#[derive(Clone, Default)]
-#[napi(namespace = "sink")]
-pub struct SinkUserMetadata(sink::UserMetadata);
-
-#[napi(namespace = "sink")]
-impl SinkUserMetadata {
-    #[napi(constructor)]
-    pub fn new() -> Self {
-        Self(sink::UserMetadata::default())
-    }
-
-    #[napi]
-    pub fn get_groups(&self) -> Vec<String> {
-        self.0.groups()
-    }
-
-    #[napi]
-    pub fn get_keys(&self, group: String) -> Vec<String> {
-        self.0.keys(group.as_str())
-    }
-
-    #[napi]
-    pub fn get_value(&self, group: String, key: String) -> Buffer {
-        Buffer::from(self.0.value(group.as_str(), key.as_str()))
-    }
-}
+use tracing::{Instrument, warn};
+
+use crate::codec::{Codec, Skipped};
+use crate::config;
+use crate::error;
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metadata::{SystemMetadata, UserMetadata};
+use crate::metrics::{self, MetricsServer};
+use crate::retry::{ReplayStream, Retry, RetryPolicy};
+use crate::trace::{self, TraceContext};
+use crate::value::DatumValue;
 
 // ==================== Message ====================
 
@@ -71,17 +27,23 @@ impl SinkUserMetadata {
 pub struct SinkMessage {
     keys: Option<Vec<String>>,
     value: Vec<u8>,
+    user_metadata: Option<UserMetadata>,
 }
 
 #[napi(namespace = "sink")]
 impl SinkMessage {
     /// Create a new Message with the given value.
-    /// Keys and user_metadata are optional.
+    /// Keys and user_metadata are optional, the user metadata maps groups to their key/value pairs.
     #[napi(constructor)]
-    pub fn new(value: Buffer, keys: Option<Vec<String>>) -> Self {
+    pub fn new(
+        value: Buffer,
+        keys: Option<Vec<String>>,
+        user_metadata: Option<HashMap<String, HashMap<String, Buffer>>>,
+    ) -> Self {
         Self {
             keys,
             value: value.into(),
+            user_metadata: user_metadata.map(|groups| UserMetadata::new(Some(groups))),
         }
     }
 }
@@ -91,7 +53,12 @@ impl From<SinkMessage> for sink::Message {
         Self {
             keys: value.keys,
             value: value.value,
-            user_metadata: None, // FIXME:
+            user_metadata: value.user_metadata.map(|metadata| {
+                metadata
+                    .into_groups()
+                    .map(|(group, kvs)| (group, kvs.into()))
+                    .collect()
+            }),
         }
     }
 }
@@ -107,6 +74,19 @@ pub enum ResponseType {
     OnSuccess,
 }
 
+impl ResponseType {
+    /// Name of the response type in the metrics.
+    fn as_str(&self) -> &'static str {
+        match self {
+            ResponseType::Success => "success",
+            ResponseType::Failure => "failure",
+            ResponseType::Fallback => "fallback",
+            ResponseType::Serve => "serve",
+            ResponseType::OnSuccess => "onSuccess",
+        }
+    }
+}
+
 impl From<ResponseType> for sink::ResponseType {
     fn from(value: ResponseType) -> Self {
         match value {
@@ -201,13 +181,41 @@ impl SinkResponse {
     }
 }
 
+/// A response resolved by the sink handler. Its payloads are moved out of the `SinkResponse`
+/// instance without copying them, a reference to the instance can't outlive the promise callback
+/// it is converted in.
+pub struct ResolvedResponse(SinkResponse);
+
+impl FromNapiValue for ResolvedResponse {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> napi::Result<Self> {
+        let mut response =
+            unsafe { ClassInstance::<SinkResponse>::from_napi_value(env, napi_val)? };
+        Ok(Self(SinkResponse {
+            id: std::mem::take(&mut response.id),
+            response_type: response.response_type.clone(),
+            err: response.err.take(),
+            serve_response: response.serve_response.take(),
+            on_success_msg: response.on_success_msg.take(),
+        }))
+    }
+}
+
+type SinkFn = ThreadsafeFunction<
+    SinkDatumIterator,
+    Promise<Vec<ResolvedResponse>>,
+    SinkDatumIterator,
+    Status,
+    false,
+    true,
+>;
+
 impl From<SinkResponse> for sink::Response {
     fn from(value: SinkResponse) -> Self {
         Self {
             id: value.id,
             response_type: value.response_type.into(),
             err: value.err,
-            serve_response: value.serve_response.map(|b| b.to_vec()),
+            serve_response: value.serve_response,
             on_success_msg: value.on_success_msg.map(|m| m.into()),
         }
     }
@@ -251,12 +259,13 @@ impl SinkResponses {
 
 // ==================== Datum ====================
 
+#[derive(Clone)]
 #[napi(namespace = "sink")]
 pub struct SinkDatum {
     /// Set of keys in the (key, value) terminology of map/reduce paradigm.
     pub keys: Vec<String>,
     /// The value in the (key, value) terminology of map/reduce paradigm.
-    value: Vec<u8>,
+    value: DatumValue,
     /// Watermark represented by time (Unix timestamp in milliseconds).
     watermark: DateTime<Utc>,
     /// Event time (Unix timestamp in milliseconds).
@@ -265,30 +274,40 @@ pub struct SinkDatum {
     pub id: String,
     /// Headers for the message.
     headers: HashMap<String, String>,
-    user_metadata: SinkUserMetadata,
-    system_metadata: SinkSystemMetadata,
+    user_metadata: UserMetadata,
+    system_metadata: SystemMetadata,
+    /// Trace context from the `traceparent` header.
+    trace_context: Option<TraceContext>,
+    /// The value decoded by the codec of the server.
+    data: Option<serde_json::Value>,
+    /// Why the value could not be decoded, when the decode error policy is `pass`.
+    decode_error: Option<String>,
 }
 
 impl From<sink::SinkRequest> for SinkDatum {
     fn from(value: sink::SinkRequest) -> Self {
         Self {
             keys: value.keys,
-            value: value.value,
+            value: value.value.into(),
             watermark: value.watermark,
             event_time: value.event_time,
             id: value.id,
+            trace_context: TraceContext::child_of(&value.headers),
             headers: value.headers,
-            user_metadata: SinkUserMetadata(value.user_metadata),
-            system_metadata: SinkSystemMetadata(value.system_metadata),
+            user_metadata: UserMetadata::from_numaflow(&value.user_metadata),
+            system_metadata: SystemMetadata::from_numaflow(&value.system_metadata),
+            data: None,
+            decode_error: None,
         }
     }
 }
 
 #[napi(namespace = "sink")]
 impl SinkDatum {
-    #[napi]
-    pub fn get_value(&self) -> Buffer {
-        Buffer::from(self.value.clone())
+    /// The value of the datum, the same buffer on every read.
+    #[napi(ts_return_type = "Buffer")]
+    pub fn get_value(&self) -> DatumValue {
+        self.value.clone()
     }
 
     #[napi]
@@ -307,14 +326,32 @@ impl SinkDatum {
     }
 
     #[napi]
-    pub fn user_metadata(&self) -> SinkUserMetadata {
+    pub fn user_metadata(&self) -> UserMetadata {
         self.user_metadata.clone()
     }
 
     #[napi]
-    pub fn system_metadata(&self) -> SinkSystemMetadata {
+    pub fn system_metadata(&self) -> SystemMetadata {
         self.system_metadata.clone()
     }
+
+    /// Context of the span the datum is handled in, if its headers carry a `traceparent`.
+    #[napi]
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        self.trace_context.clone()
+    }
+
+    /// The value decoded by the codec of the server, null with the raw codec or an empty value.
+    #[napi]
+    pub fn data(&self) -> Option<serde_json::Value> {
+        self.data.clone()
+    }
+
+    /// Why the value could not be decoded, only set when the decode error policy is `pass`.
+    #[napi]
+    pub fn decode_error(&self) -> Option<String> {
+        self.decode_error.clone()
+    }
 }
 
 // ==================== Sink ====================
@@ -323,97 +360,68 @@ impl SinkDatum {
 /// data received by the Sink.
 #[napi(namespace = "sink")]
 pub struct SinkAsyncServer {
-    sink_fn: Arc<
-        ThreadsafeFunction<
-            SinkDatumIterator,
-            Promise<Vec<&'static SinkResponse>>,
-            SinkDatumIterator,
-            Status,
-            false,
-            true,
-        >,
-    >,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    sink_fn: Arc<SinkFn>,
+    retry: Retry,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "sink")]
 impl SinkAsyncServer {
     /// Create a new SinkAsyncServer with the given callback.
-    #[napi(constructor)]
-    pub fn new(
-        sink_fn: ThreadsafeFunction<
-            SinkDatumIterator,
-            Promise<Vec<&SinkResponse>>,
-            SinkDatumIterator,
-            Status,
-            false,
-            true,
-        >,
-    ) -> napi::Result<Self> {
+    #[napi(
+        constructor,
+        ts_args_type = "sinkFn: (arg: SinkDatumIterator) => Promise<Array<SinkResponse>>, retryPolicy?: RetryPolicy | undefined | null"
+    )]
+    pub fn new(sink_fn: SinkFn, retry_policy: Option<RetryPolicy>) -> napi::Result<Self> {
         Ok(Self {
             sink_fn: Arc::new(sink_fn),
-            shutdown_tx: Mutex::new(None),
+            retry: retry_policy.try_into()?,
+            lifecycle: Lifecycle::new("sink"),
         })
     }
 
     /// Start the SinkAsyncServer with the given callback
     #[napi]
-    pub async fn start(
-        &self,
-        socket_path: Option<String>,
-        server_info_path: Option<String>,
-    ) -> napi::Result<()> {
-        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
-        self.shutdown_tx
-            .lock()
-            .map_err(|_| Error::new(Status::GenericFailure, "Failed to start the server"))?
-            .replace(shutdown_tx);
-
+    pub async fn start(&self, options: Option<ServerOptions>) -> napi::Result<()> {
+        let options = config::resolve(options)?;
         // Create the actual sink implementation with Arc clone
         let sinker = SinkImpl {
             sink_fn: Arc::clone(&self.sink_fn),
+            retry: self.retry.clone(),
+            timeout: options.handler_timeout(),
+            in_flight: self.lifecycle.in_flight(),
+            codec: Codec::new("sink", &options)?,
         };
 
         // Use socket_file and server_info_file if both are provided, else use default
-        let mut server = sink::Server::new(sinker);
-        if let Some(sock_file) = socket_path {
-            server = server.with_socket_file(sock_file);
-        }
-        if let Some(info_file) = server_info_path {
-            server = server.with_server_info_file(info_file);
-        }
+        let server = options.configure(sink::Server::new(sinker));
 
-        server.start_with_shutdown(shutdown_rx).await.map_err(|e| {
-            Error::new(
-                Status::GenericFailure,
-                format!("Failed to start server: {}", e),
-            )
-        })
+        let _metrics_server = MetricsServer::start(&options)?;
+        self.lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    Status::GenericFailure,
+                    format!("Failed to start server: {}", e),
+                )
+            })
     }
 
     /// Stop the sink server
     #[napi]
-    pub fn stop(&self) -> napi::Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
-        }
-        Ok(())
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
     }
 }
 
 // Internal implementation of the Sinker trait
 struct SinkImpl {
-    sink_fn: Arc<
-        ThreadsafeFunction<
-            SinkDatumIterator,
-            Promise<Vec<&'static SinkResponse>>,
-            SinkDatumIterator,
-            Status,
-            false,
-            true,
-        >,
-    >,
+    sink_fn: Arc<SinkFn>,
+    retry: Retry,
+    timeout: Option<Duration>,
+    in_flight: InFlight,
+    codec: Codec,
 }
 
 #[tonic::async_trait]
@@ -422,46 +430,111 @@ impl sink::Sinker for SinkImpl {
         &self,
         input: tokio::sync::mpsc::Receiver<sink::SinkRequest>,
     ) -> Vec<sink::Response> {
-        let requests = SinkDatumIterator::new(input);
-        // Call the JavaScript callback
-        match self.sink_fn.call_async(requests).await {
-            Ok(promise) => match promise.await {
-                Ok(responses) => responses.into_iter().map(|r| r.clone().into()).collect(),
-                Err(e) => {
-                    eprintln!(
-                        "[ERROR] User-defined sink function returned an error: {:?}",
-                        e
-                    );
-                    panic!("User-defined sink function returned an error: {:?}", e);
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("sink", None);
+        async {
+            let requests = ReplayStream::new(input, self.retry.enabled() || self.timeout.is_some());
+            // Read again from the start to answer a timed out batch
+            let mut batch = requests.clone();
+            let skipped = Skipped::default();
+            // Call the JavaScript callback, a retry reads the batch again from the start
+            let responses = self
+                .retry
+                .run(requests, |requests| {
+                    error::within(
+                        "sink",
+                        self.timeout,
+                        error::call_promise(
+                            "sink",
+                            &self.sink_fn,
+                            SinkDatumIterator::new(requests, self.codec, skipped.clone()),
+                        ),
+                    )
+                })
+                .await;
+            match responses {
+                Ok(responses) => {
+                    let mut responses: Vec<sink::Response> = responses
+                        .into_iter()
+                        .map(|ResolvedResponse(r)| {
+                            metrics::sink_response(r.response_type.as_str());
+                            r.into()
+                        })
+                        .collect();
+                    // Datums the codec skipped are failed, or acknowledged when they are dropped.
+                    for (id, error) in skipped.take() {
+                        let response = match error {
+                            Some(error) => {
+                                metrics::sink_response(ResponseType::Failure.as_str());
+                                sink::Response::failure(id, error)
+                            }
+                            None => {
+                                metrics::sink_response(ResponseType::Success.as_str());
+                                sink::Response::ok(id)
+                            }
+                        };
+                        responses.push(response);
+                    }
+                    responses
+                }
+                // The batch is failed rather than the server, numaflow retries the messages.
+                Err(e) if e.is_timeout() => {
+                    warn!(component = "sink", "{e}, failing the batch");
+                    let mut responses = Vec::new();
+                    while let Some(datum) = batch.next().await {
+                        metrics::sink_response(ResponseType::Failure.as_str());
+                        responses.push(sink::Response::failure(datum.id, e.to_string()));
+                    }
+                    responses
                 }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Executing user-defined sink function: {:?}", e);
-                panic!("Error executing user-defined sink function: {:?}", e);
+                Err(e) => e.raise(),
             }
         }
+        .instrument(span)
+        .await
     }
 }
 
 #[napi(namespace = "sink")]
 pub struct SinkDatumIterator {
-    source: tokio::sync::mpsc::Receiver<sink::SinkRequest>,
+    source: ReplayStream<sink::SinkRequest, SinkDatum>,
+    codec: Codec,
+    skipped: Skipped,
 }
 
 #[napi(namespace = "sink")]
 impl SinkDatumIterator {
     /// Internal constructor - not exposed to JavaScript
-    pub(crate) fn new(source: tokio::sync::mpsc::Receiver<sink::SinkRequest>) -> Self {
-        Self { source }
+    pub(crate) fn new(
+        source: ReplayStream<sink::SinkRequest, SinkDatum>,
+        codec: Codec,
+        skipped: Skipped,
+    ) -> Self {
+        Self {
+            source,
+            codec,
+            skipped,
+        }
     }
 
-    /// Returns the next datum from the stream, or None if the stream has ended
+    /// Returns the next datum from the stream, or None if the stream has ended. Datums whose value
+    /// can't be decoded are skipped unless the decode error policy is `pass`.
     /// # SAFETY
     ///
     /// Async function with &mut self is unsafe in napi because the self is also owned
     /// by the Node.js runtime. You cannot ensure that the self is only owned by Rust.
     #[napi(namespace = "sink")]
     pub async unsafe fn next(&mut self) -> Option<SinkDatum> {
-        self.source.recv().await.map(SinkDatum::from)
+        while let Some(mut datum) = self.source.next().await {
+            if let Some((data, decode_error)) = datum
+                .value
+                .with(|value| self.skipped.decode(&self.codec, &datum.id, value))
+            {
+                datum.data = data;
+                datum.decode_error = decode_error;
+                return Some(datum);
+            }
+        }
+        None
     }
 }
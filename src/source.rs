@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
 use napi::bindgen_prelude::{Buffer, Promise};
@@ -9,53 +9,14 @@ use napi_derive::napi;
 use numaflow::shared::ServerExtras;
 use numaflow::source;
 use tokio::sync::mpsc::Sender;
+use tracing::{Instrument, info};
 
-#[derive(Clone, Default)]
-#[napi(namespace = "source")]
-pub struct SourceUserMetadata(source::UserMetadata);
-
-#[napi(namespace = "source")]
-impl SourceUserMetadata {
-    #[napi(constructor)]
-    pub fn new() -> Self {
-        Self(source::UserMetadata::default())
-    }
-
-    #[napi]
-    pub fn get_groups(&self) -> Vec<String> {
-        self.0.groups()
-    }
-
-    #[napi]
-    pub fn get_keys(&self, group: String) -> Vec<String> {
-        self.0.keys(group.as_str())
-    }
-
-    #[napi]
-    pub fn get_value(&self, group: String, key: String) -> Buffer {
-        Buffer::from(self.0.value(group.as_str(), key.as_str()))
-    }
-
-    #[napi]
-    pub fn create_group(&mut self, group: String) {
-        self.0.create_group(group);
-    }
-
-    #[napi]
-    pub fn add_kv(&mut self, group: String, key: String, value: Buffer) {
-        self.0.add_kv(group, key, value.into())
-    }
-
-    #[napi]
-    pub fn remove_key(&mut self, group: String, key: String) {
-        self.0.remove_key(group.as_str(), key.as_str());
-    }
-
-    #[napi]
-    pub fn remove_group(&mut self, group: String) {
-        self.0.remove_group(group.as_str());
-    }
-}
+use crate::config;
+use crate::error::{self, UdfError};
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metadata;
+use crate::metrics::{self, MetricsServer};
+use crate::trace::{self, TraceContext};
 
 #[napi(object, namespace = "source")]
 pub struct Message {
@@ -75,23 +36,13 @@ pub struct Message {
 
 impl From<Message> for numaflow::source::Message {
     fn from(value: Message) -> Self {
-        let mut user_metadata = None;
-        if let Some(user_metadata_map) = value.user_metadata {
-            let mut metadata = source::UserMetadata::new();
-            for (group, keys) in user_metadata_map.iter() {
-                for (key, value) in keys.iter() {
-                    metadata.add_kv(group.clone(), key.clone(), value.to_vec());
-                }
-            }
-            user_metadata = Some(metadata);
-        }
         Self {
             value: value.payload.into(),
             offset: value.offset.into(),
             event_time: value.event_time,
             keys: value.keys,
             headers: value.headers,
-            user_metadata,
+            user_metadata: value.user_metadata.map(metadata::from_record),
         }
     }
 }
@@ -129,6 +80,8 @@ pub struct ReadRequest {
     num_records: u32,
     /// Request timeout in milliseconds.
     timeout_ms: u32,
+    /// Context of the trace started for the read.
+    trace_context: TraceContext,
 }
 
 #[napi(namespace = "source")]
@@ -144,6 +97,13 @@ impl ReadRequest {
     pub fn timeout_ms(&self) -> u32 {
         self.timeout_ms
     }
+
+    /// Get the context of the trace started for the read. It is set as the `traceparent` header of
+    /// the messages read that don't have one.
+    #[napi(getter)]
+    pub fn trace_context(&self) -> TraceContext {
+        self.trace_context.clone()
+    }
 }
 
 impl From<source::SourceReadRequest> for ReadRequest {
@@ -151,6 +111,7 @@ impl From<source::SourceReadRequest> for ReadRequest {
         Self {
             num_records: value.count as u32,
             timeout_ms: value.timeout.as_millis() as u32,
+            trace_context: TraceContext::root(),
         }
     }
 }
@@ -171,7 +132,7 @@ pub struct SourceAsyncServer {
     nack_fn: Arc<NackFn>,
     pending_fn: Arc<PendingFn>,
     partition_fn: Arc<PartitionFn>,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "source")]
@@ -197,57 +158,46 @@ impl SourceAsyncServer {
             nack_fn: Arc::new(nack_fn),
             pending_fn: Arc::new(pending_fn),
             partition_fn: Arc::new(partition_fn),
-            shutdown_tx: Mutex::new(None),
+            lifecycle: Lifecycle::new("source"),
         }
     }
 
     /// Start the SourceAsyncServer with the given callback
     #[napi]
-    pub async fn start(
-        &self,
-        socket_path: Option<String>,
-        server_info_path: Option<String>,
-    ) -> napi::Result<()> {
+    pub async fn start(&self, options: Option<ServerOptions>) -> napi::Result<()> {
+        let options = config::resolve(options)?;
         let sourcer = Sourcer::new(
             self.read_fn.clone(),
             self.ack_fn.clone(),
             self.nack_fn.clone(),
             self.pending_fn.clone(),
             self.partition_fn.clone(),
+            self.lifecycle.in_flight(),
         );
-        let mut server = source::Server::new(sourcer);
-        if let Some(sock_file) = socket_path {
-            server = server.with_socket_file(sock_file.clone());
-        }
-        if let Some(info_file) = server_info_path {
-            server = server.with_server_info_file(info_file);
-        }
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        {
-            self.shutdown_tx.lock().unwrap().replace(tx);
-        }
-        println!(
-            "Starting SourceAsyncServer server at {:?}",
-            server.socket_file()
+        let server = options.configure(source::Server::new(sourcer));
+        info!(
+            component = "source",
+            socket_file = ?server.socket_file(),
+            "Starting SourceAsyncServer"
         );
-        server.start_with_shutdown(rx).await.map_err(|e| {
-            Error::new(
-                Status::GenericFailure,
-                format!("Error running ReduceAsyncServer: {e:?}"),
-            )
-        })?;
-        println!("ReduceAsyncServer has shutdown...");
+        let _metrics_server = MetricsServer::start(&options)?;
+        self.lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    Status::GenericFailure,
+                    format!("Error running SourceAsyncServer: {e:?}"),
+                )
+            })?;
+        info!(component = "source", "SourceAsyncServer has shut down");
         Ok(())
     }
 
     /// Stop the SourceAsyncServer server
     #[napi]
-    pub fn stop(&self) -> napi::Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
-        }
-        Ok(())
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
     }
 }
 
@@ -257,6 +207,8 @@ struct Sourcer {
     nack_fn: Arc<NackFn>,
     pending_fn: Arc<PendingFn>,
     partition_fn: Arc<PartitionFn>,
+    /// Tracks reads, acks and nacks. The pending and partitions queries don't hold any data.
+    in_flight: InFlight,
 }
 
 impl Sourcer {
@@ -266,6 +218,7 @@ impl Sourcer {
         nack_fn: Arc<NackFn>,
         pending_fn: Arc<PendingFn>,
         partition_fn: Arc<PartitionFn>,
+        in_flight: InFlight,
     ) -> Self {
         Self {
             read_fn,
@@ -273,6 +226,7 @@ impl Sourcer {
             nack_fn,
             pending_fn,
             partition_fn,
+            in_flight,
         }
     }
 }
@@ -280,124 +234,72 @@ impl Sourcer {
 #[async_trait::async_trait]
 impl source::Sourcer for Sourcer {
     async fn read(&self, request: source::SourceReadRequest, transmitter: Sender<source::Message>) {
-        match self.read_fn.call_async(request.into()).await {
-            Ok(messages_fn) => loop {
-                match messages_fn.call_async(()).await {
-                    Ok(promise) => match promise.await {
-                        Ok(Some(message)) => {
-                            if let Err(e) = transmitter.send(message.into()).await {
-                                eprintln!("[ERROR] Sending message to numa: {:?}", e);
-                                panic!("Sending message to numa: {:?}", e);
-                            }
+        let _in_flight = self.in_flight.track();
+        let request: ReadRequest = request.into();
+        let trace_context = request.trace_context.clone();
+        let span = trace::handler_span("source read", Some(&trace_context));
+        async {
+            let messages_fn = match error::call("source read", &self.read_fn, request).await {
+                Ok(messages_fn) => messages_fn,
+                Err(e) => e.raise(),
+            };
+            loop {
+                match error::call_promise("source read iterator", &messages_fn, ()).await {
+                    Ok(Some(mut message)) => {
+                        metrics::message_emitted("source", None);
+                        if !trace::has_traceparent(&message.headers) {
+                            trace_context.inject(&mut message.headers);
                         }
-                        Ok(None) => break,
-                        Err(e) => {
-                            eprintln!("[ERROR] User-defined function returned an error: {:?}", e);
-                            panic!("User-defined function returned an error: {:?}", e);
+                        if let Err(e) = transmitter.send(message.into()).await {
+                            UdfError::forward("source read", e).raise();
                         }
-                    },
-                    Err(e) => {
-                        eprintln!("[ERROR] Executing user-defined map function: {:?}", e);
-                        panic!("Error executing user-defined map function: {:?}", e);
                     }
+                    Ok(None) => break,
+                    Err(e) => e.raise(),
                 }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Executing user-defined read function: {:?}", e);
-                panic!("Error executing user-defined read function: {:?}", e);
             }
         }
+        .instrument(span)
+        .await
     }
 
     async fn ack(&self, offsets: Vec<source::Offset>) {
-        match self
-            .ack_fn
-            .call_async(offsets.into_iter().map(|o| o.into()).collect())
-            .await
-        {
-            Ok(promise) => match promise.await {
-                Ok(_) => {}
-                Err(e) => {
-                    eprintln!(
-                        "[ERROR] User-defined ack function returned an error: {:?}",
-                        e
-                    );
-                    panic!("User-defined ack function returned an error: {:?}", e);
-                }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Executing user-defined ack function: {:?}", e);
-                panic!("Error executing user-defined ack function: {:?}", e);
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("source ack", None);
+        async {
+            let offsets = offsets.into_iter().map(|o| o.into()).collect();
+            if let Err(e) = error::call_promise("source ack", &self.ack_fn, offsets).await {
+                e.raise();
             }
         }
+        .instrument(span)
+        .await
     }
 
     async fn nack(&self, offsets: Vec<source::Offset>) {
-        match self
-            .nack_fn
-            .call_async(offsets.into_iter().map(|o| o.into()).collect())
-            .await
-        {
-            Ok(promise) => match promise.await {
-                Ok(_) => {}
-                Err(e) => {
-                    eprintln!(
-                        "[ERROR] User-defined nack function returned an error: {:?}",
-                        e
-                    );
-                    panic!("User-defined nack function returned an error: {:?}", e);
-                }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Executing user-defined nack function: {:?}", e);
-                panic!("Error executing user-defined nack function: {:?}", e);
+        let _in_flight = self.in_flight.track();
+        let span = trace::handler_span("source nack", None);
+        async {
+            let offsets = offsets.into_iter().map(|o| o.into()).collect();
+            if let Err(e) = error::call_promise("source nack", &self.nack_fn, offsets).await {
+                e.raise();
             }
         }
+        .instrument(span)
+        .await
     }
 
     async fn pending(&self) -> Option<usize> {
-        match self.pending_fn.call_async(()).await {
-            Ok(promise) => match promise.await {
-                Ok(Some(pending)) => Some(pending as usize),
-                Ok(None) => None,
-                Err(e) => {
-                    eprintln!(
-                        "[ERROR] User-defined pending function returned an error: {:?}",
-                        e
-                    );
-                    panic!("User-defined pending function returned an error: {:?}", e);
-                }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Executing user-defined pending function: {:?}", e);
-                panic!("Error executing user-defined pending function: {:?}", e);
-            }
+        match error::call_promise("source pending", &self.pending_fn, ()).await {
+            Ok(pending) => pending.map(|pending| pending as usize),
+            Err(e) => e.raise(),
         }
     }
 
     async fn partitions(&self) -> Option<Vec<i32>> {
-        match self.partition_fn.call_async(()).await {
-            Ok(promise) => match promise.await {
-                Ok(Some(partitions)) => Some(partitions),
-                Ok(None) => None,
-                Err(e) => {
-                    eprintln!(
-                        "[ERROR] User-defined partitions function returned an error: {:?}",
-                        e
-                    );
-                    panic!(
-                        "User-defined partitions function returned an error: {:?}",
-                        e
-                    );
-                }
-            },
-            Err(e) => {
-                eprintln!(
-                    "[ERROR] Executing user-defined partitions function: {:?}",
-                    e
-                );
-                panic!("Error executing user-defined partitions function: {:?}", e);
-            }
+        match error::call_promise("source partitions", &self.partition_fn, ()).await {
+            Ok(partitions) => partitions,
+            Err(e) => e.raise(),
         }
     }
 }
@@ -1,87 +1,24 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
 use napi::Status;
 use napi::bindgen_prelude::{Buffer, Promise};
 use napi::threadsafe_function::ThreadsafeFunction;
 use napi_derive::napi;
-use numaflow::shared::ServerExtras;
 use numaflow::sourcetransform;
-
-#[derive(Clone, Default)]
-#[napi(namespace = "sourceTransform")]
-pub struct SourceTransformUserMetadata(sourcetransform::UserMetadata);
-
-#[napi(namespace = "sourceTransform")]
-impl SourceTransformUserMetadata {
-    #[napi(constructor)]
-    pub fn new() -> Self {
-        Self(sourcetransform::UserMetadata::default())
-    }
-
-    #[napi]
-    pub fn get_groups(&self) -> Vec<String> {
-        self.0.groups()
-    }
-
-    #[napi]
-    pub fn get_keys(&self, group: String) -> Vec<String> {
-        self.0.keys(group.as_str())
-    }
-
-    #[napi]
-    pub fn get_value(&self, group: String, key: String) -> Buffer {
-        Buffer::from(self.0.value(group.as_str(), key.as_str()))
-    }
-
-    #[napi]
-    pub fn create_group(&mut self, group: String) {
-        self.0.create_group(group);
-    }
-
-    #[napi]
-    pub fn add_kv(&mut self, group: String, key: String, value: Buffer) {
-        self.0.add_kv(group, key, value.into())
-    }
-
-    #[napi]
-    pub fn remove_key(&mut self, group: String, key: String) {
-        self.0.remove_key(group.as_str(), key.as_str());
-    }
-
-    #[napi]
-    pub fn remove_group(&mut self, group: String) {
-        self.0.remove_group(group.as_str());
-    }
-}
-
-#[derive(Clone, Default)]
-#[napi(namespace = "sourceTransform")]
-pub struct SourceTransformSystemMetadata(sourcetransform::SystemMetadata);
-
-#[napi(namespace = "sourceTransform")]
-impl SourceTransformSystemMetadata {
-    #[napi(constructor)]
-    pub fn new() -> Self {
-        Self(sourcetransform::SystemMetadata::default())
-    }
-
-    #[napi]
-    pub fn get_groups(&self) -> Vec<String> {
-        self.0.groups()
-    }
-
-    #[napi]
-    pub fn get_keys(&self, group: String) -> Vec<String> {
-        self.0.keys(group.as_str())
-    }
-
-    #[napi]
-    pub fn get_value(&self, group: String, key: String) -> Buffer {
-        Buffer::from(self.0.value(group.as_str(), key.as_str()))
-    }
-}
+use tracing::{Instrument, error, info};
+
+use crate::codec::{Codec, Decoded};
+use crate::concurrency::ConcurrencyLimit;
+use crate::config;
+use crate::error::{self, UdfError};
+use crate::lifecycle::{DrainSummary, InFlight, Lifecycle, ServerOptions};
+use crate::metadata::{self, SystemMetadata, UserMetadata};
+use crate::metrics::{self, MetricsServer};
+use crate::retry::{Retry, RetryPolicy};
+use crate::trace::{self, TraceContext};
+use crate::value::DatumValue;
 
 #[derive(Default)]
 #[napi(object, namespace = "sourceTransform")]
@@ -91,6 +28,8 @@ pub struct SourceTransformMessage {
     pub keys: Option<Vec<String>>,
     /// Value is the value passed to the next vertex.
     pub value: Buffer,
+    /// Data encoded by the codec of the server into the value passed on, replaces `value`.
+    pub data: Option<serde_json::Value>,
     /// Time for the given event. This will be used for tracking watermarks. If cannot be derived, set it to the incoming
     /// event_time from the [`SourceTransformRequest`].
     pub event_time: DateTime<Utc>,
@@ -105,31 +44,22 @@ pub fn message_to_drop(event_time: DateTime<Utc>) -> SourceTransformMessage {
     SourceTransformMessage {
         keys: None,
         value: vec![].into(),
+        data: None,
         event_time,
         tags: Some(vec![numaflow::shared::DROP.to_string()]),
         user_metadata: None,
     }
 }
 
-impl From<SourceTransformMessage> for sourcetransform::Message {
-    fn from(value: SourceTransformMessage) -> Self {
-        let mut user_metadata = None;
-        if let Some(user_metadata_map) = value.user_metadata {
-            let mut metadata = sourcetransform::UserMetadata::new();
-            for (group, keys) in user_metadata_map.iter() {
-                for (key, value) in keys.iter() {
-                    metadata.add_kv(group.clone(), key.clone(), value.to_vec());
-                }
-            }
-            user_metadata = Some(metadata);
-        }
-        Self {
-            keys: value.keys,
-            value: value.value.into(),
-            event_time: value.event_time,
-            tags: value.tags,
-            user_metadata,
-        }
+impl SourceTransformMessage {
+    fn encode(self, codec: &Codec) -> Result<sourcetransform::Message, String> {
+        Ok(sourcetransform::Message {
+            keys: self.keys,
+            value: codec.encode(Some(self.value), self.data)?,
+            event_time: self.event_time,
+            tags: self.tags,
+            user_metadata: self.user_metadata.map(metadata::from_record),
+        })
     }
 }
 
@@ -138,7 +68,7 @@ pub struct SourceTransformDatum {
     /// Set of keys in the (key, value) terminology of map/reduce paradigm.
     pub keys: Vec<String>,
     /// The value in the (key, value) terminology of map/reduce paradigm.
-    value: Buffer,
+    value: DatumValue,
     /// [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a
     /// guarantee that we will not see an element older than this time.
     watermark: DateTime<Utc>,
@@ -147,9 +77,15 @@ pub struct SourceTransformDatum {
     /// Headers for the message.
     headers: HashMap<String, String>,
     /// User metadata for the message.
-    user_metadata: Option<SourceTransformUserMetadata>,
+    user_metadata: Option<UserMetadata>,
     /// System metadata for the message.
-    system_metadata: Option<SourceTransformSystemMetadata>,
+    system_metadata: Option<SystemMetadata>,
+    /// Trace context from the `traceparent` header.
+    trace_context: Option<TraceContext>,
+    /// The value decoded by the codec of the server.
+    data: Option<serde_json::Value>,
+    /// Why the value could not be decoded, when the decode error policy is `pass`.
+    decode_error: Option<String>,
 }
 
 #[napi(namespace = "sourceTransform")]
@@ -161,25 +97,27 @@ impl SourceTransformDatum {
         watermark: DateTime<Utc>,
         event_time: DateTime<Utc>,
         headers: HashMap<String, String>,
-        user_metadata: Option<&SourceTransformUserMetadata>,
-        system_metadata: Option<&SourceTransformSystemMetadata>,
+        user_metadata: Option<&UserMetadata>,
+        system_metadata: Option<&SystemMetadata>,
     ) -> Self {
         Self {
             keys,
-            value,
+            value: value.into(),
             watermark,
             event_time,
+            trace_context: TraceContext::child_of(&headers),
             headers,
-            user_metadata: user_metadata
-                .map(|metadata| SourceTransformUserMetadata(metadata.0.clone())),
-            system_metadata: system_metadata
-                .map(|metadata| SourceTransformSystemMetadata(metadata.0.clone())),
+            user_metadata: user_metadata.cloned(),
+            system_metadata: system_metadata.cloned(),
+            data: None,
+            decode_error: None,
         }
     }
 
-    #[napi(getter)]
-    pub fn get_value(&self) -> Buffer {
-        self.value.iter().copied().collect::<Vec<u8>>().into()
+    /// The value of the datum, the same buffer on every read.
+    #[napi(getter, ts_return_type = "Buffer")]
+    pub fn get_value(&self) -> DatumValue {
+        self.value.clone()
     }
 
     #[napi(getter)]
@@ -197,19 +135,54 @@ impl SourceTransformDatum {
         self.headers.clone()
     }
 
+    /// Context of the span the datum is handled in, if its headers carry a `traceparent`.
+    #[napi(getter)]
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        self.trace_context.clone()
+    }
+
     #[napi(getter)]
-    pub fn user_metadata(&self) -> Option<SourceTransformUserMetadata> {
+    pub fn user_metadata(&self) -> Option<UserMetadata> {
         self.user_metadata.clone()
     }
 
     #[napi(getter)]
-    pub fn system_metadata(&self) -> Option<SourceTransformSystemMetadata> {
+    pub fn system_metadata(&self) -> Option<SystemMetadata> {
         self.system_metadata.clone()
     }
 
+    /// The value decoded by the codec of the server, null with the raw codec or an empty value.
+    #[napi(getter)]
+    pub fn data(&self) -> Option<serde_json::Value> {
+        self.data.clone()
+    }
+
+    /// Why the value could not be decoded, only set when the decode error policy is `pass`.
+    #[napi(getter)]
+    pub fn decode_error(&self) -> Option<String> {
+        self.decode_error.clone()
+    }
+
     #[napi(setter)]
-    pub fn set_user_metadata(&mut self, user_metadata: &SourceTransformUserMetadata) {
-        self.user_metadata = Some(SourceTransformUserMetadata(user_metadata.0.clone()));
+    pub fn set_user_metadata(&mut self, user_metadata: &UserMetadata) {
+        self.user_metadata = Some(user_metadata.clone());
+    }
+}
+
+impl Clone for SourceTransformDatum {
+    fn clone(&self) -> Self {
+        Self {
+            keys: self.keys.clone(),
+            value: self.value.clone(),
+            watermark: self.watermark,
+            event_time: self.event_time,
+            headers: self.headers.clone(),
+            user_metadata: self.user_metadata.clone(),
+            system_metadata: self.system_metadata.clone(),
+            trace_context: self.trace_context.clone(),
+            data: self.data.clone(),
+            decode_error: self.decode_error.clone(),
+        }
     }
 }
 
@@ -220,9 +193,12 @@ impl From<sourcetransform::SourceTransformRequest> for SourceTransformDatum {
             value: value.value.into(),
             watermark: value.watermark,
             event_time: value.eventtime,
+            trace_context: TraceContext::child_of(&value.headers),
             headers: value.headers,
-            user_metadata: Some(SourceTransformUserMetadata(value.user_metadata)),
-            system_metadata: Some(SourceTransformSystemMetadata(value.system_metadata)),
+            user_metadata: Some(UserMetadata::from_numaflow(&value.user_metadata)),
+            system_metadata: Some(SystemMetadata::from_numaflow(&value.system_metadata)),
+            data: None,
+            decode_error: None,
         }
     }
 }
@@ -239,7 +215,8 @@ pub struct SourceTransformAsyncServer {
             true,
         >,
     >,
-    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    retry: Retry,
+    lifecycle: Lifecycle,
 }
 
 #[napi(namespace = "sourceTransform")]
@@ -256,45 +233,50 @@ impl SourceTransformAsyncServer {
                 true,
             >,
         >,
-    ) -> Self {
-        Self {
+        retry_policy: Option<RetryPolicy>,
+    ) -> napi::Result<Self> {
+        Ok(Self {
             source_transform_fn,
-            shutdown_tx: Mutex::new(None),
-        }
+            retry: retry_policy.try_into()?,
+            lifecycle: Lifecycle::new("sourceTransform"),
+        })
     }
 
     #[napi]
-    pub async fn start(
-        &self,
-        sock_file: Option<String>,
-        info_file: Option<String>,
-    ) -> napi::Result<()> {
-        let js_mapper = SourceTransformer::new(Arc::clone(&self.source_transform_fn));
-
-        let mut server = sourcetransform::Server::new(js_mapper);
-        if let Some(sock_file) = sock_file {
-            server = server.with_socket_file(sock_file);
-        }
-        if let Some(info_file) = info_file {
-            server = server.with_server_info_file(info_file);
+    pub async fn start(&self, options: Option<ServerOptions>) -> napi::Result<()> {
+        let options = config::resolve(options)?;
+        let js_mapper = SourceTransformer::new(
+            Arc::clone(&self.source_transform_fn),
+            self.retry.clone(),
+            ConcurrencyLimit::new("sourceTransform", &options)?,
+            self.lifecycle.in_flight(),
+            Codec::new("sourceTransform", &options)?,
+        );
+
+        let server = options.configure(sourcetransform::Server::new(js_mapper));
+
+        let _metrics_server = MetricsServer::start(&options)?;
+        if let Err(e) = self
+            .lifecycle
+            .serve(&options, |rx| server.start_with_shutdown(rx))
+            .await
+        {
+            error!(
+                component = "sourceTransform",
+                error = ?e,
+                "Error running SourceTransformAsyncServer"
+            );
         }
-
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        self.shutdown_tx.lock().unwrap().replace(tx);
-        if let Err(e) = server.start_with_shutdown(rx).await {
-            println!("Error running SourceTransformAsyncServer: {e:?}");
-        }
-        println!("SourceTransformAsyncServer has shutdown...");
+        info!(
+            component = "sourceTransform",
+            "SourceTransformAsyncServer has shut down"
+        );
         Ok(())
     }
 
     #[napi]
-    pub fn stop(&self) -> napi::Result<()> {
-        let tx = { self.shutdown_tx.lock().unwrap().take() };
-        if let Some(tx) = tx {
-            let _ = tx.send(());
-        }
-        Ok(())
+    pub async fn stop(&self, timeout_ms: Option<u32>) -> DrainSummary {
+        self.lifecycle.stop(timeout_ms).await
     }
 }
 
@@ -309,6 +291,10 @@ struct SourceTransformer {
             true,
         >,
     >,
+    retry: Retry,
+    limit: ConcurrencyLimit,
+    in_flight: InFlight,
+    codec: Codec,
 }
 
 impl SourceTransformer {
@@ -323,9 +309,17 @@ impl SourceTransformer {
                 true,
             >,
         >,
+        retry: Retry,
+        limit: ConcurrencyLimit,
+        in_flight: InFlight,
+        codec: Codec,
     ) -> Self {
         Self {
             source_transform_fn,
+            retry,
+            limit,
+            in_flight,
+            codec,
         }
     }
 }
@@ -336,21 +330,42 @@ impl sourcetransform::SourceTransformer for SourceTransformer {
         &self,
         datum: sourcetransform::SourceTransformRequest,
     ) -> Vec<sourcetransform::Message> {
-        match self.source_transform_fn.call_async(datum.into()).await {
-            Ok(promise) => match promise.await {
-                Ok(messages) => messages.into_iter().map(|message| message.into()).collect(),
-                Err(e) => {
-                    eprintln!(
-                        "[ERROR] User-defined transform function returned an error: {:?}",
-                        e
-                    );
-                    panic!("User-defined transform function returned an error: {:?}", e);
-                }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Executing user-defined transform function: {:?}", e);
-                panic!("Error executing user-defined transform function: {:?}", e);
+        let _in_flight = self.in_flight.track();
+        let mut datum: SourceTransformDatum = datum.into();
+        match datum.value.with(|value| self.codec.decode(value)) {
+            Decoded::Datum { data, error } => {
+                datum.data = data;
+                datum.decode_error = error;
+            }
+            Decoded::Drop => {
+                metrics::datum_dropped("sourceTransform");
+                return vec![sourcetransform::Message::message_to_drop(datum.event_time)];
+            }
+            Decoded::Fail(reason) => UdfError::decode("sourceTransform", reason).raise(),
+        }
+        let _permit = self.limit.acquire(&datum.keys).await;
+        let span = trace::handler_span("sourceTransform", datum.trace_context.as_ref());
+        async {
+            let messages = self
+                .retry
+                .run(datum, |datum| {
+                    error::call_promise("sourceTransform", &self.source_transform_fn, datum)
+                })
+                .await;
+            match messages {
+                Ok(messages) => messages
+                    .into_iter()
+                    .map(|message| {
+                        metrics::message_emitted("sourceTransform", message.tags.as_deref());
+                        message
+                            .encode(&self.codec)
+                            .unwrap_or_else(|e| UdfError::forward("sourceTransform", e).raise())
+                    })
+                    .collect(),
+                Err(e) => e.raise(),
             }
         }
+        .instrument(span)
+        .await
     }
 }
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use napi_derive::napi;
+use tracing::Span;
+use tracing::field::Empty;
+
+const TRACEPARENT: &str = "traceparent";
+const TRACESTATE: &str = "tracestate";
+/// Version of the `traceparent` format that is written.
+const VERSION: &str = "00";
+const FLAG_SAMPLED: u8 = 0x01;
+
+/// [W3C trace context](https://www.w3.org/TR/trace-context/) of the span a datum is handled in.
+///
+/// The span is a child of the span in the `traceparent` header of the datum, spans created by the
+/// JS handler should be parented on it, e.g. by extracting `traceparent` with the OpenTelemetry
+/// propagation API.
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceContext {
+    /// Id of the trace, 32 lowercase hex digits.
+    pub trace_id: String,
+    /// Id of the span, 16 lowercase hex digits.
+    pub span_id: String,
+    /// Id of the parent span from the `traceparent` header, not set for a trace started by the
+    /// server.
+    pub parent_span_id: Option<String>,
+    /// Whether the trace is sampled.
+    pub sampled: bool,
+    /// `traceparent` header of the span.
+    pub traceparent: String,
+    /// `tracestate` header of the datum, passed on as is.
+    pub tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// Context of a new span that is a child of the span in the `traceparent` header, if there is
+    /// a valid one.
+    pub(crate) fn child_of(headers: &HashMap<String, String>) -> Option<Self> {
+        let traceparent = header(headers, TRACEPARENT)?;
+        let (trace_id, parent_span_id, flags) = parse_traceparent(traceparent)?;
+        Some(Self::new(
+            trace_id.to_string(),
+            Some(parent_span_id.to_string()),
+            flags & FLAG_SAMPLED != 0,
+            header(headers, TRACESTATE).map(str::to_string),
+        ))
+    }
+
+    /// Context of the root span of a new, sampled trace.
+    pub(crate) fn root() -> Self {
+        Self::new(random_id(16), None, true, None)
+    }
+
+    fn new(
+        trace_id: String,
+        parent_span_id: Option<String>,
+        sampled: bool,
+        tracestate: Option<String>,
+    ) -> Self {
+        let span_id = random_id(8);
+        let flags = if sampled { FLAG_SAMPLED } else { 0 };
+        Self {
+            traceparent: format!("{VERSION}-{trace_id}-{span_id}-{flags:02x}"),
+            trace_id,
+            span_id,
+            parent_span_id,
+            sampled,
+            tracestate,
+        }
+    }
+
+    /// Sets the `traceparent` and `tracestate` headers to this context, replacing the ones of
+    /// any case that are there.
+    pub(crate) fn inject(&self, headers: &mut HashMap<String, String>) {
+        headers.retain(|name, _| {
+            !name.eq_ignore_ascii_case(TRACEPARENT) && !name.eq_ignore_ascii_case(TRACESTATE)
+        });
+        headers.insert(TRACEPARENT.to_string(), self.traceparent.clone());
+        if let Some(tracestate) = &self.tracestate {
+            headers.insert(TRACESTATE.to_string(), tracestate.clone());
+        }
+    }
+}
+
+/// Whether the headers have a `traceparent` header of any case.
+pub(crate) fn has_traceparent(headers: &HashMap<String, String>) -> bool {
+    header(headers, TRACEPARENT).is_some()
+}
+
+/// Span around a call of the JS handler, carrying the ids of `context` when it is handling a
+/// single datum.
+pub(crate) fn handler_span(handler: &'static str, context: Option<&TraceContext>) -> Span {
+    let span = tracing::info_span!(
+        "handler",
+        handler,
+        trace_id = Empty,
+        span_id = Empty,
+        parent_span_id = Empty
+    );
+    if let Some(context) = context {
+        span.record("trace_id", context.trace_id.as_str());
+        span.record("span_id", context.span_id.as_str());
+        if let Some(parent_span_id) = &context.parent_span_id {
+            span.record("parent_span_id", parent_span_id.as_str());
+        }
+    }
+    span
+}
+
+/// Header names are matched regardless of case, HTTP sources pass them on as they were sent.
+fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .or_else(|| {
+            headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value)
+        })
+        .map(|value| value.trim())
+}
+
+/// Splits a `traceparent` into trace id, parent span id and flags. Later versions may append
+/// fields, which are ignored.
+fn parse_traceparent(traceparent: &str) -> Option<(&str, &str, u8)> {
+    let mut fields = traceparent.split('-');
+    let version = fields.next()?;
+    let trace_id = fields.next()?;
+    let parent_span_id = fields.next()?;
+    let flags = fields.next()?;
+    let valid = is_hex(version, 2)
+        && version != "ff"
+        && (version != VERSION || fields.next().is_none())
+        && is_hex(trace_id, 32)
+        && is_hex(parent_span_id, 16)
+        && is_hex(flags, 2)
+        && trace_id.bytes().any(|b| b != b'0')
+        && parent_span_id.bytes().any(|b| b != b'0');
+    if !valid {
+        return None;
+    }
+    Some((
+        trace_id,
+        parent_span_id,
+        u8::from_str_radix(flags, 16).ok()?,
+    ))
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// A random, non-zero id of `bytes` bytes in lowercase hex.
+fn random_id(bytes: usize) -> String {
+    loop {
+        let id: String = (0..bytes)
+            .map(|_| format!("{:02x}", fastrand::u8(..)))
+            .collect();
+        if id.bytes().any(|b| b != b'0') {
+            return id;
+        }
+    }
+}
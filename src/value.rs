@@ -0,0 +1,68 @@
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use napi::bindgen_prelude::{Buffer, FromNapiValue, ToNapiValue, TypeName, ValueType};
+use napi::{Result, sys};
+
+/// The value of a datum, handed to the JS handler without copying it.
+///
+/// The bytes received from numaflow are moved into a Node.js buffer the first time the value is
+/// read. Every later read, including the ones of the copies of the datum that retries hand to the
+/// handler, returns that same buffer, so handlers should treat it as read-only.
+#[derive(Clone)]
+pub struct DatumValue(Arc<Mutex<Buffer>>);
+
+impl DatumValue {
+    /// Calls `f` with the bytes of the value, e.g. to decode them with the codec of the server.
+    pub(crate) fn with<T>(&self, f: impl FnOnce(&[u8]) -> T) -> T {
+        f(&self.0.lock().unwrap())
+    }
+
+    /// A copy of the value that shares no buffer with this one, for handing the datum to another
+    /// JS environment, e.g. a worker thread.
+    pub(crate) fn detach(&self) -> Self {
+        Self::from(self.with(<[u8]>::to_vec))
+    }
+}
+
+impl From<Vec<u8>> for DatumValue {
+    fn from(value: Vec<u8>) -> Self {
+        Self::from(Buffer::from(value))
+    }
+}
+
+impl From<Buffer> for DatumValue {
+    fn from(value: Buffer) -> Self {
+        Self(Arc::new(Mutex::new(value)))
+    }
+}
+
+impl TypeName for DatumValue {
+    fn type_name() -> &'static str {
+        Buffer::type_name()
+    }
+
+    fn value_type() -> ValueType {
+        Buffer::value_type()
+    }
+}
+
+impl FromNapiValue for DatumValue {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> Result<Self> {
+        Ok(Self::from(unsafe {
+            Buffer::from_napi_value(env, napi_val)?
+        }))
+    }
+}
+
+impl ToNapiValue for DatumValue {
+    unsafe fn to_napi_value(env: sys::napi_env, val: Self) -> Result<sys::napi_value> {
+        let mut buffer = val.0.lock().unwrap();
+        // Converting a buffer that still owns the bytes moves them into an external Node.js
+        // buffer, converting one that references a Node.js buffer returns it. Either way the
+        // reference it is consumed for is replaced with a new one to the same buffer.
+        let value = unsafe { Buffer::to_napi_value(env, mem::take(&mut *buffer))? };
+        *buffer = unsafe { Buffer::from_napi_value(env, value)? };
+        Ok(value)
+    }
+}
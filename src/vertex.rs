@@ -0,0 +1,247 @@
+use std::sync::LazyLock;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::config;
+
+const ENV_PIPELINE_NAME: &str = "NUMAFLOW_PIPELINE_NAME";
+const ENV_VERTEX_NAME: &str = "NUMAFLOW_VERTEX_NAME";
+const ENV_REPLICA: &str = "NUMAFLOW_REPLICA";
+/// Base64 encoded JSON of the vertex spec. Set by numaflow.
+const ENV_VERTEX_OBJECT: &str = "NUMAFLOW_VERTEX_OBJECT";
+
+static VERTEX: LazyLock<Vertex> = LazyLock::new(Vertex::from_env);
+
+/// The vertex the server runs in, as far as numaflow tells it through the environment.
+#[derive(Default)]
+pub(crate) struct Vertex {
+    pub(crate) pipeline_name: Option<String>,
+    pub(crate) vertex_name: Option<String>,
+    pub(crate) replica: Option<u32>,
+    /// Window the vertex groups its datums by, if it is a reduce vertex.
+    pub(crate) window: Option<Window>,
+}
+
+/// Window of a reduce vertex, with its durations in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Window {
+    Fixed { length_ms: i64 },
+    Sliding { length_ms: i64, slide_ms: i64 },
+}
+
+impl Window {
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::Fixed { .. } => "fixed",
+            Self::Sliding { .. } => "sliding",
+        }
+    }
+
+    pub(crate) fn length_ms(&self) -> i64 {
+        match *self {
+            Self::Fixed { length_ms } | Self::Sliding { length_ms, .. } => length_ms,
+        }
+    }
+
+    /// Time between the starts of two consecutive windows, the length of a fixed window.
+    pub(crate) fn slide_ms(&self) -> i64 {
+        match *self {
+            Self::Fixed { length_ms } => length_ms,
+            Self::Sliding { slide_ms, .. } => slide_ms,
+        }
+    }
+}
+
+/// Returns the vertex the server runs in, read from the environment on first use.
+pub(crate) fn get() -> &'static Vertex {
+    &VERTEX
+}
+
+impl Vertex {
+    fn from_env() -> Self {
+        let window = config::env_var(ENV_VERTEX_OBJECT).and_then(|encoded| {
+            decode_window(&encoded)
+                .inspect_err(|e| warn!(error = %e, "Ignoring the window of {ENV_VERTEX_OBJECT}"))
+                .ok()
+                .flatten()
+        });
+        Self {
+            pipeline_name: config::env_var(ENV_PIPELINE_NAME),
+            vertex_name: config::env_var(ENV_VERTEX_NAME),
+            replica: config::env_var(ENV_REPLICA).and_then(|replica| replica.parse().ok()),
+            window,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct VertexObject {
+    spec: VertexSpec,
+}
+
+#[derive(Deserialize)]
+struct VertexSpec {
+    udf: Option<UdfSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UdfSpec {
+    group_by: Option<GroupBy>,
+}
+
+#[derive(Deserialize)]
+struct GroupBy {
+    window: WindowSpec,
+}
+
+#[derive(Deserialize)]
+struct WindowSpec {
+    fixed: Option<FixedWindowSpec>,
+    sliding: Option<SlidingWindowSpec>,
+}
+
+#[derive(Deserialize)]
+struct FixedWindowSpec {
+    length: String,
+}
+
+#[derive(Deserialize)]
+struct SlidingWindowSpec {
+    length: String,
+    slide: String,
+}
+
+/// Decodes the window of the vertex spec, `None` if the vertex has no fixed or sliding window.
+fn decode_window(encoded: &str) -> Result<Option<Window>, String> {
+    let json = BASE64.decode(encoded).map_err(|e| e.to_string())?;
+    let vertex: VertexObject = serde_json::from_slice(&json).map_err(|e| e.to_string())?;
+    let Some(window) = vertex
+        .spec
+        .udf
+        .and_then(|udf| udf.group_by)
+        .map(|group_by| group_by.window)
+    else {
+        return Ok(None);
+    };
+    if let Some(fixed) = window.fixed {
+        return Ok(Some(Window::Fixed {
+            length_ms: duration_ms(&fixed.length)?,
+        }));
+    }
+    if let Some(sliding) = window.sliding {
+        return Ok(Some(Window::Sliding {
+            length_ms: duration_ms(&sliding.length)?,
+            slide_ms: duration_ms(&sliding.slide)?,
+        }));
+    }
+    Ok(None)
+}
+
+/// Parses a Go duration such as `1m30s` or `500ms` into milliseconds.
+fn duration_ms(duration: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid duration {duration:?}");
+    let mut rest = duration;
+    let mut total_ms = 0.0;
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let unit_at = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(invalid)?;
+        let value: f64 = rest[..unit_at].parse().map_err(|_| invalid())?;
+        let number_at = rest[unit_at..]
+            .find(|c: char| c.is_ascii_digit())
+            .map_or(rest.len(), |at| unit_at + at);
+        let unit_ms = match &rest[unit_at..number_at] {
+            "h" => 3_600_000.0,
+            "m" => 60_000.0,
+            "s" => 1_000.0,
+            "ms" => 1.0,
+            "us" | "µs" => 0.001,
+            "ns" => 0.000_001,
+            _ => return Err(invalid()),
+        };
+        total_ms += value * unit_ms;
+        rest = &rest[number_at..];
+    }
+    Ok(total_ms.round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(vertex: serde_json::Value) -> String {
+        BASE64.encode(vertex.to_string())
+    }
+
+    #[test]
+    fn parses_go_durations() {
+        assert_eq!(duration_ms("1m30s"), Ok(90_000));
+        assert_eq!(duration_ms("500ms"), Ok(500));
+        assert_eq!(duration_ms("1.5h"), Ok(5_400_000));
+        assert_eq!(duration_ms("2s1500us"), Ok(2_002));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for duration in ["", "10", "1d", "s", "1.2.3s", "-1s"] {
+            assert!(
+                duration_ms(duration).is_err(),
+                "{duration:?} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_fixed_windows() {
+        let encoded = encode(serde_json::json!({
+            "spec": { "udf": { "groupBy": { "window": { "fixed": { "length": "1m" } } } } }
+        }));
+        assert_eq!(
+            decode_window(&encoded),
+            Ok(Some(Window::Fixed { length_ms: 60_000 }))
+        );
+    }
+
+    #[test]
+    fn decodes_sliding_windows() {
+        let encoded = encode(serde_json::json!({
+            "spec": {
+                "udf": {
+                    "groupBy": { "window": { "sliding": { "length": "1m", "slide": "10s" } } }
+                }
+            }
+        }));
+        assert_eq!(
+            decode_window(&encoded),
+            Ok(Some(Window::Sliding {
+                length_ms: 60_000,
+                slide_ms: 10_000,
+            }))
+        );
+    }
+
+    #[test]
+    fn has_no_window_without_group_by() {
+        let encoded = encode(serde_json::json!({ "spec": { "udf": { "container": {} } } }));
+        assert_eq!(decode_window(&encoded), Ok(None));
+        let encoded = encode(serde_json::json!({ "spec": {} }));
+        assert_eq!(decode_window(&encoded), Ok(None));
+    }
+
+    #[test]
+    fn rejects_malformed_vertex_objects() {
+        assert!(decode_window("not base64!").is_err());
+        assert!(decode_window(&BASE64.encode("not json")).is_err());
+        let encoded = encode(serde_json::json!({
+            "spec": { "udf": { "groupBy": { "window": { "fixed": { "length": "1 minute" } } } } }
+        }));
+        assert!(decode_window(&encoded).is_err());
+    }
+}
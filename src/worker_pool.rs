@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::pin::pin;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, Weak};
+use std::time::Duration;
+
+use napi_derive::napi;
+use tokio::sync::{Notify, watch};
+use tracing::{info, warn};
+
+use crate::error::{self, UdfError};
+use crate::map::{Datum, MapFn, Message};
+
+/// How many workers may exit while handling the same datum before its request fails.
+const MAX_WORKER_EXITS: u32 = 3;
+
+/// The pools of the map servers in this process, by id, for the worker threads to register with.
+static POOLS: LazyLock<Mutex<HashMap<u32, Weak<WorkerPool>>>> = LazyLock::new(Default::default);
+
+static NEXT_POOL_ID: AtomicU32 = AtomicU32::new(1);
+
+/// A worker thread that registered its handler with a pool.
+struct Worker {
+    id: u32,
+    map_fn: Arc<MapFn>,
+    /// Set once the thread exited, settling the calls it never will.
+    exited: watch::Sender<bool>,
+}
+
+/// The worker threads a map server hands its datums to, in turns.
+///
+/// The JS side spawns the threads and reports when they exit, each thread registers its own
+/// handler. The native side picks the worker of every call and hands a datum to another worker
+/// when the one handling it exits before settling the call.
+pub(crate) struct WorkerPool {
+    id: u32,
+    workers: Mutex<Vec<Arc<Worker>>>,
+    next: AtomicUsize,
+    registered: Notify,
+}
+
+impl WorkerPool {
+    pub(crate) fn new() -> Arc<Self> {
+        let pool = Arc::new(Self {
+            id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+            workers: Mutex::default(),
+            next: AtomicUsize::new(0),
+            registered: Notify::new(),
+        });
+        POOLS.lock().unwrap().insert(pool.id, Arc::downgrade(&pool));
+        pool
+    }
+
+    /// Id the worker threads of the pool register with.
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Stops handing datums to the worker, settling the calls it was handling.
+    pub(crate) fn worker_exited(&self, worker_id: u32, exit_code: i32, error: Option<String>) {
+        warn!(
+            component = "map",
+            pool = self.id,
+            worker = worker_id,
+            exit_code,
+            error = error.as_deref(),
+            "Worker thread exited"
+        );
+        self.remove(worker_id);
+    }
+
+    fn remove(&self, worker_id: u32) {
+        let mut workers = self.workers.lock().unwrap();
+        if let Some(index) = workers.iter().position(|worker| worker.id == worker_id) {
+            workers.remove(index).exited.send_replace(true);
+        }
+    }
+
+    /// Calls the handler of the next worker with `datum`. When the worker exits before settling
+    /// the call, the datum is handed to another worker, up to [`MAX_WORKER_EXITS`] times.
+    pub(crate) async fn map(
+        &self,
+        datum: Datum,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<Message>, UdfError> {
+        let mut exits = 0;
+        loop {
+            let worker = self.next_worker().await;
+            let mut exited = worker.exited.subscribe();
+            // Every worker gets its own copy of the value, a buffer handed to one JS environment
+            // must not be read from another one.
+            let call = error::within(
+                "map",
+                timeout,
+                error::call_promise("map", &worker.map_fn, datum.detached()),
+            );
+            tokio::select! {
+                result = call => match result {
+                    // The thread is exiting, which may be noticed before it is reported to have
+                    // exited.
+                    Err(e) if e.is_interrupted() => self.remove(worker.id),
+                    result => return result,
+                },
+                _ = exited.wait_for(|exited| *exited) => {}
+            }
+            exits += 1;
+            if exits == MAX_WORKER_EXITS {
+                return Err(UdfError::worker_exited("map", exits));
+            }
+            warn!(
+                component = "map",
+                pool = self.id,
+                worker = worker.id,
+                "Worker thread exited while handling a datum, handing it to another worker"
+            );
+        }
+    }
+
+    /// The worker whose turn it is, waiting for one to register if there is none.
+    async fn next_worker(&self) -> Arc<Worker> {
+        loop {
+            let mut registered = pin!(self.registered.notified());
+            registered.as_mut().enable();
+            {
+                let workers = self.workers.lock().unwrap();
+                if !workers.is_empty() {
+                    let turn = self.next.fetch_add(1, Ordering::Relaxed);
+                    return Arc::clone(&workers[turn % workers.len()]);
+                }
+            }
+            registered.await;
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        POOLS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Registers the handler of a worker thread with the pool of a map server. Called by
+/// `map.registerWorker` in the worker thread, whose wrapper reports the errors of the handler:
+/// any other failure of a call is taken for the thread exiting.
+#[napi(
+    namespace = "map",
+    ts_args_type = "poolId: number, workerId: number, mapFn: (arg: Datum) => Promise<Array<Message>>"
+)]
+pub fn register_worker(pool_id: u32, worker_id: u32, map_fn: Arc<MapFn>) -> napi::Result<()> {
+    let pool = POOLS
+        .lock()
+        .unwrap()
+        .get(&pool_id)
+        .and_then(Weak::upgrade)
+        .ok_or_else(|| {
+            napi::Error::from_reason(format!("No worker pool with id {pool_id} in this process"))
+        })?;
+    pool.workers.lock().unwrap().push(Arc::new(Worker {
+        id: worker_id,
+        map_fn,
+        exited: watch::Sender::new(false),
+    }));
+    pool.registered.notify_waiters();
+    info!(
+        component = "map",
+        pool = pool_id,
+        worker = worker_id,
+        "Worker thread registered"
+    );
+    Ok(())
+}
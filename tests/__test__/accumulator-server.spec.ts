@@ -119,3 +119,65 @@ test('accumulator integration test', async () => {
         server.stop()
     }
 }, 120000)
+
+class Counter implements accumulator.Accumulator {
+    counter = 0
+
+    async *accumulatorFn(datums: AsyncIterableIterator<accumulator.Datum>): AsyncIterable<accumulator.Message> {
+        for await (const datum of datums) {
+            this.counter += 1
+            yield new accumulator.Message(
+                Buffer.from(`${datum.keys}:${this.counter}`),
+                datum.id,
+                datum.eventTime,
+                datum.watermark,
+                datum.headers,
+                { keys: datum.keys },
+            )
+        }
+    }
+}
+
+test('accumulator factory gives every key its own accumulator', async () => {
+    const factorySockPath = '/tmp/var/run/numaflow/accumulator-factory.sock'
+    const factoryInfoPath = '/tmp/var/run/numaflow/accumulator-factory-info.sock'
+    let created = 0
+    const server = accumulator.AsyncServer.withFactory(() => {
+        created += 1
+        return new Counter()
+    })
+
+    try {
+        server.start(factorySockPath, factoryInfoPath)
+        await sleep(500)
+
+        // The client asserts that every key counted its own datums
+        const cargoProcess = spawn(
+            'cargo',
+            ['run', '-p', 'tests', '--bin', 'accumulator_keys', '--', factorySockPath],
+            { stdio: 'pipe' },
+        )
+
+        let stdout = ''
+        let stderr = ''
+        cargoProcess.stdout?.on('data', (data) => {
+            stdout += data.toString()
+        })
+        cargoProcess.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+
+        if (exitCode !== 0) {
+            expect.fail(`Cargo command failed with exit code ${exitCode}\n\nStdout:\n${stdout}\n\nStderr:\n${stderr}`)
+        }
+        expect(created).toBe(2)
+    } finally {
+        server.stop()
+    }
+}, 120000)
@@ -0,0 +1,72 @@
+import { test, expect } from 'vitest'
+import { spawn } from 'child_process'
+import { promisify } from 'util'
+
+import { CompositeServer, map, sideInput } from '../../index.js'
+
+const sleep = promisify(setTimeout)
+
+function runClient(bin: string, args: string[]): Promise<number> {
+    const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', bin, '--', ...args], { stdio: 'inherit' })
+    return new Promise<number>((resolve) => {
+        cargoProcess.on('close', (code) => {
+            resolve(code ?? 1)
+        })
+    })
+}
+
+test('composite server runs a map and a side input server with one lifecycle', async () => {
+    let calls = 0
+    const server = new CompositeServer()
+        .add('map', new map.AsyncServer(async (datum) => [new map.Message(datum.value, { keys: datum.keys })]), {
+            socketPath: '/tmp/composite-map.sock',
+            serverInfoPath: '/tmp/composite-map.info',
+        })
+        .add(
+            'sideInput',
+            new sideInput.SideInputAsyncServer(async () => (calls++ % 2 === 0 ? Buffer.from('side-input-value') : null)),
+            { socketPath: '/tmp/composite-side-input.sock', serverInfoPath: '/tmp/composite-side-input.info' },
+        )
+    expect(server.status()).toEqual([
+        { name: 'map', state: 'idle' },
+        { name: 'sideInput', state: 'idle' },
+    ])
+
+    const started = server.start({ metricsPort: 9468 })
+    try {
+        await sleep(500)
+        expect(await runClient('map_burst', ['/tmp/composite-map.sock', '4'])).toBe(0)
+        expect(await runClient('sideinput', ['/tmp/composite-side-input.sock'])).toBe(0)
+        expect(server.status().map(({ state }) => state)).toEqual(['running', 'running'])
+
+        // Both servers are served from the one endpoint of the shared metrics port.
+        const metrics = await (await fetch('http://127.0.0.1:9468/metrics')).text()
+        expect(metrics).toContain('numaflow_js_requests_total{server="map"} 4')
+        expect(metrics).toContain('numaflow_js_requests_total{server="sideInput"} 2')
+    } finally {
+        const summaries = await server.stop()
+        expect(Object.keys(summaries)).toEqual(['map', 'sideInput'])
+    }
+    await started
+    expect(server.status().map(({ state }) => state)).toEqual(['stopped', 'stopped'])
+}, 120000)
+
+test('composite server stops the other servers when one fails', async () => {
+    const server = new CompositeServer()
+        .add('sideInput', new sideInput.SideInputAsyncServer(async () => null), {
+            socketPath: '/tmp/composite-failing-side-input.sock',
+            serverInfoPath: '/tmp/composite-failing-side-input.info',
+        })
+        .add('map', new map.AsyncServer(async () => []), {
+            socketPath: '/tmp/composite-failing-map.sock',
+            serverInfoPath: '/tmp/composite-failing-map.info',
+            maxInFlight: 0,
+        })
+
+    await expect(server.start()).rejects.toThrow(/maxInFlight must be at least 1/)
+    const [sideInputStatus, mapStatus] = server.status()
+    expect(sideInputStatus).toEqual({ name: 'sideInput', state: 'stopped' })
+    expect(mapStatus.state).toBe('failed')
+    expect(mapStatus.error?.message).toMatch(/maxInFlight must be at least 1/)
+    expect(() => server.add('map', new map.AsyncServer(async () => []))).toThrow(/already added/)
+})
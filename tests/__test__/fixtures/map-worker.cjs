@@ -0,0 +1,23 @@
+// Worker thread script of the worker pool test in map-server.spec.ts, echoes every datum. The first
+// worker to handle `value-3` exits the thread, the pool hands the datum to another worker.
+const { mkdirSync } = require('fs')
+
+const { map } = require('../../../index.js')
+
+const CRASHED_MARKER = '/tmp/map-worker-pool.crashed'
+
+function claimCrash() {
+    try {
+        mkdirSync(CRASHED_MARKER)
+        return true
+    } catch {
+        return false
+    }
+}
+
+map.registerWorker(async (datum) => {
+    if (datum.value.toString() === 'value-3' && claimCrash()) {
+        process.exit(1)
+    }
+    return [new map.Message(datum.value, { keys: datum.keys })]
+})
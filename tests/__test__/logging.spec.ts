@@ -0,0 +1,48 @@
+import { afterEach, test, expect } from 'vitest'
+import { promisify } from 'util'
+
+import { configureLogging, map, setLogHandler, LogRecord } from '../../index.js'
+
+const sleep = promisify(setTimeout)
+
+afterEach(() => {
+    setLogHandler(null)
+    configureLogging({ level: 'info', format: 'json' })
+})
+
+test('log records are routed to the log handler', async () => {
+    const records: LogRecord[] = []
+    setLogHandler((record) => records.push(record))
+
+    const server = new map.AsyncServer(async (datum) => [{ keys: datum.keys, value: datum.value }])
+    server.start({ socketPath: '/tmp/logging.sock', serverInfoPath: '/tmp/logging.info' })
+    await sleep(500)
+    await server.stop()
+    await sleep(100)
+
+    const shutdown = records.find((record) => record.message === 'MapAsyncServer has shut down')
+    expect(shutdown).toBeDefined()
+    expect(shutdown!.level).toBe('info')
+    expect(shutdown!.target).toBe('numaflow_js::map')
+    expect(shutdown!.fields).toEqual({ component: 'map' })
+    expect(shutdown!.timestamp).toBeInstanceOf(Date)
+})
+
+test('log level filters the records', async () => {
+    const records: LogRecord[] = []
+    setLogHandler((record) => records.push(record))
+    configureLogging({ level: 'warn' })
+
+    const server = new map.AsyncServer(async (datum) => [{ keys: datum.keys, value: datum.value }])
+    server.start({ socketPath: '/tmp/logging-level.sock', serverInfoPath: '/tmp/logging-level.info' })
+    await sleep(500)
+    await server.stop()
+    await sleep(100)
+
+    expect(records.filter((record) => record.level === 'info')).toEqual([])
+})
+
+test('invalid log options are rejected', () => {
+    expect(() => configureLogging({ level: 'loud' as any })).toThrow(/log level/)
+    expect(() => configureLogging({ format: 'xml' as any })).toThrow(/log format/)
+})
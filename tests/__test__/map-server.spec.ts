@@ -1,9 +1,11 @@
 import { test, expect } from 'vitest'
 import { spawn } from 'child_process'
+import { existsSync, mkdirSync, rmSync } from 'fs'
+import { createServer } from 'net'
 import { promisify } from 'util'
 
-import { map } from '../../index.js'
-const { AsyncServer, Message, UserMetadata } = map
+import { map, type Codec } from '../../index.js'
+const { AsyncServer, Message, MicroBatchServer, UserMetadata, WorkerPoolServer } = map
 
 const sleep = promisify(setTimeout)
 
@@ -80,3 +82,524 @@ test('mapper integration test', async () => {
         server.stop()
     }
 }, 120000)
+
+test('mapper retries transient failures', async () => {
+    class TransientError extends Error {
+        name = 'TransientError'
+    }
+
+    // Every datum fails on its first attempt, the retry policy has to hide that from the client.
+    const attempts = new Map<string, number>()
+    const mapFn = async (datum: map.Datum): Promise<map.Message[]> => {
+        const value = datum.value.toString()
+        const attempt = (attempts.get(value) ?? 0) + 1
+        attempts.set(value, attempt)
+        if (attempt === 1) {
+            throw new TransientError(`lookup for ${value} timed out`)
+        }
+        if (value === 'bad') {
+            return [Message.toDrop()]
+        }
+        const userMetadata = new UserMetadata()
+        userMetadata.addKv('custom-group', 'custom-key', Buffer.from('custom-value'))
+        for (const group of datum.userMetadata?.getGroups() ?? []) {
+            datum.userMetadata?.getKeys(group).forEach((key) => {
+                userMetadata.addKv(group, key, datum.userMetadata!.getValue(group, key))
+            })
+        }
+        return [{ keys: [datum.keys[0]], value: datum.value, userMetadata }]
+    }
+
+    const server = new AsyncServer(mapFn, { maxAttempts: 2, initialBackoffMs: 10, retryOn: ['TransientError'] })
+    const sockFile = '/tmp/map-retry.sock'
+    const infoFile = '/tmp/map-retry.info'
+
+    try {
+        server.start(sockFile, infoFile)
+        await sleep(500)
+
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'map', '--', sockFile], {
+            stdio: 'pipe',
+        })
+        let stderr = ''
+        cargoProcess.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+
+        if (exitCode !== 0) {
+            expect.fail(`Cargo command failed with exit code ${exitCode}\n\nStderr:\n${stderr}`)
+        }
+        expect([...attempts.values()]).toEqual([2, 2, 2])
+    } finally {
+        server.stop()
+    }
+}, 120000)
+
+test('mapper drains in-flight requests on stop', async () => {
+    // The first datum is held back until the server is stopped, which has to wait for it.
+    let markStarted: () => void
+    const started = new Promise<void>((resolve) => {
+        markStarted = resolve
+    })
+    let first = true
+    const mapFn = async (datum: map.Datum): Promise<map.Message[]> => {
+        if (first) {
+            first = false
+            markStarted()
+            await sleep(500)
+        }
+        if (datum.value.toString() === 'bad') {
+            return [Message.toDrop()]
+        }
+        const userMetadata = new UserMetadata()
+        userMetadata.addKv('custom-group', 'custom-key', Buffer.from('custom-value'))
+        for (const group of datum.userMetadata?.getGroups() ?? []) {
+            datum.userMetadata?.getKeys(group).forEach((key) => {
+                userMetadata.addKv(group, key, datum.userMetadata!.getValue(group, key))
+            })
+        }
+        return [{ keys: [datum.keys[0]], value: datum.value, userMetadata }]
+    }
+
+    const server = new AsyncServer(mapFn)
+    const sockFile = '/tmp/map-drain.sock'
+    const infoFile = '/tmp/map-drain.info'
+
+    server.start({ socketPath: sockFile, serverInfoPath: infoFile })
+    await sleep(500)
+
+    const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'map', '--', sockFile], {
+        stdio: 'pipe',
+    })
+    const exitCode = new Promise<number>((resolve) => {
+        cargoProcess.on('close', (code) => {
+            resolve(code ?? 1)
+        })
+    })
+
+    await started
+    const summary = await server.stop(10000)
+    expect(summary.drained).toBeGreaterThanOrEqual(1)
+    expect(summary.abandoned).toBe(0)
+    expect(await exitCode).toBe(0)
+}, 120000)
+
+test('mapper serves metrics', async () => {
+    const mapFn = async (datum: map.Datum): Promise<map.Message[]> => {
+        if (datum.value.toString() === 'bad') {
+            return [Message.toDrop()]
+        }
+        const userMetadata = new UserMetadata()
+        userMetadata.addKv('custom-group', 'custom-key', Buffer.from('custom-value'))
+        for (const group of datum.userMetadata?.getGroups() ?? []) {
+            datum.userMetadata?.getKeys(group).forEach((key) => {
+                userMetadata.addKv(group, key, datum.userMetadata!.getValue(group, key))
+            })
+        }
+        return [{ keys: [datum.keys[0]], value: datum.value, userMetadata }]
+    }
+
+    const server = new AsyncServer(mapFn)
+    const sockFile = '/tmp/map-metrics.sock'
+    const infoFile = '/tmp/map-metrics.info'
+    const metricsPort = 9465
+
+    try {
+        server.start({ socketPath: sockFile, serverInfoPath: infoFile, metricsPort })
+        await sleep(500)
+
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'map', '--', sockFile], {
+            stdio: 'pipe',
+        })
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+        expect(exitCode).toBe(0)
+
+        const response = await fetch(`http://127.0.0.1:${metricsPort}/metrics`)
+        expect(response.status).toBe(200)
+        const metrics = await response.text()
+        expect(metrics).toMatch(/^numaflow_js_requests_total\{server="map"\} [1-9]/m)
+        expect(metrics).toMatch(/^numaflow_js_callback_duration_seconds_count\{handler="map"\} [1-9]/m)
+        expect(metrics).toMatch(/^numaflow_js_messages_emitted_total\{server="map"\} [1-9]/m)
+        expect(metrics).toMatch(/^numaflow_js_messages_dropped_total\{server="map"\} [1-9]/m)
+    } finally {
+        await server.stop()
+    }
+}, 120000)
+
+test('mapper rejects a metrics endpoint it cannot serve', async () => {
+    const metricsPort = 9469
+    const taken = createServer()
+    await new Promise<void>((resolve) => taken.listen(metricsPort, resolve))
+
+    const server = new AsyncServer(async (datum) => [{ keys: datum.keys, value: datum.value }])
+    const options = { socketPath: '/tmp/map-metrics-taken.sock', serverInfoPath: '/tmp/map-metrics-taken.info' }
+    try {
+        await expect(server.start({ ...options, metricsPort })).rejects.toThrow(
+            /Failed to serve metrics at 0\.0\.0\.0:9469/,
+        )
+        await expect(server.start({ ...options, metricsPort, metricsAddress: 'localhost' })).rejects.toThrow(
+            /Invalid metricsAddress "localhost"/,
+        )
+    } finally {
+        taken.close()
+    }
+})
+
+test('mapper exposes the trace context of the datum', async () => {
+    const traceContexts = new Map<string, map.Datum['traceContext']>()
+    const mapFn = async (datum: map.Datum): Promise<map.Message[]> => {
+        traceContexts.set(datum.value.toString(), datum.traceContext)
+        if (datum.value.toString() === 'bad') {
+            return [Message.toDrop()]
+        }
+        const userMetadata = new UserMetadata()
+        userMetadata.addKv('custom-group', 'custom-key', Buffer.from('custom-value'))
+        for (const group of datum.userMetadata?.getGroups() ?? []) {
+            datum.userMetadata?.getKeys(group).forEach((key) => {
+                userMetadata.addKv(group, key, datum.userMetadata!.getValue(group, key))
+            })
+        }
+        return [{ keys: [datum.keys[0]], value: datum.value, userMetadata }]
+    }
+
+    const server = new AsyncServer(mapFn)
+    const sockFile = '/tmp/map-trace.sock'
+    const infoFile = '/tmp/map-trace.info'
+
+    try {
+        server.start({ socketPath: sockFile, serverInfoPath: infoFile })
+        await sleep(500)
+
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'map', '--', sockFile], {
+            stdio: 'pipe',
+        })
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+        expect(exitCode).toBe(0)
+
+        // Only the "world" request carries a traceparent header.
+        expect(traceContexts.get('hello')).toBeNull()
+        const traceContext = traceContexts.get('world')
+        expect(traceContext).toMatchObject({
+            traceId: '4bf92f3577b34da6a3ce929d0e0e4736',
+            parentSpanId: '00f067aa0ba902b7',
+            sampled: true,
+        })
+        expect(traceContext?.spanId).toMatch(/^[0-9a-f]{16}$/)
+        expect(traceContext?.spanId).not.toBe('00f067aa0ba902b7')
+        expect(traceContext?.traceparent).toBe(`00-4bf92f3577b34da6a3ce929d0e0e4736-${traceContext?.spanId}-01`)
+    } finally {
+        await server.stop()
+    }
+}, 120000)
+
+test('mapper limits the requests in flight', async () => {
+    let active = 0
+    let maxActive = 0
+    const activePerKey = new Map<string, number>()
+    const maxActivePerKey = new Map<string, number>()
+    const mapFn = async (datum: map.Datum): Promise<map.Message[]> => {
+        const key = datum.keys[0]
+        active++
+        activePerKey.set(key, (activePerKey.get(key) ?? 0) + 1)
+        maxActive = Math.max(maxActive, active)
+        maxActivePerKey.set(key, Math.max(maxActivePerKey.get(key) ?? 0, activePerKey.get(key)!))
+        await sleep(100)
+        active--
+        activePerKey.set(key, activePerKey.get(key)! - 1)
+        return [{ keys: datum.keys, value: datum.value }]
+    }
+
+    const server = new AsyncServer(mapFn)
+    const sockFile = '/tmp/map-limit.sock'
+    const infoFile = '/tmp/map-limit.info'
+    const metricsPort = 9466
+
+    try {
+        server.start({
+            socketPath: sockFile,
+            serverInfoPath: infoFile,
+            metricsPort,
+            maxInFlight: 3,
+            maxInFlightPerKey: 2,
+        })
+        await sleep(500)
+
+        // Sends 8 requests at once, alternating between two keys.
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'map_burst', '--', sockFile, '8'], {
+            stdio: 'pipe',
+        })
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+        expect(exitCode).toBe(0)
+
+        expect(maxActive).toBe(3)
+        expect(Math.max(...maxActivePerKey.values())).toBe(2)
+
+        const metrics = await (await fetch(`http://127.0.0.1:${metricsPort}/metrics`)).text()
+        expect(metrics).toMatch(/^numaflow_js_queue_wait_seconds_count\{server="map"\} [1-9]/m)
+        expect(metrics).toMatch(/^numaflow_js_queued_requests\{server="map"\} 0$/m)
+    } finally {
+        await server.stop()
+    }
+}, 120000)
+
+test('mapper rejects a concurrency limit of zero', async () => {
+    const server = new AsyncServer(async (datum) => [{ keys: datum.keys, value: datum.value }])
+    const options = {
+        socketPath: '/tmp/map-limit-zero.sock',
+        serverInfoPath: '/tmp/map-limit-zero.info',
+        maxInFlight: 0,
+    }
+    await expect(server.start(options)).rejects.toThrow(/maxInFlight must be at least 1/)
+})
+
+test('mapper hands the same value buffer to every read, including retries', async () => {
+    const reads: boolean[] = []
+    const firstReads = new Map<string, Buffer>()
+    const mapFn = async (datum: map.Datum): Promise<map.Message[]> => {
+        const value = datum.value
+        reads.push(value === datum.value && value.toString() === `value-${datum.keys[0].slice(-1)}`)
+        const first = firstReads.get(value.toString())
+        if (first === undefined) {
+            firstReads.set(value.toString(), value)
+            throw new Error('retry')
+        }
+        reads.push(first === value)
+        return [{ keys: datum.keys, value }]
+    }
+
+    const server = new AsyncServer(mapFn, { maxAttempts: 2, initialBackoffMs: 1 })
+    const sockFile = '/tmp/map-value.sock'
+    const infoFile = '/tmp/map-value.info'
+
+    try {
+        server.start({ socketPath: sockFile, serverInfoPath: infoFile })
+        await sleep(500)
+
+        // Sends `value-0` with key `key-0` and `value-1` with key `key-1`.
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'map_burst', '--', sockFile, '2'], {
+            stdio: 'pipe',
+        })
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+        expect(exitCode).toBe(0)
+        expect(reads).toEqual([true, true, true, true, true, true])
+    } finally {
+        await server.stop()
+    }
+}, 120000)
+
+test('mapper fails requests that time out', async () => {
+    const mapFn = async (datum: map.Datum): Promise<map.Message[]> => {
+        if (datum.keys[0] === 'key-1') {
+            return new Promise<map.Message[]>(() => {})
+        }
+        return [{ keys: datum.keys, value: datum.value }]
+    }
+
+    const server = new AsyncServer(mapFn)
+    const sockFile = '/tmp/map-timeout.sock'
+    const infoFile = '/tmp/map-timeout.info'
+
+    try {
+        const started = server.start({ socketPath: sockFile, serverInfoPath: infoFile, handlerTimeoutMs: 200 })
+        await sleep(500)
+
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'map_burst', '--', sockFile, '2'], {
+            stdio: 'pipe',
+        })
+        let stderr = ''
+        cargoProcess.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+        expect(exitCode).not.toBe(0)
+        expect(stderr).toContain('User-defined map function did not settle within 200ms')
+
+        // A timed out request fails like a handler error, which shuts the server down.
+        await started
+    } finally {
+        await server.stop()
+    }
+}, 120000)
+
+test('mapper decodes datums and encodes messages with the json codec', async () => {
+    const decoded: unknown[] = []
+    const mapFn = async (datum: map.Datum): Promise<map.Message[]> => {
+        decoded.push(datum.data)
+        const { count } = datum.data as { count: number }
+        return [Message.fromData({ count: count + 1 }, { keys: datum.keys })]
+    }
+
+    const server = new AsyncServer(mapFn)
+    const sockFile = '/tmp/map-codec.sock'
+    const infoFile = '/tmp/map-codec.info'
+    const metricsPort = 9467
+
+    try {
+        server.start({
+            socketPath: sockFile,
+            serverInfoPath: infoFile,
+            metricsPort,
+            codec: 'json',
+            decodeErrorPolicy: 'drop',
+        })
+        await sleep(500)
+
+        // Sends `{"count":1}`, expecting `{"count":2}` back, and a value that is not JSON, expecting it dropped.
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'map_codec', '--', sockFile], {
+            stdio: 'pipe',
+        })
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+        expect(exitCode).toBe(0)
+
+        // The value that is not JSON never reached the handler.
+        expect(decoded).toEqual([{ count: 1 }])
+        const metrics = await (await fetch(`http://127.0.0.1:${metricsPort}/metrics`)).text()
+        expect(metrics).toMatch(/^numaflow_js_decode_errors_total\{server="map"\} 1$/m)
+        expect(metrics).toMatch(/^numaflow_js_messages_dropped_total\{server="map"\} 1$/m)
+    } finally {
+        await server.stop()
+    }
+}, 120000)
+
+test('mapper rejects an unknown codec', async () => {
+    const server = new AsyncServer(async (datum) => [{ keys: datum.keys, value: datum.value }])
+    const options = {
+        socketPath: '/tmp/map-codec-unknown.sock',
+        serverInfoPath: '/tmp/map-codec-unknown.info',
+        codec: 'xml' as Codec,
+    }
+    await expect(server.start(options)).rejects.toThrow(/Invalid codec "xml"/)
+})
+
+test('micro-batching mapper hands concurrent requests to one call', async () => {
+    const batchSizes: number[] = []
+    const mapBatchFn = async (datums: map.Datum[]): Promise<map.Message[][]> => {
+        batchSizes.push(datums.length)
+        return datums.map((datum) => [new Message(datum.value, { keys: datum.keys })])
+    }
+
+    const server = new MicroBatchServer(mapBatchFn, { maxBatchSize: 4, maxBatchDelayMs: 50 })
+    const sockFile = '/tmp/map-micro-batch.sock'
+    const infoFile = '/tmp/map-micro-batch.info'
+
+    try {
+        server.start({ socketPath: sockFile, serverInfoPath: infoFile })
+        await sleep(500)
+
+        // Sends 10 requests at once and expects a response to each.
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'map_burst', '--', sockFile, '10'], {
+            stdio: 'pipe',
+        })
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+        expect(exitCode).toBe(0)
+        // Typically [4, 4, 2], but a slow client may split a batch that the delay could not fill.
+        expect(batchSizes.reduce((total, size) => total + size, 0)).toBe(10)
+        expect(Math.max(...batchSizes)).toBe(4)
+    } finally {
+        await server.stop()
+    }
+}, 120000)
+
+test('worker pool mapper hands the datum of a crashed worker to another worker', async () => {
+    const crashedMarker = '/tmp/map-worker-pool.crashed'
+    rmSync(crashedMarker, { recursive: true, force: true })
+
+    const workerScript = new URL('./fixtures/map-worker.cjs', import.meta.url)
+    const server = new WorkerPoolServer(workerScript, { workers: 2 })
+    const sockFile = '/tmp/map-worker-pool.sock'
+    const infoFile = '/tmp/map-worker-pool.info'
+
+    try {
+        server.start({ socketPath: sockFile, serverInfoPath: infoFile })
+        await sleep(1000)
+
+        // Expects a response to each of the 10 requests, including the one whose worker exited.
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'map_burst', '--', sockFile, '10'], {
+            stdio: 'pipe',
+        })
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+        expect(exitCode).toBe(0)
+        expect(existsSync(crashedMarker)).toBe(true)
+    } finally {
+        await server.stop()
+        rmSync(crashedMarker, { recursive: true, force: true })
+    }
+}, 120000)
+
+test('worker pool mapper only hands datums to the workers of its latest start', async () => {
+    // Claims the crash of the fixture, none of its workers exits on its own.
+    const crashedMarker = '/tmp/map-worker-pool.crashed'
+    mkdirSync(crashedMarker, { recursive: true })
+
+    const workerScript = new URL('./fixtures/map-worker.cjs', import.meta.url)
+    const server = new WorkerPoolServer(workerScript, { workers: 1 })
+    const options = {
+        socketPath: '/tmp/map-worker-pool-restart.sock',
+        serverInfoPath: '/tmp/map-worker-pool-restart.info',
+    }
+
+    try {
+        // Every start spawns a worker of its own and terminates it once stopped. A datum handed to the terminated
+        // workers of the 3 earlier starts would fail after as many exits.
+        for (let i = 0; i < 3; i++) {
+            const started = server.start(options)
+            await sleep(1000)
+            await server.stop()
+            await started
+        }
+        server.start(options)
+        await sleep(1000)
+
+        const cargoProcess = spawn(
+            'cargo',
+            ['run', '-p', 'tests', '--bin', 'map_burst', '--', options.socketPath, '1'],
+            { stdio: 'pipe' },
+        )
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+        expect(exitCode).toBe(0)
+    } finally {
+        await server.stop()
+        rmSync(crashedMarker, { recursive: true, force: true })
+    }
+}, 120000)
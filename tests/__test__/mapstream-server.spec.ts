@@ -24,7 +24,7 @@ test('mapstream server functionlity', async () => {
     const serverInfoPath = '/tmp/mapstream.info'
 
     try {
-        server.start(socketPath, serverInfoPath)
+        server.start({ socketPath, serverInfoPath, shutdownTimeoutMs: 5000 })
         await sleep(500)
 
         const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'mapstream', '--', socketPath], {
@@ -0,0 +1,54 @@
+import { test, expect } from 'vitest'
+
+import { map, metadata, sink, source, sourceTransform } from '../../index.js'
+
+const { UserMetadata } = metadata
+
+test('every namespace shares the metadata classes', () => {
+    expect(map.UserMetadata).toBe(UserMetadata)
+    expect(sink.UserMetadata).toBe(UserMetadata)
+    expect(source.UserMetadata).toBe(UserMetadata)
+    expect(sourceTransform.UserMetadata).toBe(UserMetadata)
+})
+
+test('user metadata is created from groups and read as entries', () => {
+    const userMetadata = new UserMetadata({ tracking: { source: Buffer.from('api') } })
+    userMetadata.addString('audit', 'user', 'alice')
+    userMetadata.createGroup('empty')
+
+    expect(userMetadata.getGroups()).toEqual(['audit', 'empty', 'tracking'])
+    expect(userMetadata.getString('tracking', 'source')).toBe('api')
+    expect(userMetadata.getString('tracking', 'missing')).toBeNull()
+    expect(userMetadata.getValue('missing', 'missing')).toEqual(Buffer.alloc(0))
+    expect(userMetadata.entries()).toEqual([
+        { group: 'audit', key: 'user', value: Buffer.from('alice') },
+        { group: 'tracking', key: 'source', value: Buffer.from('api') },
+    ])
+})
+
+test('merge replaces the values of the keys both metadata have', () => {
+    const userMetadata = new UserMetadata({ tracking: { source: Buffer.from('api'), version: Buffer.from('1') } })
+    const other = new UserMetadata({ tracking: { version: Buffer.from('2') }, audit: { user: Buffer.from('bob') } })
+    userMetadata.merge(other)
+
+    expect(userMetadata.getString('tracking', 'source')).toBe('api')
+    expect(userMetadata.getString('tracking', 'version')).toBe('2')
+    expect(userMetadata.getString('audit', 'user')).toBe('bob')
+})
+
+test('cloneWith leaves the original metadata unchanged', () => {
+    const userMetadata = new UserMetadata({ tracking: { source: Buffer.from('api') } })
+    const copy = userMetadata.cloneWith('tracking', 'stage', Buffer.from('enriched'))
+
+    expect(copy.getKeys('tracking')).toEqual(['source', 'stage'])
+    expect(userMetadata.getKeys('tracking')).toEqual(['source'])
+})
+
+test('metadata round trips through JSON', () => {
+    const userMetadata = new UserMetadata({ binary: { bytes: Buffer.from([0, 255]) } })
+    const json = JSON.parse(JSON.stringify(userMetadata))
+
+    expect(json).toEqual({ binary: { bytes: 'AP8=' } })
+    expect(UserMetadata.fromJSON(json).getValue('binary', 'bytes')).toEqual(Buffer.from([0, 255]))
+    expect(() => UserMetadata.fromJSON({ binary: { bytes: '!' } })).toThrow(/not valid base64/)
+})
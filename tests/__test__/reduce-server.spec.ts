@@ -57,3 +57,241 @@ test('reduce integration test', async () => {
         server.stop()
     }
 }, 120000)
+
+class Counter implements reduce.Reducer {
+    counter = 0
+
+    async reduceFn(keys: string[], datums: AsyncIterableIterator<reduce.Datum>): Promise<reduce.Message[]> {
+        for await (const _ of datums) {
+            this.counter += 1
+        }
+        return [{ keys, value: Buffer.from(`counter:${this.counter}`, 'utf-8') }]
+    }
+}
+
+test('reduce factory gives every window its own reducer', async () => {
+    const factorySockPath = '/tmp/var/run/numaflow/reduce-factory.sock'
+    const factoryInfoPath = '/tmp/var/run/numaflow/reduce-factory-info.sock'
+    let created = 0
+    const server = reduce.AsyncServer.withFactory(() => {
+        created += 1
+        return new Counter()
+    })
+
+    try {
+        server.start(factorySockPath, factoryInfoPath)
+        await sleep(500)
+
+        // The client asserts that every window counted its own datums
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'reduce_windows', '--', factorySockPath], {
+            stdio: 'pipe',
+        })
+
+        let stdout = ''
+        let stderr = ''
+        cargoProcess.stdout?.on('data', (data) => {
+            stdout += data.toString()
+        })
+        cargoProcess.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+
+        if (exitCode !== 0) {
+            expect.fail(`Cargo command failed with exit code ${exitCode}\n\nStdout:\n${stdout}\n\nStderr:\n${stderr}`)
+        }
+        expect(created).toBe(2)
+    } finally {
+        server.stop()
+    }
+}, 120000)
+
+test('reduce datums tell how late they arrived', async () => {
+    const lateSockPath = '/tmp/var/run/numaflow/reduce-late.sock'
+    const lateInfoPath = '/tmp/var/run/numaflow/reduce-late-info.sock'
+    const server = new reduce.AsyncServer(async (keys, datums, md) => {
+        let late = 0
+        let maxLatenessMs = 0
+        for await (const datum of datums) {
+            if (datum.late) {
+                late += 1
+            }
+            maxLatenessMs = Math.max(maxLatenessMs, datum.latenessMs)
+        }
+        const msg = `${late}:${maxLatenessMs}:${md.latestWatermark?.getTime()}`
+
+        return [{ keys, value: Buffer.from(msg, 'utf-8') }]
+    })
+
+    try {
+        server.start(lateSockPath, lateInfoPath)
+        await sleep(500)
+
+        // The client asserts on the late datums, their lateness and the latest watermark
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'reduce_late', '--', lateSockPath], {
+            stdio: 'pipe',
+        })
+
+        let stdout = ''
+        let stderr = ''
+        cargoProcess.stdout?.on('data', (data) => {
+            stdout += data.toString()
+        })
+        cargoProcess.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+
+        if (exitCode !== 0) {
+            expect.fail(`Cargo command failed with exit code ${exitCode}\n\nStdout:\n${stdout}\n\nStderr:\n${stderr}`)
+        }
+    } finally {
+        server.stop()
+    }
+}, 120000)
+
+interface Lateness {
+    late: number
+    maxLatenessMs: number
+}
+
+test('reduce aggregator adds the datums of the window to its accumulator', async () => {
+    const aggregatorSockPath = '/tmp/var/run/numaflow/reduce-aggregator.sock'
+    const aggregatorInfoPath = '/tmp/var/run/numaflow/reduce-aggregator-info.sock'
+    const server = reduce.AsyncServer.withAggregator<Lateness>({
+        createAccumulator: () => ({ late: 0, maxLatenessMs: 0 }),
+        addInput: (accumulator, datum) => ({
+            late: accumulator.late + (datum.late ? 1 : 0),
+            maxLatenessMs: Math.max(accumulator.maxLatenessMs, datum.latenessMs),
+        }),
+        mergeAccumulators: (accumulators) =>
+            accumulators.reduce((a, b) => ({
+                late: a.late + b.late,
+                maxLatenessMs: Math.max(a.maxLatenessMs, b.maxLatenessMs),
+            })),
+        extractOutput: (keys, accumulator, md) => {
+            const msg = `${accumulator.late}:${accumulator.maxLatenessMs}:${md.latestWatermark?.getTime()}`
+            return [new reduce.Message(Buffer.from(msg, 'utf-8'), { keys })]
+        },
+    })
+
+    try {
+        server.start(aggregatorSockPath, aggregatorInfoPath)
+        await sleep(500)
+
+        // The client expects the same result as the iterator handler of the late data test
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'reduce_late', '--', aggregatorSockPath], {
+            stdio: 'pipe',
+        })
+
+        let stdout = ''
+        let stderr = ''
+        cargoProcess.stdout?.on('data', (data) => {
+            stdout += data.toString()
+        })
+        cargoProcess.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+
+        if (exitCode !== 0) {
+            expect.fail(`Cargo command failed with exit code ${exitCode}\n\nStdout:\n${stdout}\n\nStderr:\n${stderr}`)
+        }
+    } finally {
+        server.stop()
+    }
+}, 120000)
+
+interface Sum {
+    sum: number
+    datums: number
+}
+
+test('reduce aggregator merges the accumulator of every micro-batch into the window', async () => {
+    const batchesSockPath = '/tmp/var/run/numaflow/reduce-batches.sock'
+    const batchesInfoPath = '/tmp/var/run/numaflow/reduce-batches-info.sock'
+    // The accumulators merged into the window, the accumulator of the window first
+    const merges: Sum[][] = []
+    const server = reduce.AsyncServer.withAggregator<Sum>(
+        {
+            createAccumulator: () => ({ sum: 0, datums: 0 }),
+            addInput: (accumulator, datum) => ({
+                sum: accumulator.sum + parseInt(datum.value.toString()),
+                datums: accumulator.datums + 1,
+            }),
+            mergeAccumulators: (accumulators) => {
+                merges.push(accumulators)
+                return accumulators.reduce((a, b) => ({ sum: a.sum + b.sum, datums: a.datums + b.datums }))
+            },
+            extractOutput: (keys, accumulator) => [
+                new reduce.Message(Buffer.from(String(accumulator.sum), 'utf-8'), { keys }),
+            ],
+        },
+        { maxBatchSize: 3, maxBatchDelayMs: 1000 },
+    )
+
+    try {
+        server.start(batchesSockPath, batchesInfoPath)
+        await sleep(500)
+
+        // The client sends the values 1 to 10 and expects their sum
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'reduce_sum', '--', batchesSockPath], {
+            stdio: 'pipe',
+        })
+
+        let stdout = ''
+        let stderr = ''
+        cargoProcess.stdout?.on('data', (data) => {
+            stdout += data.toString()
+        })
+        cargoProcess.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+
+        if (exitCode !== 0) {
+            expect.fail(`Cargo command failed with exit code ${exitCode}\n\nStdout:\n${stdout}\n\nStderr:\n${stderr}`)
+        }
+        // One merge per batch of at most three of the ten datums
+        expect(merges).toEqual([
+            [
+                { sum: 0, datums: 0 },
+                { sum: 6, datums: 3 },
+            ],
+            [
+                { sum: 6, datums: 3 },
+                { sum: 15, datums: 3 },
+            ],
+            [
+                { sum: 21, datums: 6 },
+                { sum: 24, datums: 3 },
+            ],
+            [
+                { sum: 45, datums: 9 },
+                { sum: 10, datums: 1 },
+            ],
+        ])
+    } finally {
+        server.stop()
+    }
+}, 120000)
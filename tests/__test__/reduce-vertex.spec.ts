@@ -0,0 +1,70 @@
+import { test, expect } from 'vitest'
+import { spawn } from 'child_process'
+import { promisify } from 'util'
+
+import { reduce } from '../../index.js'
+
+const sleep = promisify(setTimeout)
+
+// The server reads the vertex from the environment once per process, so this spec has a file of its own.
+process.env.NUMAFLOW_PIPELINE_NAME = 'simple-pipeline'
+process.env.NUMAFLOW_VERTEX_NAME = 'counter'
+process.env.NUMAFLOW_REPLICA = '2'
+process.env.NUMAFLOW_VERTEX_OBJECT = Buffer.from(
+    JSON.stringify({ spec: { udf: { groupBy: { window: { sliding: { length: '1m30s', slide: '500ms' } } } } } }),
+).toString('base64')
+
+const sockPath = '/tmp/var/run/numaflow/reduce-vertex.sock'
+const infoPath = '/tmp/var/run/numaflow/reduce-vertex-info.sock'
+
+test('reduce metadata tells the window kind and the vertex from the environment', async () => {
+    const metadata: reduce.Metadata[] = []
+    const server = new reduce.AsyncServer(async (keys, datums, md) => {
+        let counter = 0
+        for await (const _ of datums) {
+            counter += 1
+        }
+        metadata.push(md)
+        return [{ keys, value: Buffer.from(`counter:${counter}`, 'utf-8') }]
+    })
+
+    try {
+        server.start(sockPath, infoPath)
+        await sleep(500)
+
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'reduce', '--', sockPath], {
+            stdio: 'pipe',
+        })
+
+        let stdout = ''
+        let stderr = ''
+        cargoProcess.stdout?.on('data', (data) => {
+            stdout += data.toString()
+        })
+        cargoProcess.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+
+        if (exitCode !== 0) {
+            expect.fail(`Cargo command failed with exit code ${exitCode}\n\nStdout:\n${stdout}\n\nStderr:\n${stderr}`)
+        }
+        expect(metadata).toHaveLength(1)
+        const { windowKind, windowLengthMs, slideMs, pipelineName, vertexName, replica } = metadata[0]
+        expect({ windowKind, windowLengthMs, slideMs, pipelineName, vertexName, replica }).toEqual({
+            windowKind: 'sliding',
+            windowLengthMs: 90_000,
+            slideMs: 500,
+            pipelineName: 'simple-pipeline',
+            vertexName: 'counter',
+            replica: 2,
+        })
+    } finally {
+        server.stop()
+    }
+}, 120000)
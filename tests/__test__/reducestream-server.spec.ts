@@ -64,3 +64,59 @@ test('reduce stream integration test', async () => {
         server.stop()
     }
 }, 120000)
+
+class StreamCounter implements reduceStream.ReduceStreamer {
+    counter = 0
+
+    async *reduceStreamFn(
+        keys: string[],
+        datums: AsyncIterableIterator<reduceStream.Datum>,
+    ): AsyncIterableIterator<reduceStream.Message> {
+        for await (const _ of datums) {
+            this.counter += 1
+            yield { keys, value: Buffer.from(`counter:${this.counter}`, 'utf-8') }
+        }
+    }
+}
+
+test('reduce stream factory gives every window its own reducer', async () => {
+    const factorySockPath = '/tmp/var/run/numaflow/reduce-stream-factory.sock'
+    const factoryInfoPath = '/tmp/var/run/numaflow/reduce-stream-factory-info.sock'
+    let created = 0
+    const server = reduceStream.AsyncServer.withFactory(() => {
+        created += 1
+        return new StreamCounter()
+    })
+
+    try {
+        server.start(factorySockPath, factoryInfoPath)
+        await sleep(500)
+
+        const cargoProcess = spawn('cargo', ['run', '-p', 'tests', '--bin', 'reducestream', '--', factorySockPath], {
+            stdio: 'pipe',
+        })
+
+        let stdout = ''
+        let stderr = ''
+        cargoProcess.stdout?.on('data', (data) => {
+            stdout += data.toString()
+        })
+        cargoProcess.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+
+        if (exitCode !== 0) {
+            expect.fail(`Cargo command failed with exit code ${exitCode}\n\nStdout:\n${stdout}\n\nStderr:\n${stderr}`)
+        }
+        // The requests have a single window
+        expect(created).toBe(1)
+    } finally {
+        server.stop()
+    }
+}, 120000)
@@ -0,0 +1,88 @@
+import { afterEach, test, expect } from 'vitest'
+import { writeFileSync } from 'fs'
+
+import { loadServerConfig } from '../../index.js'
+
+const ENV_VARS = [
+    'NUMAFLOW_UD_CONTAINER_TYPE',
+    'NUMAFLOW_SERVER_CONFIG_FILE',
+    'NUMAFLOW_SERVER_SOCKET_PATH',
+    'NUMAFLOW_SERVER_INFO_PATH',
+    'NUMAFLOW_GRPC_MAX_MESSAGE_SIZE',
+    'NUMAFLOW_SHUTDOWN_TIMEOUT_MS',
+    'NUMAFLOW_HANDLE_SIGNALS',
+    'NUMAFLOW_METRICS_PORT',
+    'NUMAFLOW_METRICS_ADDRESS',
+    'NUMAFLOW_MAX_IN_FLIGHT',
+    'NUMAFLOW_MAX_IN_FLIGHT_PER_KEY',
+    'NUMAFLOW_HANDLER_TIMEOUT_MS',
+    'NUMAFLOW_CODEC',
+    'NUMAFLOW_DECODE_ERROR_POLICY',
+]
+
+afterEach(() => {
+    for (const name of ENV_VARS) {
+        delete process.env[name]
+    }
+})
+
+test('server config defaults to the explicit options', () => {
+    const config = loadServerConfig({ socketPath: '/tmp/config.sock' })
+    expect(config.containerType).toBeUndefined()
+    expect(config.configFile).toBeUndefined()
+    expect(config.options).toEqual({ socketPath: '/tmp/config.sock' })
+})
+
+test('server config merges options, environment and config file', () => {
+    const configFile = '/tmp/numaflow-server-config.yaml'
+    writeFileSync(configFile, 'socketPath: /tmp/file.sock\nserverInfoPath: /tmp/file.info\nshutdownTimeoutMs: 1000\n')
+    process.env.NUMAFLOW_SERVER_CONFIG_FILE = configFile
+    process.env.NUMAFLOW_UD_CONTAINER_TYPE = 'udf'
+    process.env.NUMAFLOW_SERVER_SOCKET_PATH = '/tmp/env.sock'
+    process.env.NUMAFLOW_GRPC_MAX_MESSAGE_SIZE = '1048576'
+    process.env.NUMAFLOW_HANDLE_SIGNALS = 'true'
+    process.env.NUMAFLOW_METRICS_PORT = '9090'
+    process.env.NUMAFLOW_METRICS_ADDRESS = '127.0.0.1'
+    process.env.NUMAFLOW_MAX_IN_FLIGHT = '16'
+    process.env.NUMAFLOW_MAX_IN_FLIGHT_PER_KEY = '4'
+    process.env.NUMAFLOW_HANDLER_TIMEOUT_MS = '5000'
+    process.env.NUMAFLOW_CODEC = 'json'
+    process.env.NUMAFLOW_DECODE_ERROR_POLICY = 'pass'
+
+    const config = loadServerConfig({ maxMessageSize: 2048 })
+    expect(config.containerType).toBe('udf')
+    expect(config.configFile).toBe(configFile)
+    expect(config.options).toEqual({
+        socketPath: '/tmp/env.sock',
+        serverInfoPath: '/tmp/file.info',
+        maxMessageSize: 2048,
+        shutdownTimeoutMs: 1000,
+        handleSignals: true,
+        metricsPort: 9090,
+        metricsAddress: '127.0.0.1',
+        maxInFlight: 16,
+        maxInFlightPerKey: 4,
+        handlerTimeoutMs: 5000,
+        codec: 'json',
+        decodeErrorPolicy: 'pass',
+    })
+})
+
+test('server config reads JSON config files', () => {
+    const configFile = '/tmp/numaflow-server-config.json'
+    writeFileSync(configFile, JSON.stringify({ maxMessageSize: 4096 }))
+    process.env.NUMAFLOW_SERVER_CONFIG_FILE = configFile
+
+    expect(loadServerConfig().options).toEqual({ maxMessageSize: 4096 })
+})
+
+test('server config rejects invalid values', () => {
+    process.env.NUMAFLOW_SHUTDOWN_TIMEOUT_MS = 'soon'
+    expect(() => loadServerConfig()).toThrow(/NUMAFLOW_SHUTDOWN_TIMEOUT_MS/)
+
+    delete process.env.NUMAFLOW_SHUTDOWN_TIMEOUT_MS
+    const configFile = '/tmp/numaflow-server-config-invalid.json'
+    writeFileSync(configFile, JSON.stringify({ socketPth: '/tmp/typo.sock' }))
+    process.env.NUMAFLOW_SERVER_CONFIG_FILE = configFile
+    expect(() => loadServerConfig()).toThrow(/socketPth/)
+})
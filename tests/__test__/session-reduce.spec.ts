@@ -30,7 +30,23 @@ class SessionReduceCounter implements sessionReduce.SessionReducer {
     }
 
     async mergeAccumulatorFn(accumulator: Buffer): Promise<void> {
-        this.counter += accumulator.readUInt8(0)
+        this.counter += parseInt(accumulator.toString())
+    }
+}
+
+// Records how many sessions were merged into each session once its datums ended.
+class SessionMergeCounter extends SessionReduceCounter {
+    constructor(private readonly mergedSessions: number[]) {
+        super()
+    }
+
+    async *sessionReduceFn(
+        keys: string[],
+        datums: AsyncIterableIterator<sessionReduce.Datum>,
+        metadata: sessionReduce.SessionMetadata,
+    ): AsyncIterableIterator<sessionReduce.Message> {
+        yield* super.sessionReduceFn(keys, datums)
+        this.mergedSessions.push(metadata.mergedSessions)
     }
 }
 
@@ -76,3 +92,52 @@ test('session reduce integration test', async () => {
         server.stop()
     }
 }, 120000)
+
+test('session reduce factory gives every session its own reducer', async () => {
+    const mergeSockPath = '/tmp/var/run/numaflow/session-reduce-merge.sock'
+    const mergeInfoPath = '/tmp/var/run/numaflow/session-reduce-merge-info.sock'
+    let created = 0
+    const mergedSessions: number[] = []
+    const server = sessionReduce.AsyncServer.withFactory(() => {
+        created += 1
+        return new SessionMergeCounter(mergedSessions)
+    })
+    // A class is not a factory, and the constructor only takes instances.
+    const notAnInstance = SessionMergeCounter as unknown as sessionReduce.SessionReducer
+    expect(() => new sessionReduce.AsyncServer(notAnInstance)).toThrow(/withFactory/)
+
+    try {
+        server.start(mergeSockPath, mergeInfoPath)
+        await sleep(500)
+
+        const cargoProcess = spawn(
+            'cargo',
+            ['run', '-p', 'tests', '--bin', 'session_reduce_merge', '--', mergeSockPath],
+            { stdio: 'pipe' },
+        )
+
+        let stdout = ''
+        let stderr = ''
+        cargoProcess.stdout?.on('data', (data) => {
+            stdout += data.toString()
+        })
+        cargoProcess.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+
+        const exitCode = await new Promise<number>((resolve) => {
+            cargoProcess.on('close', (code) => {
+                resolve(code ?? 1)
+            })
+        })
+
+        if (exitCode !== 0) {
+            expect.fail(`Cargo command failed with exit code ${exitCode}\n\nStdout:\n${stdout}\n\nStderr:\n${stderr}`)
+        }
+        // The two sessions and the session they were merged into
+        expect(created).toBe(3)
+        expect(mergedSessions.sort()).toEqual([0, 0, 2])
+    } finally {
+        server.stop()
+    }
+}, 120000)
@@ -1,5 +1,6 @@
 import { test, expect } from 'vitest'
 import { spawn } from 'child_process'
+import { mkdirSync, readlinkSync, renameSync, rmSync, symlinkSync, writeFileSync } from 'fs'
 import { promisify } from 'util'
 
 import { sideInput } from '../../index.js'
@@ -29,7 +30,7 @@ test('sideinput integration test', async () => {
 
     try {
         // Start the server (non-blocking)
-        server.start(sockPath, infoPath)
+        server.start({ socketPath: sockPath, serverInfoPath: infoPath })
 
         // Give the server time to initialize
         await sleep(500)
@@ -65,3 +66,60 @@ test('sideinput integration test', async () => {
         server.stop()
     }
 }, 120000)
+
+/**
+ * Updates a side input the way the side input synchronizer of numaflow does: writes the value to a file named after
+ * the side input and a timestamp, swaps the symlink of the side input to it and removes the previous file.
+ */
+function synchronize(dirPath: string, name: string, value: string) {
+    const link = `${dirPath}/${name}`
+    const target = `${link}_${BigInt(Date.now()) * 1_000_000n + (process.hrtime.bigint() % 1_000_000n)}`
+    let previous: string | undefined
+    try {
+        previous = readlinkSync(link)
+    } catch {
+        previous = undefined
+    }
+    writeFileSync(target, value)
+    symlinkSync(target, `${link}_temp`)
+    renameSync(`${link}_temp`, link)
+    if (previous !== undefined) {
+        rmSync(previous)
+    }
+}
+
+test('side input reader caches the side inputs and reports their changes', async () => {
+    const dirPath = '/tmp/side-input-reader'
+    rmSync(dirPath, { recursive: true, force: true })
+    mkdirSync(dirPath)
+    synchronize(dirPath, 'config', JSON.stringify({ threshold: 1 }))
+    writeFileSync(`${dirPath}/.hidden`, 'ignored')
+
+    const reader = new sideInput.SideInputReader({ dirPath, codec: 'json' })
+    try {
+        await reader.start()
+        expect(reader.names()).toEqual(['config'])
+        expect(reader.get('config')?.data).toEqual({ threshold: 1 })
+        expect(reader.get('missing')).toBeNull()
+
+        const changes: sideInput.SideInputChange[] = []
+        reader.onChange((change) => changes.push(change))
+
+        synchronize(dirPath, 'config', JSON.stringify({ threshold: 2 }))
+        writeFileSync(`${dirPath}/labels`, 'not json')
+        await sleep(200)
+        rmSync(`${dirPath}/labels`)
+        await sleep(200)
+
+        expect(changes.map(({ name, value }) => [name, value?.data ?? null, value?.decodeError ?? null])).toEqual([
+            ['config', { threshold: 2 }, null],
+            ['labels', null, expect.stringContaining('expected')],
+            ['labels', null, null],
+        ])
+        expect(reader.get('config')?.value.toString()).toBe('{"threshold":2}')
+        expect(reader.names()).toEqual(['config'])
+    } finally {
+        reader.stop()
+        rmSync(dirPath, { recursive: true, force: true })
+    }
+})
@@ -10,10 +10,26 @@ const sockPath = '/tmp/sink.sock'
 const infoPath = '/tmp/sink-info.sock'
 
 // Start the JavaScript sink server
+// The second message is forwarded to the on-success sink with its user metadata and an added group.
 const sinker = new sink.AsyncServer(async (datums: AsyncIterableIterator<sink.Datum>) => {
     const responses: sink.Response[] = []
     for await (const datum of datums) {
-        responses.push(sink.Response.ok(datum.id))
+        if (datum.id !== '2') {
+            responses.push(sink.Response.ok(datum.id))
+            continue
+        }
+        const userMetadata: Record<string, Record<string, Buffer>> = {
+            'custom-group': { 'custom-key': Buffer.from('custom-value') },
+        }
+        const incoming = datum.userMetadata()
+        for (const group of incoming.getGroups()) {
+            userMetadata[group] = {}
+            for (const key of incoming.getKeys(group)) {
+                userMetadata[group][key] = incoming.getValue(group, key)
+            }
+        }
+        const message = new sink.Message(datum.getValue(), datum.keys, userMetadata)
+        responses.push(sink.Response.onSuccess(datum.id, message))
     }
     return responses
 })
@@ -72,6 +88,40 @@ test('sink integration test', async () => {
     }
 }, 120000)
 
+test('sink fails batches that time out', async () => {
+    const hangingSinker = new sink.AsyncServer(async (datums: AsyncIterableIterator<sink.Datum>) => {
+        await datums.next()
+        return new Promise<sink.Response[]>(() => {})
+    })
+    const timeoutSockPath = '/tmp/sink-timeout.sock'
+    hangingSinker.start({
+        socketPath: timeoutSockPath,
+        serverInfoPath: '/tmp/sink-timeout.info',
+        handlerTimeoutMs: 200,
+    })
+
+    try {
+        await waitForSocket(timeoutSockPath)
+
+        // The client checks that every message is answered with a failure.
+        const rustTest = spawn('cargo', ['run', '-p', 'tests', '--bin', 'sink', '--', timeoutSockPath, 'failure'], {
+            stdio: 'pipe',
+        })
+        let stderr = ''
+        rustTest.stderr?.on('data', (data) => {
+            stderr += data.toString()
+        })
+        const exitCode = await new Promise<number>((resolve) => {
+            rustTest.on('close', resolve)
+        })
+        if (exitCode !== 0) {
+            expect.fail(`Cargo command failed with exit code ${exitCode}\n\nStderr:\n${stderr}`)
+        }
+    } finally {
+        await hangingSinker.stop(100)
+    }
+}, 120000)
+
 afterAll(async () => {
     sinker.stop()
 })
@@ -0,0 +1,107 @@
+use std::env;
+use std::path::PathBuf;
+
+use acc_proto::accumulator_request::window_operation::Event;
+use numaflow::proto::accumulator as acc_proto;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, transport::Uri};
+use tower::service_fn;
+
+fn ts(seconds: i64) -> prost_types::Timestamp {
+    prost_types::Timestamp { seconds, nanos: 0 }
+}
+
+fn keyed_window(key: &str) -> acc_proto::KeyedWindow {
+    acc_proto::KeyedWindow {
+        start: Some(ts(0)),
+        end: Some(ts(60)),
+        slot: "slot-0".to_string(),
+        keys: vec![key.into()],
+    }
+}
+
+fn request(event: Event, key: &str, id: &str) -> acc_proto::AccumulatorRequest {
+    acc_proto::AccumulatorRequest {
+        payload: Some(acc_proto::Payload {
+            keys: vec![key.into()],
+            value: id.as_bytes().to_vec(),
+            watermark: Some(ts(10)),
+            event_time: Some(ts(10)),
+            headers: Default::default(),
+            id: id.to_string(),
+        }),
+        operation: Some(acc_proto::accumulator_request::WindowOperation {
+            event: event as i32,
+            keyed_window: Some(keyed_window(key)),
+        }),
+    }
+}
+
+fn close(key: &str) -> acc_proto::AccumulatorRequest {
+    acc_proto::AccumulatorRequest {
+        payload: None,
+        operation: Some(acc_proto::accumulator_request::WindowOperation {
+            event: Event::Close as i32,
+            keyed_window: Some(keyed_window(key)),
+        }),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Default socket path with env/arg override
+    let sock_path = env::args()
+        .nth(1)
+        .or_else(|| env::var("NUMAFLOW_ACCUMULATOR_SOCK").ok())
+        .unwrap_or_else(|| "/tmp/var/run/numaflow/accumulator.sock".to_string());
+
+    // Connect over UDS
+    let channel = tonic::transport::Endpoint::try_from("http://[::]:50051")?
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let sock = PathBuf::from(sock_path.clone());
+            async move {
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(
+                    UnixStream::connect(sock).await?,
+                ))
+            }
+        }))
+        .await?;
+
+    let mut client = acc_proto::accumulator_client::AccumulatorClient::new(channel);
+
+    let (tx, rx) = mpsc::channel(16);
+
+    // Test scenario: the datums of the keys `a` and `b` interleave, `a` gets two datums and `b`
+    // three. The handler forwards every datum as `<key>:<datums counted so far>`, which only
+    // holds if every key counts on its own.
+    tx.send(request(Event::Open, "a", "a1")).await.unwrap();
+    tx.send(request(Event::Open, "b", "b1")).await.unwrap();
+    tx.send(request(Event::Append, "a", "a2")).await.unwrap();
+    tx.send(request(Event::Append, "b", "b2")).await.unwrap();
+    tx.send(request(Event::Append, "b", "b3")).await.unwrap();
+    tx.send(close("a")).await.unwrap();
+    tx.send(close("b")).await.unwrap();
+
+    // Drop sender to signal end-of-stream
+    drop(tx);
+
+    let request = Request::new(ReceiverStream::new(rx));
+    let mut resp = client.accumulate_fn(request).await?.into_inner();
+
+    let mut messages = Vec::new();
+    while let Some(r) = resp.message().await? {
+        if let Some(payload) = r.payload {
+            let value =
+                String::from_utf8(payload.value).expect("Payload value should be valid UTF-8");
+            println!("Received message: {}", value);
+            messages.push(value);
+        }
+    }
+
+    messages.sort();
+    assert_eq!(messages, ["a:1", "a:2", "b:1", "b:2", "b:3"]);
+
+    Ok(())
+}
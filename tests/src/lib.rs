@@ -1,9 +1,20 @@
+// The client binaries are declared here only so tooling picks them up as one crate.
+#![allow(dead_code)]
+
 mod accumulator;
+mod accumulator_keys;
 mod batchmap;
 mod map;
+mod map_bench;
+mod map_burst;
+mod map_codec;
 mod reduce;
+mod reduce_late;
+mod reduce_sum;
+mod reduce_windows;
 mod reducestream;
 mod session_reduce;
+mod session_reduce_merge;
 mod sideinput;
 mod sink;
 mod source;
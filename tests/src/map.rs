@@ -112,13 +112,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     {
         // we send 1 group, and test code forwards this and adds another group
         assert_eq!(user_metadata.len(), 2);
-        user_metadata.get("group1").map(|kv| {
+        if let Some(kv) = user_metadata.get("group1") {
             assert_eq!(kv.key_value.len(), 2);
             assert_eq!(
                 kv.key_value.get("key2").unwrap(),
                 &Vec::from("value2".as_bytes())
             );
-        });
+        }
     }
 
     // Request 2
@@ -128,7 +128,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             value: "world".into(),
             watermark: Some(prost_types::Timestamp::default()),
             event_time: Some(prost_types::Timestamp::default()),
-            headers: Default::default(),
+            headers: HashMap::from([(
+                "traceparent".to_string(),
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string(),
+            )]),
             metadata: Some(proto::metadata::Metadata {
                 previous_vertex: "sourcer".to_string(),
                 sys_metadata: sys_metadata.clone(),
@@ -0,0 +1,109 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use numaflow::proto;
+use numaflow::proto::map::map_client::MapClient;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Uri;
+use tower::service_fn;
+
+// Requests the client keeps in flight, like the batches numaflow reads.
+const WINDOW: usize = 64;
+
+// Rust client binary that measures the throughput of a Map server echoing its datums, for
+// requests with values of the given size in bytes. Prints the result as one line of JSON.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = env::args().skip(1);
+    let sock_path = args
+        .next()
+        .unwrap_or_else(|| "/tmp/numaflow.sock".to_string());
+    let size: usize = args
+        .next()
+        .map(|size| size.parse())
+        .transpose()?
+        .unwrap_or(1024);
+    let count: usize = args
+        .next()
+        .map(|count| count.parse())
+        .transpose()?
+        .unwrap_or(1000);
+
+    let channel = tonic::transport::Endpoint::try_from("http://[::]:50051")?
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let sock = PathBuf::from(sock_path.clone());
+            async move {
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(
+                    UnixStream::connect(sock).await?,
+                ))
+            }
+        }))
+        .await?;
+
+    let mut client = MapClient::new(channel)
+        .max_decoding_message_size(usize::MAX)
+        .max_encoding_message_size(usize::MAX);
+
+    let (tx, rx) = mpsc::channel(WINDOW);
+
+    // Handshake
+    let handshake_request = proto::map::MapRequest {
+        request: None,
+        id: "".to_string(),
+        handshake: Some(proto::map::Handshake { sot: true }),
+        status: None,
+    };
+    tx.send(handshake_request).await.unwrap();
+
+    let resp = client.map_fn(ReceiverStream::new(rx)).await.unwrap();
+    let mut resp = resp.into_inner();
+
+    let handshake_response = resp.message().await.unwrap();
+    assert!(handshake_response.is_some_and(|response| response.handshake.is_some()));
+
+    let value = vec![b'x'; size];
+    let start = Instant::now();
+    let sender = tokio::spawn(async move {
+        for i in 0..count {
+            let request = proto::map::MapRequest {
+                request: Some(proto::map::map_request::Request {
+                    keys: vec!["bench".to_string()],
+                    value: value.clone(),
+                    watermark: Some(prost_types::Timestamp::default()),
+                    event_time: Some(prost_types::Timestamp::default()),
+                    headers: Default::default(),
+                    metadata: None,
+                }),
+                id: i.to_string(),
+                handshake: None,
+                status: None,
+            };
+            tx.send(request).await.unwrap();
+        }
+        // Keep the request stream open until every response is received.
+        tx
+    });
+
+    for _ in 0..count {
+        let response = resp
+            .message()
+            .await
+            .unwrap()
+            .expect("a response per request");
+        assert_eq!(response.results.len(), 1);
+        assert_eq!(response.results[0].value.len(), size);
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    drop(sender.await?);
+
+    println!(
+        "{{\"size\":{size},\"count\":{count},\"seconds\":{seconds:.3},\"messagesPerSecond\":{:.0},\"mibPerSecond\":{:.1}}}",
+        count as f64 / seconds,
+        (size * count) as f64 / seconds / (1024.0 * 1024.0),
+    );
+
+    Ok(())
+}
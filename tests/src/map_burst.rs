@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+use std::env;
+use std::path::PathBuf;
+
+use numaflow::proto;
+use numaflow::proto::map::map_client::MapClient;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Uri;
+use tower::service_fn;
+
+// Rust client binary that sends a burst of map requests without waiting for the responses, to
+// exercise the concurrency limit of the Map server. Requests alternate between two keys.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = env::args().skip(1);
+    let sock_path = args
+        .next()
+        .or_else(|| env::var("NUMAFLOW_MAP_SOCK").ok())
+        .unwrap_or_else(|| "/tmp/numaflow.sock".to_string());
+    let count: usize = args
+        .next()
+        .map(|count| count.parse())
+        .transpose()?
+        .unwrap_or(8);
+
+    let channel = tonic::transport::Endpoint::try_from("http://[::]:50051")?
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let sock = PathBuf::from(sock_path.clone());
+            async move {
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(
+                    UnixStream::connect(sock).await?,
+                ))
+            }
+        }))
+        .await?;
+
+    let mut client = MapClient::new(channel);
+
+    let (tx, rx) = mpsc::channel(count + 1);
+
+    // Handshake
+    let handshake_request = proto::map::MapRequest {
+        request: None,
+        id: "".to_string(),
+        handshake: Some(proto::map::Handshake { sot: true }),
+        status: None,
+    };
+    tx.send(handshake_request).await.unwrap();
+
+    let resp = client.map_fn(ReceiverStream::new(rx)).await.unwrap();
+    let mut resp = resp.into_inner();
+
+    let handshake_response = resp.message().await.unwrap();
+    assert!(handshake_response.is_some_and(|response| response.handshake.is_some()));
+
+    for i in 0..count {
+        let request = proto::map::MapRequest {
+            request: Some(proto::map::map_request::Request {
+                keys: vec![format!("key-{}", i % 2)],
+                value: format!("value-{i}").into(),
+                watermark: Some(prost_types::Timestamp::default()),
+                event_time: Some(prost_types::Timestamp::default()),
+                headers: Default::default(),
+                metadata: None,
+            }),
+            id: i.to_string(),
+            handshake: None,
+            status: None,
+        };
+        tx.send(request).await.unwrap();
+    }
+
+    let mut ids = HashSet::new();
+    while ids.len() < count {
+        let response = resp
+            .message()
+            .await
+            .unwrap()
+            .expect("a response per request");
+        assert_eq!(response.results.len(), 1);
+        ids.insert(response.id);
+    }
+    assert_eq!(ids, (0..count).map(|i| i.to_string()).collect());
+
+    // close request stream
+    drop(tx);
+
+    Ok(())
+}
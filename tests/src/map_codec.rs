@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+
+use numaflow::proto;
+use numaflow::proto::map::map_client::MapClient;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Uri;
+use tower::service_fn;
+
+// Rust client binary for a Map server with the json codec and the drop decode error policy. It
+// sends a JSON value, whose count the handler increments, and a value that is not JSON, which is
+// dropped without calling the handler.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let sock_path = env::args()
+        .nth(1)
+        .or_else(|| env::var("NUMAFLOW_MAP_SOCK").ok())
+        .unwrap_or_else(|| "/tmp/numaflow.sock".to_string());
+
+    let channel = tonic::transport::Endpoint::try_from("http://[::]:50051")?
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let sock = PathBuf::from(sock_path.clone());
+            async move {
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(
+                    UnixStream::connect(sock).await?,
+                ))
+            }
+        }))
+        .await?;
+
+    let mut client = MapClient::new(channel);
+
+    let (tx, rx) = mpsc::channel(3);
+
+    // Handshake
+    let handshake_request = proto::map::MapRequest {
+        request: None,
+        id: "".to_string(),
+        handshake: Some(proto::map::Handshake { sot: true }),
+        status: None,
+    };
+    tx.send(handshake_request).await.unwrap();
+
+    let resp = client.map_fn(ReceiverStream::new(rx)).await.unwrap();
+    let mut resp = resp.into_inner();
+
+    let handshake_response = resp.message().await.unwrap();
+    assert!(handshake_response.is_some_and(|response| response.handshake.is_some()));
+
+    for (id, value) in [("valid", r#"{"count":1}"#), ("invalid", "not json")] {
+        let request = proto::map::MapRequest {
+            request: Some(proto::map::map_request::Request {
+                keys: vec!["key".to_string()],
+                value: value.into(),
+                watermark: Some(prost_types::Timestamp::default()),
+                event_time: Some(prost_types::Timestamp::default()),
+                headers: Default::default(),
+                metadata: None,
+            }),
+            id: id.to_string(),
+            handshake: None,
+            status: None,
+        };
+        tx.send(request).await.unwrap();
+    }
+
+    let mut results = HashMap::new();
+    while results.len() < 2 {
+        let response = resp
+            .message()
+            .await
+            .unwrap()
+            .expect("a response per request");
+        assert_eq!(response.results.len(), 1);
+        results.insert(response.id, response.results.into_iter().next().unwrap());
+    }
+
+    let valid = &results["valid"];
+    assert_eq!(valid.value, br#"{"count":2}"#);
+    assert!(valid.tags.is_empty());
+
+    let invalid = &results["invalid"];
+    assert_eq!(invalid.tags, vec![numaflow::shared::DROP.to_string()]);
+
+    // close request stream
+    drop(tx);
+
+    Ok(())
+}
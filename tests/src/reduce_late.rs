@@ -0,0 +1,87 @@
+use std::env;
+use std::path::PathBuf;
+
+use numaflow::proto::reduce as reduce_proto;
+use reduce_proto::reduce_request::window_operation::Event;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, transport::Uri};
+use tower::service_fn;
+
+fn ts(seconds: i64) -> prost_types::Timestamp {
+    prost_types::Timestamp { seconds, nanos: 0 }
+}
+
+fn request(event: Event, watermark: i64) -> reduce_proto::ReduceRequest {
+    reduce_proto::ReduceRequest {
+        payload: Some(reduce_proto::reduce_request::Payload {
+            keys: vec!["k".into()],
+            value: b"1".to_vec(),
+            watermark: Some(ts(watermark)),
+            event_time: Some(ts(90)),
+            headers: Default::default(),
+        }),
+        operation: Some(reduce_proto::reduce_request::WindowOperation {
+            event: event as i32,
+            windows: vec![reduce_proto::Window {
+                start: Some(ts(60)),
+                end: Some(ts(120)),
+                slot: "slot-0".to_string(),
+            }],
+        }),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Default socket path with env/arg override
+    let sock_path = env::args()
+        .nth(1)
+        .or_else(|| env::var("NUMAFLOW_REDUCE_SOCK").ok())
+        .unwrap_or_else(|| "/tmp/var/run/numaflow/reduce.sock".to_string());
+
+    // Connect over UDS
+    let channel = tonic::transport::Endpoint::try_from("http://[::]:50051")?
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let sock = PathBuf::from(sock_path.clone());
+            async move {
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(
+                    UnixStream::connect(sock).await?,
+                ))
+            }
+        }))
+        .await?;
+
+    let mut client = reduce_proto::reduce_client::ReduceClient::new(channel);
+
+    let (tx, rx) = mpsc::channel(16);
+
+    // Test scenario: the window 60s-120s gets a datum on time and one that arrives after the
+    // watermark passed the end of the window by 5s.
+    tx.send(request(Event::Open, 90)).await.unwrap();
+    tx.send(request(Event::Append, 125)).await.unwrap();
+
+    // Drop sender to signal end-of-stream (COB)
+    drop(tx);
+
+    let request = Request::new(ReceiverStream::new(rx));
+    let mut resp = client.reduce_fn(request).await?.into_inner();
+
+    // The handler replies with `<late datums>:<max lateness in ms>:<latest watermark in ms>`
+    loop {
+        if let Some(r) = resp.message().await? {
+            println!("Received response: {:#?}", r);
+            if let Some(res) = r.result {
+                let value =
+                    String::from_utf8(res.value).expect("Result value should be valid UTF-8");
+                assert_eq!(value, "1:5000:125000", "Expected one datum late by 5s");
+                break;
+            }
+        } else {
+            panic!("Stream ended without a result");
+        }
+    }
+
+    Ok(())
+}
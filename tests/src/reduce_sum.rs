@@ -0,0 +1,87 @@
+use std::env;
+use std::path::PathBuf;
+
+use numaflow::proto::reduce as reduce_proto;
+use reduce_proto::reduce_request::window_operation::Event;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, transport::Uri};
+use tower::service_fn;
+
+fn ts(seconds: i64) -> prost_types::Timestamp {
+    prost_types::Timestamp { seconds, nanos: 0 }
+}
+
+fn request(event: Event, value: u32) -> reduce_proto::ReduceRequest {
+    reduce_proto::ReduceRequest {
+        payload: Some(reduce_proto::reduce_request::Payload {
+            keys: vec!["k".into()],
+            value: value.to_string().into_bytes(),
+            watermark: Some(ts(30)),
+            event_time: Some(ts(30)),
+            headers: Default::default(),
+        }),
+        operation: Some(reduce_proto::reduce_request::WindowOperation {
+            event: event as i32,
+            windows: vec![reduce_proto::Window {
+                start: Some(ts(0)),
+                end: Some(ts(60)),
+                slot: "slot-0".to_string(),
+            }],
+        }),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Default socket path with env/arg override
+    let sock_path = env::args()
+        .nth(1)
+        .or_else(|| env::var("NUMAFLOW_REDUCE_SOCK").ok())
+        .unwrap_or_else(|| "/tmp/var/run/numaflow/reduce.sock".to_string());
+
+    // Connect over UDS
+    let channel = tonic::transport::Endpoint::try_from("http://[::]:50051")?
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let sock = PathBuf::from(sock_path.clone());
+            async move {
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(
+                    UnixStream::connect(sock).await?,
+                ))
+            }
+        }))
+        .await?;
+
+    let mut client = reduce_proto::reduce_client::ReduceClient::new(channel);
+
+    let (tx, rx) = mpsc::channel(16);
+
+    // Test scenario: the window 0s-60s gets the values 1 to 10, which the handler sums up.
+    tx.send(request(Event::Open, 1)).await.unwrap();
+    for value in 2..=10 {
+        tx.send(request(Event::Append, value)).await.unwrap();
+    }
+
+    // Drop sender to signal end-of-stream (COB)
+    drop(tx);
+
+    let request = Request::new(ReceiverStream::new(rx));
+    let mut resp = client.reduce_fn(request).await?.into_inner();
+
+    loop {
+        if let Some(r) = resp.message().await? {
+            println!("Received response: {:#?}", r);
+            if let Some(res) = r.result {
+                let value =
+                    String::from_utf8(res.value).expect("Result value should be valid UTF-8");
+                assert_eq!(value, "55", "Expected the sum of the values 1 to 10");
+                break;
+            }
+        } else {
+            panic!("Stream ended without a result");
+        }
+    }
+
+    Ok(())
+}
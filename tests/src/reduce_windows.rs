@@ -0,0 +1,110 @@
+use std::env;
+use std::path::PathBuf;
+
+use numaflow::proto::reduce as reduce_proto;
+use reduce_proto::reduce_client::ReduceClient;
+use reduce_proto::reduce_request::window_operation::Event;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Request;
+use tonic::transport::{Channel, Uri};
+use tower::service_fn;
+
+fn ts(seconds: i64) -> prost_types::Timestamp {
+    prost_types::Timestamp { seconds, nanos: 0 }
+}
+
+fn request(event: Event, start: i64) -> reduce_proto::ReduceRequest {
+    reduce_proto::ReduceRequest {
+        payload: Some(reduce_proto::reduce_request::Payload {
+            keys: vec!["k".into()],
+            value: b"1".to_vec(),
+            watermark: Some(ts(start)),
+            event_time: Some(ts(start)),
+            headers: Default::default(),
+        }),
+        operation: Some(reduce_proto::reduce_request::WindowOperation {
+            event: event as i32,
+            windows: vec![reduce_proto::Window {
+                start: Some(ts(start)),
+                end: Some(ts(start + 60)),
+                slot: "slot-0".to_string(),
+            }],
+        }),
+    }
+}
+
+/// Reduces `datums` datums in the window starting at `start` and returns the value of the result.
+async fn reduce_window(mut client: ReduceClient<Channel>, start: i64, datums: usize) -> String {
+    let (tx, rx) = mpsc::channel(16);
+    tx.send(request(Event::Open, start)).await.unwrap();
+    for _ in 1..datums {
+        tx.send(request(Event::Append, start)).await.unwrap();
+    }
+    // Drop sender to signal end-of-stream (COB)
+    drop(tx);
+
+    let mut resp = client
+        .reduce_fn(Request::new(ReceiverStream::new(rx)))
+        .await
+        .expect("Reduce call should succeed")
+        .into_inner();
+    loop {
+        match resp
+            .message()
+            .await
+            .expect("Reading a response should succeed")
+        {
+            Some(r) => {
+                println!("Received response: {:#?}", r);
+                if let Some(res) = r.result {
+                    return String::from_utf8(res.value)
+                        .expect("Result value should be valid UTF-8");
+                }
+            }
+            None => panic!("Stream ended without a result"),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Default socket path with env/arg override
+    let sock_path = env::args()
+        .nth(1)
+        .or_else(|| env::var("NUMAFLOW_REDUCE_SOCK").ok())
+        .unwrap_or_else(|| "/tmp/var/run/numaflow/reduce.sock".to_string());
+
+    // Connect over UDS
+    let channel = tonic::transport::Endpoint::try_from("http://[::]:50051")?
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let sock = PathBuf::from(sock_path.clone());
+            async move {
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(
+                    UnixStream::connect(sock).await?,
+                ))
+            }
+        }))
+        .await?;
+
+    let client = ReduceClient::new(channel);
+
+    // Test scenario: two windows are reduced at the same time, the window 0s-60s gets two
+    // datums and the window 60s-120s three. The handler replies with `counter:<datums>`, which
+    // only holds if every window counts on its own.
+    let (first, second) = tokio::join!(
+        reduce_window(client.clone(), 0, 2),
+        reduce_window(client, 60, 3),
+    );
+    assert_eq!(
+        first, "counter:2",
+        "Expected the first window to count 2 datums"
+    );
+    assert_eq!(
+        second, "counter:3",
+        "Expected the second window to count 3 datums"
+    );
+
+    Ok(())
+}
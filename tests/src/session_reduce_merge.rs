@@ -0,0 +1,138 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use numaflow::proto::session_reduce as sr_proto;
+use sr_proto::session_reduce_request::window_operation::Event;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, transport::Uri};
+use tower::service_fn;
+
+fn ts(seconds: i64) -> prost_types::Timestamp {
+    prost_types::Timestamp { seconds, nanos: 0 }
+}
+
+fn window(start: i64, end: i64) -> sr_proto::KeyedWindow {
+    sr_proto::KeyedWindow {
+        start: Some(ts(start)),
+        end: Some(ts(end)),
+        slot: "slot-0".to_string(),
+        keys: vec!["key1".into()],
+    }
+}
+
+fn request(
+    event: Event,
+    keyed_windows: Vec<sr_proto::KeyedWindow>,
+    value: Option<&[u8]>,
+) -> sr_proto::SessionReduceRequest {
+    sr_proto::SessionReduceRequest {
+        payload: value.map(|value| sr_proto::session_reduce_request::Payload {
+            keys: vec!["key1".into()],
+            value: value.to_vec(),
+            watermark: Some(ts(60000)),
+            event_time: Some(ts(60000)),
+            headers: Default::default(),
+        }),
+        operation: Some(sr_proto::session_reduce_request::WindowOperation {
+            event: event as i32,
+            keyed_windows,
+        }),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Default socket path with env/arg override
+    let sock_path = env::args()
+        .nth(1)
+        .or_else(|| env::var("NUMAFLOW_SESSION_REDUCE_SOCK").ok())
+        .unwrap_or_else(|| "/tmp/var/run/numaflow/sessionreduce.sock".to_string());
+
+    // Connect over UDS
+    let channel = tonic::transport::Endpoint::try_from("http://[::]:50051")?
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let sock = PathBuf::from(sock_path.clone());
+            async move {
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(
+                    UnixStream::connect(sock).await?,
+                ))
+            }
+        }))
+        .await?;
+
+    let mut client =
+        numaflow::proto::session_reduce::session_reduce_client::SessionReduceClient::new(channel);
+
+    let (tx, rx) = mpsc::channel(16);
+
+    // Test scenario: two sessions of the same keys count their own datums, then are merged into a
+    // session that starts from the sum of their counts.
+    tokio::spawn(async move {
+        // 1. Session A gets two datums, session B one
+        tx.send(request(Event::Open, vec![window(60000, 90000)], Some(b"1")))
+            .await
+            .unwrap();
+        tx.send(request(
+            Event::Append,
+            vec![window(60000, 90000)],
+            Some(b"1"),
+        ))
+        .await
+        .unwrap();
+        tx.send(request(
+            Event::Open,
+            vec![window(90000, 120000)],
+            Some(b"1"),
+        ))
+        .await
+        .unwrap();
+
+        // Give the handlers time to count the datums before the accumulators are read
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        // 2. MERGE operation - merge both sessions, with one more datum for the merged session
+        tx.send(request(
+            Event::Merge,
+            vec![window(60000, 90000), window(90000, 120000)],
+            Some(b"1"),
+        ))
+        .await
+        .unwrap();
+
+        // 3. CLOSE operation - close the merged session
+        tx.send(request(Event::Close, vec![window(60000, 120000)], None))
+            .await
+            .unwrap();
+    });
+
+    let request = Request::new(ReceiverStream::new(rx));
+    let mut resp = client.session_reduce_fn(request).await?.into_inner();
+
+    // Only the merged session sends a result, the merged sessions are closed without one
+    let mut results = Vec::new();
+    let mut found_eof = false;
+
+    while let Some(r) = resp.message().await? {
+        if let Some(res) = r.result {
+            let count_str =
+                String::from_utf8(res.value.clone()).expect("Result value should be valid UTF-8");
+            println!("Received result: count={}", count_str);
+            results.push(count_str.parse::<i32>().expect("Result should be a number"));
+        }
+
+        if r.eof {
+            println!("Received EOF");
+            found_eof = true;
+            break;
+        }
+    }
+
+    // 2 datums of session A, 1 of session B and 1 of the merged session
+    assert_eq!(results, vec![4], "Expected one result counting 4 datums");
+    assert!(found_eof, "Should have received EOF");
+
+    Ok(())
+}
@@ -18,6 +18,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .nth(1)
         .or_else(|| env::var("NUMAFLOW_MAP_SOCK").ok())
         .unwrap_or_else(|| "/tmp/numaflow.sock".to_string());
+    // With `failure` as second CLI arg, every message is expected to be answered with a failure.
+    let expect_failure = env::args().nth(2).is_some_and(|arg| arg == "failure");
+    let check_result = |result: &proto::sink::sink_response::Result, id: &str| {
+        assert_eq!(result.id, id);
+        if expect_failure {
+            assert_eq!(result.status, proto::sink::Status::Failure as i32);
+            assert!(!result.err_msg.is_empty());
+        } else {
+            assert_eq!(result.err_msg, "");
+        }
+    };
 
     let channel = tonic::transport::Endpoint::try_from("http://[::]:50051")?
         .connect_with_connector(service_fn(move |_: Uri| {
@@ -111,9 +122,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let resp = resp_stream.message().await.unwrap().unwrap();
     assert!(!resp.results.is_empty());
-    let msg = resp.results.first().unwrap();
-    assert_eq!(msg.err_msg, "");
-    assert_eq!(msg.id, "1");
+    check_result(resp.results.first().unwrap(), "1");
 
     // eot for first request
     let resp = resp_stream.message().await.unwrap().unwrap();
@@ -125,9 +134,23 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let resp = resp_stream.message().await.unwrap().unwrap();
     assert!(!resp.results.is_empty());
     assert!(resp.handshake.is_none());
-    let msg = resp.results.first().unwrap();
-    assert_eq!(msg.err_msg, "");
-    assert_eq!(msg.id, "2");
+    let result = resp.results.first().unwrap();
+    check_result(result, "2");
+    if !expect_failure {
+        // The second message is forwarded to the on-success sink with its user metadata and an
+        // added group.
+        assert_eq!(result.status, proto::sink::Status::OnSuccess as i32);
+        let on_success_msg = result.on_success_msg.as_ref().unwrap();
+        assert_eq!(on_success_msg.value, b"hello");
+        assert_eq!(on_success_msg.keys, vec!["first", "second"]);
+        let metadata = on_success_msg.metadata.as_ref().unwrap();
+        assert_eq!(metadata.user_metadata.len(), 2);
+        assert_eq!(metadata.user_metadata["group1"], user_metadata["group1"]);
+        assert_eq!(
+            metadata.user_metadata["custom-group"].key_value["custom-key"],
+            b"custom-value"
+        );
+    }
 
     // eot for second request
     let resp = resp_stream.message().await.unwrap().unwrap();